@@ -0,0 +1,352 @@
+//! A virtio-9p device driver.
+#![no_std]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate kerla_api;
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use kerla_api::driver::p9::{
+    register_p9_transport, P9Completion, P9Request, P9Transport, P9TransportError,
+};
+use kerla_api::driver::register_driver_prober;
+
+use virtio::device::{IsrStatus, Virtio, VirtqDescBuffer, VirtqUsedChain};
+use virtio::transports::{
+    virtio_mmio::VirtioMmio, virtio_pci_legacy::VirtioLegacyPci,
+    virtio_pci_modern::VirtioModernPci, VirtioAttachError, VirtioTransport,
+};
+
+use kerla_api::address::VAddr;
+use kerla_api::arch::PAGE_SIZE;
+use kerla_api::driver::{
+    attach_irq, pci::PciDevice, DeviceProber, Driver, ProbeResult, VirtioMmioDevice,
+};
+use kerla_api::mm::{alloc_pages, AllocPageFlags};
+use kerla_api::sync::SpinLock;
+
+const VIRTIO_9P_F_MOUNT_TAG: u64 = 1 << 0;
+
+const VIRTIO_9P_QUEUE_REQUEST: u16 = 0;
+
+/// The offsets of fields in `struct virtio_9p_config`.
+const VIRTIO_9P_CONFIG_TAG_LEN: usize = 0;
+const VIRTIO_9P_CONFIG_TAG: usize = 2;
+
+/// The maximum size of a message. Each slot has a request buffer and a
+/// response buffer of this size.
+const MESSAGE_SIZE_MAX: usize = 32 * 1024;
+
+/// The maximum number of requests in flight. Each request consumes two
+/// descriptors: the request and the response.
+const NUM_SLOTS_MAX: usize = 8;
+
+struct Slot {
+    request: VAddr,
+    response: VAddr,
+    completion: Option<P9Completion>,
+}
+
+pub struct Virtio9p {
+    virtio: Virtio,
+    slots: Vec<Slot>,
+    free_slots: Vec<usize>,
+    /// Requests waiting for a free slot.
+    pending: VecDeque<P9Request>,
+}
+
+impl Virtio9p {
+    pub fn new(transport: Arc<dyn VirtioTransport>) -> Result<Virtio9p, VirtioAttachError> {
+        let mut virtio = Virtio::new(transport);
+        virtio.initialize(VIRTIO_9P_F_MOUNT_TAG, 1 /* the request queue */)?;
+
+        let num_slots = core::cmp::min(
+            NUM_SLOTS_MAX,
+            virtio.virtq(VIRTIO_9P_QUEUE_REQUEST).num_descs() as usize / 2,
+        );
+
+        let num_pages = MESSAGE_SIZE_MAX / PAGE_SIZE;
+        let slots = (0..num_slots)
+            .map(|_| Slot {
+                request: alloc_pages(num_pages, AllocPageFlags::KERNEL)
+                    .unwrap()
+                    .as_vaddr(),
+                response: alloc_pages(num_pages, AllocPageFlags::KERNEL)
+                    .unwrap()
+                    .as_vaddr(),
+                completion: None,
+            })
+            .collect();
+
+        Ok(Virtio9p {
+            virtio,
+            slots,
+            free_slots: (0..num_slots).rev().collect(),
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Reads the mount tag from the device configuration space.
+    pub fn mount_tag(&self) -> String {
+        let tag_len = u16::from_le_bytes([
+            self.virtio
+                .read_device_config8(VIRTIO_9P_CONFIG_TAG_LEN as u16),
+            self.virtio
+                .read_device_config8((VIRTIO_9P_CONFIG_TAG_LEN + 1) as u16),
+        ]) as usize;
+
+        let tag: Vec<u8> = (0..tag_len)
+            .map(|i| {
+                self.virtio
+                    .read_device_config8((VIRTIO_9P_CONFIG_TAG + i) as u16)
+            })
+            .collect();
+
+        String::from_utf8_lossy(&tag).into_owned()
+    }
+
+    pub fn submit(&mut self, request: P9Request) {
+        self.pending.push_back(request);
+        if self.enqueue_pending() {
+            self.virtio.virtq(VIRTIO_9P_QUEUE_REQUEST).notify();
+        }
+    }
+
+    /// Moves pending requests into the virtqueue as long as there're free
+    /// slots. Returns `true` if any request is enqueued.
+    fn enqueue_pending(&mut self) -> bool {
+        let mut enqueued = false;
+        while !self.free_slots.is_empty() {
+            let request = match self.pending.pop_front() {
+                Some(request) => request,
+                None => break,
+            };
+
+            let slot_index = self.free_slots.pop().unwrap();
+            let slot = &mut self.slots[slot_index];
+            slot.request.write_bytes(&request.message);
+
+            self.virtio.virtq_mut(VIRTIO_9P_QUEUE_REQUEST).enqueue(&[
+                VirtqDescBuffer::ReadOnlyFromDevice {
+                    addr: slot.request.as_paddr(),
+                    len: request.message.len(),
+                },
+                VirtqDescBuffer::WritableFromDevice {
+                    addr: slot.response.as_paddr(),
+                    len: MESSAGE_SIZE_MAX,
+                },
+            ]);
+
+            slot.completion = Some(request.completion);
+            enqueued = true;
+        }
+
+        enqueued
+    }
+
+    /// Handles an interrupt. Returns the completed requests. The caller calls
+    /// their callbacks after unlocking the device since they may submit new
+    /// requests.
+    #[allow(clippy::type_complexity)]
+    pub fn handle_irq(&mut self) -> Vec<(P9Completion, Result<Vec<u8>, P9TransportError>)> {
+        let mut completed = Vec::new();
+        if !self
+            .virtio
+            .read_isr_status()
+            .contains(IsrStatus::QUEUE_INTR)
+        {
+            return completed;
+        }
+
+        let request_virtq = self.virtio.virtq_mut(VIRTIO_9P_QUEUE_REQUEST);
+        while let Some(VirtqUsedChain { descs, total_len }) = request_virtq.pop_used() {
+            // Determine the slot from the address of the request buffer.
+            let slot_index = match descs.first() {
+                Some(VirtqDescBuffer::ReadOnlyFromDevice { addr, .. }) => self
+                    .slots
+                    .iter()
+                    .position(|slot| slot.request.as_paddr() == *addr),
+                _ => None,
+            };
+
+            let slot_index = match slot_index {
+                Some(slot_index) => slot_index,
+                None => {
+                    warn!("virtio-9p: unexpected descriptor chain");
+                    continue;
+                }
+            };
+
+            let slot = &mut self.slots[slot_index];
+            let result = if total_len < 4 {
+                Err(P9TransportError::IoError)
+            } else {
+                // Trust the `size[4]` field in the message rather than the
+                // length reported by the device.
+                let size = unsafe { slot.response.read_volatile::<u32>() } as usize;
+                if size < 4 || size > total_len {
+                    Err(P9TransportError::IoError)
+                } else {
+                    let response =
+                        unsafe { core::slice::from_raw_parts(slot.response.as_ptr::<u8>(), size) };
+                    Ok(response.to_vec())
+                }
+            };
+
+            if let Some(completion) = slot.completion.take() {
+                completed.push((completion, result));
+            }
+
+            self.free_slots.push(slot_index);
+        }
+
+        if self.enqueue_pending() {
+            self.virtio.virtq(VIRTIO_9P_QUEUE_REQUEST).notify();
+        }
+
+        completed
+    }
+}
+
+struct Virtio9pTransport {
+    device: Arc<SpinLock<Virtio9p>>,
+    mount_tag: String,
+}
+
+impl Driver for Virtio9pTransport {
+    fn name(&self) -> &str {
+        "virtio-9p"
+    }
+}
+
+impl P9Transport for Virtio9pTransport {
+    fn mount_tag(&self) -> &str {
+        &self.mount_tag
+    }
+
+    fn max_message_size(&self) -> usize {
+        MESSAGE_SIZE_MAX
+    }
+
+    fn submit(&self, request: P9Request) {
+        if request.message.len() > MESSAGE_SIZE_MAX {
+            (request.completion)(Err(P9TransportError::TooLarge));
+            return;
+        }
+
+        self.device.lock().submit(request);
+    }
+}
+
+fn attach_device(irq: u8, transport: Arc<dyn VirtioTransport>) -> ProbeResult {
+    let device = match Virtio9p::new(transport) {
+        Ok(device) => Arc::new(SpinLock::new(device)),
+        Err(VirtioAttachError::InvalidVendorId) => {
+            // Not a virtio device.
+            return ProbeResult::NotSupported;
+        }
+        Err(err) => {
+            warn!("failed to initialize virtio-9p: {:?}", err);
+            return ProbeResult::Failed;
+        }
+    };
+
+    let mount_tag = device.lock().mount_tag();
+    info!("virtio-9p: mount_tag=\"{}\"", mount_tag);
+
+    let irq_device = device.clone();
+    attach_irq(irq, move || {
+        let completed = irq_device.lock().handle_irq();
+        for (completion, result) in completed {
+            completion(result);
+        }
+    });
+    register_p9_transport(Box::new(Virtio9pTransport { device, mount_tag }));
+
+    ProbeResult::Bound
+}
+
+pub struct Virtio9pProber {}
+
+#[allow(clippy::new_without_default)]
+impl Virtio9pProber {
+    pub fn new() -> Virtio9pProber {
+        Virtio9pProber {}
+    }
+}
+
+impl DeviceProber for Virtio9pProber {
+    fn name(&self) -> &'static str {
+        "virtio-9p"
+    }
+
+    fn probe_pci(&self, pci_device: &PciDevice) -> ProbeResult {
+        // Check if the device is a 9P transport ("4.1.2 PCI Device Discovery").
+        if pci_device.config().vendor_id() != 0x1af4 {
+            return ProbeResult::NotSupported;
+        }
+
+        // Check if it's a modern or transitional device.
+        let device_id = pci_device.config().device_id();
+        if device_id != 0x1040 + 9 && device_id != 0x1009 {
+            return ProbeResult::NotSupported;
+        }
+
+        trace!("virtio-9p: found the device (over PCI)");
+        let transport = match VirtioModernPci::probe_pci(pci_device) {
+            Ok(transport) => transport,
+            Err(VirtioAttachError::InvalidVendorId) => {
+                // Not a virtio device.
+                return ProbeResult::NotSupported;
+            }
+            Err(err) => {
+                trace!("failed to attach a virtio-9p as a modern device: {:?}, falling back to the legacy driver", err);
+                match VirtioLegacyPci::probe_pci(pci_device) {
+                    Ok(transport) => transport,
+                    Err(err) => {
+                        warn!("failed to attach a virtio-9p as a legacy device: {:?}", err);
+                        return ProbeResult::Failed;
+                    }
+                }
+            }
+        };
+
+        attach_device(pci_device.config().interrupt_line(), transport)
+    }
+
+    fn probe_virtio_mmio(&self, mmio_device: &VirtioMmioDevice) -> ProbeResult {
+        let mmio = mmio_device.mmio_base.as_vaddr();
+        let magic = unsafe { *mmio.as_ptr::<u32>() };
+        let virtio_version = unsafe { *mmio.add(4).as_ptr::<u32>() };
+        let device_id = unsafe { *mmio.add(8).as_ptr::<u32>() };
+
+        if magic != 0x74726976 {
+            return ProbeResult::NotSupported;
+        }
+
+        if virtio_version != 2 {
+            warn!("unsupported virtio device version: {}", virtio_version);
+            return ProbeResult::NotSupported;
+        }
+
+        // It looks like a virtio device. Check if the device is a 9P
+        // transport.
+        if device_id != 9 {
+            return ProbeResult::NotSupported;
+        }
+
+        trace!("virtio-9p: found the device (over MMIO)");
+
+        let transport = Arc::new(VirtioMmio::new(mmio_device.mmio_base));
+        attach_device(mmio_device.irq, transport)
+    }
+}
+
+pub fn init() {
+    register_driver_prober(Box::new(Virtio9pProber::new()));
+}
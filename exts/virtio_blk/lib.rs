@@ -0,0 +1,437 @@
+//! A virtio-blk device driver.
+#![no_std]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate kerla_api;
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem::size_of;
+use kerla_api::driver::block::{
+    register_block_driver, BlockCompletion, BlockDriver, BlockIoError, BlockOp, BlockRequest,
+    SECTOR_SIZE,
+};
+use kerla_api::driver::register_driver_prober;
+
+use virtio::device::{IsrStatus, Virtio, VirtqDescBuffer, VirtqUsedChain};
+use virtio::transports::{
+    virtio_mmio::VirtioMmio, virtio_pci_legacy::VirtioLegacyPci,
+    virtio_pci_modern::VirtioModernPci, VirtioAttachError, VirtioTransport,
+};
+
+use kerla_api::address::VAddr;
+use kerla_api::arch::PAGE_SIZE;
+use kerla_api::driver::{
+    attach_irq, pci::PciDevice, DeviceProber, Driver, ProbeResult, VirtioMmioDevice,
+};
+use kerla_api::mm::{alloc_pages, AllocPageFlags};
+use kerla_api::sync::SpinLock;
+
+const VIRTIO_BLK_F_RO: u64 = 1 << 5;
+const VIRTIO_BLK_F_BLK_SIZE: u64 = 1 << 6;
+const VIRTIO_BLK_F_FLUSH: u64 = 1 << 9;
+
+const VIRTIO_BLK_T_IN: u32 = 0;
+const VIRTIO_BLK_T_OUT: u32 = 1;
+const VIRTIO_BLK_T_FLUSH: u32 = 4;
+
+const VIRTIO_BLK_S_OK: u8 = 0;
+const VIRTIO_BLK_S_UNSUPP: u8 = 2;
+
+const VIRTIO_BLK_QUEUE_REQUEST: u16 = 0;
+
+/// The offsets of fields in `struct virtio_blk_config`.
+const VIRTIO_BLK_CONFIG_CAPACITY: usize = 0;
+const VIRTIO_BLK_CONFIG_BLK_SIZE: usize = 20;
+
+/// The maximum number of requests in flight. Each request consumes three
+/// descriptors: the header, the data buffer, and the status.
+const NUM_SLOTS_MAX: usize = 64;
+
+/// The header of `struct virtio_blk_req`.
+#[derive(Clone, Copy)]
+#[repr(C, packed)]
+struct VirtioBlkReqHeader {
+    type_: u32,
+    reserved: u32,
+    sector: u64,
+}
+
+/// The offset of the status bytes in the request page. Headers are placed
+/// from the beginning of the page.
+const STATUS_OFFSET: usize = NUM_SLOTS_MAX * size_of::<VirtioBlkReqHeader>();
+const _: () = assert!(STATUS_OFFSET + NUM_SLOTS_MAX <= PAGE_SIZE);
+
+pub struct VirtioBlk {
+    virtio: Virtio,
+    /// The page containing request headers and status bytes.
+    requests: VAddr,
+    /// The completion callbacks of in-flight requests, indexed by the slot.
+    slots: Vec<Option<BlockCompletion>>,
+    free_slots: Vec<usize>,
+    /// Requests waiting for a free slot.
+    pending: VecDeque<BlockRequest>,
+}
+
+impl VirtioBlk {
+    pub fn new(transport: Arc<dyn VirtioTransport>) -> Result<(VirtioBlk, u64), VirtioAttachError> {
+        let mut virtio = Virtio::new(transport);
+        let features = virtio.initialize_with_optional_features(
+            0,
+            VIRTIO_BLK_F_RO | VIRTIO_BLK_F_BLK_SIZE | VIRTIO_BLK_F_FLUSH,
+            1, /* the request queue */
+        )?;
+
+        let num_slots = core::cmp::min(
+            NUM_SLOTS_MAX,
+            virtio.virtq(VIRTIO_BLK_QUEUE_REQUEST).num_descs() as usize / 3,
+        );
+
+        let requests = alloc_pages(1, AllocPageFlags::KERNEL).unwrap().as_vaddr();
+
+        let mut slots = Vec::with_capacity(num_slots);
+        slots.resize_with(num_slots, || None);
+
+        Ok((
+            VirtioBlk {
+                virtio,
+                requests,
+                slots,
+                free_slots: (0..num_slots).rev().collect(),
+                pending: VecDeque::new(),
+            },
+            features,
+        ))
+    }
+
+    /// Reads the capacity of the device in sectors.
+    pub fn capacity(&self) -> u64 {
+        let mut capacity = [0; 8];
+        for (i, byte) in capacity.iter_mut().enumerate() {
+            *byte = self
+                .virtio
+                .read_device_config8((VIRTIO_BLK_CONFIG_CAPACITY + i) as u16);
+        }
+
+        u64::from_le_bytes(capacity)
+    }
+
+    /// Reads the logical block size. Available only if `VIRTIO_BLK_F_BLK_SIZE`
+    /// is negotiated.
+    pub fn blk_size(&self) -> u32 {
+        let mut blk_size = [0; 4];
+        for (i, byte) in blk_size.iter_mut().enumerate() {
+            *byte = self
+                .virtio
+                .read_device_config8((VIRTIO_BLK_CONFIG_BLK_SIZE + i) as u16);
+        }
+
+        u32::from_le_bytes(blk_size)
+    }
+
+    pub fn submit(&mut self, request: BlockRequest) {
+        self.pending.push_back(request);
+        if self.enqueue_pending() {
+            self.virtio.virtq(VIRTIO_BLK_QUEUE_REQUEST).notify();
+        }
+    }
+
+    /// Moves pending requests into the virtqueue as long as there're free
+    /// slots. Returns `true` if any request is enqueued.
+    fn enqueue_pending(&mut self) -> bool {
+        let mut enqueued = false;
+        while !self.free_slots.is_empty() {
+            let request = match self.pending.pop_front() {
+                Some(request) => request,
+                None => break,
+            };
+
+            let slot = self.free_slots.pop().unwrap();
+            let header_vaddr = self.requests.add(slot * size_of::<VirtioBlkReqHeader>());
+            let status_vaddr = self.requests.add(STATUS_OFFSET + slot);
+            unsafe {
+                header_vaddr.write_volatile(VirtioBlkReqHeader {
+                    type_: match request.op {
+                        BlockOp::Read => VIRTIO_BLK_T_IN,
+                        BlockOp::Write => VIRTIO_BLK_T_OUT,
+                        BlockOp::Flush => VIRTIO_BLK_T_FLUSH,
+                    },
+                    reserved: 0,
+                    sector: request.sector,
+                });
+                status_vaddr.write_volatile::<u8>(0xff);
+            }
+
+            let header = VirtqDescBuffer::ReadOnlyFromDevice {
+                addr: header_vaddr.as_paddr(),
+                len: size_of::<VirtioBlkReqHeader>(),
+            };
+            let status = VirtqDescBuffer::WritableFromDevice {
+                addr: status_vaddr.as_paddr(),
+                len: 1,
+            };
+
+            let request_virtq = self.virtio.virtq_mut(VIRTIO_BLK_QUEUE_REQUEST);
+            match request.op {
+                BlockOp::Read => request_virtq.enqueue(&[
+                    header,
+                    VirtqDescBuffer::WritableFromDevice {
+                        addr: request.buf,
+                        len: request.len,
+                    },
+                    status,
+                ]),
+                BlockOp::Write => request_virtq.enqueue(&[
+                    header,
+                    VirtqDescBuffer::ReadOnlyFromDevice {
+                        addr: request.buf,
+                        len: request.len,
+                    },
+                    status,
+                ]),
+                BlockOp::Flush => request_virtq.enqueue(&[header, status]),
+            }
+
+            self.slots[slot] = Some(request.completion);
+            enqueued = true;
+        }
+
+        enqueued
+    }
+
+    /// Handles an interrupt. Returns the completed requests. The caller calls
+    /// their callbacks after unlocking the device since they may submit new
+    /// requests.
+    pub fn handle_irq(&mut self) -> Vec<(BlockCompletion, Result<(), BlockIoError>)> {
+        let mut completed = Vec::new();
+        if !self
+            .virtio
+            .read_isr_status()
+            .contains(IsrStatus::QUEUE_INTR)
+        {
+            return completed;
+        }
+
+        let headers_paddr = self.requests.as_paddr();
+        let request_virtq = self.virtio.virtq_mut(VIRTIO_BLK_QUEUE_REQUEST);
+        while let Some(VirtqUsedChain { descs, .. }) = request_virtq.pop_used() {
+            // Determine the slot from the address of the header.
+            let slot = match descs.first() {
+                Some(VirtqDescBuffer::ReadOnlyFromDevice { addr, .. }) => {
+                    (addr.value() - headers_paddr.value()) / size_of::<VirtioBlkReqHeader>()
+                }
+                _ => {
+                    warn!("virtio-blk: unexpected descriptor chain");
+                    continue;
+                }
+            };
+
+            let status = unsafe {
+                self.requests
+                    .add(STATUS_OFFSET + slot)
+                    .read_volatile::<u8>()
+            };
+            let result = match status {
+                VIRTIO_BLK_S_OK => Ok(()),
+                VIRTIO_BLK_S_UNSUPP => Err(BlockIoError::Unsupported),
+                _ => Err(BlockIoError::IoError),
+            };
+
+            if let Some(completion) = self.slots[slot].take() {
+                completed.push((completion, result));
+            }
+
+            self.free_slots.push(slot);
+        }
+
+        if self.enqueue_pending() {
+            self.virtio.virtq(VIRTIO_BLK_QUEUE_REQUEST).notify();
+        }
+
+        completed
+    }
+}
+
+struct VirtioBlkDriver {
+    device: Arc<SpinLock<VirtioBlk>>,
+    num_sectors: u64,
+    block_size: usize,
+    read_only: bool,
+    flush_supported: bool,
+}
+
+impl VirtioBlkDriver {
+    fn new(device: Arc<SpinLock<VirtioBlk>>, features: u64) -> VirtioBlkDriver {
+        let num_sectors = device.lock().capacity();
+        let block_size = if features & VIRTIO_BLK_F_BLK_SIZE != 0 {
+            device.lock().blk_size() as usize
+        } else {
+            SECTOR_SIZE
+        };
+
+        VirtioBlkDriver {
+            device,
+            num_sectors,
+            block_size,
+            read_only: features & VIRTIO_BLK_F_RO != 0,
+            flush_supported: features & VIRTIO_BLK_F_FLUSH != 0,
+        }
+    }
+}
+
+impl Driver for VirtioBlkDriver {
+    fn name(&self) -> &str {
+        "virtio-blk"
+    }
+}
+
+impl BlockDriver for VirtioBlkDriver {
+    fn num_sectors(&self) -> u64 {
+        self.num_sectors
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn submit(&self, request: BlockRequest) {
+        let num_sectors = (request.len / SECTOR_SIZE) as u64;
+        match request.op {
+            BlockOp::Read | BlockOp::Write
+                if request.len % SECTOR_SIZE != 0
+                    || !request
+                        .sector
+                        .checked_add(num_sectors)
+                        .is_some_and(|end| end <= self.num_sectors) =>
+            {
+                (request.completion)(Err(BlockIoError::OutOfRange));
+            }
+            BlockOp::Write if self.read_only => {
+                (request.completion)(Err(BlockIoError::ReadOnly));
+            }
+            BlockOp::Flush if !self.flush_supported => {
+                // The device has no volatile write cache.
+                (request.completion)(Ok(()));
+            }
+            _ => {
+                self.device.lock().submit(request);
+            }
+        }
+    }
+}
+
+fn attach_device(irq: u8, transport: Arc<dyn VirtioTransport>) -> ProbeResult {
+    let (device, features) = match VirtioBlk::new(transport) {
+        Ok((device, features)) => (Arc::new(SpinLock::new(device)), features),
+        Err(VirtioAttachError::InvalidVendorId) => {
+            // Not a virtio device.
+            return ProbeResult::NotSupported;
+        }
+        Err(err) => {
+            warn!("failed to initialize virtio-blk: {:?}", err);
+            return ProbeResult::Failed;
+        }
+    };
+
+    let irq_device = device.clone();
+    attach_irq(irq, move || {
+        let completed = irq_device.lock().handle_irq();
+        for (completion, result) in completed {
+            completion(result);
+        }
+    });
+    register_block_driver(Box::new(VirtioBlkDriver::new(device, features)));
+
+    ProbeResult::Bound
+}
+
+pub struct VirtioBlkProber {}
+
+#[allow(clippy::new_without_default)]
+impl VirtioBlkProber {
+    pub fn new() -> VirtioBlkProber {
+        VirtioBlkProber {}
+    }
+}
+
+impl DeviceProber for VirtioBlkProber {
+    fn name(&self) -> &'static str {
+        "virtio-blk"
+    }
+
+    fn probe_pci(&self, pci_device: &PciDevice) -> ProbeResult {
+        // Check if the device is a block device ("4.1.2 PCI Device Discovery").
+        if pci_device.config().vendor_id() != 0x1af4 {
+            return ProbeResult::NotSupported;
+        }
+
+        // Check if the it's a legacy or traditional device.
+        let device_id = pci_device.config().device_id();
+        if device_id != 0x1040 + 2 && device_id != 0x1001 {
+            return ProbeResult::NotSupported;
+        }
+
+        trace!("virtio-blk: found the device (over PCI)");
+        let transport = match VirtioModernPci::probe_pci(pci_device) {
+            Ok(transport) => transport,
+            Err(VirtioAttachError::InvalidVendorId) => {
+                // Not a virtio device.
+                return ProbeResult::NotSupported;
+            }
+            Err(err) => {
+                trace!("failed to attach a virtio-blk as a modern device: {:?}, falling back to the legacy driver", err);
+                match VirtioLegacyPci::probe_pci(pci_device) {
+                    Ok(transport) => transport,
+                    Err(err) => {
+                        warn!(
+                            "failed to attach a virtio-blk as a legacy device: {:?}",
+                            err
+                        );
+                        return ProbeResult::Failed;
+                    }
+                }
+            }
+        };
+
+        attach_device(pci_device.config().interrupt_line(), transport)
+    }
+
+    fn probe_virtio_mmio(&self, mmio_device: &VirtioMmioDevice) -> ProbeResult {
+        let mmio = mmio_device.mmio_base.as_vaddr();
+        let magic = unsafe { *mmio.as_ptr::<u32>() };
+        let virtio_version = unsafe { *mmio.add(4).as_ptr::<u32>() };
+        let device_id = unsafe { *mmio.add(8).as_ptr::<u32>() };
+
+        if magic != 0x74726976 {
+            return ProbeResult::NotSupported;
+        }
+
+        if virtio_version != 2 {
+            warn!("unsupported virtio device version: {}", virtio_version);
+            return ProbeResult::NotSupported;
+        }
+
+        // It looks like a virtio device. Check if the device is a block
+        // device.
+        if device_id != 2 {
+            return ProbeResult::NotSupported;
+        }
+
+        trace!("virtio-blk: found the device (over MMIO)");
+
+        let transport = Arc::new(VirtioMmio::new(mmio_device.mmio_base));
+        attach_device(mmio_device.irq, transport)
+    }
+}
+
+pub fn init() {
+    register_driver_prober(Box::new(VirtioBlkProber::new()));
+}
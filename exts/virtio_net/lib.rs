@@ -24,7 +24,7 @@ use kerla_api::arch::PAGE_SIZE;
 use kerla_api::driver::{
     attach_irq,
     net::{register_ethernet_driver, EthernetDriver, MacAddress},
-    DeviceProber, Driver,
+    DeviceProber, Driver, ProbeResult,
 };
 use kerla_api::driver::{pci::PciDevice, VirtioMmioDevice};
 use kerla_api::mm::{alloc_pages, AllocPageFlags};
@@ -296,16 +296,20 @@ impl VirtioNetProber {
 }
 
 impl DeviceProber for VirtioNetProber {
-    fn probe_pci(&self, pci_device: &PciDevice) {
+    fn name(&self) -> &'static str {
+        "virtio-net"
+    }
+
+    fn probe_pci(&self, pci_device: &PciDevice) -> ProbeResult {
         // Check if the device is a network card ("4.1.2 PCI Device Discovery").
         if pci_device.config().vendor_id() != 0x1af4 {
-            return;
+            return ProbeResult::NotSupported;
         }
 
         // Check if the it's a legacy or traditional device.
         let device_id = pci_device.config().device_id();
         if device_id != 0x1040 + 1 && device_id != 0x1000 {
-            return;
+            return ProbeResult::NotSupported;
         }
 
         trace!("virtio-net: found the device (over PCI)");
@@ -313,7 +317,7 @@ impl DeviceProber for VirtioNetProber {
             Ok(transport) => transport,
             Err(VirtioAttachError::InvalidVendorId) => {
                 // Not a virtio-net device.
-                return;
+                return ProbeResult::NotSupported;
             }
             Err(err) => {
                 trace!("failed to attach a virtio-net as a modern device: {:?}, falling back to the legacy driver", err);
@@ -324,7 +328,7 @@ impl DeviceProber for VirtioNetProber {
                             "failed to attach a virtio-net as a legacy device: {:?}",
                             err
                         );
-                        return;
+                        return ProbeResult::Failed;
                     }
                 }
             }
@@ -334,7 +338,7 @@ impl DeviceProber for VirtioNetProber {
             Ok(virtio) => virtio,
             Err(err) => {
                 warn!("failed to initialize virtio-net: {:?}", err);
-                return;
+                return ProbeResult::Failed;
             }
         };
 
@@ -343,26 +347,28 @@ impl DeviceProber for VirtioNetProber {
         attach_irq(pci_device.config().interrupt_line(), move || {
             device.lock().handle_irq();
         });
+
+        ProbeResult::Bound
     }
 
-    fn probe_virtio_mmio(&self, mmio_device: &VirtioMmioDevice) {
+    fn probe_virtio_mmio(&self, mmio_device: &VirtioMmioDevice) -> ProbeResult {
         let mmio = mmio_device.mmio_base.as_vaddr();
         let magic = unsafe { *mmio.as_ptr::<u32>() };
         let virtio_version = unsafe { *mmio.add(4).as_ptr::<u32>() };
         let device_id = unsafe { *mmio.add(8).as_ptr::<u32>() };
 
         if magic != 0x74726976 {
-            return;
+            return ProbeResult::NotSupported;
         }
 
         if virtio_version != 2 {
             warn!("unsupported virtio device version: {}", virtio_version);
-            return;
+            return ProbeResult::NotSupported;
         }
 
         // It looks like a virtio device. Check if the device is a network card.
         if device_id != 1 {
-            return;
+            return ProbeResult::NotSupported;
         }
 
         trace!("virtio-net: found the device (over MMIO)");
@@ -372,11 +378,11 @@ impl DeviceProber for VirtioNetProber {
             Ok(device) => Arc::new(SpinLock::new(device)),
             Err(VirtioAttachError::InvalidVendorId) => {
                 // Not a virtio-net device.
-                return;
+                return ProbeResult::NotSupported;
             }
             Err(err) => {
                 warn!("failed to attach a virtio-net: {:?}", err);
-                return;
+                return ProbeResult::Failed;
             }
         };
 
@@ -384,6 +390,8 @@ impl DeviceProber for VirtioNetProber {
         attach_irq(mmio_device.irq, move || {
             device.lock().handle_irq();
         });
+
+        ProbeResult::Bound
     }
 }
 
@@ -0,0 +1,227 @@
+//! A virtio-rng (entropy) device driver.
+#![no_std]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate kerla_api;
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use kerla_api::driver::register_driver_prober;
+use kerla_api::driver::rng::{receive_randomness, register_rng_driver, RngDriver};
+
+use virtio::device::{IsrStatus, Virtio, VirtqDescBuffer, VirtqUsedChain};
+use virtio::transports::{
+    virtio_mmio::VirtioMmio, virtio_pci_legacy::VirtioLegacyPci,
+    virtio_pci_modern::VirtioModernPci, VirtioAttachError, VirtioTransport,
+};
+
+use kerla_api::address::VAddr;
+use kerla_api::driver::{
+    attach_irq, pci::PciDevice, DeviceProber, Driver, ProbeResult, VirtioMmioDevice,
+};
+use kerla_api::mm::{alloc_pages, AllocPageFlags};
+use kerla_api::sync::SpinLock;
+
+const VIRTIO_RNG_QUEUE_REQUEST: u16 = 0;
+
+/// The number of random bytes requested at once.
+const REQUEST_LEN: usize = 64;
+
+pub struct VirtioRng {
+    virtio: Virtio,
+    buffer: VAddr,
+    /// `true` if a request is being processed by the device.
+    pending: bool,
+}
+
+impl VirtioRng {
+    pub fn new(transport: Arc<dyn VirtioTransport>) -> Result<VirtioRng, VirtioAttachError> {
+        let mut virtio = Virtio::new(transport);
+        virtio.initialize(0, 1 /* the request queue */)?;
+
+        let buffer = alloc_pages(1, AllocPageFlags::KERNEL).unwrap().as_vaddr();
+
+        Ok(VirtioRng {
+            virtio,
+            buffer,
+            pending: false,
+        })
+    }
+
+    pub fn request(&mut self) {
+        if self.pending {
+            return;
+        }
+
+        let request_virtq = self.virtio.virtq_mut(VIRTIO_RNG_QUEUE_REQUEST);
+        request_virtq.enqueue(&[VirtqDescBuffer::WritableFromDevice {
+            addr: self.buffer.as_paddr(),
+            len: REQUEST_LEN,
+        }]);
+        request_virtq.notify();
+        self.pending = true;
+    }
+
+    pub fn handle_irq(&mut self) {
+        if !self
+            .virtio
+            .read_isr_status()
+            .contains(IsrStatus::QUEUE_INTR)
+        {
+            return;
+        }
+
+        let request_virtq = self.virtio.virtq_mut(VIRTIO_RNG_QUEUE_REQUEST);
+        while let Some(VirtqUsedChain { total_len, .. }) = request_virtq.pop_used() {
+            trace!("virtio-rng: received {} bytes", total_len);
+            let buf = unsafe {
+                core::slice::from_raw_parts(
+                    self.buffer.as_ptr::<u8>(),
+                    core::cmp::min(total_len, REQUEST_LEN),
+                )
+            };
+
+            receive_randomness(buf);
+            self.pending = false;
+        }
+    }
+}
+
+struct VirtioRngDriver {
+    device: Arc<SpinLock<VirtioRng>>,
+}
+
+impl VirtioRngDriver {
+    fn new(device: Arc<SpinLock<VirtioRng>>) -> VirtioRngDriver {
+        VirtioRngDriver { device }
+    }
+}
+
+impl Driver for VirtioRngDriver {
+    fn name(&self) -> &str {
+        "virtio-rng"
+    }
+}
+
+impl RngDriver for VirtioRngDriver {
+    fn request_randomness(&self) {
+        self.device.lock().request();
+    }
+}
+
+pub struct VirtioRngProber {}
+
+#[allow(clippy::new_without_default)]
+impl VirtioRngProber {
+    pub fn new() -> VirtioRngProber {
+        VirtioRngProber {}
+    }
+}
+
+impl DeviceProber for VirtioRngProber {
+    fn name(&self) -> &'static str {
+        "virtio-rng"
+    }
+
+    fn probe_pci(&self, pci_device: &PciDevice) -> ProbeResult {
+        // Check if the device is an entropy source ("4.1.2 PCI Device Discovery").
+        if pci_device.config().vendor_id() != 0x1af4 {
+            return ProbeResult::NotSupported;
+        }
+
+        // Check if the it's a legacy or traditional device.
+        let device_id = pci_device.config().device_id();
+        if device_id != 0x1040 + 4 && device_id != 0x1005 {
+            return ProbeResult::NotSupported;
+        }
+
+        trace!("virtio-rng: found the device (over PCI)");
+        let transport = match VirtioModernPci::probe_pci(pci_device) {
+            Ok(transport) => transport,
+            Err(VirtioAttachError::InvalidVendorId) => {
+                // Not a virtio device.
+                return ProbeResult::NotSupported;
+            }
+            Err(err) => {
+                trace!("failed to attach a virtio-rng as a modern device: {:?}, falling back to the legacy driver", err);
+                match VirtioLegacyPci::probe_pci(pci_device) {
+                    Ok(transport) => transport,
+                    Err(err) => {
+                        warn!(
+                            "failed to attach a virtio-rng as a legacy device: {:?}",
+                            err
+                        );
+                        return ProbeResult::Failed;
+                    }
+                }
+            }
+        };
+
+        let device = match VirtioRng::new(transport) {
+            Ok(device) => Arc::new(SpinLock::new(device)),
+            Err(err) => {
+                warn!("failed to initialize virtio-rng: {:?}", err);
+                return ProbeResult::Failed;
+            }
+        };
+
+        let irq_device = device.clone();
+        attach_irq(pci_device.config().interrupt_line(), move || {
+            irq_device.lock().handle_irq();
+        });
+        register_rng_driver(Box::new(VirtioRngDriver::new(device)));
+
+        ProbeResult::Bound
+    }
+
+    fn probe_virtio_mmio(&self, mmio_device: &VirtioMmioDevice) -> ProbeResult {
+        let mmio = mmio_device.mmio_base.as_vaddr();
+        let magic = unsafe { *mmio.as_ptr::<u32>() };
+        let virtio_version = unsafe { *mmio.add(4).as_ptr::<u32>() };
+        let device_id = unsafe { *mmio.add(8).as_ptr::<u32>() };
+
+        if magic != 0x74726976 {
+            return ProbeResult::NotSupported;
+        }
+
+        if virtio_version != 2 {
+            warn!("unsupported virtio device version: {}", virtio_version);
+            return ProbeResult::NotSupported;
+        }
+
+        // It looks like a virtio device. Check if the device is an entropy
+        // source.
+        if device_id != 4 {
+            return ProbeResult::NotSupported;
+        }
+
+        trace!("virtio-rng: found the device (over MMIO)");
+
+        let transport = Arc::new(VirtioMmio::new(mmio_device.mmio_base));
+        let device = match VirtioRng::new(transport) {
+            Ok(device) => Arc::new(SpinLock::new(device)),
+            Err(VirtioAttachError::InvalidVendorId) => {
+                // Not a virtio device.
+                return ProbeResult::NotSupported;
+            }
+            Err(err) => {
+                warn!("failed to attach a virtio-rng: {:?}", err);
+                return ProbeResult::Failed;
+            }
+        };
+
+        let irq_device = device.clone();
+        attach_irq(mmio_device.irq, move || {
+            irq_device.lock().handle_irq();
+        });
+        register_rng_driver(Box::new(VirtioRngDriver::new(device)));
+
+        ProbeResult::Bound
+    }
+}
+
+pub fn init() {
+    register_driver_prober(Box::new(VirtioRngProber::new()));
+}
@@ -3,9 +3,11 @@ use core::arch::global_asm;
 use kerla_runtime::{address::UserVAddr, arch::PAGE_SIZE};
 
 global_asm!(include_str!("usermode.S"));
+global_asm!(include_str!("vdso.S"));
 
 mod arch_prctl;
 mod process;
+mod vdso;
 
 pub const KERNEL_STACK_SIZE: usize = PAGE_SIZE * 256;
 pub const USER_VALLOC_END: UserVAddr = unsafe { UserVAddr::new_unchecked(0x0000_0fff_0000_0000) };
@@ -14,3 +16,4 @@ pub const USER_STACK_TOP: UserVAddr = USER_VALLOC_BASE;
 
 pub use arch_prctl::arch_prctl;
 pub use process::{switch_thread, Process};
+pub use vdso::{update_vvar, vdso_area_len, vdso_area_page, VDSO_IMAGE_OFFSET};
@@ -42,8 +42,7 @@ unsafe fn push_stack(mut rsp: *mut u64, value: u64) -> *mut u64 {
 }
 
 impl Process {
-    #[allow(unused)]
-    pub fn new_kthread(ip: VAddr, sp: VAddr) -> Process {
+    pub fn new_kthread(ip: VAddr) -> Process {
         let interrupt_stack = alloc_pages_owned(
             KERNEL_STACK_SIZE / PAGE_SIZE,
             AllocPageFlags::KERNEL | AllocPageFlags::DIRTY_OK,
@@ -61,7 +60,8 @@ impl Process {
         .expect("failed to allocate kernel stack");
 
         let rsp = unsafe {
-            let mut rsp: *mut u64 = sp.as_mut_ptr();
+            let kernel_sp = kernel_stack.as_vaddr().add(KERNEL_STACK_SIZE);
+            let mut rsp: *mut u64 = kernel_sp.as_mut_ptr();
 
             // Registers to be restored in kthread_entry().
             rsp = push_stack(rsp, ip.value() as u64); // The entry point.
@@ -282,6 +282,11 @@ impl Process {
         Ok(())
     }
 
+    /// The FS base register (used for thread-local storage).
+    pub fn fsbase(&self) -> u64 {
+        self.fsbase.load()
+    }
+
     pub fn setup_sigreturn_stack(&self, current_frame: &mut PtRegs, signaled_frame: &PtRegs) {
         *current_frame = *signaled_frame;
     }
@@ -0,0 +1,88 @@
+//! The vDSO (virtual dynamic shared object): a small shared library mapped into
+//! every process so that it can read clocks without entering the kernel.
+//!
+//! The vDSO area consists of the vvar page followed by the vDSO image
+//! (`vdso.S`). Both are in the kernel image and shared among all processes.
+use core::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use kerla_runtime::{
+    address::{PAddr, VAddr},
+    arch::{tsc_params, PAGE_SIZE},
+};
+
+/// The data read by the vDSO. Keep in sync with the offsets in `vdso.S`.
+///
+/// The wall clock parameters are the ones in `WallClockState` (`timer.rs`):
+/// the vDSO computes the wall clock in the same way as the kernel.
+#[repr(C, align(4096))]
+struct Vvar {
+    tsc_at_boot: AtomicU64,
+    /// Nanoseconds per TSC cycle in the 32.32 fixed-point representation.
+    tsc_scale: AtomicU64,
+    /// Incremented before and after updating the wall clock parameters: it's
+    /// odd while they're being updated. The vDSO retries reading them if it
+    /// has changed.
+    seq: AtomicU64,
+    /// The wall clock in nanoseconds at `mono_base`.
+    wall_base: AtomicI64,
+    /// The monotonic clock in nanoseconds.
+    mono_base: AtomicU64,
+    /// The frequency adjustment in the `timex.freq` format.
+    freq: AtomicI64,
+    /// The offset in nanoseconds to be slewed since `mono_base`.
+    offset: AtomicI64,
+}
+
+static VVAR: Vvar = Vvar {
+    tsc_at_boot: AtomicU64::new(0),
+    tsc_scale: AtomicU64::new(0),
+    seq: AtomicU64::new(0),
+    wall_base: AtomicI64::new(0),
+    mono_base: AtomicU64::new(0),
+    freq: AtomicI64::new(0),
+    offset: AtomicI64::new(0),
+};
+
+extern "C" {
+    static __vdso_image_start: u8;
+    static __vdso_image_end: u8;
+}
+
+/// The offset of the vDSO image in the vDSO area.
+pub const VDSO_IMAGE_OFFSET: usize = PAGE_SIZE;
+
+fn vdso_image() -> (VAddr, usize) {
+    unsafe {
+        let start = &__vdso_image_start as *const u8 as usize;
+        let end = &__vdso_image_end as *const u8 as usize;
+        (VAddr::new(start), end - start)
+    }
+}
+
+/// Returns the length of the vDSO area: the vvar page and the vDSO image.
+pub fn vdso_area_len() -> usize {
+    VDSO_IMAGE_OFFSET + vdso_image().1
+}
+
+/// Returns the physical page at `offset` in the vDSO area.
+pub fn vdso_area_page(offset: usize) -> PAddr {
+    debug_assert!(offset < vdso_area_len());
+    if offset < VDSO_IMAGE_OFFSET {
+        VAddr::new(&VVAR as *const Vvar as usize).as_paddr()
+    } else {
+        vdso_image().0.add(offset - VDSO_IMAGE_OFFSET).as_paddr()
+    }
+}
+
+/// Updates the vvar page with the wall clock parameters: see `Vvar` for the
+/// meaning of each one.
+pub fn update_vvar(wall_base: i64, mono_base: usize, freq: i64, offset: i64) {
+    let (tsc_at_boot, tsc_scale) = tsc_params();
+    VVAR.seq.fetch_add(1, Ordering::AcqRel);
+    VVAR.tsc_at_boot.store(tsc_at_boot, Ordering::Relaxed);
+    VVAR.tsc_scale.store(tsc_scale, Ordering::Relaxed);
+    VVAR.wall_base.store(wall_base, Ordering::Relaxed);
+    VVAR.mono_base.store(mono_base as u64, Ordering::Relaxed);
+    VVAR.freq.store(freq, Ordering::Relaxed);
+    VVAR.offset.store(offset, Ordering::Relaxed);
+    VVAR.seq.fetch_add(1, Ordering::AcqRel);
+}
@@ -0,0 +1,329 @@
+//! The buffer cache: caches contents of block devices in page-sized buffers.
+//! Writes are kept in the cache until `fsync(2)`, `sync(2)`, or eviction.
+use super::{block_devices, wait_for_completion, BlockDevice, IoRequest, IO_WAIT_QUEUE};
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+use kerla_api::driver::block::{BlockOp, SECTOR_SIZE};
+use kerla_runtime::{
+    arch::PAGE_SIZE,
+    page_allocator::{alloc_pages_owned, AllocPageFlags, OwnedPages},
+    spinlock::SpinLock,
+};
+
+/// The size of a buffer in bytes.
+const BUFFER_SIZE: usize = PAGE_SIZE;
+/// The maximum number of buffers in the cache.
+const NUM_BUFFERS_MAX: usize = 4096;
+/// The maximum number of dirty buffers written back at once on eviction.
+const NUM_WRITE_BACK_MAX: usize = 32;
+
+static BUFFER_CACHE: SpinLock<BufferCache> = SpinLock::new(BufferCache::new());
+
+/// The block device ID and the buffer index (the offset divided by
+/// `BUFFER_SIZE`).
+type BufferKey = (usize, u64);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum BufferState {
+    /// The contents are being read from the device.
+    Loading,
+    /// Failed to read the contents.
+    Invalid,
+    Clean,
+    /// Modified but not yet written back to the device.
+    Dirty,
+}
+
+struct Buffer {
+    page: OwnedPages,
+    /// The length of the valid part: a buffer at the end of the device might
+    /// be shorter than `BUFFER_SIZE`.
+    len: usize,
+    state: SpinLock<BufferState>,
+}
+
+impl Buffer {
+    fn data(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.page.as_ptr(), self.len) }
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    fn data_mut(&self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.page.as_mut_ptr(), self.len) }
+    }
+}
+
+struct CacheEntry {
+    buffer: Arc<Buffer>,
+    /// The value of `BufferCache::clock` when it's used last time.
+    last_used: u64,
+}
+
+struct BufferCache {
+    entries: BTreeMap<BufferKey, CacheEntry>,
+    clock: u64,
+}
+
+impl BufferCache {
+    const fn new() -> BufferCache {
+        BufferCache {
+            entries: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// Makes a room for a new buffer by evicting the least recently used
+    /// clean buffer. If there're only dirty ones, returns some of them
+    /// to be written back.
+    fn evict(&mut self) -> Vec<(BufferKey, Arc<Buffer>)> {
+        let mut unused = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| Arc::strong_count(&entry.buffer) == 1)
+            .map(|(key, entry)| (*key, entry.last_used, *entry.buffer.state.lock()))
+            .collect::<Vec<_>>();
+        unused.sort_by_key(|(_, last_used, _)| *last_used);
+
+        let clean = unused
+            .iter()
+            .find(|(_, _, state)| matches!(state, BufferState::Clean | BufferState::Invalid));
+        if let Some((key, _, _)) = clean {
+            self.entries.remove(key);
+            return Vec::new();
+        }
+
+        // All buffers are dirty or in use. If they're all in use, just grow
+        // the cache.
+        unused
+            .iter()
+            .filter(|(_, _, state)| *state == BufferState::Dirty)
+            .take(NUM_WRITE_BACK_MAX)
+            .map(|(key, _, _)| (*key, self.entries[key].buffer.clone()))
+            .collect()
+    }
+}
+
+/// Writes back buffers. Their states are changed into `Clean` beforehand: if
+/// they're modified during the I/O, they become dirty again.
+fn write_back(buffers: Vec<(BufferKey, Arc<Buffer>)>) -> Result<()> {
+    let mut result = Ok(());
+    for device in block_devices() {
+        let mut requests = Vec::new();
+        let mut written = Vec::new();
+        for ((id, index), buffer) in &buffers {
+            if *id != device.id {
+                continue;
+            }
+
+            let mut state = buffer.state.lock();
+            if *state != BufferState::Dirty {
+                continue;
+            }
+
+            *state = BufferState::Clean;
+            requests.push(IoRequest {
+                op: BlockOp::Write,
+                sector: index * (BUFFER_SIZE / SECTOR_SIZE) as u64,
+                buf: *buffer.page,
+                len: buffer.len,
+            });
+            written.push(buffer.clone());
+        }
+
+        if requests.is_empty() {
+            continue;
+        }
+
+        if let Err(err) = device.do_io(requests) {
+            // Keep the data in the cache.
+            for buffer in written {
+                *buffer.state.lock() = BufferState::Dirty;
+            }
+
+            result = Err(err);
+        }
+    }
+
+    result
+}
+
+/// Returns the dirty buffers of the device.
+fn dirty_buffers(device: &BlockDevice) -> Vec<(BufferKey, Arc<Buffer>)> {
+    BUFFER_CACHE
+        .lock()
+        .entries
+        .range((device.id, 0)..=(device.id, u64::MAX))
+        .filter(|(_, entry)| *entry.buffer.state.lock() == BufferState::Dirty)
+        .map(|(key, entry)| (*key, entry.buffer.clone()))
+        .collect()
+}
+
+impl BlockDevice {
+    /// Returns the buffer at `index`. If `load` is `false`, the buffer is not
+    /// read from the device when it's not in the cache: the caller is going to
+    /// overwrite it entirely. Such a new buffer is returned in the `Loading`
+    /// state so that others wait for it: the caller must fill it, mark it as
+    /// dirty, and wake up `IO_WAIT_QUEUE`.
+    fn get_buffer(&self, index: u64, load: bool) -> Result<Arc<Buffer>> {
+        let key = (self.id, index);
+        loop {
+            let (buffer, created) = {
+                let mut cache = BUFFER_CACHE.lock();
+                cache.clock += 1;
+                let now = cache.clock;
+                if let Some(entry) = cache.entries.get_mut(&key) {
+                    entry.last_used = now;
+                    (entry.buffer.clone(), false)
+                } else {
+                    if cache.entries.len() >= NUM_BUFFERS_MAX {
+                        let dirty = cache.evict();
+                        if !dirty.is_empty() {
+                            drop(cache);
+                            write_back(dirty)?;
+                            continue;
+                        }
+                    }
+
+                    let offset = index * BUFFER_SIZE as u64;
+                    let buffer = Arc::new(Buffer {
+                        page: alloc_pages_owned(1, AllocPageFlags::KERNEL)?,
+                        len: core::cmp::min(BUFFER_SIZE as u64, self.size() - offset) as usize,
+                        state: SpinLock::new(BufferState::Loading),
+                    });
+                    cache.entries.insert(
+                        key,
+                        CacheEntry {
+                            buffer: buffer.clone(),
+                            last_used: now,
+                        },
+                    );
+                    (buffer, true)
+                }
+            };
+
+            if created && !load {
+                return Ok(buffer);
+            }
+
+            if created {
+                let result = self.do_io(vec![IoRequest {
+                    op: BlockOp::Read,
+                    sector: index * (BUFFER_SIZE / SECTOR_SIZE) as u64,
+                    buf: *buffer.page,
+                    len: buffer.len,
+                }]);
+
+                let new_state = if result.is_ok() {
+                    BufferState::Clean
+                } else {
+                    BUFFER_CACHE.lock().entries.remove(&key);
+                    BufferState::Invalid
+                };
+
+                // Wake up processes waiting for the buffer.
+                *buffer.state.lock() = new_state;
+                IO_WAIT_QUEUE.wake_all();
+                return result.map(|_| buffer);
+            }
+
+            // Wait for another process to load the buffer.
+            let state = wait_for_completion(|| match *buffer.state.lock() {
+                BufferState::Loading => None,
+                state => Some(state),
+            });
+
+            if state == BufferState::Invalid {
+                return Err(Errno::EIO.into());
+            }
+
+            return Ok(buffer);
+        }
+    }
+
+    /// Reads bytes at `offset` through the buffer cache. Returns the number
+    /// of bytes read, which is smaller than `buf.len()` at the end of the
+    /// device.
+    pub(super) fn read_buffers(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let mut read_len = 0;
+        while read_len < buf.len() {
+            let pos = offset + read_len as u64;
+            if pos >= self.size() {
+                break;
+            }
+
+            let buffer = self.get_buffer(pos / BUFFER_SIZE as u64, true)?;
+            let buffer_offset = (pos % BUFFER_SIZE as u64) as usize;
+            let copy_len = core::cmp::min(buf.len() - read_len, buffer.len - buffer_offset);
+
+            let _state = buffer.state.lock();
+            buf[read_len..(read_len + copy_len)]
+                .copy_from_slice(&buffer.data()[buffer_offset..(buffer_offset + copy_len)]);
+            read_len += copy_len;
+        }
+
+        Ok(read_len)
+    }
+
+    /// Writes bytes at `offset` into the buffer cache. They're written back to
+    /// the device on `sync`.
+    pub(super) fn write_buffers(&self, offset: u64, buf: &[u8]) -> Result<usize> {
+        if self.is_read_only() {
+            return Err(Errno::EROFS.into());
+        }
+
+        let mut written_len = 0;
+        while written_len < buf.len() {
+            let pos = offset + written_len as u64;
+            if pos >= self.size() {
+                if written_len == 0 {
+                    return Err(Errno::ENOSPC.into());
+                }
+
+                break;
+            }
+
+            let buffer_offset = (pos % BUFFER_SIZE as u64) as usize;
+            let copy_len = core::cmp::min(buf.len() - written_len, BUFFER_SIZE - buffer_offset);
+            let overwrite_all = buffer_offset == 0 && copy_len == BUFFER_SIZE;
+            let buffer = self.get_buffer(pos / BUFFER_SIZE as u64, !overwrite_all)?;
+            let copy_len = core::cmp::min(copy_len, buffer.len - buffer_offset);
+
+            let mut state = buffer.state.lock();
+            buffer.data_mut()[buffer_offset..(buffer_offset + copy_len)]
+                .copy_from_slice(&buf[written_len..(written_len + copy_len)]);
+            let filled = *state == BufferState::Loading;
+            *state = BufferState::Dirty;
+            drop(state);
+            if filled {
+                // Wake up processes waiting for the new buffer (see
+                // `get_buffer`).
+                IO_WAIT_QUEUE.wake_all();
+            }
+
+            written_len += copy_len;
+        }
+
+        Ok(written_len)
+    }
+
+    /// Writes back dirty buffers of the device.
+    pub(super) fn sync_buffers(&self) -> Result<()> {
+        write_back(dirty_buffers(self))
+    }
+}
+
+/// Writes back all dirty buffers (`sync(2)`).
+pub fn sync_all() -> Result<()> {
+    let mut result = Ok(());
+    // Partitions share buffers with their disks.
+    for device in block_devices()
+        .iter()
+        .filter(|device| !device.is_partition())
+    {
+        if let Err(err) = device.sync() {
+            result = Err(err);
+        }
+    }
+
+    result
+}
@@ -0,0 +1,393 @@
+//! The block layer: block devices registered by drivers and the buffer cache
+//! between them and file systems.
+use crate::{fs::devfs::DEV_FS, prelude::*, process::WaitQueue};
+use alloc::boxed::Box;
+use kerla_api::driver::block::{BlockDriver, BlockIoError, BlockOp, BlockRequest, SECTOR_SIZE};
+use kerla_runtime::{address::PAddr, spinlock::SpinLock};
+use partition::read_partition_table;
+
+mod buffer_cache;
+mod partition;
+
+pub use buffer_cache::sync_all;
+
+static BLOCK_DEVICES: SpinLock<Vec<Arc<BlockDevice>>> = SpinLock::new(Vec::new());
+/// Processes waiting for I/O requests to complete.
+static IO_WAIT_QUEUE: WaitQueue = WaitQueue::new();
+
+/// An I/O request to be submitted to the driver.
+struct IoRequest {
+    op: BlockOp,
+    sector: u64,
+    buf: PAddr,
+    len: usize,
+}
+
+/// The major number of virtio-blk in Linux.
+const VIRTBLK_MAJOR: usize = 254;
+/// The number of minor numbers reserved for a disk and its partitions.
+const VIRTBLK_MINORS: usize = 16;
+
+enum Backend {
+    /// A disk: requests are submitted to the driver.
+    Driver(Box<dyn BlockDriver>),
+    /// A partition in a disk: requests are forwarded to the disk.
+    Partition(Partition),
+}
+
+struct Partition {
+    disk: Arc<BlockDevice>,
+    /// The partition number: `1` in `vda1`.
+    number: usize,
+    start_sector: u64,
+    num_sectors: u64,
+    uuid: String,
+    label: Option<String>,
+}
+
+/// A block device: a disk registered by a driver or a partition in it.
+pub struct BlockDevice {
+    /// The index in `BLOCK_DEVICES`. Used as a key in the buffer cache.
+    id: usize,
+    /// The device name (e.g. `vda` or `vda1`).
+    name: String,
+    minor: usize,
+    backend: Backend,
+}
+
+impl BlockDevice {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn major(&self) -> usize {
+        VIRTBLK_MAJOR
+    }
+
+    pub fn minor(&self) -> usize {
+        self.minor
+    }
+
+    fn driver(&self) -> &dyn BlockDriver {
+        match &self.backend {
+            Backend::Driver(driver) => driver.as_ref(),
+            Backend::Partition(partition) => partition.disk.driver(),
+        }
+    }
+
+    fn partition(&self) -> Option<&Partition> {
+        match &self.backend {
+            Backend::Driver(_) => None,
+            Backend::Partition(partition) => Some(partition),
+        }
+    }
+
+    pub fn is_partition(&self) -> bool {
+        self.partition().is_some()
+    }
+
+    /// The partition number: `1` in `vda1`.
+    pub fn partition_number(&self) -> Option<usize> {
+        self.partition().map(|partition| partition.number)
+    }
+
+    /// The first sector of the partition in the disk. It's 0 for disks.
+    pub fn start_sector(&self) -> u64 {
+        self.partition()
+            .map(|partition| partition.start_sector)
+            .unwrap_or(0)
+    }
+
+    /// The `PARTUUID` of the partition.
+    pub fn partuuid(&self) -> Option<&str> {
+        self.partition().map(|partition| partition.uuid.as_str())
+    }
+
+    /// The `PARTLABEL` of the partition (GPT only).
+    pub fn partlabel(&self) -> Option<&str> {
+        self.partition()
+            .and_then(|partition| partition.label.as_deref())
+    }
+
+    /// The size of the device in sectors.
+    pub fn num_sectors(&self) -> u64 {
+        match &self.backend {
+            Backend::Driver(driver) => driver.num_sectors(),
+            Backend::Partition(partition) => partition.num_sectors,
+        }
+    }
+
+    /// The size of the device in bytes.
+    pub fn size(&self) -> u64 {
+        self.num_sectors() * SECTOR_SIZE as u64
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.driver().block_size()
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.driver().is_read_only()
+    }
+
+    /// Reads bytes at `offset` through the buffer cache. Returns the number
+    /// of bytes read, which is smaller than `buf.len()` at the end of the
+    /// device.
+    pub fn read(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        match &self.backend {
+            Backend::Driver(_) => self.read_buffers(offset, buf),
+            Backend::Partition(partition) => {
+                if offset >= self.size() {
+                    return Ok(0);
+                }
+
+                let len = core::cmp::min(buf.len() as u64, self.size() - offset) as usize;
+                let disk_offset = partition.start_sector * SECTOR_SIZE as u64 + offset;
+                partition.disk.read_buffers(disk_offset, &mut buf[..len])
+            }
+        }
+    }
+
+    /// Writes bytes at `offset` into the buffer cache. They're written back to
+    /// the device on `sync`.
+    pub fn write(&self, offset: u64, buf: &[u8]) -> Result<usize> {
+        match &self.backend {
+            Backend::Driver(_) => self.write_buffers(offset, buf),
+            Backend::Partition(partition) => {
+                if offset >= self.size() {
+                    return Err(Errno::ENOSPC.into());
+                }
+
+                let len = core::cmp::min(buf.len() as u64, self.size() - offset) as usize;
+                let disk_offset = partition.start_sector * SECTOR_SIZE as u64 + offset;
+                partition.disk.write_buffers(disk_offset, &buf[..len])
+            }
+        }
+    }
+
+    /// Writes back dirty buffers and the device's write cache. Syncing a
+    /// partition syncs the whole disk.
+    pub fn sync(&self) -> Result<()> {
+        match &self.backend {
+            Backend::Driver(_) => {
+                self.sync_buffers()?;
+                self.flush()
+            }
+            Backend::Partition(partition) => partition.disk.sync(),
+        }
+    }
+
+    /// Submits requests at once and waits for all of them to complete.
+    fn do_io(&self, requests: Vec<IoRequest>) -> Result<()> {
+        let mut statuses = Vec::with_capacity(requests.len());
+        for request in requests {
+            let status = Arc::new(SpinLock::new(None));
+            let completion_status = status.clone();
+            self.driver().submit(BlockRequest {
+                op: request.op,
+                sector: request.sector,
+                buf: request.buf,
+                len: request.len,
+                completion: Box::new(move |result| {
+                    *completion_status.lock() = Some(result);
+                    IO_WAIT_QUEUE.wake_all();
+                }),
+            });
+            statuses.push(status);
+        }
+
+        // Wait for all requests even if some of them have failed: the device
+        // may still be using the buffers.
+        let mut result = Ok(());
+        for status in statuses {
+            let status: core::result::Result<(), BlockIoError> =
+                wait_for_completion(|| *status.lock());
+            if let Err(err) = status {
+                warn!("{}: I/O error: {:?}", self.name, err);
+                result = Err(err.into());
+            }
+        }
+
+        result
+    }
+
+    /// Writes back the device's volatile write cache.
+    fn flush(&self) -> Result<()> {
+        self.do_io(vec![IoRequest {
+            op: BlockOp::Flush,
+            sector: 0,
+            buf: PAddr::new(0),
+            len: 0,
+        }])
+    }
+}
+
+/// Waits for `is_done` to return `Some`. It's rechecked every time an I/O
+/// request is completed.
+fn wait_for_completion<F, R>(is_done: F) -> R
+where
+    F: FnMut() -> Option<R>,
+{
+    IO_WAIT_QUEUE.sleep_or_idle_until(is_done)
+}
+
+/// Returns the registered block devices.
+pub fn block_devices() -> Vec<Arc<BlockDevice>> {
+    BLOCK_DEVICES.lock().clone()
+}
+
+/// Adds a block device to `BLOCK_DEVICES` and `/dev`.
+fn add_block_device(name: String, minor: usize, backend: Backend) -> Arc<BlockDevice> {
+    let device = {
+        let mut devices = BLOCK_DEVICES.lock();
+        let device = Arc::new(BlockDevice {
+            id: devices.len(),
+            name,
+            minor,
+            backend,
+        });
+        devices.push(device.clone());
+        device
+    };
+
+    DEV_FS.add_block_device(device.clone());
+    device
+}
+
+/// Returns the name of the `index`-th disk in the Linux's scheme: `vda`,
+/// ..., `vdz`, `vdaa`, `vdab`, ...
+fn disk_name(index: usize) -> String {
+    let mut suffix = Vec::new();
+    let mut n = index;
+    loop {
+        suffix.push(b'a' + (n % 26) as u8);
+        n /= 26;
+        if n == 0 {
+            break;
+        }
+
+        n -= 1;
+    }
+
+    suffix.reverse();
+    format!("vd{}", core::str::from_utf8(&suffix).unwrap())
+}
+
+pub fn register_block_driver(driver: Box<dyn BlockDriver>) {
+    let disk_index = block_devices()
+        .iter()
+        .filter(|device| !device.is_partition())
+        .count();
+    let name = disk_name(disk_index);
+    info!(
+        "block: {}: {} sectors ({} MiB){}",
+        name,
+        driver.num_sectors(),
+        driver.num_sectors() * SECTOR_SIZE as u64 / (1024 * 1024),
+        if driver.is_read_only() {
+            ", read-only"
+        } else {
+            ""
+        }
+    );
+
+    add_block_device(name, disk_index * VIRTBLK_MINORS, Backend::Driver(driver));
+}
+
+/// Reads partition tables in the disks and registers their partitions as
+/// block devices (e.g. `vda1`).
+pub fn init() {
+    for disk in block_devices() {
+        if disk.is_partition() {
+            continue;
+        }
+
+        let entries = match read_partition_table(&disk) {
+            Ok(entries) => entries,
+            Err(err) => {
+                warn!(
+                    "{}: failed to read the partition table: {:?}",
+                    disk.name(),
+                    err
+                );
+                continue;
+            }
+        };
+
+        for entry in entries {
+            if entry.number >= VIRTBLK_MINORS {
+                warn!(
+                    "{}: too many partitions, ignoring #{}",
+                    disk.name(),
+                    entry.number
+                );
+                continue;
+            }
+
+            let name = format!("{}{}", disk.name(), entry.number);
+            info!(
+                "block: {}: {} sectors at {}, PARTUUID={}{}",
+                name,
+                entry.num_sectors,
+                entry.start_sector,
+                entry.uuid,
+                match &entry.label {
+                    Some(label) => format!(", PARTLABEL={}", label),
+                    None => String::new(),
+                }
+            );
+
+            add_block_device(
+                name,
+                disk.minor() + entry.number,
+                Backend::Partition(Partition {
+                    disk: disk.clone(),
+                    number: entry.number,
+                    start_sector: entry.start_sector,
+                    num_sectors: entry.num_sectors,
+                    uuid: entry.uuid,
+                    label: entry.label,
+                }),
+            );
+        }
+    }
+}
+
+/// Looks for the block device specified in the `root` kernel parameter: a
+/// device name (`vda1` or `/dev/vda1`), `PARTUUID=<uuid>`, or
+/// `PARTLABEL=<label>`.
+pub fn lookup_block_device(spec: &str) -> Option<Arc<BlockDevice>> {
+    let mut devices = block_devices().into_iter();
+    if let Some(uuid) = spec.strip_prefix("PARTUUID=") {
+        devices.find(|device| {
+            device
+                .partuuid()
+                .map(|partuuid| partuuid.eq_ignore_ascii_case(uuid))
+                .unwrap_or(false)
+        })
+    } else if let Some(label) = spec.strip_prefix("PARTLABEL=") {
+        devices.find(|device| device.partlabel() == Some(label))
+    } else {
+        let name = spec.strip_prefix("/dev/").unwrap_or(spec);
+        devices.find(|device| device.name() == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn disk_names() {
+        assert_eq!(disk_name(0), "vda");
+        assert_eq!(disk_name(25), "vdz");
+        assert_eq!(disk_name(26), "vdaa");
+        assert_eq!(disk_name(27), "vdab");
+        assert_eq!(disk_name(26 + 26 * 26 - 1), "vdzz");
+        assert_eq!(disk_name(26 + 26 * 26), "vdaaa");
+    }
+}
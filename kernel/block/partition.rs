@@ -0,0 +1,326 @@
+//! Partition tables: GPT and MBR (including logical partitions in extended
+//! partitions).
+use super::BlockDevice;
+use crate::prelude::*;
+use kerla_api::driver::block::SECTOR_SIZE;
+
+/// The MBR partition type of the protective MBR in GPT disks.
+const MBR_TYPE_GPT_PROTECTIVE: u8 = 0xee;
+/// The MBR partition types of extended partitions.
+const MBR_TYPES_EXTENDED: [u8; 3] = [0x05, 0x0f, 0x85];
+/// The maximum number of logical partitions we follow in an extended
+/// partition. It prevents infinite loops in a broken EBR chain.
+const NUM_LOGICAL_PARTITIONS_MAX: usize = 64;
+/// The maximum size of the GPT partition entry array we read in bytes.
+const GPT_ENTRIES_SIZE_MAX: usize = 1024 * 1024;
+
+/// A partition found in a partition table.
+pub struct PartitionEntry {
+    /// The partition number: `1` in `vda1`.
+    pub number: usize,
+    pub start_sector: u64,
+    pub num_sectors: u64,
+    /// The unique partition GUID in GPT, or the disk signature followed by the
+    /// partition number in MBR (e.g. `1234abcd-01`).
+    pub uuid: String,
+    /// The partition name (GPT only).
+    pub label: Option<String>,
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(buf[offset..(offset + 2)].try_into().unwrap())
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..(offset + 4)].try_into().unwrap())
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..(offset + 8)].try_into().unwrap())
+}
+
+/// Computes the CRC32 (IEEE 802.3) checksum used in GPT.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffff_u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+/// Formats a GUID in the mixed-endian textual representation.
+fn format_guid(guid: &[u8]) -> String {
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        read_u32(guid, 0),
+        read_u16(guid, 4),
+        read_u16(guid, 6),
+        guid[8],
+        guid[9],
+        guid[10],
+        guid[11],
+        guid[12],
+        guid[13],
+        guid[14],
+        guid[15]
+    )
+}
+
+struct PartitionTableReader<'a> {
+    disk: &'a BlockDevice,
+    /// The size of a LBA in bytes.
+    lba_size: u64,
+}
+
+impl<'a> PartitionTableReader<'a> {
+    fn read_lba(&self, lba: u64, len: usize) -> Result<Vec<u8>> {
+        let offset = lba
+            .checked_mul(self.lba_size)
+            .ok_or_else(|| Error::new(Errno::EINVAL))?;
+        let mut buf = vec![0; len];
+        if self.disk.read(offset, &mut buf)? != len {
+            return Err(Errno::EIO.into());
+        }
+
+        Ok(buf)
+    }
+
+    /// Converts a range in LBAs into one in sectors. Returns `None` if it's
+    /// beyond the end of the disk.
+    fn lba_to_sectors(&self, start_lba: u64, num_lbas: u64) -> Option<(u64, u64)> {
+        let sectors_per_lba = self.lba_size / SECTOR_SIZE as u64;
+        let start_sector = start_lba.checked_mul(sectors_per_lba)?;
+        let num_sectors = num_lbas.checked_mul(sectors_per_lba)?;
+        if num_sectors == 0 || start_sector.checked_add(num_sectors)? > self.disk.num_sectors() {
+            return None;
+        }
+
+        Some((start_sector, num_sectors))
+    }
+
+    fn read_gpt(&self) -> Result<Vec<PartitionEntry>> {
+        let mut header = self.read_lba(1, self.lba_size as usize)?;
+        if &header[0..8] != b"EFI PART" {
+            warn!("{}: invalid GPT header signature", self.disk.name());
+            return Ok(Vec::new());
+        }
+
+        let header_size = read_u32(&header, 12) as usize;
+        if !(92..=header.len()).contains(&header_size) {
+            warn!(
+                "{}: invalid GPT header size: {}",
+                self.disk.name(),
+                header_size
+            );
+            return Ok(Vec::new());
+        }
+
+        // The header CRC32 is computed with the CRC32 field zeroed.
+        let header_crc = read_u32(&header, 16);
+        header[16..20].fill(0);
+        if crc32(&header[..header_size]) != header_crc {
+            warn!("{}: GPT header CRC32 mismatch", self.disk.name());
+            return Ok(Vec::new());
+        }
+
+        let entries_lba = read_u64(&header, 72);
+        let num_entries = read_u32(&header, 80) as usize;
+        let entry_size = read_u32(&header, 84) as usize;
+        if !(128..=4096).contains(&entry_size) {
+            warn!(
+                "{}: invalid GPT entry size: {}",
+                self.disk.name(),
+                entry_size
+            );
+            return Ok(Vec::new());
+        }
+
+        let entries_size = num_entries
+            .checked_mul(entry_size)
+            .ok_or_else(|| Error::new(Errno::EINVAL))?;
+        if entries_size > GPT_ENTRIES_SIZE_MAX {
+            warn!(
+                "{}: too many GPT entries: {}",
+                self.disk.name(),
+                num_entries
+            );
+            return Ok(Vec::new());
+        }
+
+        let entries = self.read_lba(entries_lba, entries_size)?;
+        if crc32(&entries) != read_u32(&header, 88) {
+            warn!("{}: GPT entry array CRC32 mismatch", self.disk.name());
+            return Ok(Vec::new());
+        }
+
+        let mut partitions = Vec::new();
+        for (i, entry) in entries.chunks_exact(entry_size).enumerate() {
+            // An unused entry has the zero type GUID.
+            if entry[0..16].iter().all(|b| *b == 0) {
+                continue;
+            }
+
+            let first_lba = read_u64(entry, 32);
+            let last_lba = read_u64(entry, 40);
+            let range = last_lba
+                .checked_sub(first_lba)
+                .and_then(|len| len.checked_add(1))
+                .and_then(|num_lbas| self.lba_to_sectors(first_lba, num_lbas));
+            let (start_sector, num_sectors) = match range {
+                Some(range) => range,
+                None => {
+                    warn!("{}: invalid GPT entry #{}", self.disk.name(), i + 1);
+                    continue;
+                }
+            };
+
+            // The partition name in UTF-16LE.
+            let name = entry[56..128]
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .take_while(|c| *c != 0);
+            let label: String = char::decode_utf16(name)
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect();
+
+            partitions.push(PartitionEntry {
+                number: i + 1,
+                start_sector,
+                num_sectors,
+                uuid: format_guid(&entry[16..32]),
+                label: if label.is_empty() { None } else { Some(label) },
+            });
+        }
+
+        Ok(partitions)
+    }
+
+    /// Reads logical partitions in the extended partition starting at
+    /// `extended_lba`.
+    fn read_logical_partitions(
+        &self,
+        extended_lba: u64,
+        disk_signature: u32,
+        partitions: &mut Vec<PartitionEntry>,
+    ) -> Result<()> {
+        let mut ebr_lba = extended_lba;
+        for number in 5..(5 + NUM_LOGICAL_PARTITIONS_MAX) {
+            let ebr = self.read_lba(ebr_lba, 512)?;
+            if ebr[510..512] != [0x55, 0xaa] {
+                warn!("{}: invalid EBR signature", self.disk.name());
+                break;
+            }
+
+            // The first entry describes the logical partition relative to the
+            // EBR.
+            let start_lba = ebr_lba + read_u32(&ebr, 446 + 8) as u64;
+            let num_lbas = read_u32(&ebr, 446 + 12) as u64;
+            match self.lba_to_sectors(start_lba, num_lbas) {
+                Some((start_sector, num_sectors)) => {
+                    partitions.push(PartitionEntry {
+                        number,
+                        start_sector,
+                        num_sectors,
+                        uuid: format!("{:08x}-{:02x}", disk_signature, number),
+                        label: None,
+                    });
+                }
+                None => {
+                    warn!(
+                        "{}: invalid logical partition #{}",
+                        self.disk.name(),
+                        number
+                    );
+                }
+            }
+
+            // The second entry points to the next EBR relative to the
+            // extended partition.
+            let next = read_u32(&ebr, 446 + 16 + 8) as u64;
+            if next == 0 {
+                break;
+            }
+
+            ebr_lba = extended_lba + next;
+        }
+
+        Ok(())
+    }
+
+    fn read_mbr(&self, mbr: &[u8]) -> Result<Vec<PartitionEntry>> {
+        let disk_signature = read_u32(mbr, 440);
+        let mut partitions = Vec::new();
+        for i in 0..4 {
+            let entry = &mbr[(446 + i * 16)..(446 + (i + 1) * 16)];
+            let partition_type = entry[4];
+            let start_lba = read_u32(entry, 8) as u64;
+            let num_lbas = read_u32(entry, 12) as u64;
+            if partition_type == 0 || num_lbas == 0 {
+                continue;
+            }
+
+            if MBR_TYPES_EXTENDED.contains(&partition_type) {
+                self.read_logical_partitions(start_lba, disk_signature, &mut partitions)?;
+                continue;
+            }
+
+            match self.lba_to_sectors(start_lba, num_lbas) {
+                Some((start_sector, num_sectors)) => {
+                    partitions.push(PartitionEntry {
+                        number: i + 1,
+                        start_sector,
+                        num_sectors,
+                        uuid: format!("{:08x}-{:02x}", disk_signature, i + 1),
+                        label: None,
+                    });
+                }
+                None => {
+                    warn!("{}: invalid MBR entry #{}", self.disk.name(), i + 1);
+                }
+            }
+        }
+
+        partitions.sort_by_key(|partition| partition.number);
+        Ok(partitions)
+    }
+}
+
+/// Reads the partition table in the disk. Returns an empty `Vec` if it's not
+/// partitioned.
+pub fn read_partition_table(disk: &BlockDevice) -> Result<Vec<PartitionEntry>> {
+    let reader = PartitionTableReader {
+        disk,
+        lba_size: core::cmp::max(disk.block_size(), SECTOR_SIZE) as u64,
+    };
+
+    let mbr = reader.read_lba(0, 512)?;
+    if mbr[510..512] != [0x55, 0xaa] {
+        return Ok(Vec::new());
+    }
+
+    let is_gpt = (0..4).any(|i| mbr[446 + i * 16 + 4] == MBR_TYPE_GPT_PROTECTIVE);
+    if is_gpt {
+        reader.read_gpt()
+    } else {
+        reader.read_mbr(&mbr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+}
@@ -0,0 +1,136 @@
+use core::fmt;
+
+use crate::{
+    block::BlockDevice,
+    ctypes::{c_int, c_ulong},
+    fs::{
+        inode::{FileLike, INodeNo, PollStatus},
+        opened_file::OpenOptions,
+        stat::{DevId, FileMode, Stat, S_IFBLK},
+    },
+    prelude::*,
+    user_buffer::{UserBufReader, UserBufWriter, UserBuffer, UserBufferMut},
+};
+use kerla_api::driver::block::SECTOR_SIZE;
+use kerla_runtime::address::UserVAddr;
+
+const BLKROGET: usize = 0x125e;
+const BLKGETSIZE: usize = 0x1260;
+const BLKFLSBUF: usize = 0x1261;
+const BLKSSZGET: usize = 0x1268;
+const BLKGETSIZE64: usize = 0x80081272;
+
+/// The maximum number of bytes copied through the kernel buffer at once.
+const CHUNK_LEN_MAX: usize = 64 * 1024;
+
+/// A block device file (e.g. `/dev/vda`). Reads and writes go through the
+/// buffer cache.
+pub(super) struct BlockDeviceFile {
+    device: Arc<BlockDevice>,
+    inode_no: INodeNo,
+    rdev: DevId,
+}
+
+impl BlockDeviceFile {
+    pub fn new(device: Arc<BlockDevice>, inode_no: INodeNo, rdev: DevId) -> BlockDeviceFile {
+        BlockDeviceFile {
+            device,
+            inode_no,
+            rdev,
+        }
+    }
+}
+
+impl fmt::Debug for BlockDeviceFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockDeviceFile")
+            .field("name", &self.device.name())
+            .finish()
+    }
+}
+
+impl FileLike for BlockDeviceFile {
+    fn stat(&self) -> Result<Stat> {
+        Ok(Stat {
+            inode_no: self.inode_no,
+            mode: FileMode::new(S_IFBLK | 0o660),
+            rdev: self.rdev,
+            ..Stat::zeroed()
+        })
+    }
+
+    fn read(&self, offset: usize, buf: UserBufferMut<'_>, _options: &OpenOptions) -> Result<usize> {
+        let mut chunk = vec![0; core::cmp::min(buf.len(), CHUNK_LEN_MAX)];
+        let mut writer = UserBufWriter::from(buf);
+        while writer.remaining_len() > 0 {
+            let chunk_len = core::cmp::min(writer.remaining_len(), chunk.len());
+            let read_len = self.device.read(
+                (offset + writer.written_len()) as u64,
+                &mut chunk[..chunk_len],
+            )?;
+            if read_len == 0 {
+                break;
+            }
+
+            writer.write_bytes(&chunk[..read_len])?;
+        }
+
+        Ok(writer.written_len())
+    }
+
+    fn write(&self, offset: usize, buf: UserBuffer<'_>, _options: &OpenOptions) -> Result<usize> {
+        let mut chunk = vec![0; core::cmp::min(buf.len(), CHUNK_LEN_MAX)];
+        let mut reader = UserBufReader::from(buf);
+        let mut written_len = 0;
+        loop {
+            let read_len = reader.read_bytes(&mut chunk)?;
+            if read_len == 0 {
+                break;
+            }
+
+            let pos = (offset + written_len) as u64;
+            match self.device.write(pos, &chunk[..read_len]) {
+                Ok(len) => written_len += len,
+                Err(_) if written_len > 0 => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(written_len)
+    }
+
+    fn fsync(&self) -> Result<()> {
+        self.device.sync()
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus::POLLIN | PollStatus::POLLOUT)
+    }
+
+    fn ioctl(&self, cmd: usize, arg: usize) -> Result<isize> {
+        match cmd {
+            BLKROGET => {
+                let arg = UserVAddr::new_nonnull(arg)?;
+                arg.write::<c_int>(&(self.device.is_read_only() as c_int))?;
+            }
+            BLKGETSIZE => {
+                let arg = UserVAddr::new_nonnull(arg)?;
+                arg.write::<c_ulong>(&(self.device.size() / SECTOR_SIZE as u64))?;
+            }
+            BLKFLSBUF => {
+                self.device.sync()?;
+            }
+            BLKSSZGET => {
+                let arg = UserVAddr::new_nonnull(arg)?;
+                arg.write::<c_int>(&(self.device.block_size() as c_int))?;
+            }
+            BLKGETSIZE64 => {
+                let arg = UserVAddr::new_nonnull(arg)?;
+                arg.write::<u64>(&self.device.size())?;
+            }
+            _ => return Err(Errno::ENOTTY.into()),
+        }
+
+        Ok(0)
+    }
+}
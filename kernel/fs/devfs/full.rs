@@ -0,0 +1,58 @@
+use core::fmt;
+
+use crate::{
+    fs::{
+        inode::{FileLike, INodeNo},
+        opened_file::OpenOptions,
+        stat::{FileMode, Stat, S_IFCHR},
+    },
+    result::{Errno, Result},
+    user_buffer::UserBuffer,
+    user_buffer::{UserBufWriter, UserBufferMut},
+};
+
+/// The `/dev/full` file: reads return zeroes like `/dev/zero` and writes fail
+/// with `ENOSPC`.
+pub(super) struct FullFile {}
+
+impl FullFile {
+    pub fn new() -> FullFile {
+        FullFile {}
+    }
+}
+
+impl fmt::Debug for FullFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DevFull").finish()
+    }
+}
+
+impl FileLike for FullFile {
+    fn stat(&self) -> Result<Stat> {
+        Ok(Stat {
+            inode_no: INodeNo::new(7),
+            mode: FileMode::new(S_IFCHR | 0o666),
+            ..Stat::zeroed()
+        })
+    }
+
+    fn read(
+        &self,
+        _offset: usize,
+        buf: UserBufferMut<'_>,
+        _options: &OpenOptions,
+    ) -> Result<usize> {
+        let len = buf.len();
+        let mut writer = UserBufWriter::from(buf);
+        writer.fill(0, len)?;
+        Ok(len)
+    }
+
+    fn write(&self, _offset: usize, buf: UserBuffer<'_>, _options: &OpenOptions) -> Result<usize> {
+        if buf.len() == 0 {
+            return Ok(0);
+        }
+
+        Err(Errno::ENOSPC.into())
+    }
+}
@@ -0,0 +1,169 @@
+use core::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use alloc::{format, string::String, sync::Arc};
+
+use crate::{
+    fs::{
+        inode::{FileLike, INodeNo, PollStatus},
+        opened_file::OpenOptions,
+        stat::{FileMode, Stat, S_IFCHR},
+    },
+    logger::{wake_log_readers, LogRecord, KERNEL_LOG_BUF, LOG_MESSAGE_LEN_MAX, LOG_WAIT_QUEUE},
+    result::{Errno, Result},
+    user_buffer::{UserBufReader, UserBufWriter, UserBuffer, UserBufferMut},
+};
+
+fn kmsg_stat() -> Result<Stat> {
+    Ok(Stat {
+        inode_no: INodeNo::new(8),
+        mode: FileMode::new(S_IFCHR | 0o644),
+        ..Stat::zeroed()
+    })
+}
+
+/// Logs a line written into `/dev/kmsg`.
+fn write_kmsg(buf: UserBuffer<'_>) -> Result<usize> {
+    let mut message = [0; LOG_MESSAGE_LEN_MAX];
+    let mut reader = UserBufReader::from(buf);
+    let len = reader.read_bytes(&mut message)?;
+    let mut message = &message[..len];
+
+    // An optional syslog prefix: "<N>".
+    let mut level = 6;
+    if message.first() == Some(&b'<') {
+        if let Some(end) = message.iter().position(|&ch| ch == b'>') {
+            let prefix = core::str::from_utf8(&message[1..end]).ok();
+            if let Some(value) = prefix.and_then(|s| s.parse::<u32>().ok()) {
+                level = value & 7;
+                message = &message[end + 1..];
+            }
+        }
+    }
+
+    if message.last() == Some(&b'\n') {
+        message = &message[..message.len() - 1];
+    }
+
+    let message = String::from_utf8_lossy(message);
+    match level {
+        0..=3 => error!("{}", message),
+        4 => warn!("{}", message),
+        5 | 6 => info!("{}", message),
+        _ => debug!("{}", message),
+    }
+
+    wake_log_readers();
+    Ok(reader.buffer_len())
+}
+
+/// The `/dev/kmsg` file. Each `open(2)` creates a `KmsgReader`.
+pub(super) struct KmsgFile {}
+
+impl KmsgFile {
+    pub fn new() -> KmsgFile {
+        KmsgFile {}
+    }
+}
+
+impl fmt::Debug for KmsgFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DevKmsg").finish()
+    }
+}
+
+impl FileLike for KmsgFile {
+    fn open(&self, _options: &OpenOptions) -> Result<Option<Arc<dyn FileLike>>> {
+        Ok(Some(Arc::new(KmsgReader::new()) as Arc<dyn FileLike>))
+    }
+
+    fn stat(&self) -> Result<Stat> {
+        kmsg_stat()
+    }
+
+    fn write(&self, _offset: usize, buf: UserBuffer<'_>, _options: &OpenOptions) -> Result<usize> {
+        write_kmsg(buf)
+    }
+}
+
+/// An opened `/dev/kmsg`. Each reader has its own position in the log.
+struct KmsgReader {
+    /// The sequence number of the next record to be read.
+    next_seq: AtomicU64,
+}
+
+impl KmsgReader {
+    fn new() -> KmsgReader {
+        KmsgReader {
+            next_seq: AtomicU64::new(KERNEL_LOG_BUF.lock().first_seq()),
+        }
+    }
+}
+
+impl fmt::Debug for KmsgReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KmsgReader")
+            .field("next_seq", &self.next_seq.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+impl FileLike for KmsgReader {
+    fn stat(&self) -> Result<Stat> {
+        kmsg_stat()
+    }
+
+    fn read(&self, _offset: usize, buf: UserBufferMut<'_>, options: &OpenOptions) -> Result<usize> {
+        let record: LogRecord = LOG_WAIT_QUEUE.sleep_signalable_until(|| {
+            // Copy the record so that we don't hold the lock while copying
+            // into the user buffer.
+            let log = KERNEL_LOG_BUF.lock();
+            let seq = self.next_seq.load(Ordering::Relaxed);
+            if seq < log.first_seq() {
+                // The record has been overwritten. Skip to the oldest one.
+                self.next_seq.store(log.first_seq(), Ordering::Relaxed);
+                return Err(Errno::EPIPE.into());
+            }
+
+            match log.get_record(seq) {
+                Some(record) => Ok(Some(*record)),
+                None if options.nonblock => Err(Errno::EAGAIN.into()),
+                None => Ok(None),
+            }
+        })?;
+
+        // "<level>,<seq>,<timestamp>,<flags>;<message>\n"
+        let header = format!(
+            "{},{},{},-;",
+            record.level(),
+            record.seq(),
+            record.timestamp()
+        );
+        let message = record.message();
+        if header.len() + message.len() + 1 > buf.len() {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let mut writer = UserBufWriter::from(buf);
+        writer.write_bytes(header.as_bytes())?;
+        writer.write_bytes(message)?;
+        writer.write_bytes(b"\n")?;
+        self.next_seq.store(record.seq() + 1, Ordering::Relaxed);
+        Ok(writer.written_len())
+    }
+
+    fn write(&self, _offset: usize, buf: UserBuffer<'_>, _options: &OpenOptions) -> Result<usize> {
+        write_kmsg(buf)
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        let mut status = PollStatus::POLLOUT;
+        if self.next_seq.load(Ordering::Relaxed) < KERNEL_LOG_BUF.lock().next_seq() {
+            status |= PollStatus::POLLIN;
+        }
+
+        Ok(status)
+    }
+}
@@ -1,9 +1,14 @@
-use self::{null::NullFile, tty::Tty};
+use self::{
+    block::BlockDeviceFile, full::FullFile, kmsg::KmsgFile, null::NullFile, random::RandomFile,
+    tty::Tty, zero::ZeroFile,
+};
 
 use crate::{
+    block::BlockDevice,
     fs::{
-        file_system::FileSystem,
-        inode::{Directory, FileLike},
+        file_system::{register_file_system_type, FileSystem, FileSystemType, FsStat},
+        inode::{Directory, FileLike, INodeNo},
+        stat::DevId,
     },
     result::Result,
     tty::pty::Ptmx,
@@ -13,14 +18,22 @@ use kerla_utils::once::Once;
 
 use super::tmpfs::TmpFs;
 
+mod block;
+mod full;
+mod kmsg;
 mod null;
+mod random;
 mod tty;
+mod zero;
 
 pub static DEV_FS: Once<Arc<DevFs>> = Once::new();
 static NULL_FILE: Once<Arc<dyn FileLike>> = Once::new();
 pub static SERIAL_TTY: Once<Arc<Tty>> = Once::new();
 pub static PTMX: Once<Arc<Ptmx>> = Once::new();
 
+/// The inode number of the first block device file.
+const BLOCK_DEVICE_INODE_NO_BASE: usize = 0x100;
+
 pub struct DevFs(TmpFs);
 
 impl DevFs {
@@ -36,18 +49,49 @@ impl DevFs {
         root_dir.add_file("null", NULL_FILE.clone());
         root_dir.add_file("tty", SERIAL_TTY.clone() as Arc<dyn FileLike>);
         root_dir.add_file("console", SERIAL_TTY.clone() as Arc<dyn FileLike>);
+        root_dir.add_file("ttyS0", SERIAL_TTY.clone() as Arc<dyn FileLike>);
         root_dir.add_file("ptmx", PTMX.clone() as Arc<dyn FileLike>);
+        root_dir.add_file("random", Arc::new(RandomFile::new(INodeNo::new(4), true)));
+        root_dir.add_file("urandom", Arc::new(RandomFile::new(INodeNo::new(5), false)));
+        root_dir.add_file("zero", Arc::new(ZeroFile::new()));
+        root_dir.add_file("full", Arc::new(FullFile::new()));
+        root_dir.add_file("kmsg", Arc::new(KmsgFile::new()));
 
         DevFs(tmpfs)
     }
+
+    /// Adds a block device file (e.g. `/dev/vda` or `/dev/vda1`).
+    pub fn add_block_device(&self, device: Arc<BlockDevice>) {
+        let file = BlockDeviceFile::new(
+            device.clone(),
+            INodeNo::new(BLOCK_DEVICE_INODE_NO_BASE + device.id()),
+            DevId::new(device.major(), device.minor()),
+        );
+        self.0
+            .root_tmpfs_dir()
+            .add_file(device.name(), Arc::new(file) as Arc<dyn FileLike>);
+    }
 }
 
 impl FileSystem for DevFs {
+    fn name(&self) -> &'static str {
+        "devtmpfs"
+    }
+
     fn root_dir(&self) -> Result<Arc<dyn Directory>> {
         self.0.root_dir()
     }
+
+    fn statfs(&self) -> Result<FsStat> {
+        self.0.statfs()
+    }
 }
 
 pub fn init() {
     DEV_FS.init(|| Arc::new(DevFs::new()));
+    register_file_system_type(FileSystemType {
+        name: "devtmpfs",
+        requires_device: false,
+        mount: |_source, _data| Ok(DEV_FS.clone()),
+    });
 }
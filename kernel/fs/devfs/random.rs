@@ -0,0 +1,60 @@
+use core::fmt;
+
+use crate::{
+    fs::{
+        inode::{FileLike, INodeNo},
+        opened_file::OpenOptions,
+        stat::{FileMode, Stat, S_IFCHR},
+    },
+    random::{
+        add_user_randomness, read_insecure_random, read_secure_random, try_read_secure_random,
+    },
+    result::Result,
+    user_buffer::UserBuffer,
+    user_buffer::UserBufferMut,
+};
+
+/// The `/dev/random` and `/dev/urandom` files.
+pub(super) struct RandomFile {
+    inode_no: INodeNo,
+    /// Reads block until the CRNG is initialized (`/dev/random`).
+    blocking: bool,
+}
+
+impl RandomFile {
+    pub fn new(inode_no: INodeNo, blocking: bool) -> RandomFile {
+        RandomFile { inode_no, blocking }
+    }
+}
+
+impl fmt::Debug for RandomFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DevRandom")
+            .field("blocking", &self.blocking)
+            .finish()
+    }
+}
+
+impl FileLike for RandomFile {
+    fn stat(&self) -> Result<Stat> {
+        Ok(Stat {
+            inode_no: self.inode_no,
+            mode: FileMode::new(S_IFCHR | 0o666),
+            ..Stat::zeroed()
+        })
+    }
+
+    fn read(&self, _offset: usize, buf: UserBufferMut<'_>, options: &OpenOptions) -> Result<usize> {
+        if !self.blocking {
+            read_insecure_random(buf)
+        } else if options.nonblock {
+            try_read_secure_random(buf)
+        } else {
+            read_secure_random(buf)
+        }
+    }
+
+    fn write(&self, _offset: usize, buf: UserBuffer<'_>, _options: &OpenOptions) -> Result<usize> {
+        add_user_randomness(buf)
+    }
+}
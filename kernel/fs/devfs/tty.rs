@@ -65,6 +65,10 @@ impl fmt::Debug for Tty {
 }
 
 impl FileLike for Tty {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
     fn ioctl(&self, cmd: usize, arg: usize) -> Result<isize> {
         match cmd {
             TIOCGPGRP => {
@@ -0,0 +1,797 @@
+use super::{
+    file::{Ext2File, Ext2Symlink},
+    file_type_from_mode, make_inode, read_u16, read_u32, write_u16, write_u32, Inode, Locked,
+    Volume, FEATURE_INCOMPAT_FILETYPE, INODE_FLAG_INDEX, LINK_MAX, ROOT_INO,
+};
+use crate::{
+    fs::{
+        inode::{DirEntry, Directory, FileType, INode, INodeNo, RenameFlags, SetAttr},
+        stat::{
+            FileMode, Stat, S_IFBLK, S_IFCHR, S_IFDIR, S_IFIFO, S_IFLNK, S_IFMT, S_IFREG, S_IFSOCK,
+        },
+    },
+    prelude::*,
+};
+use core::fmt;
+use kerla_utils::{alignment::align_up, downcast::downcast};
+
+/// The maximum length of a file name.
+const NAME_LEN_MAX: usize = 255;
+/// The size of a directory entry without the name.
+const DIR_ENTRY_HEADER_SIZE: usize = 8;
+
+/// Converts `S_IFMT` bits into the file type in directory entries.
+fn dir_entry_file_type(mode: u32) -> u8 {
+    match mode & S_IFMT {
+        S_IFREG => 1,
+        S_IFDIR => 2,
+        S_IFCHR => 3,
+        S_IFBLK => 4,
+        S_IFIFO => 5,
+        S_IFSOCK => 6,
+        S_IFLNK => 7,
+        _ => 0,
+    }
+}
+
+/// The size of a directory entry including the padding.
+fn dir_entry_len(name_len: usize) -> usize {
+    align_up(DIR_ENTRY_HEADER_SIZE + name_len, 4)
+}
+
+/// A directory entry in a directory block.
+struct RawDirEntry {
+    /// The offset in the block.
+    offset: usize,
+    /// The inode number. 0 if the entry is unused.
+    ino: u32,
+    rec_len: usize,
+    name_len: usize,
+    file_type: u8,
+}
+
+impl RawDirEntry {
+    fn name<'a>(&self, block: &'a [u8]) -> &'a [u8] {
+        let start = self.offset + DIR_ENTRY_HEADER_SIZE;
+        &block[start..(start + self.name_len)]
+    }
+}
+
+/// A directory entry found by [`Locked::locate_entry`].
+struct EntryLocation {
+    /// The block number.
+    block: u64,
+    /// The block contents.
+    data: Vec<u8>,
+    entry: RawDirEntry,
+    /// The offset of the preceding entry in the block.
+    prev: Option<usize>,
+}
+
+fn parse_dir_block(block: &[u8]) -> Result<Vec<RawDirEntry>> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    while offset + DIR_ENTRY_HEADER_SIZE <= block.len() {
+        let rec_len = read_u16(block, offset + 4) as usize;
+        let name_len = block[offset + 6] as usize;
+        if rec_len < DIR_ENTRY_HEADER_SIZE
+            || rec_len % 4 != 0
+            || offset + rec_len > block.len()
+            || DIR_ENTRY_HEADER_SIZE + name_len > rec_len
+        {
+            warn!("ext2: corrupted directory entry");
+            return Err(Errno::EIO.into());
+        }
+
+        entries.push(RawDirEntry {
+            offset,
+            ino: read_u32(block, offset),
+            rec_len,
+            name_len,
+            file_type: block[offset + 7],
+        });
+        offset += rec_len;
+    }
+
+    Ok(entries)
+}
+
+impl<'a> Locked<'a> {
+    /// Reads the directory blocks. Holes are skipped.
+    fn read_dir_blocks(&self, dir: &Inode) -> Result<Vec<(u64, Vec<u8>)>> {
+        let num_blocks = dir.size() / self.vol.block_size() as u64;
+        let mut blocks = Vec::new();
+        for index in 0..num_blocks {
+            if let Some(block) = self.bmap(dir, index)? {
+                blocks.push((block, self.vol.read_block(block)?));
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    /// Looks for the entry.
+    fn locate_entry(&self, dir: &Inode, name: &str) -> Result<Option<EntryLocation>> {
+        for (block, data) in self.read_dir_blocks(dir)? {
+            let mut prev = None;
+            for entry in parse_dir_block(&data)? {
+                if entry.ino != 0 && entry.name(&data) == name.as_bytes() {
+                    return Ok(Some(EntryLocation {
+                        block,
+                        data,
+                        entry,
+                        prev,
+                    }));
+                }
+
+                prev = Some(entry.offset);
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Looks for the inode number of the entry.
+    fn find_entry(&self, dir: &Inode, name: &str) -> Result<Option<u32>> {
+        Ok(self.locate_entry(dir, name)?.map(|loc| loc.entry.ino))
+    }
+
+    /// Returns `true` if the directory has no entries except `.` and `..`.
+    fn is_empty_dir(&self, dir: &Inode) -> Result<bool> {
+        for (_, block) in self.read_dir_blocks(dir)? {
+            for entry in parse_dir_block(&block)? {
+                let name = entry.name(&block);
+                if entry.ino != 0 && name != b"." && name != b".." {
+                    return Ok(false);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Returns `true` if `dir` is `ancestor` or its descendant.
+    fn is_ancestor(&self, ancestor: u32, mut dir: u32) -> Result<bool> {
+        // Don't loop forever on a corrupted file system.
+        for _ in 0..self.vol.sb.inodes_count {
+            if dir == ancestor {
+                return Ok(true);
+            }
+
+            if dir == ROOT_INO {
+                return Ok(false);
+            }
+
+            let inode = self.read_inode(dir)?;
+            dir = self
+                .find_entry(&inode, "..")?
+                .ok_or_else(|| Error::new(Errno::EIO))?;
+        }
+
+        warn!("ext2: a loop in the directory tree");
+        Err(Errno::EIO.into())
+    }
+
+    fn write_dir_entry(
+        &self,
+        block: &mut [u8],
+        offset: usize,
+        rec_len: usize,
+        ino: u32,
+        name: &str,
+        mode: u32,
+    ) {
+        let file_type = if self.vol.sb.has_incompat(FEATURE_INCOMPAT_FILETYPE) {
+            dir_entry_file_type(mode)
+        } else {
+            0
+        };
+
+        write_u32(block, offset, ino);
+        write_u16(block, offset + 4, rec_len as u16);
+        block[offset + 6] = name.len() as u8;
+        block[offset + 7] = file_type;
+        let name_offset = offset + DIR_ENTRY_HEADER_SIZE;
+        block[name_offset..(name_offset + name.len())].copy_from_slice(name.as_bytes());
+    }
+
+    /// Adds a directory entry. The caller must check if the name is not used.
+    fn add_entry(&mut self, dir: &mut Inode, name: &str, ino: u32, mode: u32) -> Result<()> {
+        let needed_len = dir_entry_len(name.len());
+        for (block, mut data) in self.read_dir_blocks(dir)? {
+            for entry in parse_dir_block(&data)? {
+                let used_len = if entry.ino == 0 {
+                    0
+                } else {
+                    dir_entry_len(entry.name_len)
+                };
+
+                if entry.rec_len - used_len < needed_len {
+                    continue;
+                }
+
+                // Reuse the unused entry or split the entry.
+                if used_len > 0 {
+                    write_u16(&mut data, entry.offset + 4, used_len as u16);
+                }
+
+                let offset = entry.offset + used_len;
+                self.write_dir_entry(&mut data, offset, entry.rec_len - used_len, ino, name, mode);
+                self.vol.write_bytes(self.vol.block_offset(block), &data)?;
+                return self.update_dir(dir);
+            }
+        }
+
+        // No room in the existing blocks. Append a new block.
+        let block_size = self.vol.block_size();
+        let size = dir.size();
+        let result = self
+            .bmap_alloc(dir, size / block_size as u64)
+            .and_then(|block| {
+                let mut data = vec![0; block_size];
+                self.write_dir_entry(&mut data, 0, block_size, ino, name, mode);
+                self.vol.write_bytes(self.vol.block_offset(block), &data)
+            });
+
+        if let Err(err) = result {
+            // Free the blocks allocated above (including indirect ones).
+            self.truncate_data(dir, size)?;
+            return Err(err);
+        }
+
+        dir.set_size(size + block_size as u64);
+        self.update_dir(dir)
+    }
+
+    /// Removes a directory entry. The link count of the inode is not changed.
+    fn remove_entry(&self, dir: &mut Inode, name: &str) -> Result<()> {
+        let EntryLocation {
+            block,
+            mut data,
+            entry,
+            prev,
+        } = self
+            .locate_entry(dir, name)?
+            .ok_or_else(|| Error::new(Errno::ENOENT))?;
+
+        match prev {
+            // Merge the entry into the preceding one.
+            Some(prev) => {
+                let rec_len = read_u16(&data, prev + 4) as usize + entry.rec_len;
+                write_u16(&mut data, prev + 4, rec_len as u16);
+            }
+            // The first entry in the block: mark it as unused.
+            None => write_u32(&mut data, entry.offset, 0),
+        }
+
+        self.vol.write_bytes(self.vol.block_offset(block), &data)?;
+        self.update_dir(dir)
+    }
+
+    /// Makes an existing directory entry refer to another inode.
+    fn replace_entry(&self, dir: &mut Inode, name: &str, ino: u32, mode: u32) -> Result<()> {
+        let EntryLocation {
+            block,
+            mut data,
+            entry,
+            ..
+        } = self
+            .locate_entry(dir, name)?
+            .ok_or_else(|| Error::new(Errno::ENOENT))?;
+
+        write_u32(&mut data, entry.offset, ino);
+        if self.vol.sb.has_incompat(FEATURE_INCOMPAT_FILETYPE) {
+            data[entry.offset + 7] = dir_entry_file_type(mode);
+        }
+
+        self.vol.write_bytes(self.vol.block_offset(block), &data)?;
+        self.update_dir(dir)
+    }
+
+    /// Decrements the link count of the inode. It's freed if it was the last
+    /// link.
+    fn drop_link(&mut self, ino: u32) -> Result<()> {
+        let mut inode = self.read_inode(ino)?;
+        let links_count = if inode.file_type() == S_IFDIR {
+            // The directory is being removed: "." is also gone. Free the
+            // directory blocks now so that it looks empty to those who still
+            // refer it.
+            self.truncate_data(&mut inode, 0)?;
+            0
+        } else {
+            inode.links_count().saturating_sub(1)
+        };
+
+        inode.set_links_count(links_count);
+        inode.touch_ctime();
+        self.write_inode(&inode)?;
+        if links_count == 0 {
+            self.release_inode(ino)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds `delta` to the link count of the directory.
+    fn add_dir_links(&self, ino: u32, delta: i32) -> Result<()> {
+        let mut dir = self.read_inode(ino)?;
+        dir.set_links_count((dir.links_count() as i32 + delta) as u16);
+        dir.touch_ctime();
+        self.write_inode(&dir)
+    }
+
+    /// `rename(2)` without `RENAME_EXCHANGE`.
+    fn rename_entry(
+        &mut self,
+        old_parent: u32,
+        old_name: &str,
+        new_parent: u32,
+        new_name: &str,
+        no_replace: bool,
+    ) -> Result<()> {
+        self.check_writable()?;
+        if new_name.len() > NAME_LEN_MAX {
+            return Err(Errno::ENAMETOOLONG.into());
+        }
+
+        let old_dir = self.read_inode(old_parent)?;
+        let ino = self
+            .find_entry(&old_dir, old_name)?
+            .ok_or_else(|| Error::new(Errno::ENOENT))?;
+        let inode = self.read_inode(ino)?;
+        let is_dir = inode.file_type() == S_IFDIR;
+
+        let mut new_dir = self.read_inode(new_parent)?;
+        if new_dir.links_count() == 0 {
+            // The directory has been removed.
+            return Err(Errno::ENOENT.into());
+        }
+
+        let target = self.find_entry(&new_dir, new_name)?;
+        if let Some(target) = target {
+            if no_replace {
+                return Err(Errno::EEXIST.into());
+            }
+
+            // "If oldpath and newpath are existing hard links referring to the
+            // same file, then rename() does nothing, and returns a success
+            // status." -- rename(2)
+            if target == ino {
+                return Ok(());
+            }
+
+            let target_inode = self.read_inode(target)?;
+            match (is_dir, target_inode.file_type() == S_IFDIR) {
+                (true, false) => return Err(Errno::ENOTDIR.into()),
+                (false, true) => return Err(Errno::EISDIR.into()),
+                (true, true) if !self.is_empty_dir(&target_inode)? => {
+                    return Err(Errno::ENOTEMPTY.into())
+                }
+                _ => {}
+            }
+        }
+
+        let moves_dir = is_dir && old_parent != new_parent;
+        if moves_dir {
+            if self.is_ancestor(ino, new_parent)? {
+                return Err(Errno::EINVAL.into());
+            }
+
+            if target.is_none() && new_dir.links_count() >= LINK_MAX {
+                return Err(Errno::EMLINK.into());
+            }
+        }
+
+        // Everything looks good. Update the destination.
+        match target {
+            Some(target) => {
+                self.replace_entry(&mut new_dir, new_name, ino, inode.mode())?;
+                if is_dir {
+                    // The replaced directory's ".." no longer refers to
+                    // `new_dir`.
+                    self.add_dir_links(new_parent, -1)?;
+                }
+                self.drop_link(target)?;
+            }
+            None => self.add_entry(&mut new_dir, new_name, ino, inode.mode())?,
+        }
+
+        // Remove the source. `old_dir` may be modified above if it's
+        // `new_dir`: read it again.
+        let mut old_dir = self.read_inode(old_parent)?;
+        self.remove_entry(&mut old_dir, old_name)?;
+
+        let mut inode = self.read_inode(ino)?;
+        if moves_dir {
+            self.replace_entry(&mut inode, "..", new_parent, S_IFDIR)?;
+            self.add_dir_links(old_parent, -1)?;
+            self.add_dir_links(new_parent, 1)?;
+        }
+
+        inode.touch_ctime();
+        self.write_inode(&inode)
+    }
+
+    /// `rename(2)` with `RENAME_EXCHANGE`.
+    fn exchange_entries(
+        &mut self,
+        parent1: u32,
+        name1: &str,
+        parent2: u32,
+        name2: &str,
+    ) -> Result<()> {
+        self.check_writable()?;
+
+        let ino1 = self
+            .find_entry(&self.read_inode(parent1)?, name1)?
+            .ok_or_else(|| Error::new(Errno::ENOENT))?;
+        let ino2 = self
+            .find_entry(&self.read_inode(parent2)?, name2)?
+            .ok_or_else(|| Error::new(Errno::ENOENT))?;
+        if ino1 == ino2 {
+            return Ok(());
+        }
+
+        let mode1 = self.read_inode(ino1)?.mode();
+        let mode2 = self.read_inode(ino2)?.mode();
+        let is_dir1 = mode1 & S_IFMT == S_IFDIR;
+        let is_dir2 = mode2 & S_IFMT == S_IFDIR;
+        if parent1 != parent2 {
+            if (is_dir1 && self.is_ancestor(ino1, parent2)?)
+                || (is_dir2 && self.is_ancestor(ino2, parent1)?)
+            {
+                return Err(Errno::EINVAL.into());
+            }
+
+            if (is_dir1 && !is_dir2 && self.read_inode(parent2)?.links_count() >= LINK_MAX)
+                || (is_dir2 && !is_dir1 && self.read_inode(parent1)?.links_count() >= LINK_MAX)
+            {
+                return Err(Errno::EMLINK.into());
+            }
+        }
+
+        // Read the parents each time: they may be the same directory.
+        self.replace_entry(&mut self.read_inode(parent1)?, name1, ino2, mode2)?;
+        self.replace_entry(&mut self.read_inode(parent2)?, name2, ino1, mode1)?;
+
+        if parent1 != parent2 {
+            if is_dir1 {
+                self.replace_entry(&mut self.read_inode(ino1)?, "..", parent2, S_IFDIR)?;
+            }
+            if is_dir2 {
+                self.replace_entry(&mut self.read_inode(ino2)?, "..", parent1, S_IFDIR)?;
+            }
+
+            // A directory moved to the other parent without one coming back.
+            if is_dir1 && !is_dir2 {
+                self.add_dir_links(parent1, -1)?;
+                self.add_dir_links(parent2, 1)?;
+            } else if is_dir2 && !is_dir1 {
+                self.add_dir_links(parent2, -1)?;
+                self.add_dir_links(parent1, 1)?;
+            }
+        }
+
+        for ino in [ino1, ino2] {
+            let mut inode = self.read_inode(ino)?;
+            inode.touch_ctime();
+            self.write_inode(&inode)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes back the modified directory.
+    fn update_dir(&self, dir: &mut Inode) -> Result<()> {
+        // We don't maintain the hashed index: fall back to linear lookups.
+        dir.set_flags(dir.flags() & !INODE_FLAG_INDEX);
+        dir.touch();
+        self.write_inode(dir)
+    }
+
+    /// Frees an inode allocated for a new entry which couldn't be added to
+    /// the directory, and the blocks allocated to it.
+    fn discard_new_inode(&mut self, inode: &mut Inode) {
+        inode.set_links_count(0);
+        let result = self
+            .write_inode(inode)
+            .and_then(|()| self.free_inode(inode.ino));
+        if let Err(err) = result {
+            warn!("ext2: failed to free inode {}: {:?}", inode.ino, err);
+        }
+    }
+
+    /// Writes the first block of a new directory: `.` and `..` entries.
+    fn init_dir(&mut self, inode: &mut Inode, parent_ino: u32) -> Result<()> {
+        let block_size = self.vol.block_size();
+        let block = self.alloc_block(inode)?;
+        inode.set_block(0, block as u32);
+        inode.set_size(block_size as u64);
+        inode.set_links_count(2);
+
+        let mut data = vec![0; block_size];
+        self.write_dir_entry(&mut data, 0, 12, inode.ino, ".", S_IFDIR);
+        self.write_dir_entry(&mut data, 12, block_size - 12, parent_ino, "..", S_IFDIR);
+        self.vol.write_bytes(self.vol.block_offset(block), &data)?;
+        self.write_inode(inode)
+    }
+
+    /// Checks if we can add a new entry to the directory.
+    fn check_new_entry(&self, dir: &Inode, name: &str) -> Result<()> {
+        self.check_writable()?;
+        if name.len() > NAME_LEN_MAX {
+            return Err(Errno::ENAMETOOLONG.into());
+        }
+
+        if dir.links_count() == 0 {
+            // The directory has been removed.
+            return Err(Errno::ENOENT.into());
+        }
+
+        if self.find_entry(dir, name)?.is_some() {
+            return Err(Errno::EEXIST.into());
+        }
+
+        Ok(())
+    }
+}
+
+pub(super) struct Ext2Dir {
+    vol: Arc<Volume>,
+    ino: u32,
+}
+
+impl Ext2Dir {
+    pub fn new(vol: Arc<Volume>, ino: u32) -> Ext2Dir {
+        vol.get_inode_ref(ino);
+        Ext2Dir { vol, ino }
+    }
+}
+
+impl Drop for Ext2Dir {
+    fn drop(&mut self) {
+        self.vol.put_inode_ref(self.ino);
+    }
+}
+
+impl Directory for Ext2Dir {
+    fn lookup(&self, name: &str) -> Result<INode> {
+        let fs = self.vol.lock();
+        let dir = fs.read_inode(self.ino)?;
+        let ino = fs
+            .find_entry(&dir, name)?
+            .ok_or_else(|| Error::new(Errno::ENOENT))?;
+        let inode = fs.read_inode(ino)?;
+        Ok(make_inode(&self.vol, &inode))
+    }
+
+    fn create_file(&self, name: &str, mode: FileMode) -> Result<INode> {
+        let mut fs = self.vol.lock();
+        let mut dir = fs.read_inode(self.ino)?;
+        fs.check_new_entry(&dir, name)?;
+
+        let mode = S_IFREG | (mode.as_u32() & 0o7777);
+        let mut inode = fs.alloc_inode(&dir, mode)?;
+        inode.set_links_count(1);
+        let result = fs
+            .write_inode(&inode)
+            .and_then(|()| fs.add_entry(&mut dir, name, inode.ino, mode));
+        if let Err(err) = result {
+            fs.discard_new_inode(&mut inode);
+            return Err(err);
+        }
+
+        Ok(make_inode(&self.vol, &inode))
+    }
+
+    fn create_dir(&self, name: &str, mode: FileMode) -> Result<INode> {
+        let mut fs = self.vol.lock();
+        let mut dir = fs.read_inode(self.ino)?;
+        fs.check_new_entry(&dir, name)?;
+        if dir.links_count() >= LINK_MAX {
+            return Err(Errno::EMLINK.into());
+        }
+
+        let mode = S_IFDIR | (mode.as_u32() & 0o7777);
+        let mut inode = fs.alloc_inode(&dir, mode)?;
+        let result = fs
+            .init_dir(&mut inode, dir.ino)
+            .and_then(|()| fs.add_entry(&mut dir, name, inode.ino, mode));
+        if let Err(err) = result {
+            fs.discard_new_inode(&mut inode);
+            return Err(err);
+        }
+
+        // The new directory's ".." refers to the parent. Update it only
+        // after the entry is added so that it's not left incremented on
+        // failures.
+        dir.set_links_count(dir.links_count() + 1);
+        fs.write_inode(&dir)?;
+        Ok(make_inode(&self.vol, &inode))
+    }
+
+    fn stat(&self) -> Result<Stat> {
+        let fs = self.vol.lock();
+        let inode = fs.read_inode(self.ino)?;
+        Ok(fs.stat(&inode))
+    }
+
+    fn set_attr(&self, attr: &SetAttr) -> Result<()> {
+        self.vol.lock().set_attr(self.ino, attr)
+    }
+
+    fn readdir(&self, index: usize) -> Result<Option<DirEntry>> {
+        let fs = self.vol.lock();
+        let dir = fs.read_inode(self.ino)?;
+        let has_file_type = self.vol.sb.has_incompat(FEATURE_INCOMPAT_FILETYPE);
+        let mut i = 0;
+        for (_, block) in fs.read_dir_blocks(&dir)? {
+            for entry in parse_dir_block(&block)? {
+                if entry.ino == 0 {
+                    continue;
+                }
+
+                if i < index {
+                    i += 1;
+                    continue;
+                }
+
+                let file_type = match entry.file_type {
+                    1 if has_file_type => FileType::Regular,
+                    2 if has_file_type => FileType::Directory,
+                    3 if has_file_type => FileType::CharDevice,
+                    4 if has_file_type => FileType::BlockDevice,
+                    5 if has_file_type => FileType::Fifo,
+                    6 if has_file_type => FileType::Socket,
+                    7 if has_file_type => FileType::Link,
+                    _ => file_type_from_mode(fs.read_inode(entry.ino)?.mode()),
+                };
+
+                return Ok(Some(DirEntry {
+                    inode_no: INodeNo::new(entry.ino as usize),
+                    file_type,
+                    name: String::from_utf8_lossy(entry.name(&block)).into_owned(),
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn link(&self, name: &str, link_to: &INode) -> Result<()> {
+        let (vol, ino) = match link_to {
+            INode::FileLike(file) => match downcast::<_, Ext2File>(file) {
+                Some(file) => (&file.vol, file.ino),
+                None => return Err(Errno::EXDEV.into()),
+            },
+            INode::Symlink(symlink) => match downcast::<_, Ext2Symlink>(symlink) {
+                Some(symlink) => (&symlink.vol, symlink.ino),
+                None => return Err(Errno::EXDEV.into()),
+            },
+            // Hard links to directories are not allowed.
+            INode::Directory(_) => return Err(Errno::EPERM.into()),
+        };
+
+        if !Arc::ptr_eq(vol, &self.vol) {
+            return Err(Errno::EXDEV.into());
+        }
+
+        let mut fs = self.vol.lock();
+        let mut dir = fs.read_inode(self.ino)?;
+        fs.check_new_entry(&dir, name)?;
+
+        let mut inode = fs.read_inode(ino)?;
+        if inode.links_count() >= LINK_MAX {
+            return Err(Errno::EMLINK.into());
+        }
+
+        // Increment the link count after the entry is added so that it's not
+        // left incremented on failures.
+        fs.add_entry(&mut dir, name, ino, inode.mode())?;
+        inode.set_links_count(inode.links_count() + 1);
+        inode.touch_ctime();
+        fs.write_inode(&inode)
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        let mut fs = self.vol.lock();
+        fs.check_writable()?;
+        let mut dir = fs.read_inode(self.ino)?;
+        let ino = fs
+            .find_entry(&dir, name)?
+            .ok_or_else(|| Error::new(Errno::ENOENT))?;
+        if fs.read_inode(ino)?.file_type() == S_IFDIR {
+            return Err(Errno::EISDIR.into());
+        }
+
+        fs.remove_entry(&mut dir, name)?;
+        fs.drop_link(ino)
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        let mut fs = self.vol.lock();
+        fs.check_writable()?;
+        let mut dir = fs.read_inode(self.ino)?;
+        let ino = fs
+            .find_entry(&dir, name)?
+            .ok_or_else(|| Error::new(Errno::ENOENT))?;
+        let inode = fs.read_inode(ino)?;
+        if inode.file_type() != S_IFDIR {
+            return Err(Errno::ENOTDIR.into());
+        }
+
+        if !fs.is_empty_dir(&inode)? {
+            return Err(Errno::ENOTEMPTY.into());
+        }
+
+        fs.remove_entry(&mut dir, name)?;
+        // The removed directory's ".." no longer refers to this directory.
+        fs.add_dir_links(self.ino, -1)?;
+        fs.drop_link(ino)
+    }
+
+    fn rename(
+        &self,
+        old_name: &str,
+        new_dir: &Arc<dyn Directory>,
+        new_name: &str,
+        flags: RenameFlags,
+    ) -> Result<()> {
+        let new_dir = match downcast::<_, Ext2Dir>(new_dir) {
+            Some(new_dir) if Arc::ptr_eq(&new_dir.vol, &self.vol) => new_dir,
+            _ => return Err(Errno::EXDEV.into()),
+        };
+
+        if flags.contains(RenameFlags::RENAME_WHITEOUT) {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let mut fs = self.vol.lock();
+        if flags.contains(RenameFlags::RENAME_EXCHANGE) {
+            fs.exchange_entries(self.ino, old_name, new_dir.ino, new_name)
+        } else {
+            let no_replace = flags.contains(RenameFlags::RENAME_NOREPLACE);
+            fs.rename_entry(self.ino, old_name, new_dir.ino, new_name, no_replace)
+        }
+    }
+
+    fn create_symlink(&self, name: &str, target: &str) -> Result<INode> {
+        let mut fs = self.vol.lock();
+        let mut dir = fs.read_inode(self.ino)?;
+        fs.check_new_entry(&dir, name)?;
+        // `Ext2Symlink` reads the target from a single block.
+        if target.len() >= self.vol.block_size() {
+            return Err(Errno::ENAMETOOLONG.into());
+        }
+
+        let mode = S_IFLNK | 0o777;
+        let mut inode = fs.alloc_inode(&dir, mode)?;
+        inode.set_links_count(1);
+        let result = if target.len() < inode.block_area().len() {
+            // A fast symbolic link: store the target in `i_block`.
+            inode.block_area_mut()[..target.len()].copy_from_slice(target.as_bytes());
+            inode.set_size(target.len() as u64);
+            fs.write_inode(&inode)
+        } else {
+            fs.write_data(&mut inode, 0, target.as_bytes()).map(|_| ())
+        };
+
+        let result = result.and_then(|()| fs.add_entry(&mut dir, name, inode.ino, mode));
+        if let Err(err) = result {
+            fs.discard_new_inode(&mut inode);
+            return Err(err);
+        }
+
+        Ok(make_inode(&self.vol, &inode))
+    }
+
+    fn fsync(&self) -> Result<()> {
+        self.vol.device.sync()
+    }
+}
+
+impl fmt::Debug for Ext2Dir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ext2Dir").field("ino", &self.ino).finish()
+    }
+}
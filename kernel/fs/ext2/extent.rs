@@ -0,0 +1,59 @@
+//! Extent trees (ext4). They're read-only.
+use super::{read_u16, read_u32, Inode, Volume};
+use crate::prelude::*;
+
+const EXTENT_MAGIC: u16 = 0xf30a;
+/// The maximum depth of an extent tree.
+const DEPTH_MAX: u16 = 5;
+/// An extent longer than this is uninitialized: it reads as zeroes.
+const INIT_LEN_MAX: u16 = 32768;
+/// The size of the header and each entry in bytes.
+const ENTRY_SIZE: usize = 12;
+
+/// Returns the physical block number of the `index`-th block in the file.
+/// Returns `None` if it's a hole.
+pub(super) fn lookup(vol: &Volume, inode: &Inode, index: u64) -> Result<Option<u64>> {
+    let mut node = inode.block_area().to_vec();
+    let mut expected_depth = None;
+    loop {
+        let num_entries = read_u16(&node, 2) as usize;
+        let depth = read_u16(&node, 6);
+        if read_u16(&node, 0) != EXTENT_MAGIC
+            || depth > DEPTH_MAX
+            || expected_depth
+                .map(|expected| expected != depth)
+                .unwrap_or(false)
+            || ENTRY_SIZE * (num_entries + 1) > node.len()
+        {
+            warn!("ext2: corrupted extent tree in inode {}", inode.ino);
+            return Err(Errno::EIO.into());
+        }
+
+        // Look for the last entry which covers blocks from or before `index`.
+        let entry = (1..=num_entries)
+            .map(|i| &node[(i * ENTRY_SIZE)..((i + 1) * ENTRY_SIZE)])
+            .take_while(|entry| read_u32(entry, 0) as u64 <= index)
+            .last();
+        let entry = match entry {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        if depth == 0 {
+            // A leaf node: the entry is an extent.
+            let first_block = read_u32(entry, 0) as u64;
+            let len = read_u16(entry, 4);
+            if len > INIT_LEN_MAX || index >= first_block + len as u64 {
+                return Ok(None);
+            }
+
+            let start = ((read_u16(entry, 6) as u64) << 32) | read_u32(entry, 8) as u64;
+            return Ok(Some(start + (index - first_block)));
+        }
+
+        // An index node: visit the child node.
+        let child = read_u32(entry, 4) as u64 | ((read_u16(entry, 8) as u64) << 32);
+        node = vol.read_block(child)?;
+        expected_depth = Some(depth - 1);
+    }
+}
@@ -0,0 +1,198 @@
+use super::Volume;
+use crate::{
+    fs::{
+        inode::{FileLike, SetAttr, Symlink},
+        opened_file::OpenOptions,
+        path::PathBuf,
+        stat::{Stat, S_IFREG},
+    },
+    prelude::*,
+    user_buffer::{UserBufReader, UserBufWriter, UserBuffer, UserBufferMut},
+};
+use core::fmt;
+
+/// The maximum number of bytes copied through the kernel buffer at once. The
+/// file system is not locked while accessing the user buffer.
+const CHUNK_LEN_MAX: usize = 64 * 1024;
+
+/// A regular file, or a device file, FIFO, or socket which can't be read or
+/// written.
+pub(super) struct Ext2File {
+    pub(super) vol: Arc<Volume>,
+    pub(super) ino: u32,
+}
+
+impl Ext2File {
+    pub fn new(vol: Arc<Volume>, ino: u32) -> Ext2File {
+        vol.get_inode_ref(ino);
+        Ext2File { vol, ino }
+    }
+}
+
+impl Drop for Ext2File {
+    fn drop(&mut self) {
+        self.vol.put_inode_ref(self.ino);
+    }
+}
+
+impl FileLike for Ext2File {
+    fn stat(&self) -> Result<Stat> {
+        let fs = self.vol.lock();
+        let inode = fs.read_inode(self.ino)?;
+        Ok(fs.stat(&inode))
+    }
+
+    fn set_attr(&self, attr: &SetAttr) -> Result<()> {
+        self.vol.lock().set_attr(self.ino, attr)
+    }
+
+    fn read(&self, offset: usize, buf: UserBufferMut<'_>, _options: &OpenOptions) -> Result<usize> {
+        let mut chunk = vec![0; core::cmp::min(buf.len(), CHUNK_LEN_MAX)];
+        let mut writer = UserBufWriter::from(buf);
+        while writer.remaining_len() > 0 {
+            let chunk_len = core::cmp::min(writer.remaining_len(), chunk.len());
+            let read_len = {
+                let fs = self.vol.lock();
+                let inode = fs.read_inode(self.ino)?;
+                if inode.file_type() != S_IFREG {
+                    return Err(Errno::ENXIO.into());
+                }
+
+                fs.read_data(
+                    &inode,
+                    (offset + writer.written_len()) as u64,
+                    &mut chunk[..chunk_len],
+                )?
+            };
+
+            if read_len == 0 {
+                break;
+            }
+
+            writer.write_bytes(&chunk[..read_len])?;
+        }
+
+        Ok(writer.written_len())
+    }
+
+    fn write(&self, offset: usize, buf: UserBuffer<'_>, _options: &OpenOptions) -> Result<usize> {
+        let mut chunk = vec![0; core::cmp::min(buf.len(), CHUNK_LEN_MAX)];
+        let mut reader = UserBufReader::from(buf);
+        let mut written_len = 0;
+        loop {
+            let read_len = reader.read_bytes(&mut chunk)?;
+            if read_len == 0 {
+                break;
+            }
+
+            let result = {
+                let mut fs = self.vol.lock();
+                let mut inode = fs.read_inode(self.ino)?;
+                if inode.file_type() != S_IFREG {
+                    return Err(Errno::ENXIO.into());
+                }
+
+                let pos = (offset + written_len) as u64;
+                fs.write_data(&mut inode, pos, &chunk[..read_len])
+            };
+
+            match result {
+                Ok(len) => {
+                    written_len += len;
+                    if len < read_len {
+                        break;
+                    }
+                }
+                Err(_) if written_len > 0 => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(written_len)
+    }
+
+    fn truncate(&self, length: usize) -> Result<()> {
+        let mut fs = self.vol.lock();
+        let mut inode = fs.read_inode(self.ino)?;
+        if inode.file_type() != S_IFREG {
+            return Err(Errno::EINVAL.into());
+        }
+
+        fs.truncate_data(&mut inode, length as u64)
+    }
+
+    fn fsync(&self) -> Result<()> {
+        self.vol.device.sync()
+    }
+}
+
+impl fmt::Debug for Ext2File {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ext2File").field("ino", &self.ino).finish()
+    }
+}
+
+pub(super) struct Ext2Symlink {
+    pub(super) vol: Arc<Volume>,
+    pub(super) ino: u32,
+}
+
+impl Ext2Symlink {
+    pub fn new(vol: Arc<Volume>, ino: u32) -> Ext2Symlink {
+        vol.get_inode_ref(ino);
+        Ext2Symlink { vol, ino }
+    }
+}
+
+impl Drop for Ext2Symlink {
+    fn drop(&mut self) {
+        self.vol.put_inode_ref(self.ino);
+    }
+}
+
+impl Symlink for Ext2Symlink {
+    fn stat(&self) -> Result<Stat> {
+        let fs = self.vol.lock();
+        let inode = fs.read_inode(self.ino)?;
+        Ok(fs.stat(&inode))
+    }
+
+    fn set_attr(&self, attr: &SetAttr) -> Result<()> {
+        self.vol.lock().set_attr(self.ino, attr)
+    }
+
+    fn linked_to(&self) -> Result<PathBuf> {
+        let fs = self.vol.lock();
+        let inode = fs.read_inode(self.ino)?;
+        let len = inode.size() as usize;
+
+        let target = if inode.is_fast_symlink(self.vol.block_size()) {
+            inode.block_area()[..len].to_vec()
+        } else {
+            if len > self.vol.block_size() {
+                return Err(Errno::EIO.into());
+            }
+
+            let mut buf = vec![0; len];
+            if fs.read_data(&inode, 0, &mut buf)? != len {
+                return Err(Errno::EIO.into());
+            }
+            buf
+        };
+
+        let target = String::from_utf8(target).map_err(|_| Error::new(Errno::EIO))?;
+        Ok(PathBuf::from(target))
+    }
+
+    fn fsync(&self) -> Result<()> {
+        self.vol.device.sync()
+    }
+}
+
+impl fmt::Debug for Ext2Symlink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ext2Symlink")
+            .field("ino", &self.ino)
+            .finish()
+    }
+}
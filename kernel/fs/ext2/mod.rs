@@ -0,0 +1,1292 @@
+//! The ext2 file system on a block device. ext3 and ext4 file systems (extents,
+//! flex_bg, and 64-bit block numbers) are mounted read-only: we don't replay
+//! or write the journal, nor update extent trees and metadata checksums.
+//!
+//! <https://www.nongnu.org/ext2-doc/ext2.html>
+//! <https://www.kernel.org/doc/html/latest/filesystems/ext4/index.html>
+use crate::{
+    block::{lookup_block_device, BlockDevice},
+    fs::{
+        file_system::{register_file_system_type, FileSystem, FileSystemType, FsStat},
+        inode::{Directory, FileLike, FileType, INode, INodeNo, SetAttr},
+        stat::{
+            BlockCount, BlockSize, DevId, FileMode, FileSize, GId, NLink, Stat, Time, UId, S_IFBLK,
+            S_IFCHR, S_IFDIR, S_IFIFO, S_IFLNK, S_IFMT, S_IFREG, S_IFSOCK,
+        },
+    },
+    prelude::*,
+    process::{Mutex, MutexGuard},
+    timer::read_wall_clock,
+};
+use alloc::collections::{BTreeMap, BTreeSet};
+use kerla_runtime::spinlock::SpinLock;
+
+mod dir;
+mod extent;
+mod file;
+
+use dir::Ext2Dir;
+use file::{Ext2File, Ext2Symlink};
+
+/// The offset of the superblock from the beginning of the device.
+const SUPERBLOCK_OFFSET: u64 = 1024;
+const SUPERBLOCK_SIZE: usize = 1024;
+const EXT2_MAGIC: u16 = 0xef53;
+/// The inode number of the root directory.
+const ROOT_INO: u32 = 2;
+/// The number of direct block pointers in an inode. They're followed by
+/// single, double, and triple indirect block pointers.
+const NUM_DIRECT_BLOCKS: u64 = 12;
+/// The number of bytes we read and write in an on-disk inode. The rest of
+/// larger inodes (e.g. extended attributes in ext4) are left untouched.
+const INODE_RAW_SIZE: usize = 128;
+/// The maximum `i_links_count`.
+const LINK_MAX: u16 = 65000;
+
+const FEATURE_INCOMPAT_FILETYPE: u32 = 0x2;
+const FEATURE_INCOMPAT_RECOVER: u32 = 0x4;
+const FEATURE_INCOMPAT_META_BG: u32 = 0x10;
+const FEATURE_INCOMPAT_EXTENTS: u32 = 0x40;
+const FEATURE_INCOMPAT_64BIT: u32 = 0x80;
+const FEATURE_INCOMPAT_MMP: u32 = 0x100;
+const FEATURE_INCOMPAT_FLEX_BG: u32 = 0x200;
+const FEATURE_INCOMPAT_CSUM_SEED: u32 = 0x2000;
+const FEATURE_INCOMPAT_LARGEDIR: u32 = 0x4000;
+const FEATURE_RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+const FEATURE_RO_COMPAT_LARGE_FILE: u32 = 0x2;
+const FEATURE_RO_COMPAT_HUGE_FILE: u32 = 0x8;
+
+/// The incompatible features we can read.
+const FEATURE_INCOMPAT_SUPPORTED: u32 = FEATURE_INCOMPAT_FILETYPE
+    | FEATURE_INCOMPAT_RECOVER
+    | FEATURE_INCOMPAT_META_BG
+    | FEATURE_INCOMPAT_EXTENTS
+    | FEATURE_INCOMPAT_64BIT
+    | FEATURE_INCOMPAT_MMP
+    | FEATURE_INCOMPAT_FLEX_BG
+    | FEATURE_INCOMPAT_CSUM_SEED
+    | FEATURE_INCOMPAT_LARGEDIR;
+/// The incompatible features we can write.
+const FEATURE_INCOMPAT_WRITABLE: u32 = FEATURE_INCOMPAT_FILETYPE;
+/// The read-only compatible features we can write.
+const FEATURE_RO_COMPAT_WRITABLE: u32 =
+    FEATURE_RO_COMPAT_SPARSE_SUPER | FEATURE_RO_COMPAT_LARGE_FILE;
+
+/// The directory is indexed by a hashed B-tree.
+const INODE_FLAG_INDEX: u32 = 0x1000;
+/// `i_blocks` is in file system blocks instead of 512-byte sectors.
+const INODE_FLAG_HUGE_FILE: u32 = 0x40000;
+/// The inode uses an extent tree instead of indirect blocks.
+const INODE_FLAG_EXTENTS: u32 = 0x80000;
+/// The file data is stored in the inode.
+const INODE_FLAG_INLINE_DATA: u32 = 0x10000000;
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(buf[offset..(offset + 2)].try_into().unwrap())
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..(offset + 4)].try_into().unwrap())
+}
+
+fn write_u16(buf: &mut [u8], offset: usize, value: u16) {
+    buf[offset..(offset + 2)].copy_from_slice(&value.to_le_bytes());
+}
+
+fn write_u32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..(offset + 4)].copy_from_slice(&value.to_le_bytes());
+}
+
+fn now() -> u32 {
+    read_wall_clock().secs_from_epoch() as u32
+}
+
+/// Immutable parameters read from the superblock.
+struct Superblock {
+    block_size: usize,
+    blocks_count: u64,
+    /// Blocks reserved for the superuser.
+    reserved_blocks_count: u64,
+    first_data_block: u64,
+    blocks_per_group: u64,
+    inodes_count: u32,
+    inodes_per_group: u32,
+    inode_size: usize,
+    num_groups: usize,
+    desc_size: usize,
+    first_meta_bg: usize,
+    feature_incompat: u32,
+    feature_ro_compat: u32,
+}
+
+impl Superblock {
+    fn parse(raw: &[u8]) -> Result<Superblock> {
+        if read_u16(raw, 56) != EXT2_MAGIC {
+            return Err(Errno::EINVAL.into());
+        }
+
+        // We support block sizes up to 4 KiB.
+        let log_block_size = read_u32(raw, 24);
+        if log_block_size > 2 {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let rev_level = read_u32(raw, 76);
+        let (inode_size, feature_incompat, feature_ro_compat) = if rev_level == 0 {
+            (128, 0, 0)
+        } else {
+            (
+                read_u16(raw, 88) as usize,
+                read_u32(raw, 96),
+                read_u32(raw, 100),
+            )
+        };
+
+        let unsupported = feature_incompat & !FEATURE_INCOMPAT_SUPPORTED;
+        if unsupported != 0 {
+            warn!("ext2: unsupported incompatible features: {:x}", unsupported);
+            return Err(Errno::EINVAL.into());
+        }
+
+        let is_64bit = feature_incompat & FEATURE_INCOMPAT_64BIT != 0;
+        let block_size = 1024 << log_block_size;
+        let mut blocks_count = read_u32(raw, 4) as u64;
+        let mut reserved_blocks_count = read_u32(raw, 8) as u64;
+        let mut desc_size = 32;
+        if is_64bit {
+            blocks_count |= (read_u32(raw, 0x150) as u64) << 32;
+            reserved_blocks_count |= (read_u32(raw, 0x154) as u64) << 32;
+            desc_size = core::cmp::max(read_u16(raw, 0xfe) as usize, 32);
+        }
+
+        let first_data_block = read_u32(raw, 20) as u64;
+        let blocks_per_group = read_u32(raw, 32) as u64;
+        let inodes_per_group = read_u32(raw, 40);
+        if blocks_per_group == 0
+            || inodes_per_group == 0
+            || blocks_per_group > (block_size * 8) as u64
+            || inodes_per_group as usize > block_size * 8
+            || inode_size < INODE_RAW_SIZE
+            || inode_size > block_size
+            || desc_size > block_size
+            || blocks_count <= first_data_block
+        {
+            return Err(Errno::EINVAL.into());
+        }
+
+        // Inode numbers are used to index the group descriptors: they must
+        // not exceed the inodes in the groups.
+        let num_groups = (blocks_count - first_data_block).div_ceil(blocks_per_group);
+        let inodes_count = read_u32(raw, 0);
+        if inodes_count as u64 > inodes_per_group as u64 * num_groups {
+            warn!("ext2: too many inodes: {}", inodes_count);
+            return Err(Errno::EINVAL.into());
+        }
+
+        Ok(Superblock {
+            block_size,
+            blocks_count,
+            reserved_blocks_count,
+            first_data_block,
+            blocks_per_group,
+            inodes_count,
+            inodes_per_group,
+            inode_size,
+            num_groups: num_groups as usize,
+            desc_size,
+            first_meta_bg: read_u32(raw, 0x104) as usize,
+            feature_incompat,
+            feature_ro_compat,
+        })
+    }
+
+    fn has_incompat(&self, feature: u32) -> bool {
+        self.feature_incompat & feature != 0
+    }
+
+    fn is_writable(&self) -> bool {
+        self.feature_incompat & !FEATURE_INCOMPAT_WRITABLE == 0
+            && self.feature_ro_compat & !FEATURE_RO_COMPAT_WRITABLE == 0
+    }
+
+    /// Returns `true` if the block group contains a copy of the superblock
+    /// and the group descriptors.
+    fn has_superblock(&self, group: usize) -> bool {
+        fn is_power_of(mut n: usize, base: usize) -> bool {
+            while n > 1 && n % base == 0 {
+                n /= base;
+            }
+            n == 1
+        }
+
+        self.feature_ro_compat & FEATURE_RO_COMPAT_SPARSE_SUPER == 0
+            || group <= 1
+            || is_power_of(group, 3)
+            || is_power_of(group, 5)
+            || is_power_of(group, 7)
+    }
+
+    fn group_first_block(&self, group: usize) -> u64 {
+        self.first_data_block + group as u64 * self.blocks_per_group
+    }
+
+    /// The number of blocks in the group. The last group may be smaller.
+    fn blocks_in_group(&self, group: usize) -> u64 {
+        core::cmp::min(
+            self.blocks_per_group,
+            self.blocks_count - self.group_first_block(group),
+        )
+    }
+
+    /// The byte offset of the group descriptor on the device.
+    fn group_desc_offset(&self, group: usize) -> u64 {
+        let descs_per_block = self.block_size / self.desc_size;
+        let desc_block = group / descs_per_block;
+        let block =
+            if self.has_incompat(FEATURE_INCOMPAT_META_BG) && desc_block >= self.first_meta_bg {
+                // The descriptors are in the first group of the meta group.
+                let first_group = desc_block * descs_per_block;
+                self.group_first_block(first_group) + self.has_superblock(first_group) as u64
+            } else {
+                self.first_data_block + 1 + desc_block as u64
+            };
+
+        block * self.block_size as u64 + ((group % descs_per_block) * self.desc_size) as u64
+    }
+}
+
+struct GroupDesc {
+    /// The byte offset of the descriptor on the device.
+    offset: u64,
+    block_bitmap: u64,
+    inode_bitmap: u64,
+    inode_table: u64,
+    free_blocks_count: u32,
+    free_inodes_count: u32,
+    used_dirs_count: u32,
+}
+
+impl GroupDesc {
+    fn parse(offset: u64, raw: &[u8], is_64bit: bool) -> GroupDesc {
+        let hi = |lo_offset: usize, hi_offset: usize| -> u64 {
+            let lo = read_u32(raw, lo_offset) as u64;
+            if is_64bit && raw.len() >= 64 {
+                lo | ((read_u32(raw, hi_offset) as u64) << 32)
+            } else {
+                lo
+            }
+        };
+        let hi16 = |lo_offset: usize, hi_offset: usize| -> u32 {
+            let lo = read_u16(raw, lo_offset) as u32;
+            if is_64bit && raw.len() >= 64 {
+                lo | ((read_u16(raw, hi_offset) as u32) << 16)
+            } else {
+                lo
+            }
+        };
+
+        GroupDesc {
+            offset,
+            block_bitmap: hi(0, 0x20),
+            inode_bitmap: hi(4, 0x24),
+            inode_table: hi(8, 0x28),
+            free_blocks_count: hi16(12, 0x2c),
+            free_inodes_count: hi16(14, 0x2e),
+            used_dirs_count: hi16(16, 0x30),
+        }
+    }
+}
+
+/// An on-disk inode.
+#[derive(Clone)]
+struct Inode {
+    ino: u32,
+    raw: [u8; INODE_RAW_SIZE],
+}
+
+impl Inode {
+    fn mode(&self) -> u32 {
+        read_u16(&self.raw, 0) as u32
+    }
+
+    fn set_mode(&mut self, mode: u32) {
+        write_u16(&mut self.raw, 0, mode as u16);
+    }
+
+    fn file_type(&self) -> u32 {
+        self.mode() & S_IFMT
+    }
+
+    fn uid(&self) -> u32 {
+        read_u16(&self.raw, 2) as u32 | ((read_u16(&self.raw, 120) as u32) << 16)
+    }
+
+    fn set_uid(&mut self, uid: u32) {
+        write_u16(&mut self.raw, 2, uid as u16);
+        write_u16(&mut self.raw, 120, (uid >> 16) as u16);
+    }
+
+    fn gid(&self) -> u32 {
+        read_u16(&self.raw, 24) as u32 | ((read_u16(&self.raw, 122) as u32) << 16)
+    }
+
+    fn set_gid(&mut self, gid: u32) {
+        write_u16(&mut self.raw, 24, gid as u16);
+        write_u16(&mut self.raw, 122, (gid >> 16) as u16);
+    }
+
+    fn size(&self) -> u64 {
+        let lo = read_u32(&self.raw, 4) as u64;
+        if self.file_type() == S_IFREG {
+            lo | ((read_u32(&self.raw, 108) as u64) << 32)
+        } else {
+            lo
+        }
+    }
+
+    fn set_size(&mut self, size: u64) {
+        write_u32(&mut self.raw, 4, size as u32);
+        if self.file_type() == S_IFREG {
+            write_u32(&mut self.raw, 108, (size >> 32) as u32);
+        }
+    }
+
+    /// `i_atime`. Timestamps are signed 32-bit seconds from the epoch.
+    fn atime(&self) -> i32 {
+        read_u32(&self.raw, 8) as i32
+    }
+
+    fn set_atime(&mut self, secs: i32) {
+        write_u32(&mut self.raw, 8, secs as u32);
+    }
+
+    fn ctime(&self) -> i32 {
+        read_u32(&self.raw, 12) as i32
+    }
+
+    fn set_ctime(&mut self, secs: i32) {
+        write_u32(&mut self.raw, 12, secs as u32);
+    }
+
+    fn mtime(&self) -> i32 {
+        read_u32(&self.raw, 16) as i32
+    }
+
+    fn set_mtime(&mut self, secs: i32) {
+        write_u32(&mut self.raw, 16, secs as u32);
+    }
+
+    /// Updates the modification and the status change time.
+    fn touch(&mut self) {
+        let now = now();
+        write_u32(&mut self.raw, 12, now);
+        write_u32(&mut self.raw, 16, now);
+    }
+
+    /// Updates the status change time.
+    fn touch_ctime(&mut self) {
+        write_u32(&mut self.raw, 12, now());
+    }
+
+    fn links_count(&self) -> u16 {
+        read_u16(&self.raw, 26)
+    }
+
+    fn set_links_count(&mut self, count: u16) {
+        write_u16(&mut self.raw, 26, count);
+    }
+
+    /// Sets `i_dtime`: the time when the inode was deleted.
+    fn set_dtime(&mut self, time: u32) {
+        write_u32(&mut self.raw, 20, time);
+    }
+
+    /// `i_blocks`: the number of blocks allocated to the inode.
+    fn blocks(&self) -> u64 {
+        read_u32(&self.raw, 28) as u64 | ((read_u16(&self.raw, 116) as u64) << 32)
+    }
+
+    fn set_blocks(&mut self, blocks: u64) {
+        write_u32(&mut self.raw, 28, blocks as u32);
+        write_u16(&mut self.raw, 116, (blocks >> 32) as u16);
+    }
+
+    fn flags(&self) -> u32 {
+        read_u32(&self.raw, 32)
+    }
+
+    fn set_flags(&mut self, flags: u32) {
+        write_u32(&mut self.raw, 32, flags);
+    }
+
+    /// The `i_block` array: block pointers, an extent tree, or the target of
+    /// a fast symbolic link.
+    fn block_area(&self) -> &[u8] {
+        &self.raw[40..100]
+    }
+
+    fn block_area_mut(&mut self) -> &mut [u8] {
+        &mut self.raw[40..100]
+    }
+
+    fn block(&self, index: usize) -> u32 {
+        read_u32(&self.raw, 40 + index * 4)
+    }
+
+    fn set_block(&mut self, index: usize, block: u32) {
+        write_u32(&mut self.raw, 40 + index * 4, block);
+    }
+
+    fn file_acl(&self) -> u64 {
+        read_u32(&self.raw, 104) as u64 | ((read_u16(&self.raw, 118) as u64) << 32)
+    }
+
+    /// Returns `true` if it's a "fast" symbolic link: the path is stored in
+    /// `i_block` instead of data blocks.
+    fn is_fast_symlink(&self, block_size: usize) -> bool {
+        let acl_blocks = if self.file_acl() != 0 {
+            (block_size / 512) as u64
+        } else {
+            0
+        };
+
+        self.file_type() == S_IFLNK
+            && self.flags() & INODE_FLAG_EXTENTS == 0
+            && self.blocks() == acl_blocks
+            && self.size() as usize <= self.block_area().len()
+    }
+
+    /// Returns `true` if `i_block` points to data blocks.
+    fn has_data_blocks(&self, block_size: usize) -> bool {
+        match self.file_type() {
+            S_IFREG | S_IFDIR => true,
+            S_IFLNK => !self.is_fast_symlink(block_size),
+            // `i_block` holds the device number or nothing.
+            _ => false,
+        }
+    }
+
+    /// The device number of a character/block device file.
+    fn rdev(&self) -> DevId {
+        let old = self.block(0);
+        if old != 0 {
+            DevId::new(((old >> 8) & 0xff) as usize, (old & 0xff) as usize)
+        } else {
+            let new = self.block(1);
+            DevId::new(
+                ((new & 0xfff00) >> 8) as usize,
+                ((new & 0xff) | ((new >> 12) & 0xfff00)) as usize,
+            )
+        }
+    }
+}
+
+/// Converts `S_IFMT` bits into the file type in directory entries.
+fn file_type_from_mode(mode: u32) -> FileType {
+    match mode & S_IFMT {
+        S_IFIFO => FileType::Fifo,
+        S_IFCHR => FileType::CharDevice,
+        S_IFDIR => FileType::Directory,
+        S_IFBLK => FileType::BlockDevice,
+        S_IFLNK => FileType::Link,
+        S_IFSOCK => FileType::Socket,
+        _ => FileType::Regular,
+    }
+}
+
+/// Mutable states of the file system.
+struct State {
+    groups: Vec<GroupDesc>,
+    free_blocks_count: u64,
+    free_inodes_count: u32,
+}
+
+/// Inodes referenced from memory.
+struct LiveInodes {
+    /// The number of inode objects (`Ext2File`, `Ext2Dir`, and
+    /// `Ext2Symlink`) for each inode.
+    refs: BTreeMap<u32, usize>,
+    /// Inodes which have no links but are still referenced (e.g. an opened
+    /// file which has been unlinked). They're freed when the last object is
+    /// dropped. Unlike Linux, we don't record them in the on-disk orphan list:
+    /// fsck reclaims them after a crash.
+    orphans: BTreeSet<u32>,
+}
+
+struct Volume {
+    device: Arc<BlockDevice>,
+    sb: Superblock,
+    read_only: bool,
+    dev_id: DevId,
+    state: Mutex<State>,
+    live: SpinLock<LiveInodes>,
+}
+
+impl Volume {
+    fn block_size(&self) -> usize {
+        self.sb.block_size
+    }
+
+    fn read_bytes(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        if self.device.read(offset, buf)? != buf.len() {
+            return Err(Errno::EIO.into());
+        }
+
+        Ok(())
+    }
+
+    fn write_bytes(&self, offset: u64, buf: &[u8]) -> Result<()> {
+        debug_assert!(!self.read_only);
+        if self.device.write(offset, buf)? != buf.len() {
+            return Err(Errno::EIO.into());
+        }
+
+        Ok(())
+    }
+
+    fn read_u32_at(&self, offset: u64) -> Result<u32> {
+        let mut buf = [0; 4];
+        self.read_bytes(offset, &mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn block_offset(&self, block: u64) -> u64 {
+        block * self.block_size() as u64
+    }
+
+    fn read_block(&self, block: u64) -> Result<Vec<u8>> {
+        let mut buf = vec![0; self.block_size()];
+        self.read_bytes(self.block_offset(block), &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Records a new inode object. Called when an `Ext2File`, `Ext2Dir`, or
+    /// `Ext2Symlink` is created.
+    fn get_inode_ref(&self, ino: u32) {
+        *self.live.lock().refs.entry(ino).or_insert(0) += 1;
+    }
+
+    /// Drops an inode object. The inode is freed if it's the last reference
+    /// to an unlinked inode.
+    fn put_inode_ref(&self, ino: u32) {
+        let is_orphan = {
+            let mut live = self.live.lock();
+            let refs = live.refs.get_mut(&ino).unwrap();
+            *refs -= 1;
+            if *refs > 0 {
+                return;
+            }
+
+            live.refs.remove(&ino);
+            live.orphans.remove(&ino)
+        };
+
+        if is_orphan {
+            if let Err(err) = self.lock().free_inode(ino) {
+                warn!("ext2: failed to free inode {}: {:?}", ino, err);
+            }
+        }
+    }
+
+    /// Locks the file system for an operation.
+    fn lock(&self) -> Locked<'_> {
+        Locked {
+            vol: self,
+            state: self.state.lock(),
+        }
+    }
+
+    /// Returns the slot in `i_block` and the entry indices in indirect blocks
+    /// to reach the `index`-th block of a file.
+    fn indirect_path(&self, mut index: u64) -> Option<(usize, Vec<usize>)> {
+        if index < NUM_DIRECT_BLOCKS {
+            return Some((index as usize, Vec::new()));
+        }
+
+        index -= NUM_DIRECT_BLOCKS;
+        let entries_per_block = (self.block_size() / 4) as u64;
+        let mut num_blocks = 1;
+        for depth in 1..=3 {
+            num_blocks *= entries_per_block;
+            if index < num_blocks {
+                let indices = (0..depth)
+                    .rev()
+                    .map(|level| {
+                        (index / entries_per_block.pow(level) % entries_per_block) as usize
+                    })
+                    .collect();
+                return Some((NUM_DIRECT_BLOCKS as usize + depth as usize - 1, indices));
+            }
+
+            index -= num_blocks;
+        }
+
+        None
+    }
+}
+
+/// The file system locked for an operation. Metadata are read and modified
+/// only through this.
+struct Locked<'a> {
+    vol: &'a Volume,
+    state: MutexGuard<'a, State>,
+}
+
+impl<'a> Locked<'a> {
+    fn check_writable(&self) -> Result<()> {
+        if self.vol.read_only {
+            return Err(Errno::EROFS.into());
+        }
+
+        Ok(())
+    }
+
+    fn inode_offset(&self, ino: u32) -> Result<u64> {
+        if ino == 0 || ino > self.vol.sb.inodes_count {
+            warn!("ext2: invalid inode number: {}", ino);
+            return Err(Errno::EIO.into());
+        }
+
+        let group = ((ino - 1) / self.vol.sb.inodes_per_group) as usize;
+        let index = ((ino - 1) % self.vol.sb.inodes_per_group) as u64;
+        let table = self.state.groups[group].inode_table;
+        Ok(self.vol.block_offset(table) + index * self.vol.sb.inode_size as u64)
+    }
+
+    fn read_inode(&self, ino: u32) -> Result<Inode> {
+        let mut inode = Inode {
+            ino,
+            raw: [0; INODE_RAW_SIZE],
+        };
+        self.vol
+            .read_bytes(self.inode_offset(ino)?, &mut inode.raw)?;
+        Ok(inode)
+    }
+
+    fn write_inode(&self, inode: &Inode) -> Result<()> {
+        self.vol
+            .write_bytes(self.inode_offset(inode.ino)?, &inode.raw)
+    }
+
+    fn stat(&self, inode: &Inode) -> Stat {
+        let blocks = if inode.flags() & INODE_FLAG_HUGE_FILE != 0
+            && self.vol.sb.feature_ro_compat & FEATURE_RO_COMPAT_HUGE_FILE != 0
+        {
+            inode.blocks() * (self.vol.block_size() / 512) as u64
+        } else {
+            inode.blocks()
+        };
+
+        let rdev = match inode.file_type() {
+            S_IFCHR | S_IFBLK => inode.rdev(),
+            _ => DevId::new(0, 0),
+        };
+
+        Stat {
+            dev: self.vol.dev_id,
+            inode_no: INodeNo::new(inode.ino as usize),
+            nlink: NLink(inode.links_count() as usize),
+            mode: FileMode::new(inode.mode()),
+            uid: UId(inode.uid()),
+            gid: GId(inode.gid()),
+            rdev,
+            size: FileSize(inode.size() as isize),
+            blksize: BlockSize(self.vol.block_size() as isize),
+            blocks: BlockCount(blocks as isize),
+            atime: Time::from_secs(inode.atime() as isize),
+            mtime: Time::from_secs(inode.mtime() as isize),
+            ctime: Time::from_secs(inode.ctime() as isize),
+            ..Stat::zeroed()
+        }
+    }
+
+    /// `chmod(2)`, `chown(2)`, and `utimensat(2)`. Timestamps are truncated
+    /// to seconds: ext2 inodes don't have sub-second fields.
+    fn set_attr(&self, ino: u32, attr: &SetAttr) -> Result<()> {
+        self.check_writable()?;
+        let mut inode = self.read_inode(ino)?;
+        let mut stat = self.stat(&inode);
+        attr.apply(&mut stat);
+        inode.set_mode(stat.mode.as_u32());
+        inode.set_uid(stat.uid.0);
+        inode.set_gid(stat.gid.0);
+        inode.set_atime(stat.atime.secs as i32);
+        inode.set_mtime(stat.mtime.secs as i32);
+        inode.set_ctime(stat.ctime.secs as i32);
+        self.write_inode(&inode)
+    }
+
+    fn check_block(&self, block: u64) -> Result<u64> {
+        if block >= self.vol.sb.blocks_count {
+            warn!("ext2: invalid block number: {}", block);
+            return Err(Errno::EIO.into());
+        }
+
+        Ok(block)
+    }
+
+    /// Returns the block number of the `index`-th block in the file. Returns
+    /// `None` if it's a hole.
+    fn bmap(&self, inode: &Inode, index: u64) -> Result<Option<u64>> {
+        if inode.flags() & INODE_FLAG_EXTENTS != 0 {
+            return match extent::lookup(self.vol, inode, index)? {
+                Some(block) => Ok(Some(self.check_block(block)?)),
+                None => Ok(None),
+            };
+        }
+
+        let (slot, indices) = match self.vol.indirect_path(index) {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        let mut block = inode.block(slot) as u64;
+        for index in indices {
+            if block == 0 {
+                return Ok(None);
+            }
+
+            let offset = self.vol.block_offset(self.check_block(block)?) + index as u64 * 4;
+            block = self.vol.read_u32_at(offset)? as u64;
+        }
+
+        if block == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(self.check_block(block)?))
+    }
+
+    /// Same as `bmap` but allocates the block (and indirect blocks) if it's
+    /// a hole.
+    fn bmap_alloc(&mut self, inode: &mut Inode, index: u64) -> Result<u64> {
+        debug_assert!(inode.flags() & INODE_FLAG_EXTENTS == 0);
+
+        let (slot, indices) = self
+            .vol
+            .indirect_path(index)
+            .ok_or_else(|| Error::new(Errno::EFBIG))?;
+
+        let mut block = inode.block(slot) as u64;
+        if block == 0 {
+            block = self.alloc_block(inode)?;
+            inode.set_block(slot, block as u32);
+        }
+
+        for index in indices {
+            let offset = self.vol.block_offset(self.check_block(block)?) + index as u64 * 4;
+            let mut next = self.vol.read_u32_at(offset)? as u64;
+            if next == 0 {
+                next = self.alloc_block(inode)?;
+                self.vol.write_bytes(offset, &(next as u32).to_le_bytes())?;
+            }
+
+            block = next;
+        }
+
+        self.check_block(block)
+    }
+
+    fn group_of(&self, ino: u32) -> usize {
+        ((ino - 1) / self.vol.sb.inodes_per_group) as usize
+    }
+
+    /// Writes back the free blocks/inodes counts of the group and the
+    /// superblock.
+    fn write_counts(&self, group: usize) -> Result<()> {
+        let desc = &self.state.groups[group];
+        self.vol.write_bytes(
+            desc.offset + 12,
+            &(desc.free_blocks_count as u16).to_le_bytes(),
+        )?;
+        self.vol.write_bytes(
+            desc.offset + 14,
+            &(desc.free_inodes_count as u16).to_le_bytes(),
+        )?;
+        self.vol.write_bytes(
+            desc.offset + 16,
+            &(desc.used_dirs_count as u16).to_le_bytes(),
+        )?;
+        self.vol.write_bytes(
+            SUPERBLOCK_OFFSET + 12,
+            &(self.state.free_blocks_count as u32).to_le_bytes(),
+        )?;
+        self.vol.write_bytes(
+            SUPERBLOCK_OFFSET + 16,
+            &self.state.free_inodes_count.to_le_bytes(),
+        )
+    }
+
+    /// Finds a zero bit in the first `len` bits of the bitmap block, sets it,
+    /// and returns its index.
+    fn alloc_bit(&self, bitmap_block: u64, len: usize) -> Result<Option<usize>> {
+        let bitmap = self.vol.read_block(bitmap_block)?;
+        for (byte_index, byte) in bitmap.iter().enumerate().take(len.div_ceil(8)) {
+            if *byte == 0xff {
+                continue;
+            }
+
+            let bit = (!byte).trailing_zeros() as usize;
+            let index = byte_index * 8 + bit;
+            if index >= len {
+                break;
+            }
+
+            let offset = self.vol.block_offset(bitmap_block) + byte_index as u64;
+            self.vol.write_bytes(offset, &[byte | (1 << bit)])?;
+            return Ok(Some(index));
+        }
+
+        Ok(None)
+    }
+
+    /// Allocates a zero-filled block for the inode. The block is added to
+    /// `i_blocks`.
+    fn alloc_block(&mut self, inode: &mut Inode) -> Result<u64> {
+        let num_groups = self.vol.sb.num_groups;
+        let goal = self.group_of(inode.ino);
+        for i in 0..num_groups {
+            let group = (goal + i) % num_groups;
+            if self.state.groups[group].free_blocks_count == 0 {
+                continue;
+            }
+
+            let bitmap = self.state.groups[group].block_bitmap;
+            let len = self.vol.sb.blocks_in_group(group) as usize;
+            if let Some(index) = self.alloc_bit(bitmap, len)? {
+                self.state.groups[group].free_blocks_count -= 1;
+                self.state.free_blocks_count -= 1;
+                self.write_counts(group)?;
+
+                let block = self.vol.sb.group_first_block(group) + index as u64;
+                let zeroes = vec![0; self.vol.block_size()];
+                self.vol
+                    .write_bytes(self.vol.block_offset(block), &zeroes)?;
+                inode.set_blocks(inode.blocks() + (self.vol.block_size() / 512) as u64);
+                return Ok(block);
+            }
+        }
+
+        Err(Errno::ENOSPC.into())
+    }
+
+    /// Clears the `index`-th bit in the bitmap block.
+    fn free_bit(&self, bitmap_block: u64, index: usize) -> Result<()> {
+        let offset = self.vol.block_offset(bitmap_block) + (index / 8) as u64;
+        let mut byte = [0];
+        self.vol.read_bytes(offset, &mut byte)?;
+        self.vol
+            .write_bytes(offset, &[byte[0] & !(1 << (index % 8))])
+    }
+
+    /// Frees a block of the inode. The block is removed from `i_blocks`.
+    fn free_block(&mut self, inode: &mut Inode, block: u64) -> Result<()> {
+        let block = self.check_block(block)?;
+        if block < self.vol.sb.first_data_block {
+            warn!("ext2: invalid block number: {}", block);
+            return Err(Errno::EIO.into());
+        }
+
+        let group =
+            ((block - self.vol.sb.first_data_block) / self.vol.sb.blocks_per_group) as usize;
+        let index = (block - self.vol.sb.group_first_block(group)) as usize;
+        self.free_bit(self.state.groups[group].block_bitmap, index)?;
+        self.state.groups[group].free_blocks_count += 1;
+        self.state.free_blocks_count += 1;
+        self.write_counts(group)?;
+
+        let sectors = (self.vol.block_size() / 512) as u64;
+        inode.set_blocks(inode.blocks().saturating_sub(sectors));
+        Ok(())
+    }
+
+    /// Frees the blocks at or after the `first_index`-th block of the file in
+    /// the (indirect) block tree rooted at `block`. `depth` is 0 for a data
+    /// block and `base` is the index of the first block covered by the tree.
+    /// Returns `true` if `block` itself has been freed.
+    fn free_blocks_in_tree(
+        &mut self,
+        inode: &mut Inode,
+        block: u64,
+        depth: u32,
+        base: u64,
+        first_index: u64,
+    ) -> Result<bool> {
+        if depth == 0 {
+            if base < first_index {
+                return Ok(false);
+            }
+
+            self.free_block(inode, block)?;
+            return Ok(true);
+        }
+
+        let entries_per_block = (self.vol.block_size() / 4) as u64;
+        let span = entries_per_block.pow(depth - 1);
+        let mut entries = self.vol.read_block(self.check_block(block)?)?;
+        let mut in_use = false;
+        for i in 0..entries_per_block {
+            let offset = i as usize * 4;
+            let child = read_u32(&entries, offset) as u64;
+            if child == 0 {
+                continue;
+            }
+
+            let child_base = base + i * span;
+            if child_base + span <= first_index {
+                in_use = true;
+                continue;
+            }
+
+            if self.free_blocks_in_tree(inode, child, depth - 1, child_base, first_index)? {
+                write_u32(&mut entries, offset, 0);
+            } else {
+                in_use = true;
+            }
+        }
+
+        if !in_use {
+            self.free_block(inode, block)?;
+            return Ok(true);
+        }
+
+        self.vol
+            .write_bytes(self.vol.block_offset(block), &entries)?;
+        Ok(false)
+    }
+
+    /// Changes the file size. Blocks beyond the new size are freed.
+    fn truncate_data(&mut self, inode: &mut Inode, size: u64) -> Result<()> {
+        self.check_writable()?;
+        debug_assert!(inode.flags() & INODE_FLAG_EXTENTS == 0);
+
+        let block_size = self.vol.block_size() as u64;
+        let first_index = size.div_ceil(block_size);
+        for slot in first_index..NUM_DIRECT_BLOCKS {
+            let block = inode.block(slot as usize) as u64;
+            if block != 0 {
+                self.free_block(inode, block)?;
+                inode.set_block(slot as usize, 0);
+            }
+        }
+
+        let entries_per_block = (self.vol.block_size() / 4) as u64;
+        let mut base = NUM_DIRECT_BLOCKS;
+        let mut span = entries_per_block;
+        for depth in 1..=3 {
+            let slot = NUM_DIRECT_BLOCKS as usize + depth as usize - 1;
+            let block = inode.block(slot) as u64;
+            if block != 0
+                && base + span > first_index
+                && self.free_blocks_in_tree(inode, block, depth, base, first_index)?
+            {
+                inode.set_block(slot, 0);
+            }
+
+            base += span;
+            span *= entries_per_block;
+        }
+
+        // Zero the rest of the last block: it's visible if the file is
+        // extended later.
+        let tail = (size % block_size) as usize;
+        if tail != 0 {
+            if let Some(block) = self.bmap(inode, size / block_size)? {
+                let zeroes = vec![0; block_size as usize - tail];
+                self.vol
+                    .write_bytes(self.vol.block_offset(block) + tail as u64, &zeroes)?;
+            }
+        }
+
+        inode.set_size(size);
+        inode.touch();
+        self.write_inode(inode)
+    }
+
+    /// Frees the inode which no longer has links, or defers it until the
+    /// last inode object is dropped.
+    fn release_inode(&mut self, ino: u32) -> Result<()> {
+        {
+            let mut live = self.vol.live.lock();
+            if live.refs.contains_key(&ino) {
+                live.orphans.insert(ino);
+                return Ok(());
+            }
+        }
+
+        self.free_inode(ino)
+    }
+
+    /// Frees the inode and its blocks.
+    fn free_inode(&mut self, ino: u32) -> Result<()> {
+        let mut inode = self.read_inode(ino)?;
+        debug_assert!(inode.links_count() == 0);
+
+        if inode.has_data_blocks(self.vol.block_size()) {
+            self.truncate_data(&mut inode, 0)?;
+        }
+
+        inode.set_dtime(now());
+        self.write_inode(&inode)?;
+
+        let group = self.group_of(ino);
+        let index = ((ino - 1) % self.vol.sb.inodes_per_group) as usize;
+        self.free_bit(self.state.groups[group].inode_bitmap, index)?;
+        let desc = &mut self.state.groups[group];
+        desc.free_inodes_count += 1;
+        if inode.file_type() == S_IFDIR {
+            desc.used_dirs_count = desc.used_dirs_count.saturating_sub(1);
+        }
+        self.state.free_inodes_count += 1;
+        self.write_counts(group)
+    }
+
+    /// Allocates a zero-filled inode near `parent`.
+    fn alloc_inode(&mut self, parent: &Inode, mode: u32) -> Result<Inode> {
+        let num_groups = self.vol.sb.num_groups;
+        let goal = self.group_of(parent.ino);
+        for i in 0..num_groups {
+            let group = (goal + i) % num_groups;
+            if self.state.groups[group].free_inodes_count == 0 {
+                continue;
+            }
+
+            let bitmap = self.state.groups[group].inode_bitmap;
+            let len = self.vol.sb.inodes_per_group as usize;
+            if let Some(index) = self.alloc_bit(bitmap, len)? {
+                let desc = &mut self.state.groups[group];
+                desc.free_inodes_count -= 1;
+                if mode & S_IFMT == S_IFDIR {
+                    desc.used_dirs_count += 1;
+                }
+                self.state.free_inodes_count -= 1;
+                self.write_counts(group)?;
+
+                let ino = group as u32 * self.vol.sb.inodes_per_group + index as u32 + 1;
+                let zeroes = vec![0; self.vol.sb.inode_size];
+                self.vol.write_bytes(self.inode_offset(ino)?, &zeroes)?;
+
+                let mut inode = Inode {
+                    ino,
+                    raw: [0; INODE_RAW_SIZE],
+                };
+                let now = now();
+                inode.set_mode(mode);
+                write_u32(&mut inode.raw, 8, now);
+                write_u32(&mut inode.raw, 12, now);
+                write_u32(&mut inode.raw, 16, now);
+                return Ok(inode);
+            }
+        }
+
+        Err(Errno::ENOSPC.into())
+    }
+
+    /// Reads the file data at `offset`. Returns the number of bytes read.
+    fn read_data(&self, inode: &Inode, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        if inode.flags() & INODE_FLAG_INLINE_DATA != 0 {
+            warn!("ext2: inline data is not supported");
+            return Err(Errno::EIO.into());
+        }
+
+        let size = inode.size();
+        if offset >= size {
+            return Ok(0);
+        }
+
+        let len = core::cmp::min(buf.len() as u64, size - offset) as usize;
+        let block_size = self.vol.block_size() as u64;
+        let mut read_len = 0;
+        while read_len < len {
+            let pos = offset + read_len as u64;
+            let block_offset = (pos % block_size) as usize;
+            let copy_len = core::cmp::min(len - read_len, block_size as usize - block_offset);
+            let dst = &mut buf[read_len..(read_len + copy_len)];
+            match self.bmap(inode, pos / block_size)? {
+                Some(block) => {
+                    self.vol
+                        .read_bytes(self.vol.block_offset(block) + block_offset as u64, dst)?;
+                }
+                None => dst.fill(0),
+            }
+
+            read_len += copy_len;
+        }
+
+        Ok(read_len)
+    }
+
+    /// Writes the file data at `offset` and updates the inode.
+    fn write_data(&mut self, inode: &mut Inode, offset: u64, buf: &[u8]) -> Result<usize> {
+        self.check_writable()?;
+
+        let block_size = self.vol.block_size() as u64;
+        let mut written_len = 0;
+        // Update the inode even if an error occurred: some blocks might have
+        // been allocated.
+        let mut result = Ok(());
+        while written_len < buf.len() {
+            let pos = offset + written_len as u64;
+            let block_offset = (pos % block_size) as usize;
+            let copy_len =
+                core::cmp::min(buf.len() - written_len, block_size as usize - block_offset);
+            let block = match self.bmap_alloc(inode, pos / block_size) {
+                Ok(block) => block,
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            };
+
+            if let Err(err) = self.vol.write_bytes(
+                self.vol.block_offset(block) + block_offset as u64,
+                &buf[written_len..(written_len + copy_len)],
+            ) {
+                result = Err(err);
+                break;
+            }
+
+            written_len += copy_len;
+        }
+
+        if offset + written_len as u64 > inode.size() {
+            inode.set_size(offset + written_len as u64);
+        }
+
+        inode.touch();
+        self.write_inode(inode)?;
+
+        match result {
+            Err(err) if written_len == 0 => Err(err),
+            _ => Ok(written_len),
+        }
+    }
+}
+
+pub struct Ext2Fs {
+    vol: Arc<Volume>,
+}
+
+impl Ext2Fs {
+    /// Mounts the file system on the device. It's mounted read-only if it
+    /// uses features we can't write (e.g. ext4 extents) or the device is
+    /// read-only.
+    pub fn mount(device: Arc<BlockDevice>) -> Result<Arc<Ext2Fs>> {
+        let mut raw = vec![0; SUPERBLOCK_SIZE];
+        if device.read(SUPERBLOCK_OFFSET, &mut raw)? != raw.len() {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let sb = Superblock::parse(&raw)?;
+        let is_64bit = sb.has_incompat(FEATURE_INCOMPAT_64BIT);
+        let mut groups = Vec::with_capacity(sb.num_groups);
+        for group in 0..sb.num_groups {
+            let offset = sb.group_desc_offset(group);
+            let mut desc = vec![0; sb.desc_size];
+            if device.read(offset, &mut desc)? != desc.len() {
+                return Err(Errno::EIO.into());
+            }
+
+            groups.push(GroupDesc::parse(offset, &desc, is_64bit));
+        }
+
+        if sb.has_incompat(FEATURE_INCOMPAT_RECOVER) {
+            warn!(
+                "ext2: {}: the journal needs recovery, some changes may be missing",
+                device.name()
+            );
+        }
+
+        let read_only = !sb.is_writable() || device.is_read_only();
+        info!(
+            "ext2: mounted {} ({} blocks, {}-byte blocks, {})",
+            device.name(),
+            sb.blocks_count,
+            sb.block_size,
+            if read_only { "read-only" } else { "read-write" }
+        );
+
+        let mut free_blocks_count = read_u32(&raw, 12) as u64;
+        if is_64bit {
+            free_blocks_count |= (read_u32(&raw, 0x158) as u64) << 32;
+        }
+
+        Ok(Arc::new(Ext2Fs {
+            vol: Arc::new(Volume {
+                dev_id: DevId::new(device.major(), device.minor()),
+                device,
+                sb,
+                read_only,
+                state: Mutex::new(State {
+                    groups,
+                    free_blocks_count,
+                    free_inodes_count: read_u32(&raw, 16),
+                }),
+                live: SpinLock::new(LiveInodes {
+                    refs: BTreeMap::new(),
+                    orphans: BTreeSet::new(),
+                }),
+            }),
+        }))
+    }
+}
+
+/// Returns the inode object for the on-disk inode.
+fn make_inode(vol: &Arc<Volume>, inode: &Inode) -> INode {
+    match inode.file_type() {
+        S_IFDIR => {
+            INode::Directory(Arc::new(Ext2Dir::new(vol.clone(), inode.ino)) as Arc<dyn Directory>)
+        }
+        S_IFLNK => INode::Symlink(Arc::new(Ext2Symlink::new(vol.clone(), inode.ino))),
+        _ => INode::FileLike(Arc::new(Ext2File::new(vol.clone(), inode.ino)) as Arc<dyn FileLike>),
+    }
+}
+
+impl FileSystem for Ext2Fs {
+    fn name(&self) -> &'static str {
+        if self.vol.sb.has_incompat(FEATURE_INCOMPAT_EXTENTS) {
+            "ext4"
+        } else {
+            "ext2"
+        }
+    }
+
+    fn root_dir(&self) -> Result<Arc<dyn Directory>> {
+        Ok(Arc::new(Ext2Dir::new(self.vol.clone(), ROOT_INO)))
+    }
+
+    fn statfs(&self) -> Result<FsStat> {
+        let sb = &self.vol.sb;
+        let state = self.vol.state.lock();
+        Ok(FsStat {
+            magic: EXT2_MAGIC as u64,
+            block_size: sb.block_size,
+            total_blocks: sb.blocks_count,
+            free_blocks: state.free_blocks_count,
+            available_blocks: state
+                .free_blocks_count
+                .saturating_sub(sb.reserved_blocks_count),
+            total_inodes: sb.inodes_count as u64,
+            free_inodes: state.free_inodes_count as u64,
+            max_name_len: 255,
+        })
+    }
+}
+
+/// Mounts the block device specified in `source` (e.g. `/dev/vda1`).
+fn mount_device(source: &str, _data: &str) -> Result<Arc<dyn FileSystem>> {
+    let device = lookup_block_device(source).ok_or_else(|| Error::new(Errno::ENOTBLK))?;
+    Ok(Ext2Fs::mount(device)?)
+}
+
+pub fn init() {
+    // ext3 and ext4 file systems are also accepted as long as they don't use
+    // unsupported features.
+    for name in ["ext2", "ext3", "ext4"] {
+        register_file_system_type(FileSystemType {
+            name,
+            requires_device: true,
+            mount: mount_device,
+        });
+    }
+}
@@ -0,0 +1,475 @@
+//! Advisory file locks: POSIX record locks (`F_SETLK`), open file description
+//! locks (`F_OFD_SETLK`), and `flock(2)`.
+//!
+//! Record locks (POSIX and OFD ones) conflict with each other. `flock(2)`
+//! locks are independent from them as in Linux.
+use super::{
+    inode::{INode, INodeNo},
+    opened_file::{OpenedFile, ANONYMOUS_MOUNT_ID},
+};
+use crate::{
+    prelude::*,
+    process::{PId, WaitQueue},
+};
+use core::cmp::{max, min};
+use hashbrown::HashMap;
+use kerla_runtime::spinlock::SpinLock;
+use kerla_utils::once::Once;
+
+static LOCK_TABLE: Once<SpinLock<LockTable>> = Once::new();
+static LOCK_WAIT_QUEUE: Once<WaitQueue> = Once::new();
+
+/// The file which locks are attached to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LockKey {
+    INode {
+        mount_id: usize,
+        inode_no: INodeNo,
+    },
+    /// Files without an inode number (e.g. sockets), identified by the address
+    /// of the object.
+    Anonymous(usize),
+}
+
+impl LockKey {
+    /// Returns the key of the file. Note that this calls `stat`, which may
+    /// sleep on some file systems.
+    pub fn new(opened_file: &OpenedFile) -> LockKey {
+        let path = opened_file.path();
+        if path.mount_id != ANONYMOUS_MOUNT_ID {
+            if let Ok(stat) = path.inode.stat() {
+                return LockKey::INode {
+                    mount_id: path.mount_id,
+                    inode_no: stat.inode_no,
+                };
+            }
+        }
+
+        let addr = match &path.inode {
+            INode::FileLike(file) => Arc::as_ptr(file) as *const u8 as usize,
+            INode::Directory(dir) => Arc::as_ptr(dir) as *const u8 as usize,
+            INode::Symlink(symlink) => Arc::as_ptr(symlink) as *const u8 as usize,
+        };
+
+        LockKey::Anonymous(addr)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LockOwner {
+    /// POSIX record locks are owned by the process.
+    Process(PId),
+    /// OFD locks and `flock(2)` locks are owned by the opened file (aka. open
+    /// file description), identified by its address.
+    OpenedFile(usize),
+}
+
+impl LockOwner {
+    pub fn opened_file(opened_file: &OpenedFile) -> LockOwner {
+        LockOwner::OpenedFile(opened_file as *const OpenedFile as usize)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LockType {
+    Read,
+    Write,
+}
+
+/// A byte-range lock.
+#[derive(Debug, Copy, Clone)]
+pub struct RecordLock {
+    pub owner: LockOwner,
+    pub lock_type: LockType,
+    pub start: u64,
+    /// The end of the range (exclusive). `u64::MAX` means the end of the file
+    /// including its future extensions.
+    pub end: u64,
+}
+
+impl RecordLock {
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start < end && start < self.end
+    }
+
+    fn conflicts_with(&self, other: &RecordLock) -> bool {
+        self.owner != other.owner
+            && self.overlaps(other.start, other.end)
+            && (self.lock_type == LockType::Write || other.lock_type == LockType::Write)
+    }
+}
+
+#[derive(Default)]
+struct FileLocks {
+    records: Vec<RecordLock>,
+    flocks: Vec<(LockOwner, LockType)>,
+}
+
+impl FileLocks {
+    fn is_empty(&self) -> bool {
+        self.records.is_empty() && self.flocks.is_empty()
+    }
+
+    /// Removes locks held by `owner` in the range, splitting them if needed.
+    fn remove_range(&mut self, owner: LockOwner, start: u64, end: u64) {
+        let mut split_locks = Vec::new();
+        for lock in &mut self.records {
+            if lock.owner != owner || !lock.overlaps(start, end) {
+                continue;
+            }
+
+            if lock.start < start && lock.end > end {
+                split_locks.push(RecordLock {
+                    start: end,
+                    ..*lock
+                });
+                lock.end = start;
+            } else if lock.start < start {
+                lock.end = start;
+            } else if lock.end > end {
+                lock.start = end;
+            } else {
+                // Remove the whole lock below.
+                lock.end = lock.start;
+            }
+        }
+
+        self.records.retain(|lock| lock.start < lock.end);
+        self.records.extend(split_locks);
+    }
+
+    /// Places `lock`, replacing locks of the same owner in the range. Locks of
+    /// the same owner and type adjacent to it are merged into one as in Linux.
+    fn insert(&mut self, mut lock: RecordLock) {
+        self.remove_range(lock.owner, lock.start, lock.end);
+        self.records.retain(|other| {
+            let adjacent = other.owner == lock.owner
+                && other.lock_type == lock.lock_type
+                && (other.end == lock.start || other.start == lock.end);
+            if adjacent {
+                lock.start = min(lock.start, other.start);
+                lock.end = max(lock.end, other.end);
+            }
+
+            !adjacent
+        });
+        self.records.push(lock);
+    }
+}
+
+struct LockTable {
+    files: HashMap<LockKey, FileLocks>,
+    /// Processes blocked in `F_SETLKW`: `(waiter, holder)` pairs. Used for
+    /// deadlock detection.
+    waiting: Vec<(LockOwner, LockOwner)>,
+}
+
+impl LockTable {
+    /// Returns true if `waiter` waiting for `holder` closes a cycle.
+    fn would_deadlock(&self, waiter: LockOwner, holder: LockOwner) -> bool {
+        let mut current = holder;
+        // A process waits for one holder at a time: the chain is at most as
+        // long as `waiting`.
+        for _ in 0..=self.waiting.len() {
+            if current == waiter {
+                return true;
+            }
+
+            match self.waiting.iter().find(|(w, _)| *w == current) {
+                Some((_, next)) => current = *next,
+                None => return false,
+            }
+        }
+
+        false
+    }
+
+    /// Removes locks matching `pred`. Returns true if any lock is removed.
+    fn remove_locks<F>(&mut self, key: Option<LockKey>, pred: F) -> bool
+    where
+        F: Fn(LockOwner) -> bool,
+    {
+        let mut removed = false;
+        for (file_key, locks) in self.files.iter_mut() {
+            if matches!(key, Some(key) if key != *file_key) {
+                continue;
+            }
+
+            let num_locks = locks.records.len() + locks.flocks.len();
+            locks.records.retain(|lock| !pred(lock.owner));
+            locks.flocks.retain(|(owner, _)| !pred(*owner));
+            removed |= num_locks != locks.records.len() + locks.flocks.len();
+        }
+
+        self.files.retain(|_, locks| !locks.is_empty());
+        removed
+    }
+}
+
+fn lock_table() -> kerla_runtime::spinlock::SpinLockGuard<'static, LockTable> {
+    LOCK_TABLE.lock()
+}
+
+/// `F_GETLK`: returns a lock which prevents `lock` from being placed.
+pub fn get_conflicting_lock(key: LockKey, lock: &RecordLock) -> Option<RecordLock> {
+    lock_table()
+        .files
+        .get(&key)
+        .and_then(|locks| locks.records.iter().find(|l| l.conflicts_with(lock)))
+        .copied()
+}
+
+/// `F_SETLK` and `F_SETLKW`: places `lock`, replacing locks of the same owner
+/// in the range. If `wait` is true, sleeps until conflicting locks are
+/// released. Otherwise, returns `EAGAIN`.
+pub fn set_record_lock(key: LockKey, lock: RecordLock, wait: bool) -> Result<()> {
+    let result = LOCK_WAIT_QUEUE.sleep_signalable_until(|| {
+        let mut table = lock_table();
+        table.waiting.retain(|(waiter, _)| *waiter != lock.owner);
+
+        let locks = table.files.entry(key).or_default();
+        let holder = match locks.records.iter().find(|l| l.conflicts_with(&lock)) {
+            Some(conflicting) => conflicting.owner,
+            None => {
+                locks.insert(lock);
+                return Ok(Some(()));
+            }
+        };
+
+        if !wait {
+            return Err(Errno::EAGAIN.into());
+        }
+
+        // Only POSIX locks are checked for deadlocks as Linux does.
+        if let LockOwner::Process(_) = lock.owner {
+            if table.would_deadlock(lock.owner, holder) {
+                return Err(Errno::EDEADLK.into());
+            }
+
+            table.waiting.push((lock.owner, holder));
+        }
+
+        Ok(None)
+    });
+
+    // Remove the wait-for edge whether we got the lock or not (e.g. EINTR).
+    let mut table = lock_table();
+    table.waiting.retain(|(waiter, _)| *waiter != lock.owner);
+    table.files.retain(|_, locks| !locks.is_empty());
+    drop(table);
+
+    // The lock might have been downgraded or merged: wake up waiters to let
+    // them re-check conflicts.
+    LOCK_WAIT_QUEUE.wake_all();
+    result
+}
+
+/// `F_UNLCK`: removes locks held by `owner` in the range.
+pub fn unlock_record(key: LockKey, owner: LockOwner, start: u64, end: u64) {
+    let mut table = lock_table();
+    if let Some(locks) = table.files.get_mut(&key) {
+        locks.remove_range(owner, start, end);
+        if locks.is_empty() {
+            table.files.remove(&key);
+        }
+    }
+
+    drop(table);
+    LOCK_WAIT_QUEUE.wake_all();
+}
+
+/// `flock(2)`. `lock_type` is `None` for `LOCK_UN`.
+pub fn flock(
+    key: LockKey,
+    owner: LockOwner,
+    lock_type: Option<LockType>,
+    wait: bool,
+) -> Result<()> {
+    // Like Linux, converting a lock is not atomic: the existing lock is
+    // removed first. Keep OFD record locks of the same owner.
+    let mut table = lock_table();
+    let mut removed = false;
+    if let Some(locks) = table.files.get_mut(&key) {
+        let num_flocks = locks.flocks.len();
+        locks.flocks.retain(|(o, _)| *o != owner);
+        removed = locks.flocks.len() != num_flocks;
+        if locks.is_empty() {
+            table.files.remove(&key);
+        }
+    }
+
+    drop(table);
+    if removed {
+        LOCK_WAIT_QUEUE.wake_all();
+    }
+
+    let lock_type = match lock_type {
+        Some(lock_type) => lock_type,
+        None => return Ok(()),
+    };
+
+    let result = LOCK_WAIT_QUEUE.sleep_signalable_until(|| {
+        let mut table = lock_table();
+        let locks = table.files.entry(key).or_default();
+        let conflicts = locks
+            .flocks
+            .iter()
+            .any(|(o, t)| *o != owner && (lock_type == LockType::Write || *t == LockType::Write));
+
+        if !conflicts {
+            locks.flocks.push((owner, lock_type));
+            Ok(Some(()))
+        } else if wait {
+            Ok(None)
+        } else {
+            Err(Errno::EAGAIN.into())
+        }
+    });
+
+    lock_table().files.retain(|_, locks| !locks.is_empty());
+    result
+}
+
+/// Releases POSIX locks on the file held by the process. Called when the
+/// process closes a file descriptor: "all of the locks held by a process on
+/// a file are released when any file descriptor referring to the file is
+/// closed" -- fcntl(2).
+pub fn release_posix_locks(pid: PId, opened_file: &OpenedFile) {
+    let owner = LockOwner::Process(pid);
+    let has_locks = lock_table()
+        .files
+        .values()
+        .any(|locks| locks.records.iter().any(|lock| lock.owner == owner));
+    if !has_locks {
+        return;
+    }
+
+    // Don't hold the lock table lock: LockKey::new may sleep.
+    let key = LockKey::new(opened_file);
+    if lock_table().remove_locks(Some(key), |o| o == owner) {
+        LOCK_WAIT_QUEUE.wake_all();
+    }
+}
+
+/// Releases all POSIX locks held by the process. Called when it exits.
+pub fn release_process_locks(pid: PId) {
+    let owner = LockOwner::Process(pid);
+    if lock_table().remove_locks(None, |o| o == owner) {
+        LOCK_WAIT_QUEUE.wake_all();
+    }
+}
+
+/// Releases OFD locks and `flock(2)` locks held by the opened file. Called
+/// when the last reference to it is dropped.
+pub fn release_opened_file_locks(opened_file: &OpenedFile) {
+    let owner = LockOwner::opened_file(opened_file);
+    if lock_table().remove_locks(None, |o| o == owner) {
+        LOCK_WAIT_QUEUE.wake_all();
+    }
+}
+
+pub fn init() {
+    LOCK_TABLE.init(|| {
+        SpinLock::new(LockTable {
+            files: HashMap::new(),
+            waiting: Vec::new(),
+        })
+    });
+    LOCK_WAIT_QUEUE.init(WaitQueue::new);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_lock(owner: LockOwner, start: u64, end: u64) -> RecordLock {
+        RecordLock {
+            owner,
+            lock_type: LockType::Write,
+            start,
+            end,
+        }
+    }
+
+    fn read_lock(owner: LockOwner, start: u64, end: u64) -> RecordLock {
+        RecordLock {
+            lock_type: LockType::Read,
+            ..write_lock(owner, start, end)
+        }
+    }
+
+    fn ranges(locks: &FileLocks) -> Vec<(u64, u64)> {
+        let mut ranges: Vec<(u64, u64)> = locks
+            .records
+            .iter()
+            .map(|lock| (lock.start, lock.end))
+            .collect();
+        ranges.sort_unstable();
+        ranges
+    }
+
+    #[test_case]
+    fn remove_range_splits_locks() {
+        let owner = LockOwner::Process(PId::new(1));
+        let other = LockOwner::Process(PId::new(2));
+        let mut locks = FileLocks::default();
+        locks.records.push(write_lock(owner, 0, 100));
+        locks.records.push(write_lock(other, 200, 300));
+
+        // Unlocking the middle splits the lock into two.
+        locks.remove_range(owner, 10, 20);
+        assert_eq!(ranges(&locks), vec![(0, 10), (20, 100), (200, 300)]);
+
+        // Unlocking overlapping ends shrinks them.
+        locks.remove_range(owner, 5, 30);
+        assert_eq!(ranges(&locks), vec![(0, 5), (30, 100), (200, 300)]);
+
+        // Locks held by others are kept.
+        locks.remove_range(owner, 0, u64::MAX);
+        assert_eq!(ranges(&locks), vec![(200, 300)]);
+    }
+
+    #[test_case]
+    fn insert_merges_locks() {
+        let owner = LockOwner::Process(PId::new(1));
+        let other = LockOwner::Process(PId::new(2));
+        let mut locks = FileLocks::default();
+
+        // Adjacent and overlapping locks of the same type are merged.
+        locks.insert(write_lock(owner, 0, 10));
+        locks.insert(write_lock(owner, 10, 20));
+        locks.insert(write_lock(owner, 15, 30));
+        assert_eq!(ranges(&locks), vec![(0, 30)]);
+
+        // A lock of another type splits the existing one.
+        locks.insert(read_lock(owner, 10, 20));
+        assert_eq!(ranges(&locks), vec![(0, 10), (10, 20), (20, 30)]);
+
+        // Converting it back merges all of them.
+        locks.insert(write_lock(owner, 10, 20));
+        assert_eq!(ranges(&locks), vec![(0, 30)]);
+
+        // Locks held by others are never merged.
+        locks.insert(read_lock(other, 30, 40));
+        locks.insert(read_lock(owner, 40, 50));
+        assert_eq!(ranges(&locks), vec![(0, 30), (30, 40), (40, 50)]);
+    }
+
+    #[test_case]
+    fn would_deadlock() {
+        let p1 = LockOwner::Process(PId::new(1));
+        let p2 = LockOwner::Process(PId::new(2));
+        let p3 = LockOwner::Process(PId::new(3));
+        let p4 = LockOwner::Process(PId::new(4));
+        let table = LockTable {
+            files: HashMap::new(),
+            // p1 waits for p2, which waits for p3.
+            waiting: vec![(p1, p2), (p2, p3)],
+        };
+
+        assert!(table.would_deadlock(p3, p1));
+        assert!(table.would_deadlock(p2, p1));
+        assert!(!table.would_deadlock(p4, p1));
+        assert!(!table.would_deadlock(p3, p4));
+    }
+}
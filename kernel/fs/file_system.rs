@@ -1,7 +1,77 @@
 use super::inode::Directory;
-use crate::result::Result;
-use alloc::sync::Arc;
+use crate::prelude::*;
+use kerla_runtime::{arch::PAGE_SIZE, spinlock::SpinLock};
 
 pub trait FileSystem: Send + Sync {
+    /// The file system type name (e.g. `tmpfs`) shown in `/proc/mounts`.
+    fn name(&self) -> &'static str;
     fn root_dir(&self) -> Result<Arc<dyn Directory>>;
+    /// `statfs(2)`.
+    fn statfs(&self) -> Result<FsStat>;
+}
+
+/// File system statistics reported by `statfs(2)`.
+#[derive(Debug, Copy, Clone)]
+pub struct FsStat {
+    /// The file system type magic number (e.g. `TMPFS_MAGIC`).
+    pub magic: u64,
+    /// The block size in bytes.
+    pub block_size: usize,
+    pub total_blocks: u64,
+    pub free_blocks: u64,
+    /// Free blocks available to unprivileged users.
+    pub available_blocks: u64,
+    pub total_inodes: u64,
+    pub free_inodes: u64,
+    /// The maximum length of file names.
+    pub max_name_len: usize,
+}
+
+impl FsStat {
+    /// Statistics of a file system without any capacity (e.g. procfs).
+    pub fn empty(magic: u64) -> FsStat {
+        FsStat {
+            magic,
+            block_size: PAGE_SIZE,
+            total_blocks: 0,
+            free_blocks: 0,
+            available_blocks: 0,
+            total_inodes: 0,
+            free_inodes: 0,
+            max_name_len: 255,
+        }
+    }
+}
+
+/// A file system type which can be mounted through `mount(2)`.
+#[derive(Clone, Copy)]
+pub struct FileSystemType {
+    /// The name passed to `mount(2)` (e.g. `tmpfs` or `ext4`).
+    pub name: &'static str,
+    /// `true` if the file system is backed by a block device. Otherwise,
+    /// it's marked as `nodev` in `/proc/filesystems`.
+    pub requires_device: bool,
+    /// Instantiates the file system. `source` is the source argument of
+    /// `mount(2)` (e.g. `/dev/vda1`) and `data` is the file system specific
+    /// options (e.g. `size=16m`).
+    pub mount: fn(source: &str, data: &str) -> Result<Arc<dyn FileSystem>>,
+}
+
+static FILE_SYSTEM_TYPES: SpinLock<Vec<FileSystemType>> = SpinLock::new(Vec::new());
+
+pub fn register_file_system_type(fs_type: FileSystemType) {
+    FILE_SYSTEM_TYPES.lock().push(fs_type);
+}
+
+pub fn lookup_file_system_type(name: &str) -> Option<FileSystemType> {
+    FILE_SYSTEM_TYPES
+        .lock()
+        .iter()
+        .find(|fs_type| fs_type.name == name)
+        .copied()
+}
+
+/// Returns the registered file system types in the order they were registered.
+pub fn file_system_types() -> Vec<FileSystemType> {
+    FILE_SYSTEM_TYPES.lock().clone()
 }
@@ -2,7 +2,7 @@
 //! <https://www.kernel.org/doc/html/latest/driver-api/early-userspace/buffer-format.html>
 use crate::{
     fs::{
-        file_system::FileSystem,
+        file_system::{FileSystem, FsStat},
         inode::{DirEntry, Directory, FileLike, FileType, INode, INodeNo},
         path::Path,
         stat::FileMode,
@@ -27,6 +27,9 @@ fn parse_hex_field(bytes: &[u8]) -> usize {
     usize::from_str_radix(parse_str_field(bytes), 16).unwrap()
 }
 
+/// The rootfs in Linux is a ramfs.
+const RAMFS_MAGIC: u64 = 0x858458f6;
+
 pub static INITRAM_FS: Once<Arc<InitramFs>> = Once::new();
 
 struct InitramFsFile {
@@ -313,9 +316,17 @@ impl InitramFs {
 }
 
 impl FileSystem for InitramFs {
+    fn name(&self) -> &'static str {
+        "rootfs"
+    }
+
     fn root_dir(&self) -> Result<Arc<dyn Directory>> {
         Ok(self.root_dir.clone())
     }
+
+    fn statfs(&self) -> Result<FsStat> {
+        Ok(FsStat::empty(RAMFS_MAGIC))
+    }
 }
 
 pub fn init() {
@@ -1,10 +1,14 @@
 use core::fmt::{self, Debug};
 
-use super::{opened_file::OpenOptions, path::PathBuf, stat::FileMode};
-use crate::ctypes::c_short;
+use super::{
+    opened_file::OpenOptions,
+    path::PathBuf,
+    stat::{FileMode, GId, Time, UId, S_IFMT},
+};
+use crate::ctypes::{c_int, c_short};
 use crate::prelude::*;
 use crate::{fs::stat::Stat, user_buffer::UserBufferMut};
-use crate::{net::*, user_buffer::UserBuffer};
+use crate::{net::*, pipe::PipeBuffer, user_buffer::UserBuffer};
 use bitflags::bitflags;
 use kerla_utils::downcast::Downcastable;
 
@@ -38,6 +42,54 @@ bitflags! {
     }
 }
 
+bitflags! {
+    pub struct RenameFlags: c_int {
+        const RENAME_NOREPLACE = 1 << 0;
+        const RENAME_EXCHANGE  = 1 << 1;
+        const RENAME_WHITEOUT  = 1 << 2;
+    }
+}
+
+/// Changes in inode attributes by `chmod(2)`, `chown(2)`, and
+/// `utimensat(2)`. `None` leaves the attribute unchanged.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SetAttr {
+    /// The permission bits. The file type bits are ignored.
+    pub mode: Option<FileMode>,
+    pub uid: Option<UId>,
+    pub gid: Option<GId>,
+    pub atime: Option<Time>,
+    pub mtime: Option<Time>,
+}
+
+impl SetAttr {
+    /// Applies the changes to `stat`. The status change time is also updated.
+    pub fn apply(&self, stat: &mut Stat) {
+        if let Some(mode) = self.mode {
+            let file_type = stat.mode.as_u32() & S_IFMT;
+            stat.mode = FileMode::new(file_type | (mode.as_u32() & 0o7777));
+        }
+
+        if let Some(uid) = self.uid {
+            stat.uid = uid;
+        }
+
+        if let Some(gid) = self.gid {
+            stat.gid = gid;
+        }
+
+        if let Some(atime) = self.atime {
+            stat.atime = atime;
+        }
+
+        if let Some(mtime) = self.mtime {
+            stat.mtime = mtime;
+        }
+
+        stat.ctime = Time::now();
+    }
+}
+
 /// A file-like object.
 ///
 /// This trait represents an object which behaves like a file such as files on
@@ -84,6 +136,63 @@ pub trait FileLike: Debug + Send + Sync + Downcastable {
         Err(Error::new(Errno::EBADF))
     }
 
+    /// `truncate(2)`.
+    fn truncate(&self, _length: usize) -> Result<()> {
+        // "EINVAL - The argument length is negative or larger than the maximum
+        // file size." -- truncate(2)
+        Err(Error::new(Errno::EINVAL))
+    }
+
+    /// Returns `false` if the file has no file position like pipes and
+    /// sockets. `lseek(2)`, `pread64(2)`, etc. fail with `ESPIPE` on them.
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    /// `lseek(2)` with `SEEK_DATA`: the offset of the first data at or after
+    /// `offset`. By default, the whole file is data.
+    fn next_data(&self, offset: usize) -> Result<usize> {
+        let size = self.stat()?.size.0 as usize;
+        if offset >= size {
+            return Err(Error::new(Errno::ENXIO));
+        }
+
+        Ok(offset)
+    }
+
+    /// `lseek(2)` with `SEEK_HOLE`: the offset of the first hole at or after
+    /// `offset`. The end of the file is considered as a hole.
+    fn next_hole(&self, offset: usize) -> Result<usize> {
+        let size = self.stat()?.size.0 as usize;
+        if offset >= size {
+            return Err(Error::new(Errno::ENXIO));
+        }
+
+        Ok(size)
+    }
+
+    /// Returns up to `len` bytes at `offset` for `splice(2)`, `sendfile(2)`
+    /// and `copy_file_range(2)`. An empty `Vec` means EOF.
+    ///
+    /// Implementations should return references to their own buffers (e.g.
+    /// pages in tmpfs) if possible. By default, the data are read into a new
+    /// kernel buffer.
+    fn splice_read(
+        &self,
+        offset: usize,
+        len: usize,
+        options: &OpenOptions,
+    ) -> Result<Vec<PipeBuffer>> {
+        let mut data = vec![0; len];
+        let read_len = self.read(offset, UserBufferMut::from(data.as_mut_slice()), options)?;
+        if read_len == 0 {
+            return Ok(Vec::new());
+        }
+
+        data.truncate(read_len);
+        Ok(vec![PipeBuffer::from(data)])
+    }
+
     /// `bind(2)`.
     fn bind(&self, _sockaddr: SockAddr) -> Result<()> {
         Err(Error::new(Errno::EBADF))
@@ -114,6 +223,11 @@ pub trait FileLike: Debug + Send + Sync + Downcastable {
         Ok(())
     }
 
+    /// `chmod(2)`, `chown(2)`, and `utimensat(2)`.
+    fn set_attr(&self, _attr: &SetAttr) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
+
     /// `accept(2)`.
     fn accept(&self, _options: &OpenOptions) -> Result<(Arc<dyn FileLike>, SockAddr)> {
         Err(Error::new(Errno::EBADF))
@@ -150,9 +264,13 @@ pub trait FileLike: Debug + Send + Sync + Downcastable {
 #[repr(u8)]
 #[non_exhaustive]
 pub enum FileType {
+    Fifo = 1,
+    CharDevice = 2,
     Directory = 4,
+    BlockDevice = 6,
     Regular = 8,
     Link = 10,
+    Socket = 12,
 }
 
 /// A directory entry (ones returned from `readdir(3)`).
@@ -170,9 +288,9 @@ pub struct DirEntry {
 pub trait Directory: Debug + Send + Sync + Downcastable {
     /// Looks for an existing file.
     fn lookup(&self, name: &str) -> Result<INode>;
-    /// Creates a file. Returns `EEXIST` if the it already exists.
+    /// Creates a file. Returns `EEXIST` if it already exists.
     fn create_file(&self, _name: &str, _mode: FileMode) -> Result<INode>;
-    /// Creates a directory. Returns `EEXIST` if the it already exists.
+    /// Creates a directory. Returns `EEXIST` if it already exists.
     fn create_dir(&self, _name: &str, _mode: FileMode) -> Result<INode>;
     /// `stat(2)`.
     fn stat(&self) -> Result<Stat>;
@@ -180,10 +298,37 @@ pub trait Directory: Debug + Send + Sync + Downcastable {
     fn readdir(&self, index: usize) -> Result<Option<DirEntry>>;
     /// `link(2)`.
     fn link(&self, _name: &str, _link_to: &INode) -> Result<()>;
+    /// `unlink(2)`.
+    fn unlink(&self, _name: &str) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
+    /// `rmdir(2)`. Returns `ENOTEMPTY` if the directory is not empty.
+    fn rmdir(&self, _name: &str) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
+    /// `renameat2(2)`. Moves `old_name` in this directory to `new_name` in
+    /// `new_dir`. Returns `EXDEV` if `new_dir` is in another file system.
+    fn rename(
+        &self,
+        _old_name: &str,
+        _new_dir: &Arc<dyn Directory>,
+        _new_name: &str,
+        _flags: RenameFlags,
+    ) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
+    /// `symlink(2)`. Returns `EEXIST` if it already exists.
+    fn create_symlink(&self, _name: &str, _target: &str) -> Result<INode> {
+        Err(Error::new(Errno::EPERM))
+    }
     /// `fsync(2)`.
     fn fsync(&self) -> Result<()> {
         Ok(())
     }
+    /// `chmod(2)`, `chown(2)`, and `utimensat(2)`.
+    fn set_attr(&self, _attr: &SetAttr) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
     /// `readlink(2)`.
     fn readlink(&self) -> Result<PathBuf> {
         // "EINVAL - The named file is not a symbolic link." -- readlink(2)
@@ -205,6 +350,10 @@ pub trait Symlink: Debug + Send + Sync + Downcastable {
     fn fsync(&self) -> Result<()> {
         Ok(())
     }
+    /// `chmod(2)`, `chown(2)`, and `utimensat(2)`.
+    fn set_attr(&self, _attr: &SetAttr) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
 }
 
 /// An inode object.
@@ -277,10 +426,13 @@ impl INode {
         }
     }
 
-    /// `chmod(2)`
-    pub fn chmod(&self, _mode: FileMode) -> Result<()> {
-        // FIXME: Ignore all chmod requests for now.
-        Ok(())
+    /// `chmod(2)`, `chown(2)`, and `utimensat(2)`.
+    pub fn set_attr(&self, attr: &SetAttr) -> Result<()> {
+        match self {
+            INode::FileLike(file) => file.set_attr(attr),
+            INode::Symlink(file) => file.set_attr(attr),
+            INode::Directory(dir) => dir.set_attr(attr),
+        }
     }
 }
 
@@ -0,0 +1,470 @@
+//! File change notifications (`inotify(7)`).
+//!
+//! Events are emitted from the VFS layer (system calls and `OpenedFile`) so
+//! that they're delivered for any file system. Inodes are identified by the
+//! mount and the inode number (see `WatchKey`).
+use super::{
+    inode::{FileLike, INodeNo, PollStatus},
+    opened_file::{OpenOptions, PathComponent, ANONYMOUS_MOUNT_ID},
+};
+use crate::{
+    ctypes::c_int,
+    poll::POLL_WAIT_QUEUE,
+    prelude::*,
+    process::WaitQueue,
+    user_buffer::{UserBufWriter, UserBuffer, UserBufferMut},
+};
+use alloc::collections::{BTreeMap, VecDeque};
+use bitflags::bitflags;
+use core::{
+    fmt,
+    mem::size_of,
+    sync::atomic::{AtomicU32, AtomicUsize, Ordering},
+};
+use kerla_runtime::{address::UserVAddr, spinlock::SpinLock};
+use kerla_utils::alignment::align_up;
+
+/// The maximum number of events queued in an inotify instance
+/// (`/proc/sys/fs/inotify/max_queued_events` in Linux).
+const MAX_QUEUED_EVENTS: usize = 16384;
+
+/// The `ioctl(2)` command to get the number of bytes available to read.
+const FIONREAD: usize = 0x541b;
+
+/// Inotify instances. Dropped ones are removed lazily.
+static INSTANCES: SpinLock<Vec<Weak<Inotify>>> = SpinLock::new(Vec::new());
+/// The number of watches in all instances. Used to skip looking for watches
+/// when no one watches anything.
+static NUM_WATCHES: AtomicUsize = AtomicUsize::new(0);
+/// The cookie which associates `IN_MOVED_FROM` and `IN_MOVED_TO` events.
+static NEXT_COOKIE: AtomicU32 = AtomicU32::new(1);
+
+bitflags! {
+    pub struct InotifyMask: u32 {
+        const IN_ACCESS        = 0x00000001;
+        const IN_MODIFY        = 0x00000002;
+        const IN_ATTRIB        = 0x00000004;
+        const IN_CLOSE_WRITE   = 0x00000008;
+        const IN_CLOSE_NOWRITE = 0x00000010;
+        const IN_OPEN          = 0x00000020;
+        const IN_MOVED_FROM    = 0x00000040;
+        const IN_MOVED_TO      = 0x00000080;
+        const IN_CREATE        = 0x00000100;
+        const IN_DELETE        = 0x00000200;
+        const IN_DELETE_SELF   = 0x00000400;
+        const IN_MOVE_SELF     = 0x00000800;
+        const IN_UNMOUNT       = 0x00002000;
+        const IN_Q_OVERFLOW    = 0x00004000;
+        const IN_IGNORED       = 0x00008000;
+        const IN_ONLYDIR       = 0x01000000;
+        const IN_DONT_FOLLOW   = 0x02000000;
+        const IN_EXCL_UNLINK   = 0x04000000;
+        const IN_MASK_CREATE   = 0x10000000;
+        const IN_MASK_ADD      = 0x20000000;
+        const IN_ISDIR         = 0x40000000;
+        const IN_ONESHOT       = 0x80000000;
+    }
+}
+
+impl InotifyMask {
+    /// Events which can be watched.
+    pub fn all_events() -> InotifyMask {
+        InotifyMask::from_bits_truncate(0xfff)
+    }
+}
+
+/// Identifies a watched inode. Inode objects can't be used for this since
+/// some file systems (e.g. ext2) create a new one on every lookup.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WatchKey {
+    mount_id: usize,
+    inode_no: INodeNo,
+}
+
+impl WatchKey {
+    /// Returns the key of the file. Note that this calls `stat`, which may
+    /// sleep on some file systems. Anonymous files (e.g. pipes) can't be
+    /// watched.
+    pub fn new(path: &PathComponent) -> Result<WatchKey> {
+        if path.mount_id == ANONYMOUS_MOUNT_ID {
+            return Err(Errno::EINVAL.into());
+        }
+
+        Ok(WatchKey {
+            mount_id: path.mount_id,
+            inode_no: path.inode.stat()?.inode_no,
+        })
+    }
+}
+
+/// Returns `true` if someone watches something.
+fn is_watched() -> bool {
+    NUM_WATCHES.load(Ordering::Relaxed) > 0
+}
+
+/// Emits an event on the file. `name` is the name of the entry if the file
+/// is a directory and the event is about its entry.
+fn notify(path: &PathComponent, mask: InotifyMask, name: Option<&str>, cookie: u32) {
+    let key = match WatchKey::new(path) {
+        Ok(key) => key,
+        Err(_) => return,
+    };
+
+    let instances: Vec<Arc<Inotify>> = {
+        let mut instances = INSTANCES.lock();
+        instances.retain(|instance| instance.strong_count() > 0);
+        instances
+            .iter()
+            .filter_map(|instance| instance.upgrade())
+            .collect()
+    };
+
+    for instance in instances {
+        instance.queue_event(key, mask, name, cookie);
+    }
+}
+
+/// Emits an event on the file and its parent directory.
+pub fn notify_path(path: &PathComponent, mut mask: InotifyMask) {
+    if !is_watched() {
+        return;
+    }
+
+    if path.inode.is_dir() {
+        mask |= InotifyMask::IN_ISDIR;
+    }
+
+    notify(path, mask, None, 0);
+    if let Some(parent_dir) = &path.parent_dir {
+        notify(parent_dir, mask, Some(&path.name), 0);
+    }
+}
+
+/// Emits an event on the entry `name` in the directory `dir` (e.g.
+/// `IN_CREATE`).
+pub fn notify_entry(dir: &PathComponent, name: &str, mut mask: InotifyMask, is_dir: bool) {
+    if !is_watched() {
+        return;
+    }
+
+    if is_dir {
+        mask |= InotifyMask::IN_ISDIR;
+    }
+
+    notify(dir, mask, Some(name), 0);
+}
+
+/// Emits `IN_MOVED_FROM` and `IN_MOVED_TO` events for `rename(2)`.
+pub fn notify_move(
+    old_dir: &PathComponent,
+    old_name: &str,
+    new_dir: &PathComponent,
+    new_name: &str,
+    is_dir: bool,
+) {
+    if !is_watched() {
+        return;
+    }
+
+    let mut mask = InotifyMask::empty();
+    if is_dir {
+        mask |= InotifyMask::IN_ISDIR;
+    }
+
+    let cookie = NEXT_COOKIE.fetch_add(1, Ordering::Relaxed);
+    notify(
+        old_dir,
+        mask | InotifyMask::IN_MOVED_FROM,
+        Some(old_name),
+        cookie,
+    );
+    notify(
+        new_dir,
+        mask | InotifyMask::IN_MOVED_TO,
+        Some(new_name),
+        cookie,
+    );
+}
+
+struct Watch {
+    key: WatchKey,
+    mask: InotifyMask,
+}
+
+#[derive(PartialEq, Eq)]
+struct Event {
+    wd: c_int,
+    mask: InotifyMask,
+    cookie: u32,
+    name: Option<String>,
+}
+
+impl Event {
+    /// The length of the name field: NUL-terminated and padded to the size of
+    /// `struct inotify_event`.
+    fn name_len(&self) -> usize {
+        match &self.name {
+            Some(name) => align_up(name.len() + 1, size_of::<InotifyEventHeader>()),
+            None => 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        size_of::<InotifyEventHeader>() + self.name_len()
+    }
+}
+
+/// `struct inotify_event` without the name.
+#[derive(Copy, Clone)]
+#[repr(C)]
+struct InotifyEventHeader {
+    wd: c_int,
+    mask: u32,
+    cookie: u32,
+    len: u32,
+}
+
+struct InotifyInner {
+    watches: BTreeMap<c_int, Watch>,
+    next_wd: c_int,
+    events: VecDeque<Event>,
+}
+
+impl InotifyInner {
+    fn push_event(&mut self, event: Event) {
+        // Merge into the last event if they're identical as Linux does.
+        if self.events.back() == Some(&event) {
+            return;
+        }
+
+        if self.events.len() >= MAX_QUEUED_EVENTS {
+            if !matches!(self.events.back(), Some(last) if last.mask == InotifyMask::IN_Q_OVERFLOW)
+            {
+                self.events.push_back(Event {
+                    wd: -1,
+                    mask: InotifyMask::IN_Q_OVERFLOW,
+                    cookie: 0,
+                    name: None,
+                });
+            }
+            return;
+        }
+
+        self.events.push_back(event);
+    }
+
+    fn remove_watch(&mut self, wd: c_int) -> Result<()> {
+        if self.watches.remove(&wd).is_none() {
+            return Err(Errno::EINVAL.into());
+        }
+
+        NUM_WATCHES.fetch_sub(1, Ordering::Relaxed);
+        self.push_event(Event {
+            wd,
+            mask: InotifyMask::IN_IGNORED,
+            cookie: 0,
+            name: None,
+        });
+        Ok(())
+    }
+}
+
+/// An inotify instance created by `inotify_init1(2)`.
+pub struct Inotify {
+    inner: SpinLock<InotifyInner>,
+    wait_queue: WaitQueue,
+}
+
+impl Inotify {
+    pub fn new() -> Arc<Inotify> {
+        let inotify = Arc::new(Inotify {
+            inner: SpinLock::new(InotifyInner {
+                watches: BTreeMap::new(),
+                next_wd: 1,
+                events: VecDeque::new(),
+            }),
+            wait_queue: WaitQueue::new(),
+        });
+
+        INSTANCES.lock().push(Arc::downgrade(&inotify));
+        inotify
+    }
+
+    /// `inotify_add_watch(2)`. Returns the watch descriptor.
+    pub fn add_watch(&self, path: &PathComponent, mask: InotifyMask) -> Result<c_int> {
+        if mask.contains(InotifyMask::IN_MASK_ADD | InotifyMask::IN_MASK_CREATE)
+            || !mask.intersects(InotifyMask::all_events())
+        {
+            return Err(Errno::EINVAL.into());
+        }
+
+        if mask.contains(InotifyMask::IN_ONLYDIR) && !path.inode.is_dir() {
+            return Err(Errno::ENOTDIR.into());
+        }
+
+        let key = WatchKey::new(path)?;
+        let new_mask = mask
+            & !(InotifyMask::IN_ONLYDIR
+                | InotifyMask::IN_DONT_FOLLOW
+                | InotifyMask::IN_MASK_ADD
+                | InotifyMask::IN_MASK_CREATE);
+
+        let mut inner = self.inner.lock();
+        let existing = inner.watches.iter_mut().find(|(_, watch)| watch.key == key);
+        if let Some((wd, watch)) = existing {
+            if mask.contains(InotifyMask::IN_MASK_CREATE) {
+                return Err(Errno::EEXIST.into());
+            }
+
+            if mask.contains(InotifyMask::IN_MASK_ADD) {
+                watch.mask |= new_mask;
+            } else {
+                watch.mask = new_mask;
+            }
+
+            return Ok(*wd);
+        }
+
+        let wd = inner.next_wd;
+        inner.next_wd = wd.checked_add(1).ok_or_else(|| Error::new(Errno::ENOSPC))?;
+        inner.watches.insert(
+            wd,
+            Watch {
+                key,
+                mask: new_mask,
+            },
+        );
+
+        NUM_WATCHES.fetch_add(1, Ordering::Relaxed);
+        Ok(wd)
+    }
+
+    /// `inotify_rm_watch(2)`.
+    pub fn rm_watch(&self, wd: c_int) -> Result<()> {
+        self.inner.lock().remove_watch(wd)?;
+        self.wake_readers();
+        Ok(())
+    }
+
+    fn queue_event(&self, key: WatchKey, mask: InotifyMask, name: Option<&str>, cookie: u32) {
+        let mut inner = self.inner.lock();
+        let matched: Vec<(c_int, bool)> = inner
+            .watches
+            .iter()
+            .filter(|(_, watch)| {
+                watch.key == key && watch.mask.intersects(mask & InotifyMask::all_events())
+            })
+            .map(|(wd, watch)| (*wd, watch.mask.contains(InotifyMask::IN_ONESHOT)))
+            .collect();
+
+        if matched.is_empty() {
+            return;
+        }
+
+        for (wd, oneshot) in matched {
+            inner.push_event(Event {
+                wd,
+                mask,
+                cookie,
+                name: name.map(|name| name.to_owned()),
+            });
+
+            if oneshot {
+                inner.remove_watch(wd).ok();
+            }
+        }
+
+        drop(inner);
+        self.wake_readers();
+    }
+
+    fn wake_readers(&self) {
+        self.wait_queue.wake_all();
+        POLL_WAIT_QUEUE.wake_all();
+    }
+}
+
+impl Drop for Inotify {
+    fn drop(&mut self) {
+        NUM_WATCHES.fetch_sub(self.inner.lock().watches.len(), Ordering::Relaxed);
+    }
+}
+
+impl FileLike for Inotify {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
+    fn read(&self, _offset: usize, buf: UserBufferMut<'_>, options: &OpenOptions) -> Result<usize> {
+        let mut writer = UserBufWriter::from(buf);
+        self.wait_queue.sleep_signalable_until(|| {
+            let mut inner = self.inner.lock();
+            if inner.events.is_empty() {
+                if options.nonblock {
+                    return Err(Errno::EAGAIN.into());
+                }
+
+                return Ok(None);
+            }
+
+            // Read whole events as many as possible.
+            while let Some(event) = inner.events.front() {
+                if event.len() > writer.remaining_len() {
+                    break;
+                }
+
+                let name_len = event.name_len();
+                writer.write(InotifyEventHeader {
+                    wd: event.wd,
+                    mask: event.mask.bits(),
+                    cookie: event.cookie,
+                    len: name_len as u32,
+                })?;
+
+                if let Some(name) = &event.name {
+                    writer.write_bytes(name.as_bytes())?;
+                    writer.fill(0, name_len - name.len())?;
+                }
+
+                inner.events.pop_front();
+            }
+
+            // "EINVAL - The buffer is too small to hold the next event."
+            if writer.written_len() == 0 {
+                return Err(Errno::EINVAL.into());
+            }
+
+            Ok(Some(writer.written_len()))
+        })
+    }
+
+    fn write(&self, _offset: usize, _buf: UserBuffer<'_>, _options: &OpenOptions) -> Result<usize> {
+        Err(Errno::EINVAL.into())
+    }
+
+    fn ioctl(&self, cmd: usize, arg: usize) -> Result<isize> {
+        match cmd {
+            FIONREAD => {
+                let len: usize = self.inner.lock().events.iter().map(Event::len).sum();
+                UserVAddr::new_nonnull(arg)?.write::<c_int>(&(len as c_int))?;
+                Ok(0)
+            }
+            _ => Err(Errno::EINVAL.into()),
+        }
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        let mut status = PollStatus::empty();
+        if !self.inner.lock().events.is_empty() {
+            status |= PollStatus::POLLIN;
+        }
+
+        Ok(status)
+    }
+}
+
+impl fmt::Debug for Inotify {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Inotify").finish()
+    }
+}
@@ -1,10 +1,16 @@
 pub mod devfs;
+pub mod ext2;
+pub mod file_lock;
 pub mod file_system;
 pub mod initramfs;
 pub mod inode;
+pub mod inotify;
 pub mod mount;
 pub mod opened_file;
+pub mod overlayfs;
+pub mod p9;
 pub mod path;
 pub mod procfs;
 pub mod stat;
+pub mod sysfs;
 pub mod tmpfs;
@@ -3,48 +3,391 @@ use super::{
     inode::{Directory, FileLike, INode, INodeNo},
     opened_file::OpenedFileTable,
     opened_file::PathComponent,
-    path::Path,
+    path::{Path, PathBuf},
 };
 use crate::prelude::*;
-use crate::syscalls::CwdOrFd;
+use crate::process::list_processes;
+use crate::syscalls::{AtFlags, CwdOrFd};
+use kerla_runtime::spinlock::SpinLock;
 
-use hashbrown::HashMap;
+use bitflags::bitflags;
 
 const DEFAULT_SYMLINK_FOLLOW_MAX: usize = 8;
 
+/// The mount ID of the root file system.
+const ROOT_MOUNT_ID: usize = 1;
+
+bitflags! {
+    /// The flags of `mount(2)`.
+    pub struct MountFlags: usize {
+        const MS_RDONLY = 1;
+        const MS_NOSUID = 2;
+        const MS_NODEV = 4;
+        const MS_NOEXEC = 8;
+        const MS_SYNCHRONOUS = 16;
+        const MS_REMOUNT = 32;
+        const MS_MANDLOCK = 64;
+        const MS_DIRSYNC = 128;
+        const MS_NOATIME = 1024;
+        const MS_NODIRATIME = 2048;
+        const MS_BIND = 4096;
+        const MS_MOVE = 8192;
+        const MS_REC = 16384;
+        const MS_SILENT = 32768;
+        const MS_UNBINDABLE = 1 << 17;
+        const MS_PRIVATE = 1 << 18;
+        const MS_SLAVE = 1 << 19;
+        const MS_SHARED = 1 << 20;
+        const MS_RELATIME = 1 << 21;
+        const MS_STRICTATIME = 1 << 24;
+        const MS_LAZYTIME = 1 << 25;
+
+        /// The flags kept in each mount. The others specify the operation.
+        const PER_MOUNT = Self::MS_RDONLY.bits
+            | Self::MS_NOSUID.bits
+            | Self::MS_NODEV.bits
+            | Self::MS_NOEXEC.bits
+            | Self::MS_NOATIME.bits
+            | Self::MS_NODIRATIME.bits
+            | Self::MS_RELATIME.bits;
+        /// The flags to change the mount propagation type.
+        const PROPAGATION = Self::MS_UNBINDABLE.bits
+            | Self::MS_PRIVATE.bits
+            | Self::MS_SLAVE.bits
+            | Self::MS_SHARED.bits;
+    }
+}
+
 pub struct MountPoint {
+    /// The unique ID of the mount (`mount_id` in `/proc/self/mountinfo`).
+    id: usize,
+    /// The ID of the mount which this mount is mounted on. The root file
+    /// system points to itself.
+    parent_id: usize,
+    /// The inode number of the directory mounted on (in the parent mount).
+    mounted_on: INodeNo,
+    /// The absolute path to the mount point.
+    path: PathBuf,
+    /// The source given to `mount(2)` (e.g. `/dev/vda1`).
+    source: String,
     fs: Arc<dyn FileSystem>,
+    /// The root directory of the mount. It's a subdirectory of the file
+    /// system in a bind mount.
+    root_dir: Arc<dyn Directory>,
+    /// The path to `root_dir` in the file system (`root` in
+    /// `/proc/self/mountinfo`).
+    root: PathBuf,
+    flags: MountFlags,
+}
+
+impl MountPoint {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn parent_id(&self) -> usize {
+        self.parent_id
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn fs(&self) -> &Arc<dyn FileSystem> {
+        &self.fs
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn flags(&self) -> MountFlags {
+        self.flags
+    }
+
+    /// Returns the mount options shown in `/proc/mounts` (e.g. `ro,noexec`).
+    pub fn options(&self) -> String {
+        let mut options = String::from(if self.flags.contains(MountFlags::MS_RDONLY) {
+            "ro"
+        } else {
+            "rw"
+        });
+
+        for (flag, name) in [
+            (MountFlags::MS_NOSUID, "nosuid"),
+            (MountFlags::MS_NODEV, "nodev"),
+            (MountFlags::MS_NOEXEC, "noexec"),
+            (MountFlags::MS_NOATIME, "noatime"),
+            (MountFlags::MS_NODIRATIME, "nodiratime"),
+            (MountFlags::MS_RELATIME, "relatime"),
+        ] {
+            if self.flags.contains(flag) {
+                options.push(',');
+                options.push_str(name);
+            }
+        }
+
+        options
+    }
 }
 
 pub struct RootFs {
     root_path: Arc<PathComponent>,
     cwd_path: Arc<PathComponent>,
-    mount_points: HashMap<INodeNo, MountPoint>,
+    /// The mounts in the order they were mounted. The first one is the root
+    /// file system.
+    mount_points: Vec<MountPoint>,
+    next_mount_id: usize,
     symlink_follow_limit: usize,
 }
 
 impl RootFs {
     pub fn new(root: Arc<dyn FileSystem>) -> Result<RootFs> {
+        let root_dir = root.root_dir()?;
         let root_path = Arc::new(PathComponent {
             parent_dir: None,
             name: String::new(),
-            inode: root.root_dir()?.into(),
+            inode: root_dir.clone().into(),
+            mount_id: ROOT_MOUNT_ID,
         });
 
         Ok(RootFs {
-            mount_points: HashMap::new(),
+            mount_points: vec![MountPoint {
+                id: ROOT_MOUNT_ID,
+                parent_id: ROOT_MOUNT_ID,
+                mounted_on: root_dir.stat()?.inode_no,
+                path: PathBuf::from("/"),
+                source: root.name().to_owned(),
+                root_dir,
+                root: PathBuf::from("/"),
+                fs: root,
+                flags: MountFlags::empty(),
+            }],
+            next_mount_id: ROOT_MOUNT_ID + 1,
             root_path: root_path.clone(),
             cwd_path: root_path,
             symlink_follow_limit: DEFAULT_SYMLINK_FOLLOW_MAX,
         })
     }
 
-    pub fn mount(&mut self, dir: Arc<dyn Directory>, fs: Arc<dyn FileSystem>) -> Result<()> {
+    /// Mounts `fs` on the directory at `path`.
+    pub fn mount(&mut self, path: &Path, fs: Arc<dyn FileSystem>) -> Result<()> {
+        self.mount_fs(path, fs.name(), fs, MountFlags::empty())
+    }
+
+    /// Mounts `fs` on the directory at `path` with the flags. `source` is
+    /// the source given to `mount(2)`.
+    pub fn mount_fs(
+        &mut self,
+        path: &Path,
+        source: &str,
+        fs: Arc<dyn FileSystem>,
+        flags: MountFlags,
+    ) -> Result<()> {
+        let root_dir = fs.root_dir()?;
+        self.add_mount_point(path, source, fs, root_dir, PathBuf::from("/"), flags)
+    }
+
+    /// Makes the directory at `source` also visible at `path` (`MS_BIND`).
+    pub fn bind_mount(&mut self, source: &Path, path: &Path, flags: MountFlags) -> Result<()> {
+        let source_comp = self.lookup_path(source, true)?;
+        let source_dir = match &source_comp.inode {
+            INode::Directory(dir) => dir.clone(),
+            // TODO: Support bind-mounting a file.
+            _ => return Err(Errno::ENOTDIR.into()),
+        };
+
+        let source_mount = self.find_mount_point(source_comp.mount_id)?;
+        let fs = source_mount.fs.clone();
+        let mut root = source_mount.root.clone();
+        for name in self.names_in_mount(&source_comp) {
+            root.push(name);
+        }
+
+        let source = source_comp.resolve_absolute_path();
+
+        self.add_mount_point(path, source.as_str(), fs, source_dir, root, flags)
+    }
+
+    /// Changes the flags of the mount whose root is at `path` (`MS_REMOUNT`).
+    pub fn remount(&mut self, path: &Path, flags: MountFlags) -> Result<()> {
+        let mount_id = self.lookup_mount_root(path, true)?;
+        let mount_point = self
+            .mount_points
+            .iter_mut()
+            .find(|mount_point| mount_point.id == mount_id)
+            .ok_or_else(|| Error::new(Errno::EINVAL))?;
+
+        mount_point.flags = flags & MountFlags::PER_MOUNT;
+        Ok(())
+    }
+
+    /// Unmounts the mount whose root is at `path`.
+    ///
+    /// If `detach` is `false`, it fails with `EBUSY` if the mount is in use.
+    /// Otherwise, it's detached from the tree (along with the mounts on it)
+    /// immediately and the files already opened in it remain accessible
+    /// (`MNT_DETACH`).
+    pub fn unmount(&mut self, path: &Path, follow_symlink: bool, detach: bool) -> Result<()> {
+        let mount_id = self.lookup_mount_root(path, follow_symlink)?;
+        if mount_id == ROOT_MOUNT_ID || (!detach && self.is_busy(mount_id)) {
+            return Err(Errno::EBUSY.into());
+        }
+
+        // A child mount always has a larger ID than its parent's.
+        let mut detached = vec![mount_id];
+        for mount_point in &self.mount_points {
+            if detached.contains(&mount_point.parent_id) && mount_point.id != ROOT_MOUNT_ID {
+                detached.push(mount_point.id);
+            }
+        }
+
         self.mount_points
-            .insert(dir.stat()?.inode_no, MountPoint { fs });
+            .retain(|mount_point| !detached.contains(&mount_point.id));
+        Ok(())
+    }
+
+    /// Returns the mounted file systems (including the root file system) in
+    /// the order they were mounted.
+    pub fn mount_points(&self) -> &[MountPoint] {
+        &self.mount_points
+    }
+
+    /// Returns `EROFS` if `path_comp` is in a read-only mount.
+    pub fn check_writable(&self, path_comp: &PathComponent) -> Result<()> {
+        if self.mount_flags(path_comp).contains(MountFlags::MS_RDONLY) {
+            return Err(Errno::EROFS.into());
+        }
+
+        Ok(())
+    }
+
+    /// Returns `EBUSY` if `path_comp` is the root of a mount: it can't be
+    /// removed or renamed.
+    pub fn check_not_mount_point(&self, path_comp: &PathComponent) -> Result<()> {
+        match &path_comp.parent_dir {
+            Some(parent_dir) if parent_dir.mount_id == path_comp.mount_id => Ok(()),
+            _ => Err(Errno::EBUSY.into()),
+        }
+    }
+
+    /// Returns `EACCES` if `path_comp` is in a `noexec` mount.
+    pub fn check_executable(&self, path_comp: &PathComponent) -> Result<()> {
+        if self.mount_flags(path_comp).contains(MountFlags::MS_NOEXEC) {
+            return Err(Errno::EACCES.into());
+        }
+
         Ok(())
     }
 
+    /// Returns the mount which `path_comp` belongs to. Fails if it has been
+    /// detached or the path is anonymous.
+    pub fn mount_point_of(&self, path_comp: &PathComponent) -> Result<&MountPoint> {
+        self.find_mount_point(path_comp.mount_id)
+    }
+
+    fn mount_flags(&self, path_comp: &PathComponent) -> MountFlags {
+        // The mount may have been already detached (or the path is anonymous).
+        self.find_mount_point(path_comp.mount_id)
+            .map(|mount_point| mount_point.flags)
+            .unwrap_or_else(|_| MountFlags::empty())
+    }
+
+    fn find_mount_point(&self, mount_id: usize) -> Result<&MountPoint> {
+        self.mount_points
+            .iter()
+            .find(|mount_point| mount_point.id == mount_id)
+            .ok_or_else(|| Error::new(Errno::EINVAL))
+    }
+
+    fn add_mount_point(
+        &mut self,
+        path: &Path,
+        source: &str,
+        fs: Arc<dyn FileSystem>,
+        root_dir: Arc<dyn Directory>,
+        root: PathBuf,
+        flags: MountFlags,
+    ) -> Result<()> {
+        let path_comp = self.lookup_path(path, true)?;
+        if path_comp.parent_dir.is_none() {
+            // We don't support replacing the root directory.
+            return Err(Errno::EBUSY.into());
+        }
+
+        let mounted_on = path_comp.inode.as_dir()?.stat()?.inode_no;
+        let id = self.next_mount_id;
+        self.next_mount_id += 1;
+        self.mount_points.push(MountPoint {
+            id,
+            parent_id: path_comp.mount_id,
+            mounted_on,
+            path: path_comp.resolve_absolute_path(),
+            source: source.to_owned(),
+            fs,
+            root_dir,
+            root,
+            flags: flags & MountFlags::PER_MOUNT,
+        });
+        Ok(())
+    }
+
+    /// Returns the ID of the mount whose root is at `path`. Returns `EINVAL`
+    /// if `path` is not a mount point.
+    fn lookup_mount_root(&self, path: &Path, follow_symlink: bool) -> Result<usize> {
+        let path_comp = self.lookup_path(path, follow_symlink)?;
+        match &path_comp.parent_dir {
+            Some(parent_dir) if parent_dir.mount_id == path_comp.mount_id => {
+                Err(Errno::EINVAL.into())
+            }
+            _ => Ok(path_comp.mount_id),
+        }
+    }
+
+    /// Returns the path components from the root of the mount to
+    /// `path_comp`.
+    fn names_in_mount<'a>(&self, path_comp: &'a PathComponent) -> Vec<&'a str> {
+        let mut names = Vec::new();
+        let mut current = path_comp;
+        while let Some(parent_dir) = &current.parent_dir {
+            if parent_dir.mount_id != current.mount_id {
+                // `current` is the root of the mount.
+                break;
+            }
+
+            names.push(current.name.as_str());
+            current = parent_dir;
+        }
+
+        names.reverse();
+        names
+    }
+
+    /// Returns `true` if the mount is in use: other file systems are mounted
+    /// on it, it contains the current working directory, or a process has
+    /// opened a file in it.
+    fn is_busy(&self, mount_id: usize) -> bool {
+        let has_child = self
+            .mount_points
+            .iter()
+            .any(|mount_point| mount_point.parent_id == mount_id && mount_point.id != mount_id);
+        if has_child || self.cwd_path.mount_id == mount_id {
+            return true;
+        }
+
+        list_processes().iter().any(|process| {
+            process
+                .opened_files()
+                .lock()
+                .iter()
+                .any(|opened_file| opened_file.path().mount_id == mount_id)
+        })
+    }
+
     /// Resolves a path (from the current working directory) into an inode.
     /// This method resolves symbolic links: it will never return `INode::Symlink`.
     pub fn lookup(&self, path: &Path) -> Result<INode> {
@@ -77,6 +420,20 @@ impl RootFs {
         }
     }
 
+    /// Resolves a path (from the current working directory) into a directory
+    /// to be modified (e.g. creating a file in it). Returns `EROFS` if it's in
+    /// a read-only mount.
+    pub fn lookup_writable_dir(&self, path: &Path) -> Result<Arc<dyn Directory>> {
+        let path_comp = self.lookup_path(path, true)?;
+        self.check_writable(&path_comp)?;
+        match &path_comp.inode {
+            INode::Directory(dir) => Ok(dir.clone()),
+            INode::FileLike(_) => Err(Error::new(Errno::ENOTDIR)),
+            // Symbolic links should be already resolved.
+            INode::Symlink(_) => unreachable!(),
+        }
+    }
+
     /// Changes the current working directory.
     pub fn chdir(&mut self, path: &Path) -> Result<()> {
         self.cwd_path = self.lookup_path(path, true)?;
@@ -94,10 +451,34 @@ impl RootFs {
             .map(|path_comp| path_comp.inode.clone())
     }
 
-    fn lookup_mount_point(&self, dir: &Arc<dyn Directory>) -> Result<Option<&MountPoint>> {
+    fn lookup_mount_point(
+        &self,
+        mount_id: usize,
+        dir: &Arc<dyn Directory>,
+    ) -> Result<Option<&MountPoint>> {
         let stat = dir.stat()?;
         let inode_no = stat.inode_no; // Move out of unaligned
-        Ok(self.mount_points.get(&inode_no))
+        Ok(self.mount_points.iter().find(|mount_point| {
+            mount_point.id != ROOT_MOUNT_ID
+                && mount_point.parent_id == mount_id
+                && mount_point.mounted_on == inode_no
+        }))
+    }
+
+    /// If `dir` is a mount point, returns the root directory of the mounted
+    /// file system (the topmost one if multiple mounts are stacked) and its
+    /// mount ID.
+    fn cross_mount_points(
+        &self,
+        mut mount_id: usize,
+        mut dir: Arc<dyn Directory>,
+    ) -> Result<(INode, usize)> {
+        while let Some(mount_point) = self.lookup_mount_point(mount_id, &dir)? {
+            mount_id = mount_point.id;
+            dir = mount_point.root_dir.clone();
+        }
+
+        Ok((dir.into(), mount_id))
     }
 
     /// Resolves a path into `PathComponent`. If `follow_symlink` is `true`,
@@ -122,7 +503,7 @@ impl RootFs {
     /// never return `INode::Symlink`.
     pub fn lookup_path_at(
         &self,
-        opened_files: &OpenedFileTable,
+        opened_files: &SpinLock<OpenedFileTable>,
         cwd_or_fd: &CwdOrFd,
         path: &Path,
         follow_symlink: bool,
@@ -135,9 +516,35 @@ impl RootFs {
         )
     }
 
+    /// Resolves a path from `cwd_or_fd` according to the flags of `*at`
+    /// system calls: the last symbolic link is followed unless
+    /// `AT_SYMLINK_NOFOLLOW` is set, and an empty path refers to `cwd_or_fd`
+    /// itself if `AT_EMPTY_PATH` is set.
+    pub fn lookup_path_at_flags(
+        &self,
+        opened_files: &SpinLock<OpenedFileTable>,
+        cwd_or_fd: &CwdOrFd,
+        path: &Path,
+        flags: AtFlags,
+    ) -> Result<Arc<PathComponent>> {
+        if path.is_empty() && flags.contains(AtFlags::AT_EMPTY_PATH) {
+            return match cwd_or_fd {
+                CwdOrFd::AtCwd => Ok(self.cwd_path.clone()),
+                CwdOrFd::Fd(fd) => Ok(opened_files.lock().get(*fd)?.path().clone()),
+            };
+        }
+
+        self.lookup_path_at(
+            opened_files,
+            cwd_or_fd,
+            path,
+            !flags.contains(AtFlags::AT_SYMLINK_NOFOLLOW),
+        )
+    }
+
     pub fn lookup_parent_path_at<'a>(
         &self,
-        opened_files: &OpenedFileTable,
+        opened_files: &SpinLock<OpenedFileTable>,
         cwd_or_fd: &CwdOrFd,
         path: &'a Path,
         follow_symlink: bool,
@@ -149,9 +556,27 @@ impl RootFs {
         Ok((path, name))
     }
 
+    /// Resolves the parent directory of `path` from `cwd_or_fd` to be
+    /// modified (e.g. creating a file in it). Returns `EROFS` if it's in a
+    /// read-only mount.
+    pub fn lookup_writable_parent_at<'a>(
+        &self,
+        opened_files: &SpinLock<OpenedFileTable>,
+        cwd_or_fd: &CwdOrFd,
+        path: &'a Path,
+    ) -> Result<(Arc<PathComponent>, &'a str)> {
+        let (parent_dir, name) = self.lookup_parent_path_at(opened_files, cwd_or_fd, path, true)?;
+        self.check_writable(&parent_dir)?;
+        if !parent_dir.inode.is_dir() {
+            return Err(Errno::ENOTDIR.into());
+        }
+
+        Ok((parent_dir, name))
+    }
+
     fn resolve_cwd_or_fd(
         &self,
-        opened_files: &OpenedFileTable,
+        opened_files: &SpinLock<OpenedFileTable>,
         cwd_or_fd: &CwdOrFd,
         path: &Path,
     ) -> Result<Arc<PathComponent>> {
@@ -161,7 +586,7 @@ impl RootFs {
             match cwd_or_fd {
                 CwdOrFd::AtCwd => Ok(self.cwd_path.clone()),
                 CwdOrFd::Fd(fd) => {
-                    let opened_file = opened_files.get(*fd)?;
+                    let opened_file = opened_files.lock().get(*fd)?.clone();
                     Ok(opened_file.path().clone())
                 }
             }
@@ -194,20 +619,20 @@ impl RootFs {
                     .clone(),
                 // Look for the entry with the name in the directory.
                 _ => {
-                    let inode = match parent_dir.inode.as_dir()?.lookup(name)? {
+                    let (inode, mount_id) = match parent_dir.inode.as_dir()?.lookup(name)? {
                         // If it is a directory and it's a mount point, go
                         // into the mounted file system's root.
-                        INode::Directory(dir) => match self.lookup_mount_point(&dir)? {
-                            Some(mount_point) => mount_point.fs.root_dir()?.into(),
-                            None => dir.into(),
-                        },
-                        inode => inode,
+                        INode::Directory(dir) => {
+                            self.cross_mount_points(parent_dir.mount_id, dir)?
+                        }
+                        inode => (inode, parent_dir.mount_id),
                     };
 
                     Arc::new(PathComponent {
                         parent_dir: Some(parent_dir.clone()),
                         name: name.to_owned(),
                         inode,
+                        mount_id,
                     })
                 }
             };
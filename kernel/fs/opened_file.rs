@@ -2,20 +2,33 @@
 #![allow(clippy::bad_bit_mask)]
 
 use super::{
+    file_lock,
     inode::{DirEntry, Directory, FileLike, INode},
+    inotify::{self, InotifyMask},
     path::PathBuf,
 };
 use crate::ctypes::c_int;
 use crate::fs::inode::PollStatus;
+use crate::pipe::{PipeReader, PipeWriter};
 use crate::prelude::*;
 use crate::user_buffer::UserBufferMut;
 use crate::{net::*, user_buffer::UserBuffer};
 use atomic_refcell::AtomicRefCell;
 use bitflags::bitflags;
+use core::cmp::min;
 use crossbeam::atomic::AtomicCell;
+use kerla_runtime::arch::PAGE_SIZE;
+use kerla_utils::downcast::downcast;
 
 const FD_MAX: c_int = 1024;
 
+/// The maximum length `splice_to` reads from a file at once.
+const SPLICE_CHUNK_LEN: usize = 16 * PAGE_SIZE;
+
+/// The mount ID of paths not reachable from the root directory (e.g. unnamed
+/// pipes).
+pub const ANONYMOUS_MOUNT_ID: usize = 0;
+
 bitflags! {
     pub struct OpenFlags: i32 {
         const O_RDONLY = 0o0;
@@ -32,10 +45,39 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// The flags of `preadv2(2)` and `pwritev2(2)`.
+    pub struct RwFlags: c_int {
+        const RWF_HIPRI = 0x1;
+        const RWF_DSYNC = 0x2;
+        const RWF_SYNC = 0x4;
+        const RWF_NOWAIT = 0x8;
+        const RWF_APPEND = 0x10;
+    }
+}
+
+/// The `whence` argument of `lseek(2)`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SeekWhence {
+    /// `SEEK_SET`: the offset is relative to the beginning of the file.
+    Set,
+    /// `SEEK_CUR`: the offset is relative to the current position.
+    Cur,
+    /// `SEEK_END`: the offset is relative to the end of the file.
+    End,
+    /// `SEEK_DATA`: the next data at or after the offset.
+    Data,
+    /// `SEEK_HOLE`: the next hole at or after the offset.
+    Hole,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct OpenOptions {
     pub nonblock: bool,
     pub close_on_exec: bool,
+    pub append: bool,
+    pub readable: bool,
+    pub writable: bool,
 }
 
 impl OpenOptions {
@@ -43,6 +85,9 @@ impl OpenOptions {
         OpenOptions {
             nonblock,
             close_on_exec: cloexec,
+            append: false,
+            readable: true,
+            writable: true,
         }
     }
 
@@ -50,6 +95,9 @@ impl OpenOptions {
         OpenOptions {
             nonblock: false,
             close_on_exec: false,
+            append: false,
+            readable: true,
+            writable: true,
         }
     }
 
@@ -57,6 +105,9 @@ impl OpenOptions {
         OpenOptions {
             nonblock: false,
             close_on_exec: false,
+            append: false,
+            readable: true,
+            writable: true,
         }
     }
 }
@@ -66,6 +117,9 @@ impl From<OpenFlags> for OpenOptions {
         OpenOptions {
             nonblock: flags.contains(OpenFlags::O_NONBLOCK),
             close_on_exec: flags.contains(OpenFlags::O_CLOEXEC),
+            append: flags.contains(OpenFlags::O_APPEND),
+            readable: !flags.contains(OpenFlags::O_WRONLY),
+            writable: flags.intersects(OpenFlags::O_WRONLY | OpenFlags::O_RDWR),
         }
     }
 }
@@ -102,6 +156,10 @@ pub struct PathComponent {
     pub name: String,
     /// The referenced inode.
     pub inode: INode,
+    /// The ID of the mount which the inode belongs to. It's
+    /// `ANONYMOUS_MOUNT_ID` if the path is not reachable from the root
+    /// directory.
+    pub mount_id: usize,
 }
 
 impl PathComponent {
@@ -114,6 +172,7 @@ impl PathComponent {
             parent_dir: None,
             name: "anon".to_owned(),
             inode,
+            mount_id: ANONYMOUS_MOUNT_ID,
         })
     }
 
@@ -184,35 +243,222 @@ impl OpenedFile {
     }
 
     pub fn read(&self, buf: UserBufferMut<'_>) -> Result<usize> {
+        self.read_at(None, buf, RwFlags::empty())
+    }
+
+    pub fn write(&self, buf: UserBuffer<'_>) -> Result<usize> {
+        self.write_at(None, buf, RwFlags::empty())
+    }
+
+    /// Reads from `offset` without changing the file position, or from the
+    /// current position (and advances it) if `offset` is `None`.
+    pub fn read_at(
+        &self,
+        offset: Option<usize>,
+        buf: UserBufferMut<'_>,
+        flags: RwFlags,
+    ) -> Result<usize> {
         // Avoid holding self.options and self.pos locks by copying.
-        let options = self.options();
-        let pos = self.pos();
+        let mut options = self.options();
+        if flags.contains(RwFlags::RWF_NOWAIT) {
+            options.nonblock = true;
+        }
 
-        let read_len = self.as_file()?.read(pos, buf, &options)?;
-        self.pos.fetch_add(read_len);
-        Ok(read_len)
+        let file = self.as_file()?;
+        match offset {
+            Some(offset) => {
+                self.check_seekable()?;
+                file.read(offset, buf, &options)
+            }
+            None => {
+                let read_len = file.read(self.pos(), buf, &options)?;
+                self.pos.fetch_add(read_len);
+                Ok(read_len)
+            }
+        }
     }
 
-    pub fn write(&self, buf: UserBuffer<'_>) -> Result<usize> {
+    /// Writes to `offset` without changing the file position, or to the
+    /// current position (and advances it) if `offset` is `None`.
+    ///
+    /// In the append mode, data are always written at the end of the file
+    /// regardless of `offset` as Linux does.
+    pub fn write_at(
+        &self,
+        offset: Option<usize>,
+        buf: UserBuffer<'_>,
+        flags: RwFlags,
+    ) -> Result<usize> {
         // Avoid holding self.options and self.pos locks by copying.
-        let options = self.options();
-        let pos = self.pos();
+        let mut options = self.options();
+        if flags.contains(RwFlags::RWF_NOWAIT) {
+            options.nonblock = true;
+        }
+
+        let file = self.as_file()?;
+        if offset.is_some() {
+            self.check_seekable()?;
+        }
+
+        let append = options.append || flags.contains(RwFlags::RWF_APPEND);
+        let pos = match file.stat() {
+            Ok(stat) if append && stat.mode.is_regular_file() => stat.size.0 as usize,
+            _ => offset.unwrap_or_else(|| self.pos()),
+        };
+
+        let written_len = file.write(pos, buf, &options)?;
+        if offset.is_none() {
+            self.pos.store(pos + written_len);
+        }
+
+        if written_len > 0 {
+            inotify::notify_path(&self.path, InotifyMask::IN_MODIFY);
+        }
+
+        if flags.intersects(RwFlags::RWF_DSYNC | RwFlags::RWF_SYNC) {
+            file.fsync()?;
+        }
 
-        let written_len = self.as_file()?.write(pos, buf, &options)?;
-        self.pos.fetch_add(written_len);
         Ok(written_len)
     }
 
-    pub fn set_cloexec(&self, cloexec: bool) {
-        // FIXME: Modify LocalOpenedFile as well!
-        self.options.borrow_mut().close_on_exec = cloexec;
+    /// `lseek(2)`.
+    pub fn lseek(&self, offset: isize, whence: SeekWhence) -> Result<usize> {
+        self.check_seekable()?;
+
+        let new_pos = match whence {
+            SeekWhence::Set => Some(offset),
+            SeekWhence::Cur => (self.pos() as isize).checked_add(offset),
+            SeekWhence::End => self.path.inode.stat()?.size.0.checked_add(offset),
+            SeekWhence::Data | SeekWhence::Hole => {
+                if offset < 0 {
+                    return Err(Error::new(Errno::ENXIO));
+                }
+
+                let file = self.as_file()?;
+                let pos = if whence == SeekWhence::Data {
+                    file.next_data(offset as usize)?
+                } else {
+                    file.next_hole(offset as usize)?
+                };
+
+                Some(pos as isize)
+            }
+        };
+
+        match new_pos {
+            Some(new_pos) if new_pos >= 0 => {
+                self.pos.store(new_pos as usize);
+                Ok(new_pos as usize)
+            }
+            Some(_) => Err(Error::new(Errno::EINVAL)),
+            None => Err(Error::new(Errno::EOVERFLOW)),
+        }
     }
 
-    pub fn set_flags(&self, flags: OpenFlags) -> Result<()> {
-        if flags.contains(OpenFlags::O_NONBLOCK) {
-            self.options.borrow_mut().nonblock = true;
+    /// Moves up to `len` bytes from `self` into `dst` within the kernel:
+    /// `splice(2)`, `sendfile(2)` and `copy_file_range(2)`. Offsets of `None`
+    /// mean the current file positions, which are advanced by the moved
+    /// length.
+    pub fn splice_to(
+        &self,
+        offset: Option<usize>,
+        dst: &OpenedFile,
+        dst_offset: Option<usize>,
+        len: usize,
+        nonblock: bool,
+    ) -> Result<usize> {
+        // Avoid holding self.options and dst.options locks by copying.
+        let mut options = self.options();
+        let mut dst_options = dst.options();
+        if nonblock {
+            options.nonblock = true;
+            dst_options.nonblock = true;
+        }
+
+        if dst_options.append {
+            return Err(Error::new(Errno::EINVAL));
+        }
+
+        if offset.is_some() {
+            self.check_seekable()?;
         }
 
+        if dst_offset.is_some() {
+            dst.check_seekable()?;
+        }
+
+        let src = self.as_file()?;
+        let dst_file = dst.as_file()?;
+        let pos = offset.unwrap_or_else(|| self.pos());
+        let dst_pos = dst_offset.unwrap_or_else(|| dst.pos());
+        let moved_len = if let Some(reader) = downcast::<_, PipeReader>(src) {
+            reader.splice_to(dst_file, dst_pos, len, &options, &dst_options)?
+        } else if let Some(writer) = downcast::<_, PipeWriter>(dst_file) {
+            writer.splice_from(src, pos, len, &options, &dst_options)?
+        } else {
+            // Data read from a socket would be lost if `dst` doesn't accept
+            // all of them.
+            if !src.is_seekable() {
+                return Err(Error::new(Errno::EINVAL));
+            }
+
+            let mut total_len = 0;
+            'outer: while total_len < len {
+                let chunk_len = min(len - total_len, SPLICE_CHUNK_LEN);
+                let bufs = src.splice_read(pos + total_len, chunk_len, &options)?;
+                if bufs.is_empty() {
+                    break;
+                }
+
+                for buf in bufs {
+                    let written_len = match dst_file.write(
+                        dst_pos + total_len,
+                        UserBuffer::from(buf.as_bytes()),
+                        &dst_options,
+                    ) {
+                        Ok(written_len) => written_len,
+                        Err(_) if total_len > 0 => break 'outer,
+                        Err(err) => return Err(err),
+                    };
+
+                    total_len += written_len;
+                    if written_len < buf.len() {
+                        break 'outer;
+                    }
+                }
+            }
+
+            total_len
+        };
+
+        if offset.is_none() {
+            self.pos.fetch_add(moved_len);
+        }
+
+        if dst_offset.is_none() {
+            dst.pos.fetch_add(moved_len);
+        }
+
+        if moved_len > 0 {
+            inotify::notify_path(&dst.path, InotifyMask::IN_MODIFY);
+        }
+
+        Ok(moved_len)
+    }
+
+    fn check_seekable(&self) -> Result<()> {
+        match &self.path.inode {
+            INode::FileLike(file) if !file.is_seekable() => Err(Error::new(Errno::ESPIPE)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn set_flags(&self, flags: OpenFlags) -> Result<()> {
+        let mut options = self.options.borrow_mut();
+        options.nonblock = flags.contains(OpenFlags::O_NONBLOCK);
+        options.append = flags.contains(OpenFlags::O_APPEND);
+
         Ok(())
     }
 
@@ -290,6 +536,19 @@ impl OpenedFile {
     }
 }
 
+impl Drop for OpenedFile {
+    fn drop(&mut self) {
+        file_lock::release_opened_file_locks(self);
+
+        let mask = if self.options.borrow().writable {
+            InotifyMask::IN_CLOSE_WRITE
+        } else {
+            InotifyMask::IN_CLOSE_NOWRITE
+        };
+        inotify::notify_path(&self.path, mask);
+    }
+}
+
 /// A opened file with process-local fields.
 #[derive(Clone)]
 struct LocalOpenedFile {
@@ -320,14 +579,43 @@ impl OpenedFileTable {
         }
     }
 
-    /// Closes an opened file.
-    pub fn close(&mut self, fd: Fd) -> Result<()> {
+    /// Returns an iterator over the opened files.
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<OpenedFile>> {
+        self.files
+            .iter()
+            .filter_map(|file| file.as_ref().map(|file| &file.opened_file))
+    }
+
+    /// Returns the `FD_CLOEXEC` flag of the file descriptor.
+    pub fn get_cloexec(&self, fd: Fd) -> Result<bool> {
+        match self.files.get(fd.as_usize()) {
+            Some(Some(LocalOpenedFile { close_on_exec, .. })) => Ok(*close_on_exec),
+            _ => Err(Error::new(Errno::EBADF)),
+        }
+    }
+
+    /// Sets the `FD_CLOEXEC` flag of the file descriptor.
+    pub fn set_cloexec(&mut self, fd: Fd, cloexec: bool) -> Result<()> {
         match self.files.get_mut(fd.as_usize()) {
-            Some(opened_file) => *opened_file = None,
-            _ => return Err(Errno::EBADF.into()),
+            Some(Some(LocalOpenedFile { close_on_exec, .. })) => {
+                *close_on_exec = cloexec;
+                Ok(())
+            }
+            _ => Err(Error::new(Errno::EBADF)),
         }
+    }
 
-        Ok(())
+    /// Closes an opened file. Returns the closed file so that the caller can
+    /// release its POSIX locks after unlocking this table.
+    pub fn close(&mut self, fd: Fd) -> Result<Arc<OpenedFile>> {
+        match self
+            .files
+            .get_mut(fd.as_usize())
+            .and_then(|slot| slot.take())
+        {
+            Some(LocalOpenedFile { opened_file, .. }) => Ok(opened_file),
+            None => Err(Errno::EBADF.into()),
+        }
     }
 
     /// Opens a file.
@@ -352,25 +640,9 @@ impl OpenedFileTable {
     pub fn open_with_fixed_fd(
         &mut self,
         fd: Fd,
-        mut opened_file: Arc<OpenedFile>,
+        opened_file: Arc<OpenedFile>,
         options: OpenOptions,
     ) -> Result<()> {
-        if let INode::FileLike(file) = &opened_file.path.inode {
-            if let Some(new_inode) = file.open(&options)? {
-                // Replace inode if FileLike::open returned Some. Currently it's
-                // used only for /dev/ptmx.
-                opened_file = Arc::new(OpenedFile {
-                    pos: AtomicCell::new(0),
-                    options: AtomicRefCell::new(options),
-                    path: Arc::new(PathComponent {
-                        name: opened_file.path.name.clone(),
-                        parent_dir: opened_file.path.parent_dir.clone(),
-                        inode: new_inode.into(),
-                    }),
-                })
-            }
-        }
-
         match self.files.get_mut(fd.as_usize()) {
             Some(Some(_)) => {
                 return Err(Error::with_message(
@@ -416,27 +688,41 @@ impl OpenedFileTable {
     }
 
     /// Duplicates a file descriptor into the given file descriptor `new`.
-    pub fn dup2(&mut self, old: Fd, new: Fd, options: OpenOptions) -> Result<()> {
+    ///
+    /// Returns the file previously opened at `new`, if any.
+    pub fn dup2(
+        &mut self,
+        old: Fd,
+        new: Fd,
+        options: OpenOptions,
+    ) -> Result<Option<Arc<OpenedFile>>> {
         let opened_file = match self.files.get(old.as_usize()) {
             Some(Some(opened_file)) => opened_file.opened_file.clone(),
             _ => return Err(Errno::EBADF.into()),
         };
 
-        if let Some(Some(_)) = self.files.get(new.as_usize()) {
-            self.close(new).ok();
+        if old == new {
+            return Ok(None);
         }
 
+        let closed_file = self.close(new).ok();
         self.open_with_fixed_fd(new, opened_file, options)?;
-        Ok(())
+        Ok(closed_file)
     }
 
-    /// Closes all opened files.
-    pub fn close_all(&mut self) {
-        self.files.clear();
+    /// Closes all opened files. Returns the closed files so that the caller
+    /// can drop them after unlocking this table.
+    pub fn close_all(&mut self) -> Vec<Arc<OpenedFile>> {
+        self.files
+            .drain(..)
+            .flatten()
+            .map(|file| file.opened_file)
+            .collect()
     }
 
-    /// Closes opened files with `CLOEXEC` set.
-    pub fn close_cloexec_files(&mut self) {
+    /// Closes opened files with `CLOEXEC` set. Returns the closed files.
+    pub fn close_cloexec_files(&mut self) -> Vec<Arc<OpenedFile>> {
+        let mut closed_files = Vec::new();
         for slot in &mut self.files {
             if matches!(
                 slot,
@@ -445,9 +731,11 @@ impl OpenedFileTable {
                     ..
                 })
             ) {
-                *slot = None;
+                closed_files.extend(slot.take().map(|file| file.opened_file));
             }
         }
+
+        closed_files
     }
 
     /// Allocates an unused fd. Note that this method does not any reservations
@@ -0,0 +1,838 @@
+//! An overlay file system: merges a read-only lower directory and a writable
+//! upper directory (typically a tmpfs).
+//!
+//! - Lookups look for the upper layer first, and then the lower layer.
+//! - A file in the lower layer is copied into the upper layer (with its
+//!   ancestor directories) when it's modified for the first time (*copy-up*).
+//! - A deleted file in the lower layer is hidden by a *whiteout*: a special
+//!   file with the same name in the upper layer.
+//! - A directory in the upper layer which replaces one in the lower layer
+//!   (e.g. created after `rmdir(2)`) is marked as *opaque*: it contains a
+//!   whiteout named `.wh..wh..opq` and the lower one is not merged.
+use crate::{
+    fs::{
+        file_system::{register_file_system_type, FileSystem, FileSystemType, FsStat},
+        inode::{DirEntry, Directory, FileLike, INode, INodeNo, RenameFlags, SetAttr},
+        opened_file::OpenOptions,
+        path::Path,
+        stat::{FileMode, Stat, S_IFCHR},
+    },
+    pipe::PipeBuffer,
+    prelude::*,
+    process::{current_process, Mutex},
+    user_buffer::{UserBuffer, UserBufferMut},
+};
+use core::fmt;
+use hashbrown::HashMap;
+use kerla_runtime::spinlock::SpinLock;
+use kerla_utils::downcast::downcast;
+
+const OVERLAYFS_SUPER_MAGIC: u64 = 0x794c7630;
+
+/// Serializes modifications to the upper layer (e.g. a copy-up and a file
+/// creation in the same directory). It's a sleeping lock since the lower
+/// layer may be on a disk.
+static UPPER_LOCK: Mutex<()> = Mutex::new(());
+
+/// Returns the inode number in the overlay of a file which exists in the
+/// lower layer. It doesn't change on a copy-up.
+fn lower_inode_no(inode_no: INodeNo) -> INodeNo {
+    INodeNo::new(inode_no.as_u64() as usize * 2)
+}
+
+/// Returns the inode number in the overlay of a file which exists only in
+/// the upper layer.
+fn upper_inode_no(inode_no: INodeNo) -> INodeNo {
+    INodeNo::new(inode_no.as_u64() as usize * 2 + 1)
+}
+
+/// Returns the attributes to be copied into the upper layer on a copy-up.
+fn copied_up_attrs(lower: &Stat) -> SetAttr {
+    SetAttr {
+        mode: Some(lower.mode),
+        uid: Some(lower.uid),
+        gid: Some(lower.gid),
+        atime: Some(lower.atime),
+        mtime: Some(lower.mtime),
+    }
+}
+
+/// A whiteout in the upper layer. Like Linux's overlayfs, it looks like a
+/// character device with the device number 0/0.
+struct Whiteout {}
+
+impl FileLike for Whiteout {
+    fn stat(&self) -> Result<Stat> {
+        Ok(Stat {
+            mode: FileMode::new(S_IFCHR),
+            ..Stat::zeroed()
+        })
+    }
+}
+
+impl fmt::Debug for Whiteout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OverlayWhiteout").finish()
+    }
+}
+
+/// The name of the whiteout which makes a directory opaque. It's hidden from
+/// the overlay since it's a whiteout.
+const OPAQUE_NAME: &str = ".wh..wh..opq";
+
+fn new_whiteout() -> INode {
+    INode::FileLike(Arc::new(Whiteout {}))
+}
+
+fn is_whiteout(inode: &INode) -> bool {
+    match inode {
+        INode::FileLike(file) => downcast::<_, Whiteout>(file).is_some(),
+        _ => false,
+    }
+}
+
+/// Looks for an entry in a layer. Returns `None` if it doesn't exist.
+fn lookup_in_layer(dir: &Arc<dyn Directory>, name: &str) -> Result<Option<INode>> {
+    match dir.lookup(name) {
+        Ok(inode) => Ok(Some(inode)),
+        Err(err) if err.errno() == Errno::ENOENT => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Removes the whiteout with `name` in the upper directory, if any. Returns
+/// `true` if it existed.
+fn remove_whiteout(upper: &Arc<dyn Directory>, name: &str) -> Result<bool> {
+    match lookup_in_layer(upper, name)? {
+        Some(inode) if is_whiteout(&inode) => {
+            upper.unlink(name)?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+fn is_opaque(upper: &Arc<dyn Directory>) -> Result<bool> {
+    Ok(matches!(lookup_in_layer(upper, OPAQUE_NAME)?, Some(inode) if is_whiteout(&inode)))
+}
+
+/// Removes the directory `name` in the upper directory, if any. It must be
+/// empty in the overlay, that is, it contains only whiteouts.
+fn remove_upper_dir(upper: &Arc<dyn Directory>, name: &str) -> Result<()> {
+    let dir = match lookup_in_layer(upper, name)? {
+        Some(INode::Directory(dir)) => dir,
+        _ => return Ok(()),
+    };
+
+    let mut whiteouts = Vec::new();
+    let mut index = 0;
+    while let Some(entry) = dir.readdir(index)? {
+        index += 1;
+        whiteouts.push(entry.name);
+    }
+
+    for whiteout in whiteouts {
+        dir.unlink(&whiteout)?;
+    }
+
+    upper.rmdir(name)
+}
+
+/// A directory in the overlay shared by its `OverlayDir` instances and its
+/// children.
+struct DirNode {
+    /// The parent directory. `None` if this is the root directory.
+    parent: Option<Arc<DirNode>>,
+    name: String,
+    lower: Option<Arc<dyn Directory>>,
+    /// The directory in the upper layer. `None` if it's not copied up yet
+    /// (or not looked up yet).
+    upper: SpinLock<Option<Arc<dyn Directory>>>,
+}
+
+impl DirNode {
+    /// Returns the directory in the upper layer if it exists.
+    fn upper(&self) -> Result<Option<Arc<dyn Directory>>> {
+        if let Some(upper) = &*self.upper.lock() {
+            return Ok(Some(upper.clone()));
+        }
+
+        // It might have been copied up through another path component.
+        let parent_upper = match &self.parent {
+            Some(parent) => parent.upper()?,
+            None => None,
+        };
+
+        let upper = match parent_upper {
+            Some(parent_upper) => match lookup_in_layer(&parent_upper, &self.name)? {
+                Some(INode::Directory(dir)) => Some(dir),
+                _ => None,
+            },
+            None => None,
+        };
+
+        if let Some(upper) = &upper {
+            *self.upper.lock() = Some(upper.clone());
+        }
+
+        Ok(upper)
+    }
+
+    /// Creates the directory in the upper layer (and its ancestors) if it
+    /// doesn't exist yet. The caller must hold `UPPER_LOCK`.
+    fn copy_up(&self) -> Result<Arc<dyn Directory>> {
+        if let Some(upper) = self.upper()? {
+            return Ok(upper);
+        }
+
+        // The root directory always exists in the upper layer.
+        let parent = self.parent.as_ref().unwrap();
+        let parent_upper = parent.copy_up()?;
+        let lower_stat = self.lower.as_ref().unwrap().stat()?;
+        let upper = parent_upper
+            .create_dir(&self.name, lower_stat.mode)?
+            .as_dir()?
+            .clone();
+        upper.set_attr(&copied_up_attrs(&lower_stat))?;
+
+        *self.upper.lock() = Some(upper.clone());
+        Ok(upper)
+    }
+
+    /// Looks for an entry in the lower layer.
+    fn lookup_lower(&self, name: &str) -> Result<Option<INode>> {
+        match &self.lower {
+            Some(lower) => lookup_in_layer(lower, name),
+            None => Ok(None),
+        }
+    }
+}
+
+pub struct OverlayDir {
+    node: Arc<DirNode>,
+    /// The merged entries read at `readdir(0)`. Following `readdir` calls
+    /// return entries from it.
+    entries: SpinLock<Option<Arc<Vec<DirEntry>>>>,
+}
+
+impl OverlayDir {
+    fn new(node: DirNode) -> OverlayDir {
+        OverlayDir {
+            node: Arc::new(node),
+            entries: SpinLock::new(None),
+        }
+    }
+
+    fn new_child(
+        &self,
+        name: &str,
+        lower: Option<Arc<dyn Directory>>,
+        upper: Option<Arc<dyn Directory>>,
+    ) -> INode {
+        INode::Directory(Arc::new(OverlayDir::new(DirNode {
+            parent: Some(self.node.clone()),
+            name: name.to_owned(),
+            lower,
+            upper: SpinLock::new(upper),
+        })))
+    }
+
+    /// Wraps a regular file to copy it up on a write. Other files (e.g.
+    /// device files) are returned as they are.
+    fn new_child_file(
+        &self,
+        name: &str,
+        lower: Option<Arc<dyn FileLike>>,
+        upper: Option<Arc<dyn FileLike>>,
+    ) -> Result<INode> {
+        let file = match (&upper, &lower) {
+            (Some(file), _) | (None, Some(file)) => file.clone(),
+            (None, None) => unreachable!(),
+        };
+
+        if !file.stat()?.mode.is_regular_file() {
+            return Ok(INode::FileLike(file));
+        }
+
+        Ok(INode::FileLike(Arc::new(OverlayFile {
+            parent: self.node.clone(),
+            name: name.to_owned(),
+            lower,
+            upper: SpinLock::new(upper),
+        })))
+    }
+
+    /// Reads and merges entries in both layers.
+    fn read_entries(&self) -> Result<Vec<DirEntry>> {
+        let mut entries = Vec::new();
+        // Names in the upper layer (including whiteouts) hide ones in the
+        // lower layer. The value is the index in `entries` (`None` for a
+        // whiteout).
+        let mut upper_names = HashMap::new();
+        if let Some(upper) = self.node.upper()? {
+            let mut index = 0;
+            while let Some(entry) = upper.readdir(index)? {
+                index += 1;
+                let whiteout = match lookup_in_layer(&upper, &entry.name)? {
+                    Some(inode) => is_whiteout(&inode),
+                    None => continue,
+                };
+
+                if whiteout {
+                    upper_names.insert(entry.name, None);
+                } else {
+                    upper_names.insert(entry.name.clone(), Some(entries.len()));
+                    entries.push(DirEntry {
+                        inode_no: upper_inode_no(entry.inode_no),
+                        ..entry
+                    });
+                }
+            }
+        }
+
+        if let Some(lower) = &self.node.lower {
+            let mut index = 0;
+            while let Some(entry) = lower.readdir(index)? {
+                index += 1;
+                match upper_names.get(&entry.name) {
+                    // Copied up: use the inode number in the lower layer.
+                    Some(Some(i)) if entries[*i].file_type == entry.file_type => {
+                        entries[*i].inode_no = lower_inode_no(entry.inode_no);
+                    }
+                    Some(_) => {}
+                    None => entries.push(DirEntry {
+                        inode_no: lower_inode_no(entry.inode_no),
+                        ..entry
+                    }),
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Returns `ENOTEMPTY` if the directory `name` is not empty in the overlay.
+    fn check_empty_dir(&self, name: &str) -> Result<()> {
+        let inode = self.lookup(name)?;
+        let dir: &OverlayDir = match &inode {
+            INode::Directory(dir) => downcast(dir).unwrap(),
+            _ => return Err(Errno::ENOTDIR.into()),
+        };
+
+        if !dir.read_entries()?.is_empty() {
+            return Err(Errno::ENOTEMPTY.into());
+        }
+
+        Ok(())
+    }
+
+    /// Returns `EEXIST` if `name` exists in the overlay.
+    fn check_not_exists(&self, name: &str) -> Result<()> {
+        match self.lookup(name) {
+            Ok(_) => Err(Errno::EEXIST.into()),
+            Err(err) if err.errno() == Errno::ENOENT => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl Directory for OverlayDir {
+    fn lookup(&self, name: &str) -> Result<INode> {
+        let lower = self.node.lookup_lower(name)?;
+        if let Some(upper) = self.node.upper()? {
+            match lookup_in_layer(&upper, name)? {
+                Some(inode) if is_whiteout(&inode) => return Err(Errno::ENOENT.into()),
+                Some(INode::Directory(dir)) => {
+                    let lower = match lower {
+                        Some(INode::Directory(lower)) if !is_opaque(&dir)? => Some(lower),
+                        _ => None,
+                    };
+
+                    return Ok(self.new_child(name, lower, Some(dir)));
+                }
+                Some(INode::FileLike(file)) => {
+                    let lower = match lower {
+                        Some(INode::FileLike(lower)) => Some(lower),
+                        _ => None,
+                    };
+
+                    return self.new_child_file(name, lower, Some(file));
+                }
+                Some(inode) => return Ok(inode),
+                None => {}
+            }
+        }
+
+        match lower {
+            Some(INode::Directory(dir)) => Ok(self.new_child(name, Some(dir), None)),
+            Some(INode::FileLike(file)) => self.new_child_file(name, Some(file), None),
+            Some(inode) => Ok(inode),
+            None => Err(Errno::ENOENT.into()),
+        }
+    }
+
+    fn create_file(&self, name: &str, mode: FileMode) -> Result<INode> {
+        let _lock = UPPER_LOCK.lock();
+        self.check_not_exists(name)?;
+        let upper = self.node.copy_up()?;
+        remove_whiteout(&upper, name)?;
+        let file = upper.create_file(name, mode)?.as_file()?.clone();
+        self.new_child_file(name, None, Some(file))
+    }
+
+    fn create_dir(&self, name: &str, mode: FileMode) -> Result<INode> {
+        let _lock = UPPER_LOCK.lock();
+        self.check_not_exists(name)?;
+        let upper = self.node.copy_up()?;
+        let replaced = remove_whiteout(&upper, name)?;
+        let dir = upper.create_dir(name, mode)?.as_dir()?.clone();
+        if replaced {
+            // Don't merge the deleted directory in the lower layer.
+            dir.link(OPAQUE_NAME, &new_whiteout())?;
+        }
+
+        Ok(self.new_child(name, None, Some(dir)))
+    }
+
+    fn create_symlink(&self, name: &str, target: &str) -> Result<INode> {
+        let _lock = UPPER_LOCK.lock();
+        self.check_not_exists(name)?;
+        let upper = self.node.copy_up()?;
+        remove_whiteout(&upper, name)?;
+        upper.create_symlink(name, target)
+    }
+
+    fn stat(&self) -> Result<Stat> {
+        let upper = self.node.upper()?;
+        match (&self.node.lower, upper) {
+            (Some(lower), upper) => {
+                let lower_stat = lower.stat()?;
+                let mut stat = match upper {
+                    Some(upper) => upper.stat()?,
+                    None => lower_stat,
+                };
+                stat.inode_no = lower_inode_no(lower_stat.inode_no);
+                Ok(stat)
+            }
+            (None, Some(upper)) => {
+                let mut stat = upper.stat()?;
+                stat.inode_no = upper_inode_no(stat.inode_no);
+                Ok(stat)
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+
+    fn set_attr(&self, attr: &SetAttr) -> Result<()> {
+        let upper = {
+            let _lock = UPPER_LOCK.lock();
+            self.node.copy_up()?
+        };
+
+        upper.set_attr(attr)
+    }
+
+    fn readdir(&self, index: usize) -> Result<Option<DirEntry>> {
+        let cached = self.entries.lock().clone();
+        let entries = match cached {
+            Some(entries) if index > 0 => entries,
+            _ => {
+                let entries = Arc::new(self.read_entries()?);
+                *self.entries.lock() = Some(entries.clone());
+                entries
+            }
+        };
+
+        Ok(entries.get(index).map(|entry| DirEntry {
+            inode_no: entry.inode_no,
+            file_type: entry.file_type,
+            name: entry.name.clone(),
+        }))
+    }
+
+    fn link(&self, name: &str, link_to: &INode) -> Result<()> {
+        let _lock = UPPER_LOCK.lock();
+        self.check_not_exists(name)?;
+        let target = match link_to {
+            // Hard links to directories are not allowed.
+            INode::Directory(_) => return Err(Errno::EPERM.into()),
+            // Symbolic links and device files are not wrapped: they may be
+            // ones in the lower layer or in another file system.
+            INode::FileLike(file) => match downcast::<_, OverlayFile>(file) {
+                Some(file) => INode::FileLike(file.copy_up()?),
+                None => return Err(Errno::EXDEV.into()),
+            },
+            INode::Symlink(_) => return Err(Errno::EXDEV.into()),
+        };
+
+        let upper = self.node.copy_up()?;
+        remove_whiteout(&upper, name)?;
+        upper.link(name, &target)
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        let _lock = UPPER_LOCK.lock();
+        if self.lookup(name)?.is_dir() {
+            return Err(Errno::EISDIR.into());
+        }
+
+        let upper = self.node.copy_up()?;
+        if lookup_in_layer(&upper, name)?.is_some() {
+            upper.unlink(name)?;
+        }
+
+        if self.node.lookup_lower(name)?.is_some() {
+            upper.link(name, &new_whiteout())?;
+        }
+
+        Ok(())
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        let _lock = UPPER_LOCK.lock();
+        self.check_empty_dir(name)?;
+        let upper = self.node.copy_up()?;
+        remove_upper_dir(&upper, name)?;
+        if self.node.lookup_lower(name)?.is_some() {
+            upper.link(name, &new_whiteout())?;
+        }
+
+        Ok(())
+    }
+
+    fn rename(
+        &self,
+        old_name: &str,
+        new_dir: &Arc<dyn Directory>,
+        new_name: &str,
+        flags: RenameFlags,
+    ) -> Result<()> {
+        let new_dir: &OverlayDir = downcast(new_dir).ok_or_else(|| Error::new(Errno::EXDEV))?;
+        if flags.intersects(RenameFlags::RENAME_EXCHANGE | RenameFlags::RENAME_WHITEOUT) {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let _lock = UPPER_LOCK.lock();
+        let old = self.lookup(old_name)?;
+        let old_dir_ino = self.stat()?.inode_no;
+        let new_dir_ino = new_dir.stat()?.inode_no;
+        if old_name == new_name && old_dir_ino == new_dir_ino {
+            return Ok(());
+        }
+
+        match new_dir.lookup(new_name) {
+            Ok(_) if flags.contains(RenameFlags::RENAME_NOREPLACE) => {
+                return Err(Errno::EEXIST.into());
+            }
+            Ok(new) => match (old.is_dir(), new.is_dir()) {
+                (true, false) => return Err(Errno::ENOTDIR.into()),
+                (false, true) => return Err(Errno::EISDIR.into()),
+                (true, true) => new_dir.check_empty_dir(new_name)?,
+                (false, false) => {}
+            },
+            Err(err) if err.errno() == Errno::ENOENT => {}
+            Err(err) => return Err(err),
+        }
+
+        // Like Linux's overlayfs without `redirect_dir`, directories in the
+        // lower layer can't be renamed. Userspace falls back to copying them
+        // (e.g. mv(1)).
+        if let INode::Directory(dir) = &old {
+            let dir: &OverlayDir = downcast(dir).unwrap();
+            if dir.node.lower.is_some() {
+                return Err(Errno::EXDEV.into());
+            }
+        }
+
+        // Copy up the file to be moved.
+        let old_upper = self.node.copy_up()?;
+        let new_upper = new_dir.node.copy_up()?;
+        if let INode::FileLike(file) = &old {
+            if let Some(file) = downcast::<_, OverlayFile>(file) {
+                file.copy_up()?;
+            }
+        }
+
+        if lookup_in_layer(&old_upper, old_name)?.is_none() {
+            match &old {
+                INode::Symlink(symlink) => {
+                    old_upper.create_symlink(old_name, symlink.linked_to()?.as_str())?;
+                }
+                // Device files in the lower layer.
+                _ => return Err(Errno::EXDEV.into()),
+            }
+        }
+
+        // Remove the destination in the upper layer. Other files are
+        // replaced by the rename.
+        remove_whiteout(&new_upper, new_name)?;
+        remove_upper_dir(&new_upper, new_name)?;
+        old_upper.rename(old_name, &new_upper, new_name, RenameFlags::empty())?;
+
+        if lookup_in_layer(&old_upper, old_name)?.is_none()
+            && self.node.lookup_lower(old_name)?.is_some()
+        {
+            old_upper.link(old_name, &new_whiteout())?;
+        }
+
+        if old.is_dir() && new_dir.node.lookup_lower(new_name)?.is_some() {
+            if let Some(INode::Directory(dir)) = lookup_in_layer(&new_upper, new_name)? {
+                if !is_opaque(&dir)? {
+                    dir.link(OPAQUE_NAME, &new_whiteout())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for OverlayDir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OverlayDir")
+            .field("name", &self.node.name)
+            .finish()
+    }
+}
+
+/// A regular file in the overlay.
+struct OverlayFile {
+    parent: Arc<DirNode>,
+    name: String,
+    lower: Option<Arc<dyn FileLike>>,
+    /// The file in the upper layer. `None` if it's not copied up through this
+    /// object. Like Linux's overlayfs, a file opened read-only before a
+    /// copy-up keeps referring to the lower one.
+    upper: SpinLock<Option<Arc<dyn FileLike>>>,
+}
+
+impl OverlayFile {
+    /// Returns the file to be read: the one in the upper layer if it exists.
+    fn current(&self) -> Result<Arc<dyn FileLike>> {
+        match &*self.upper.lock() {
+            Some(upper) => Ok(upper.clone()),
+            None => Ok(self.lower.clone().unwrap()),
+        }
+    }
+
+    /// Copies the file into the upper layer if it's not there yet. The caller
+    /// must hold `UPPER_LOCK`.
+    fn copy_up(&self) -> Result<Arc<dyn FileLike>> {
+        if let Some(upper) = &*self.upper.lock() {
+            return Ok(upper.clone());
+        }
+
+        let lower = self.lower.as_ref().unwrap();
+        let parent_upper = self.parent.copy_up()?;
+
+        // It might have been copied up through another `OverlayFile` (e.g.
+        // looked up by another process before this one is opened).
+        if let Some(inode @ INode::FileLike(_)) = lookup_in_layer(&parent_upper, &self.name)? {
+            if !is_whiteout(&inode) {
+                let upper = inode.as_file()?.clone();
+                *self.upper.lock() = Some(upper.clone());
+                return Ok(upper);
+            }
+        }
+
+        let lower_stat = lower.stat()?;
+        let upper = parent_upper
+            .create_file(&self.name, lower_stat.mode)?
+            .as_file()?
+            .clone();
+
+        let options = OpenOptions::readwrite();
+        let mut buf = vec![0; 4096];
+        let mut offset = 0;
+        loop {
+            let read_len = lower.read(offset, UserBufferMut::from(buf.as_mut_slice()), &options)?;
+            if read_len == 0 {
+                break;
+            }
+
+            upper.write(offset, UserBuffer::from(&buf[..read_len]), &options)?;
+            offset += read_len;
+        }
+
+        // Copy them after the data so that the modification time is kept.
+        upper.set_attr(&copied_up_attrs(&lower_stat))?;
+
+        *self.upper.lock() = Some(upper.clone());
+        Ok(upper)
+    }
+}
+
+impl FileLike for OverlayFile {
+    /// Copies up the file when it's opened for writing so that the file
+    /// descriptor refers to the file in the upper layer.
+    fn open(&self, options: &OpenOptions) -> Result<Option<Arc<dyn FileLike>>> {
+        if !options.writable || self.upper.lock().is_some() {
+            return Ok(None);
+        }
+
+        let upper = {
+            let _lock = UPPER_LOCK.lock();
+            self.copy_up()?
+        };
+
+        Ok(Some(Arc::new(OverlayFile {
+            parent: self.parent.clone(),
+            name: self.name.clone(),
+            lower: self.lower.clone(),
+            upper: SpinLock::new(Some(upper)),
+        })))
+    }
+
+    fn stat(&self) -> Result<Stat> {
+        let mut stat = self.current()?.stat()?;
+        stat.inode_no = match &self.lower {
+            Some(lower) => lower_inode_no(lower.stat()?.inode_no),
+            None => upper_inode_no(stat.inode_no),
+        };
+        Ok(stat)
+    }
+
+    fn read(&self, offset: usize, buf: UserBufferMut<'_>, options: &OpenOptions) -> Result<usize> {
+        self.current()?.read(offset, buf, options)
+    }
+
+    fn write(&self, offset: usize, buf: UserBuffer<'_>, options: &OpenOptions) -> Result<usize> {
+        let upper = {
+            let _lock = UPPER_LOCK.lock();
+            self.copy_up()?
+        };
+
+        upper.write(offset, buf, options)
+    }
+
+    fn truncate(&self, length: usize) -> Result<()> {
+        let upper = {
+            let _lock = UPPER_LOCK.lock();
+            self.copy_up()?
+        };
+
+        upper.truncate(length)
+    }
+
+    fn splice_read(
+        &self,
+        offset: usize,
+        len: usize,
+        options: &OpenOptions,
+    ) -> Result<Vec<PipeBuffer>> {
+        self.current()?.splice_read(offset, len, options)
+    }
+
+    fn next_data(&self, offset: usize) -> Result<usize> {
+        self.current()?.next_data(offset)
+    }
+
+    fn next_hole(&self, offset: usize) -> Result<usize> {
+        self.current()?.next_hole(offset)
+    }
+
+    fn fsync(&self) -> Result<()> {
+        self.current()?.fsync()
+    }
+
+    fn set_attr(&self, attr: &SetAttr) -> Result<()> {
+        let upper = {
+            let _lock = UPPER_LOCK.lock();
+            self.copy_up()?
+        };
+
+        upper.set_attr(attr)
+    }
+}
+
+impl fmt::Debug for OverlayFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OverlayFile")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+pub struct OverlayFs {
+    root_dir: Arc<OverlayDir>,
+    /// The file system containing the upper directory. Its capacity is
+    /// reported as the overlay's one.
+    upper_fs: Arc<dyn FileSystem>,
+}
+
+impl OverlayFs {
+    /// Creates an overlay of `upper` (in `upper_fs`) on `lower`. `upper`
+    /// needs to support linking foreign files to store whiteouts (e.g. tmpfs).
+    pub fn new(
+        lower: Arc<dyn Directory>,
+        upper_fs: Arc<dyn FileSystem>,
+        upper: Arc<dyn Directory>,
+    ) -> OverlayFs {
+        OverlayFs {
+            root_dir: Arc::new(OverlayDir::new(DirNode {
+                parent: None,
+                name: String::new(),
+                lower: Some(lower),
+                upper: SpinLock::new(Some(upper)),
+            })),
+            upper_fs,
+        }
+    }
+}
+
+impl FileSystem for OverlayFs {
+    fn name(&self) -> &'static str {
+        "overlay"
+    }
+
+    fn root_dir(&self) -> Result<Arc<dyn Directory>> {
+        Ok(self.root_dir.clone())
+    }
+
+    fn statfs(&self) -> Result<FsStat> {
+        // New files are created in the upper layer: report its capacity as
+        // Linux does.
+        Ok(FsStat {
+            magic: OVERLAYFS_SUPER_MAGIC,
+            ..self.upper_fs.statfs()?
+        })
+    }
+}
+
+/// Mounts an overlay specified in `data` (`lowerdir=<path>,upperdir=<path>`).
+fn mount_overlay(_source: &str, data: &str) -> Result<Arc<dyn FileSystem>> {
+    let mut lower_dir = None;
+    let mut upper_dir = None;
+    for option in data.split(',') {
+        match option.split_once('=') {
+            Some(("lowerdir", path)) => lower_dir = Some(path),
+            Some(("upperdir", path)) => upper_dir = Some(path),
+            // The work directory is not used.
+            Some(("workdir", _)) => {}
+            _ => return Err(Errno::EINVAL.into()),
+        }
+    }
+
+    let (lower_dir, upper_dir) = match (lower_dir, upper_dir) {
+        (Some(lower_dir), Some(upper_dir)) => (lower_dir, upper_dir),
+        _ => return Err(Errno::EINVAL.into()),
+    };
+
+    let root_fs = current_process().root_fs().lock();
+    let lower = root_fs.lookup_dir(Path::new(lower_dir))?;
+    let upper_path = root_fs.lookup_path(Path::new(upper_dir), true)?;
+    let upper = match &upper_path.inode {
+        INode::Directory(dir) => dir.clone(),
+        _ => return Err(Errno::ENOTDIR.into()),
+    };
+    let upper_fs = root_fs.mount_point_of(&upper_path)?.fs().clone();
+    Ok(Arc::new(OverlayFs::new(lower, upper_fs, upper)))
+}
+
+pub fn init() {
+    register_file_system_type(FileSystemType {
+        name: "overlay",
+        requires_device: false,
+        mount: mount_overlay,
+    });
+}
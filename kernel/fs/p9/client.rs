@@ -0,0 +1,637 @@
+//! The 9P2000.L protocol: message encoding and RPCs over a transport.
+use crate::{
+    fs::{
+        inode::INodeNo,
+        stat::{BlockCount, BlockSize, DevId, FileMode, FileSize, GId, NLink, Stat, Time, UId},
+    },
+    prelude::*,
+    process::WaitQueue,
+    workqueue::Work,
+};
+use alloc::boxed::Box;
+use core::sync::atomic::{AtomicU16, AtomicU32, Ordering};
+use kerla_api::driver::p9::{P9Request, P9Transport, P9TransportError};
+use kerla_runtime::spinlock::SpinLock;
+use kerla_utils::bytes_parser::BytesParser;
+
+const RLERROR: u8 = 7;
+const TLOPEN: u8 = 12;
+const TLCREATE: u8 = 14;
+const TSYMLINK: u8 = 16;
+const TREADLINK: u8 = 22;
+const TGETATTR: u8 = 24;
+const TSETATTR: u8 = 26;
+const TREADDIR: u8 = 40;
+const TFSYNC: u8 = 50;
+const TLINK: u8 = 70;
+const TMKDIR: u8 = 72;
+const TRENAMEAT: u8 = 74;
+const TUNLINKAT: u8 = 76;
+const TVERSION: u8 = 100;
+const TATTACH: u8 = 104;
+const TWALK: u8 = 110;
+const TREAD: u8 = 116;
+const TWRITE: u8 = 118;
+const TCLUNK: u8 = 120;
+
+const VERSION: &str = "9P2000.L";
+/// The tag used in `Tversion`.
+const NOTAG: u16 = 0xffff;
+/// The fid which means "no fid" (e.g. `afid` in `Tattach` without
+/// authentication).
+const NOFID: u32 = 0xffff_ffff;
+/// `P9_GETATTR_BASIC`: all fields in `struct stat`.
+const GETATTR_BASIC: u64 = 0x7ff;
+/// The size of the header: `size[4] type[1] tag[2]`.
+const HEADER_LEN: usize = 7;
+/// The size of `Twrite` without the data.
+const TWRITE_HEADER_LEN: usize = HEADER_LEN + 4 + 8 + 4;
+/// The size of `Rread` and `Rreaddir` without the data.
+const RREAD_HEADER_LEN: usize = HEADER_LEN + 4;
+
+/// Processes waiting for responses.
+static RPC_WAIT_QUEUE: WaitQueue = WaitQueue::new();
+/// Clunks fids in a context which may sleep.
+static CLUNK_WORK: Work = Work::new("p9_clunk");
+
+/// Converts an error number in `Rlerror` (Linux's errno).
+fn errno_from_lerror(ecode: u32) -> Errno {
+    match ecode {
+        1 => Errno::EPERM,
+        2 => Errno::ENOENT,
+        9 => Errno::EBADF,
+        12 => Errno::ENOMEM,
+        13 => Errno::EACCES,
+        16 => Errno::EBUSY,
+        17 => Errno::EEXIST,
+        18 => Errno::EXDEV,
+        20 => Errno::ENOTDIR,
+        21 => Errno::EISDIR,
+        22 => Errno::EINVAL,
+        26 => Errno::ETXTBSY,
+        27 => Errno::EFBIG,
+        28 => Errno::ENOSPC,
+        30 => Errno::EROFS,
+        31 => Errno::EMLINK,
+        36 => Errno::ENAMETOOLONG,
+        38 => Errno::ENOSYS,
+        40 => Errno::ELOOP,
+        _ => Errno::EIO,
+    }
+}
+
+/// Decodes `dev_t` in the Linux's encoding.
+fn decode_dev(dev: u64) -> DevId {
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    DevId::new(major as usize, minor as usize)
+}
+
+/// The server's unique identifier of a file.
+#[derive(Debug, Copy, Clone)]
+pub struct Qid {
+    pub path: u64,
+}
+
+/// The attributes returned by `Tgetattr`.
+pub struct Attr {
+    pub qid: Qid,
+    pub mode: u32,
+    uid: u32,
+    gid: u32,
+    nlink: u64,
+    rdev: u64,
+    size: u64,
+    blksize: u64,
+    blocks: u64,
+    atime: u64,
+    mtime: u64,
+    ctime: u64,
+}
+
+impl Attr {
+    pub fn to_stat(&self) -> Stat {
+        Stat {
+            inode_no: INodeNo::new(self.qid.path as usize),
+            nlink: NLink(self.nlink as usize),
+            mode: FileMode::new(self.mode),
+            uid: UId(self.uid),
+            gid: GId(self.gid),
+            rdev: decode_dev(self.rdev),
+            size: FileSize(self.size as isize),
+            blksize: BlockSize(self.blksize as isize),
+            blocks: BlockCount(self.blocks as isize),
+            atime: Time::from_secs(self.atime as isize),
+            mtime: Time::from_secs(self.mtime as isize),
+            ctime: Time::from_secs(self.ctime as isize),
+            ..Stat::zeroed()
+        }
+    }
+}
+
+/// An entry in `Rreaddir`.
+pub struct RawDirEntry {
+    pub qid: Qid,
+    /// The offset of the next entry.
+    pub offset: u64,
+    /// The file type (`DT_*`).
+    pub file_type: u8,
+    pub name: String,
+}
+
+/// A T-message being built.
+struct Message {
+    buf: Vec<u8>,
+}
+
+impl Message {
+    fn new(type_: u8) -> Message {
+        let mut buf = Vec::with_capacity(64);
+        buf.extend_from_slice(&[0; 4]); // size[4]: filled in `Client::rpc`.
+        buf.push(type_);
+        buf.extend_from_slice(&[0; 2]); // tag[2]: filled in `Client::rpc`.
+        Message { buf }
+    }
+
+    fn u16(mut self, value: u16) -> Message {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn u32(mut self, value: u32) -> Message {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn u64(mut self, value: u64) -> Message {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn str(self, value: &str) -> Message {
+        self.u16(value.len() as u16).bytes(value.as_bytes())
+    }
+
+    fn bytes(mut self, value: &[u8]) -> Message {
+        self.buf.extend_from_slice(value);
+        self
+    }
+}
+
+/// Parses an R-message. Malformed messages are reported as `EIO`.
+struct Reader<'a> {
+    parser: BytesParser<'a>,
+}
+
+impl<'a> Reader<'a> {
+    fn new(body: &'a [u8]) -> Reader<'a> {
+        Reader {
+            parser: BytesParser::new(body),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        self.parser
+            .consume_le_u16()
+            .map_err(|_| Error::new(Errno::EIO))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        self.parser
+            .consume_le_u32()
+            .map_err(|_| Error::new(Errno::EIO))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        self.parser
+            .consume_le_u64()
+            .map_err(|_| Error::new(Errno::EIO))
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        self.parser
+            .consume_bytes(len)
+            .map_err(|_| Error::new(Errno::EIO))
+    }
+
+    fn str(&mut self) -> Result<String> {
+        let len = self.u16()? as usize;
+        let bytes = self.bytes(len)?;
+        Ok(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    fn qid(&mut self) -> Result<Qid> {
+        let _type = self.u8()?;
+        let _version = self.u32()?;
+        let path = self.u64()?;
+        Ok(Qid { path })
+    }
+}
+
+pub struct Client {
+    transport: Arc<dyn P9Transport>,
+    /// The maximum message size negotiated in `Tversion`.
+    msize: usize,
+    next_tag: AtomicU16,
+    next_fid: AtomicU32,
+}
+
+impl Client {
+    /// Negotiates the protocol version with the server.
+    pub fn connect(transport: Arc<dyn P9Transport>) -> Result<Arc<Client>> {
+        let mut client = Client {
+            msize: transport.max_message_size(),
+            transport,
+            next_tag: AtomicU16::new(0),
+            next_fid: AtomicU32::new(0),
+        };
+
+        let body = client.rpc_with_tag(
+            Message::new(TVERSION).u32(client.msize as u32).str(VERSION),
+            NOTAG,
+        )?;
+        let mut r = Reader::new(&body);
+        let msize = r.u32()? as usize;
+        let version = r.str()?;
+        if version != VERSION {
+            warn!("9p: the server doesn't support {}: {}", VERSION, version);
+            return Err(Errno::EINVAL.into());
+        }
+
+        if msize < TWRITE_HEADER_LEN + 1 {
+            warn!("9p: too small msize: {}", msize);
+            return Err(Errno::EINVAL.into());
+        }
+
+        client.msize = core::cmp::min(client.msize, msize);
+        Ok(Arc::new(client))
+    }
+
+    pub fn mount_tag(&self) -> &str {
+        self.transport.mount_tag()
+    }
+
+    /// The maximum length of data in a `Tread` or `Treaddir`.
+    fn max_read_len(&self) -> usize {
+        self.msize - RREAD_HEADER_LEN
+    }
+
+    /// The maximum length of data in a `Twrite`.
+    fn max_write_len(&self) -> usize {
+        self.msize - TWRITE_HEADER_LEN
+    }
+
+    /// Allocates a fid number. It's not used by the server until a request
+    /// (e.g. `Twalk`) associates a file with it.
+    fn alloc_fid(&self) -> u32 {
+        loop {
+            let fid = self.next_fid.fetch_add(1, Ordering::Relaxed);
+            if fid != NOFID {
+                return fid;
+            }
+        }
+    }
+
+    fn rpc(&self, message: Message) -> Result<Vec<u8>> {
+        let tag = loop {
+            let tag = self.next_tag.fetch_add(1, Ordering::Relaxed);
+            if tag != NOTAG {
+                break tag;
+            }
+        };
+
+        self.rpc_with_tag(message, tag)
+    }
+
+    /// Sends a request and waits for the response. Returns the body of the
+    /// response.
+    fn rpc_with_tag(&self, message: Message, tag: u16) -> Result<Vec<u8>> {
+        let mut buf = message.buf;
+        let type_ = buf[4];
+        let size = buf.len() as u32;
+        buf[0..4].copy_from_slice(&size.to_le_bytes());
+        buf[5..7].copy_from_slice(&tag.to_le_bytes());
+
+        let status = Arc::new(SpinLock::new(None));
+        let completion_status = status.clone();
+        self.transport.submit(P9Request {
+            message: buf,
+            completion: Box::new(move |result| {
+                *completion_status.lock() = Some(result);
+                RPC_WAIT_QUEUE.wake_all();
+            }),
+        });
+
+        let result: core::result::Result<Vec<u8>, P9TransportError> =
+            RPC_WAIT_QUEUE.sleep_or_idle_until(|| status.lock().take());
+        let mut response = match result {
+            Ok(response) => response,
+            Err(err) => {
+                warn!("9p: transport error: {:?}", err);
+                return Err(Errno::EIO.into());
+            }
+        };
+
+        if response.len() < HEADER_LEN || u16::from_le_bytes([response[5], response[6]]) != tag {
+            warn!("9p: malformed response");
+            return Err(Errno::EIO.into());
+        }
+
+        let body = response.split_off(HEADER_LEN);
+        match response[4] {
+            RLERROR => {
+                let ecode = Reader::new(&body).u32()?;
+                Err(errno_from_lerror(ecode).into())
+            }
+            // An R-message's type is its T-message's type plus one.
+            rtype if rtype == type_ + 1 => Ok(body),
+            rtype => {
+                warn!("9p: unexpected response type: {} (T={})", rtype, type_);
+                Err(Errno::EIO.into())
+            }
+        }
+    }
+
+    /// Attaches to the root of the exported file system.
+    pub fn attach(self: &Arc<Client>) -> Result<Fid> {
+        let fid = self.alloc_fid();
+        self.rpc(
+            Message::new(TATTACH)
+                .u32(fid)
+                .u32(NOFID)
+                .str("root") // uname
+                .str("") // aname
+                .u32(0), // n_uname
+        )?;
+
+        Ok(Fid {
+            client: self.clone(),
+            fid,
+        })
+    }
+
+    fn clunk(&self, fid: u32) {
+        if let Err(err) = self.rpc(Message::new(TCLUNK).u32(fid)) {
+            warn!("9p: failed to clunk fid {}: {:?}", fid, err);
+        }
+    }
+}
+
+/// A reference to a file in the server. It's clunked when dropped.
+pub struct Fid {
+    client: Arc<Client>,
+    fid: u32,
+}
+
+impl Fid {
+    pub fn client(&self) -> &Arc<Client> {
+        &self.client
+    }
+
+    /// The maximum length of data read or written in a single request.
+    pub fn max_io_len(&self) -> usize {
+        core::cmp::min(self.client.max_read_len(), self.client.max_write_len())
+    }
+
+    /// Walks from the file to `name` (or clones it if `name` is `None`).
+    pub fn walk(&self, name: Option<&str>) -> Result<Fid> {
+        let new_fid = self.client.alloc_fid();
+        let names: &[&str] = match &name {
+            Some(name) => core::slice::from_ref(name),
+            None => &[],
+        };
+
+        let mut message = Message::new(TWALK)
+            .u32(self.fid)
+            .u32(new_fid)
+            .u16(names.len() as u16);
+        for name in names {
+            message = message.str(name);
+        }
+
+        let body = self.client.rpc(message)?;
+        let nwqid = Reader::new(&body).u16()? as usize;
+        if nwqid < names.len() {
+            // Partially walked: the new fid is not associated with a file.
+            return Err(Errno::ENOENT.into());
+        }
+
+        Ok(Fid {
+            client: self.client.clone(),
+            fid: new_fid,
+        })
+    }
+
+    /// Clones the fid and opens it with `flags` (`O_*` in Linux).
+    pub fn open(&self, flags: u32) -> Result<Fid> {
+        let fid = self.walk(None)?;
+        self.client
+            .rpc(Message::new(TLOPEN).u32(fid.fid).u32(flags))?;
+        Ok(fid)
+    }
+
+    /// Creates a regular file in the directory. Returns the new file opened
+    /// with `flags`.
+    pub fn create(&self, name: &str, flags: u32, mode: u32) -> Result<Fid> {
+        let fid = self.walk(None)?;
+        self.client.rpc(
+            Message::new(TLCREATE)
+                .u32(fid.fid)
+                .str(name)
+                .u32(flags)
+                .u32(mode)
+                .u32(0), // gid
+        )?;
+        Ok(fid)
+    }
+
+    pub fn mkdir(&self, name: &str, mode: u32) -> Result<()> {
+        self.client.rpc(
+            Message::new(TMKDIR)
+                .u32(self.fid)
+                .str(name)
+                .u32(mode)
+                .u32(0), // gid
+        )?;
+        Ok(())
+    }
+
+    pub fn unlink(&self, name: &str, flags: u32) -> Result<()> {
+        self.client
+            .rpc(Message::new(TUNLINKAT).u32(self.fid).str(name).u32(flags))?;
+        Ok(())
+    }
+
+    pub fn symlink(&self, name: &str, target: &str) -> Result<()> {
+        self.client.rpc(
+            Message::new(TSYMLINK)
+                .u32(self.fid)
+                .str(name)
+                .str(target)
+                .u32(0), // gid
+        )?;
+        Ok(())
+    }
+
+    /// Moves `old_name` in the directory to `new_name` in `new_dir`.
+    pub fn rename(&self, old_name: &str, new_dir: &Fid, new_name: &str) -> Result<()> {
+        self.client.rpc(
+            Message::new(TRENAMEAT)
+                .u32(self.fid)
+                .str(old_name)
+                .u32(new_dir.fid)
+                .str(new_name),
+        )?;
+        Ok(())
+    }
+
+    /// Creates a hard link to `target` in the directory.
+    pub fn link(&self, target: &Fid, name: &str) -> Result<()> {
+        self.client
+            .rpc(Message::new(TLINK).u32(self.fid).u32(target.fid).str(name))?;
+        Ok(())
+    }
+
+    pub fn getattr(&self) -> Result<Attr> {
+        let body = self
+            .client
+            .rpc(Message::new(TGETATTR).u32(self.fid).u64(GETATTR_BASIC))?;
+        let mut r = Reader::new(&body);
+        let _valid = r.u64()?;
+        let qid = r.qid()?;
+        let mode = r.u32()?;
+        let uid = r.u32()?;
+        let gid = r.u32()?;
+        let nlink = r.u64()?;
+        let rdev = r.u64()?;
+        let size = r.u64()?;
+        let blksize = r.u64()?;
+        let blocks = r.u64()?;
+        let atime = r.u64()?;
+        let _atime_nsec = r.u64()?;
+        let mtime = r.u64()?;
+        let _mtime_nsec = r.u64()?;
+        let ctime = r.u64()?;
+        Ok(Attr {
+            qid,
+            mode,
+            uid,
+            gid,
+            nlink,
+            rdev,
+            size,
+            blksize,
+            blocks,
+            atime,
+            mtime,
+            ctime,
+        })
+    }
+
+    pub fn truncate(&self, size: u64) -> Result<()> {
+        const P9_SETATTR_SIZE: u32 = 0x8;
+        self.client.rpc(
+            Message::new(TSETATTR)
+                .u32(self.fid)
+                .u32(P9_SETATTR_SIZE)
+                .u32(0) // mode
+                .u32(0) // uid
+                .u32(0) // gid
+                .u64(size)
+                .u64(0) // atime_sec
+                .u64(0) // atime_nsec
+                .u64(0) // mtime_sec
+                .u64(0), // mtime_nsec
+        )?;
+        Ok(())
+    }
+
+    pub fn readlink(&self) -> Result<String> {
+        let body = self.client.rpc(Message::new(TREADLINK).u32(self.fid))?;
+        Reader::new(&body).str()
+    }
+
+    /// Reads the opened file. It may read less than `buf.len()`.
+    pub fn read(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let count = core::cmp::min(buf.len(), self.client.max_read_len());
+        let body = self.client.rpc(
+            Message::new(TREAD)
+                .u32(self.fid)
+                .u64(offset)
+                .u32(count as u32),
+        )?;
+
+        let mut r = Reader::new(&body);
+        let read_len = r.u32()? as usize;
+        if read_len > count {
+            return Err(Errno::EIO.into());
+        }
+
+        buf[..read_len].copy_from_slice(r.bytes(read_len)?);
+        Ok(read_len)
+    }
+
+    /// Writes into the opened file. It may write less than `data.len()`.
+    pub fn write(&self, offset: u64, data: &[u8]) -> Result<usize> {
+        let count = core::cmp::min(data.len(), self.client.max_write_len());
+        let body = self.client.rpc(
+            Message::new(TWRITE)
+                .u32(self.fid)
+                .u64(offset)
+                .u32(count as u32)
+                .bytes(&data[..count]),
+        )?;
+
+        let written_len = Reader::new(&body).u32()? as usize;
+        Ok(core::cmp::min(written_len, count))
+    }
+
+    /// Reads entries from the opened directory. `offset` is `0` or the
+    /// offset of the last entry returned. Returns an empty `Vec` at the end
+    /// of the directory.
+    pub fn readdir(&self, offset: u64) -> Result<Vec<RawDirEntry>> {
+        let count = self.client.max_read_len();
+        let body = self.client.rpc(
+            Message::new(TREADDIR)
+                .u32(self.fid)
+                .u64(offset)
+                .u32(count as u32),
+        )?;
+
+        let mut r = Reader::new(&body);
+        let len = r.u32()? as usize;
+        let mut r = Reader::new(r.bytes(len)?);
+        let mut entries = Vec::new();
+        while r.parser.remaining_len() > 0 {
+            entries.push(RawDirEntry {
+                qid: r.qid()?,
+                offset: r.u64()?,
+                file_type: r.u8()?,
+                name: r.str()?,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// Flushes the opened file.
+    pub fn fsync(&self) -> Result<()> {
+        self.client
+            .rpc(Message::new(TFSYNC).u32(self.fid).u32(0 /* datasync */))?;
+        Ok(())
+    }
+}
+
+impl Drop for Fid {
+    fn drop(&mut self) {
+        // Fids may be dropped with the opened file table locked (e.g. in
+        // exit(2) or dup2(2)): clunk it in a context which may sleep.
+        let client = self.client.clone();
+        let fid = self.fid;
+        CLUNK_WORK.queue(move || client.clunk(fid));
+    }
+}
@@ -0,0 +1,179 @@
+use super::{
+    client::{Fid, RawDirEntry},
+    file::{P9File, P9Symlink},
+    make_inode,
+};
+use crate::{
+    fs::{
+        inode::{DirEntry, Directory, FileType, INode, INodeNo, RenameFlags},
+        opened_file::OpenFlags,
+        stat::{FileMode, Stat},
+    },
+    prelude::*,
+};
+use core::fmt;
+use kerla_runtime::spinlock::SpinLock;
+use kerla_utils::downcast::downcast;
+
+pub(super) struct P9Dir {
+    fid: Fid,
+    /// The entries read at `readdir(0)`. Following `readdir` calls return
+    /// entries from it.
+    entries: SpinLock<Option<Arc<Vec<RawDirEntry>>>>,
+}
+
+impl P9Dir {
+    pub fn new(fid: Fid) -> P9Dir {
+        P9Dir {
+            fid,
+            entries: SpinLock::new(None),
+        }
+    }
+
+    /// Reads all entries in the directory.
+    fn read_entries(&self) -> Result<Vec<RawDirEntry>> {
+        let opened = self
+            .fid
+            .open((OpenFlags::O_RDONLY | OpenFlags::O_DIRECTORY).bits() as u32)?;
+        let mut entries: Vec<RawDirEntry> = Vec::new();
+        loop {
+            let offset = entries.last().map(|entry| entry.offset).unwrap_or(0);
+            let mut new_entries = opened.readdir(offset)?;
+            if new_entries.is_empty() {
+                break;
+            }
+
+            entries.append(&mut new_entries);
+        }
+
+        Ok(entries)
+    }
+}
+
+impl Directory for P9Dir {
+    fn lookup(&self, name: &str) -> Result<INode> {
+        let fid = self.fid.walk(Some(name))?;
+        let attr = fid.getattr()?;
+        Ok(make_inode(fid, &attr))
+    }
+
+    fn create_file(&self, name: &str, mode: FileMode) -> Result<INode> {
+        let flags = OpenFlags::O_RDWR | OpenFlags::O_CREAT | OpenFlags::O_EXCL;
+        let opened = self
+            .fid
+            .create(name, flags.bits() as u32, mode.as_u32() & 0o7777)?;
+        let fid = self.fid.walk(Some(name))?;
+        Ok(INode::FileLike(Arc::new(P9File::with_opened_fid(
+            fid, opened,
+        ))))
+    }
+
+    fn create_dir(&self, name: &str, mode: FileMode) -> Result<INode> {
+        self.fid.mkdir(name, mode.as_u32() & 0o7777)?;
+        let fid = self.fid.walk(Some(name))?;
+        Ok(INode::Directory(Arc::new(P9Dir::new(fid))))
+    }
+
+    fn stat(&self) -> Result<Stat> {
+        Ok(self.fid.getattr()?.to_stat())
+    }
+
+    fn readdir(&self, index: usize) -> Result<Option<DirEntry>> {
+        let cached = self.entries.lock().clone();
+        let entries = match cached {
+            Some(entries) if index > 0 => entries,
+            _ => {
+                // Don't hold the lock while waiting for the server.
+                let entries = Arc::new(self.read_entries()?);
+                *self.entries.lock() = Some(entries.clone());
+                entries
+            }
+        };
+
+        let entry = match entries.get(index) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        // Convert the `DT_*` value in `Rreaddir`. Others (`DT_REG` and
+        // `DT_UNKNOWN`) are treated as regular files.
+        let file_type = match entry.file_type {
+            1 => FileType::Fifo,
+            2 => FileType::CharDevice,
+            4 => FileType::Directory,
+            6 => FileType::BlockDevice,
+            10 => FileType::Link,
+            12 => FileType::Socket,
+            _ => FileType::Regular,
+        };
+
+        Ok(Some(DirEntry {
+            inode_no: INodeNo::new(entry.qid.path as usize),
+            file_type,
+            name: entry.name.clone(),
+        }))
+    }
+
+    fn link(&self, name: &str, link_to: &INode) -> Result<()> {
+        let target = match link_to {
+            INode::FileLike(file) => downcast::<_, P9File>(file).map(|file| &file.fid),
+            INode::Symlink(symlink) => {
+                downcast::<_, P9Symlink>(symlink).map(|symlink| &symlink.fid)
+            }
+            // Hard links to directories are not allowed.
+            INode::Directory(_) => return Err(Errno::EPERM.into()),
+        };
+
+        match target {
+            Some(target) if Arc::ptr_eq(target.client(), self.fid.client()) => {
+                self.fid.link(target, name)
+            }
+            _ => Err(Errno::EXDEV.into()),
+        }
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        self.fid.unlink(name, 0)
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        const AT_REMOVEDIR: u32 = 0x200;
+        self.fid.unlink(name, AT_REMOVEDIR)
+    }
+
+    fn rename(
+        &self,
+        old_name: &str,
+        new_dir: &Arc<dyn Directory>,
+        new_name: &str,
+        flags: RenameFlags,
+    ) -> Result<()> {
+        let new_dir = match downcast::<_, P9Dir>(new_dir) {
+            Some(new_dir) if Arc::ptr_eq(new_dir.fid.client(), self.fid.client()) => new_dir,
+            _ => return Err(Errno::EXDEV.into()),
+        };
+
+        // 9P2000.L's renameat doesn't take flags.
+        if flags.intersects(RenameFlags::RENAME_EXCHANGE | RenameFlags::RENAME_WHITEOUT) {
+            return Err(Errno::EINVAL.into());
+        }
+
+        if flags.contains(RenameFlags::RENAME_NOREPLACE) && new_dir.fid.walk(Some(new_name)).is_ok()
+        {
+            return Err(Errno::EEXIST.into());
+        }
+
+        self.fid.rename(old_name, &new_dir.fid, new_name)
+    }
+
+    fn create_symlink(&self, name: &str, target: &str) -> Result<INode> {
+        self.fid.symlink(name, target)?;
+        self.lookup(name)
+    }
+}
+
+impl fmt::Debug for P9Dir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("P9Dir").finish()
+    }
+}
@@ -0,0 +1,165 @@
+use super::client::Fid;
+use crate::{
+    fs::{
+        inode::{FileLike, Symlink},
+        opened_file::{OpenFlags, OpenOptions},
+        path::PathBuf,
+        stat::Stat,
+    },
+    prelude::*,
+    user_buffer::{UserBufReader, UserBufWriter, UserBuffer, UserBufferMut},
+};
+use core::fmt;
+use kerla_runtime::spinlock::SpinLock;
+
+/// A regular file, or a device file, FIFO, or socket which can't be read or
+/// written.
+pub(super) struct P9File {
+    pub(super) fid: Fid,
+    regular: bool,
+    /// The fid opened for reading. Opened on demand.
+    reader: SpinLock<Option<Arc<Fid>>>,
+    /// The fid opened for writing. Opened on demand.
+    writer: SpinLock<Option<Arc<Fid>>>,
+}
+
+impl P9File {
+    pub fn new(fid: Fid, regular: bool) -> P9File {
+        P9File {
+            fid,
+            regular,
+            reader: SpinLock::new(None),
+            writer: SpinLock::new(None),
+        }
+    }
+
+    /// Creates a regular file object with a fid already opened for both
+    /// reading and writing.
+    pub fn with_opened_fid(fid: Fid, opened: Fid) -> P9File {
+        let opened = Arc::new(opened);
+        P9File {
+            fid,
+            regular: true,
+            reader: SpinLock::new(Some(opened.clone())),
+            writer: SpinLock::new(Some(opened)),
+        }
+    }
+
+    fn opened_fid(&self, write: bool) -> Result<Arc<Fid>> {
+        if !self.regular {
+            return Err(Errno::ENXIO.into());
+        }
+
+        let slot = if write { &self.writer } else { &self.reader };
+        if let Some(fid) = &*slot.lock() {
+            return Ok(fid.clone());
+        }
+
+        // Don't hold the lock while waiting for the server.
+        let flags = if write {
+            OpenFlags::O_WRONLY
+        } else {
+            OpenFlags::O_RDONLY
+        };
+        let fid = Arc::new(self.fid.open(flags.bits() as u32)?);
+        Ok(slot.lock().get_or_insert(fid).clone())
+    }
+}
+
+impl FileLike for P9File {
+    fn stat(&self) -> Result<Stat> {
+        Ok(self.fid.getattr()?.to_stat())
+    }
+
+    fn read(&self, offset: usize, buf: UserBufferMut<'_>, _options: &OpenOptions) -> Result<usize> {
+        let fid = self.opened_fid(false)?;
+        let mut chunk = vec![0; core::cmp::min(buf.len(), fid.max_io_len())];
+        let mut writer = UserBufWriter::from(buf);
+        while writer.remaining_len() > 0 {
+            let chunk_len = core::cmp::min(writer.remaining_len(), chunk.len());
+            let pos = (offset + writer.written_len()) as u64;
+            let read_len = fid.read(pos, &mut chunk[..chunk_len])?;
+            if read_len == 0 {
+                break;
+            }
+
+            writer.write_bytes(&chunk[..read_len])?;
+        }
+
+        Ok(writer.written_len())
+    }
+
+    fn write(&self, offset: usize, buf: UserBuffer<'_>, _options: &OpenOptions) -> Result<usize> {
+        let fid = self.opened_fid(true)?;
+        let mut chunk = vec![0; core::cmp::min(buf.len(), fid.max_io_len())];
+        let mut reader = UserBufReader::from(buf);
+        let mut written_len = 0;
+        loop {
+            let read_len = reader.read_bytes(&mut chunk)?;
+            if read_len == 0 {
+                break;
+            }
+
+            match fid.write((offset + written_len) as u64, &chunk[..read_len]) {
+                Ok(len) => {
+                    written_len += len;
+                    if len < read_len {
+                        break;
+                    }
+                }
+                Err(_) if written_len > 0 => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(written_len)
+    }
+
+    fn truncate(&self, length: usize) -> Result<()> {
+        if !self.regular {
+            return Err(Errno::EINVAL.into());
+        }
+
+        self.fid.truncate(length as u64)
+    }
+
+    fn fsync(&self) -> Result<()> {
+        let writer = self.writer.lock().clone();
+        match writer {
+            Some(fid) => fid.fsync(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Debug for P9File {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("P9File").finish()
+    }
+}
+
+pub(super) struct P9Symlink {
+    pub(super) fid: Fid,
+}
+
+impl P9Symlink {
+    pub fn new(fid: Fid) -> P9Symlink {
+        P9Symlink { fid }
+    }
+}
+
+impl Symlink for P9Symlink {
+    fn stat(&self) -> Result<Stat> {
+        Ok(self.fid.getattr()?.to_stat())
+    }
+
+    fn linked_to(&self) -> Result<PathBuf> {
+        Ok(PathBuf::from(self.fid.readlink()?))
+    }
+}
+
+impl fmt::Debug for P9Symlink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("P9Symlink").finish()
+    }
+}
@@ -0,0 +1,87 @@
+//! A 9P2000.L client file system. It mounts a directory exported by the host
+//! (e.g. QEMU's `-virtfs`).
+use crate::{
+    fs::{
+        file_system::{register_file_system_type, FileSystem, FileSystemType, FsStat},
+        inode::{Directory, INode},
+        stat::{S_IFDIR, S_IFLNK, S_IFMT, S_IFREG},
+    },
+    prelude::*,
+};
+use alloc::boxed::Box;
+use client::{Attr, Client, Fid};
+use dir::P9Dir;
+use file::{P9File, P9Symlink};
+use kerla_api::driver::p9::P9Transport;
+use kerla_runtime::spinlock::SpinLock;
+
+mod client;
+mod dir;
+mod file;
+
+const V9FS_MAGIC: u64 = 0x01021997;
+
+static TRANSPORTS: SpinLock<Vec<Arc<dyn P9Transport>>> = SpinLock::new(Vec::new());
+
+pub fn register_p9_transport(transport: Box<dyn P9Transport>) {
+    TRANSPORTS.lock().push(Arc::from(transport));
+}
+
+fn make_inode(fid: Fid, attr: &Attr) -> INode {
+    match attr.mode & S_IFMT {
+        S_IFDIR => INode::Directory(Arc::new(P9Dir::new(fid))),
+        S_IFLNK => INode::Symlink(Arc::new(P9Symlink::new(fid))),
+        file_type => INode::FileLike(Arc::new(P9File::new(fid, file_type == S_IFREG))),
+    }
+}
+
+pub struct P9Fs {
+    root_dir: Arc<P9Dir>,
+}
+
+impl P9Fs {
+    /// Connects to the server over the transport with `mount_tag`.
+    pub fn mount(mount_tag: &str) -> Result<Arc<P9Fs>> {
+        let transport = TRANSPORTS
+            .lock()
+            .iter()
+            .find(|transport| transport.mount_tag() == mount_tag)
+            .cloned()
+            .ok_or_else(|| Error::new(Errno::ENOENT))?;
+
+        let client = Client::connect(transport)?;
+        let root = client.attach()?;
+        if root.getattr()?.mode & S_IFMT != S_IFDIR {
+            return Err(Errno::ENOTDIR.into());
+        }
+
+        info!("9p: mounted \"{}\"", client.mount_tag());
+        Ok(Arc::new(P9Fs {
+            root_dir: Arc::new(P9Dir::new(root)),
+        }))
+    }
+}
+
+impl FileSystem for P9Fs {
+    fn name(&self) -> &'static str {
+        "9p"
+    }
+
+    fn root_dir(&self) -> Result<Arc<dyn Directory>> {
+        Ok(self.root_dir.clone())
+    }
+
+    fn statfs(&self) -> Result<FsStat> {
+        // TODO: Ask the server (Tstatfs).
+        Ok(FsStat::empty(V9FS_MAGIC))
+    }
+}
+
+pub fn init() {
+    // The source is the mount tag (e.g. `mount -t 9p host /mnt`).
+    register_file_system_type(FileSystemType {
+        name: "9p",
+        requires_device: false,
+        mount: |source, _data| Ok(P9Fs::mount(source)?),
+    });
+}
@@ -0,0 +1,62 @@
+use core::fmt;
+
+use super::read_rendered;
+use crate::{
+    fs::{
+        inode::{FileLike, INodeNo},
+        opened_file::OpenOptions,
+        stat::{FileMode, Stat, S_IFREG},
+    },
+    prelude::*,
+    process::core_dump::{core_pattern, set_core_pattern},
+    user_buffer::{UserBufReader, UserBuffer, UserBufferMut},
+};
+
+/// The maximum length of `core_pattern` (`CORENAME_MAX_SIZE` in Linux).
+const CORE_PATTERN_MAX_LEN: usize = 128;
+
+/// The `/proc/sys/kernel/core_pattern` file.
+pub(super) struct CorePatternFile {}
+
+impl CorePatternFile {
+    pub fn new() -> CorePatternFile {
+        CorePatternFile {}
+    }
+}
+
+impl fmt::Debug for CorePatternFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CorePattern").finish()
+    }
+}
+
+impl FileLike for CorePatternFile {
+    fn stat(&self) -> Result<Stat> {
+        Ok(Stat {
+            inode_no: INodeNo::new(10),
+            mode: FileMode::new(S_IFREG | 0o644),
+            ..Stat::zeroed()
+        })
+    }
+
+    fn read(&self, offset: usize, buf: UserBufferMut<'_>, _options: &OpenOptions) -> Result<usize> {
+        use core::fmt::Write;
+
+        let mut contents = String::new();
+        let _ = writeln!(contents, "{}", core_pattern());
+        read_rendered(&contents, offset, buf)
+    }
+
+    fn write(&self, _offset: usize, buf: UserBuffer<'_>, _options: &OpenOptions) -> Result<usize> {
+        let len = buf.len();
+        if len > CORE_PATTERN_MAX_LEN {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let mut pattern = vec![0; len];
+        UserBufReader::from(buf).read_bytes(&mut pattern)?;
+        let pattern = core::str::from_utf8(&pattern).map_err(|_| Error::new(Errno::EINVAL))?;
+        set_core_pattern(pattern);
+        Ok(len)
+    }
+}
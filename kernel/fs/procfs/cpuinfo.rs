@@ -0,0 +1,180 @@
+use core::fmt;
+
+use kerla_runtime::arch::tsc_frequency;
+use x86::cpuid::CpuId;
+
+use super::read_rendered;
+use crate::{
+    fs::{
+        inode::{FileLike, INodeNo},
+        opened_file::OpenOptions,
+        stat::{FileMode, Stat, S_IFREG},
+    },
+    prelude::*,
+    user_buffer::UserBufferMut,
+};
+
+/// The `/proc/cpuinfo` file.
+pub(super) struct CpuInfoFile {}
+
+impl CpuInfoFile {
+    pub fn new() -> CpuInfoFile {
+        CpuInfoFile {}
+    }
+}
+
+impl fmt::Debug for CpuInfoFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CpuInfo").finish()
+    }
+}
+
+/// Returns the CPU feature flags in the same names as Linux.
+fn cpu_flags(cpuid: &CpuId) -> Vec<&'static str> {
+    let mut flags = Vec::new();
+    if let Some(info) = cpuid.get_feature_info() {
+        let features = [
+            ("fpu", info.has_fpu()),
+            ("vme", info.has_vme()),
+            ("de", info.has_de()),
+            ("pse", info.has_pse()),
+            ("tsc", info.has_tsc()),
+            ("msr", info.has_msr()),
+            ("pae", info.has_pae()),
+            ("mce", info.has_mce()),
+            ("cx8", info.has_cmpxchg8b()),
+            ("apic", info.has_apic()),
+            ("sep", info.has_sysenter_sysexit()),
+            ("mtrr", info.has_mtrr()),
+            ("pge", info.has_pge()),
+            ("mca", info.has_mca()),
+            ("cmov", info.has_cmov()),
+            ("pat", info.has_pat()),
+            ("pse36", info.has_pse36()),
+            ("clflush", info.has_clflush()),
+            ("mmx", info.has_mmx()),
+            ("fxsr", info.has_fxsave_fxstor()),
+            ("sse", info.has_sse()),
+            ("sse2", info.has_sse2()),
+            ("ss", info.has_ss()),
+            ("ht", info.has_htt()),
+            ("pni", info.has_sse3()),
+            ("pclmulqdq", info.has_pclmulqdq()),
+            ("vmx", info.has_vmx()),
+            ("ssse3", info.has_ssse3()),
+            ("fma", info.has_fma()),
+            ("cx16", info.has_cmpxchg16b()),
+            ("pcid", info.has_pcid()),
+            ("sse4_1", info.has_sse41()),
+            ("sse4_2", info.has_sse42()),
+            ("x2apic", info.has_x2apic()),
+            ("movbe", info.has_movbe()),
+            ("popcnt", info.has_popcnt()),
+            ("tsc_deadline_timer", info.has_tsc_deadline()),
+            ("aes", info.has_aesni()),
+            ("xsave", info.has_xsave()),
+            ("avx", info.has_avx()),
+            ("f16c", info.has_f16c()),
+            ("rdrand", info.has_rdrand()),
+            ("hypervisor", info.has_hypervisor()),
+        ];
+
+        flags.extend(
+            features
+                .iter()
+                .filter(|(_, has)| *has)
+                .map(|(name, _)| *name),
+        );
+    }
+
+    if let Some(info) = cpuid.get_extended_feature_info() {
+        let features = [
+            ("fsgsbase", info.has_fsgsbase()),
+            ("bmi1", info.has_bmi1()),
+            ("avx2", info.has_avx2()),
+            ("smep", info.has_smep()),
+            ("bmi2", info.has_bmi2()),
+            ("erms", info.has_rep_movsb_stosb()),
+            ("invpcid", info.has_invpcid()),
+            ("rdseed", info.has_rdseed()),
+            ("adx", info.has_adx()),
+            ("smap", info.has_smap()),
+            ("clflushopt", info.has_clflushopt()),
+            ("clwb", info.has_clwb()),
+            ("avx512f", info.has_avx512f()),
+            ("sha_ni", info.has_sha()),
+            ("umip", info.has_umip()),
+            ("pku", info.has_pku()),
+            ("rdpid", info.has_rdpid()),
+        ];
+
+        flags.extend(
+            features
+                .iter()
+                .filter(|(_, has)| *has)
+                .map(|(name, _)| *name),
+        );
+    }
+
+    flags
+}
+
+impl FileLike for CpuInfoFile {
+    fn stat(&self) -> Result<Stat> {
+        Ok(Stat {
+            inode_no: INodeNo::new(4),
+            mode: FileMode::new(S_IFREG | 0o444),
+            ..Stat::zeroed()
+        })
+    }
+
+    fn read(&self, offset: usize, buf: UserBufferMut<'_>, _options: &OpenOptions) -> Result<usize> {
+        use core::fmt::Write;
+
+        let cpuid = CpuId::new();
+        let vendor = cpuid.get_vendor_info();
+        let brand = cpuid.get_processor_brand_string();
+        let (family, model, stepping) = match cpuid.get_feature_info() {
+            Some(info) => (info.family_id(), info.model_id(), info.stepping_id()),
+            None => (0, 0, 0),
+        };
+        // Hypervisors often don't report the frequency in CPUID.
+        let mhz = cpuid
+            .get_processor_frequency_info()
+            .map(|info| info.processor_base_frequency() as u64)
+            .filter(|mhz| *mhz != 0)
+            .unwrap_or_else(|| tsc_frequency() / 1_000_000);
+
+        let mut contents = String::new();
+        let _ = write!(
+            contents,
+            concat!(
+                "processor\t: 0\n",
+                "vendor_id\t: {vendor}\n",
+                "cpu family\t: {family}\n",
+                "model\t\t: {model}\n",
+                "model name\t: {brand}\n",
+                "stepping\t: {stepping}\n",
+                "cpu MHz\t\t: {mhz}.000\n",
+                "physical id\t: 0\n",
+                "siblings\t: 1\n",
+                "core id\t\t: 0\n",
+                "cpu cores\t: 1\n",
+                "flags\t\t: {flags}\n",
+                "\n",
+            ),
+            vendor = vendor.as_ref().map(|v| v.as_str()).unwrap_or("unknown"),
+            family = family,
+            model = model,
+            brand = brand
+                .as_ref()
+                .map(|b| b.as_str().trim())
+                .unwrap_or("unknown"),
+            stepping = stepping,
+            mhz = mhz,
+            flags = cpu_flags(&cpuid).join(" "),
+        );
+
+        read_rendered(&contents, offset, buf)
+    }
+}
@@ -0,0 +1,50 @@
+use core::fmt;
+
+use super::read_rendered;
+use crate::{
+    fs::{
+        file_system::file_system_types,
+        inode::{FileLike, INodeNo},
+        opened_file::OpenOptions,
+        stat::{FileMode, Stat, S_IFREG},
+    },
+    prelude::*,
+    user_buffer::UserBufferMut,
+};
+
+/// The `/proc/filesystems` file.
+pub(super) struct FileSystemsFile {}
+
+impl FileSystemsFile {
+    pub fn new() -> FileSystemsFile {
+        FileSystemsFile {}
+    }
+}
+
+impl fmt::Debug for FileSystemsFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileSystems").finish()
+    }
+}
+
+impl FileLike for FileSystemsFile {
+    fn stat(&self) -> Result<Stat> {
+        Ok(Stat {
+            inode_no: INodeNo::new(11),
+            mode: FileMode::new(S_IFREG | 0o444),
+            ..Stat::zeroed()
+        })
+    }
+
+    fn read(&self, offset: usize, buf: UserBufferMut<'_>, _options: &OpenOptions) -> Result<usize> {
+        use core::fmt::Write;
+
+        let mut contents = String::new();
+        for fs_type in file_system_types() {
+            let nodev = if fs_type.requires_device { "" } else { "nodev" };
+            let _ = writeln!(contents, "{}\t{}", nodev, fs_type.name);
+        }
+
+        read_rendered(&contents, offset, buf)
+    }
+}
@@ -0,0 +1,59 @@
+use core::fmt;
+
+use super::read_rendered;
+use crate::{
+    fs::{
+        inode::{FileLike, INodeNo},
+        opened_file::OpenOptions,
+        stat::{FileMode, Stat, S_IFREG},
+    },
+    prelude::*,
+    process::{num_running_processes, read_load_average, read_process_stats},
+    user_buffer::UserBufferMut,
+};
+
+/// The `/proc/loadavg` file.
+pub(super) struct LoadAvgFile {}
+
+impl LoadAvgFile {
+    pub fn new() -> LoadAvgFile {
+        LoadAvgFile {}
+    }
+}
+
+impl fmt::Debug for LoadAvgFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoadAvg").finish()
+    }
+}
+
+impl FileLike for LoadAvgFile {
+    fn stat(&self) -> Result<Stat> {
+        Ok(Stat {
+            inode_no: INodeNo::new(6),
+            mode: FileMode::new(S_IFREG | 0o444),
+            ..Stat::zeroed()
+        })
+    }
+
+    fn read(&self, offset: usize, buf: UserBufferMut<'_>, _options: &OpenOptions) -> Result<usize> {
+        use core::fmt::Write;
+
+        let [load1, load5, load15] = read_load_average();
+        let process_stats = read_process_stats();
+
+        let mut contents = String::new();
+        let _ = writeln!(
+            contents,
+            "{} {} {} {}/{} {}",
+            load1,
+            load5,
+            load15,
+            num_running_processes(),
+            process_stats.num_processes,
+            process_stats.last_pid.as_i32(),
+        );
+
+        read_rendered(&contents, offset, buf)
+    }
+}
@@ -0,0 +1,79 @@
+use core::fmt;
+
+use kerla_runtime::{
+    arch::PAGE_SIZE, global_allocator::read_kernel_heap_stats, page_allocator::read_allocator_stats,
+};
+
+use super::read_rendered;
+use crate::{
+    fs::{
+        inode::{FileLike, INodeNo},
+        opened_file::OpenOptions,
+        stat::{FileMode, Stat, S_IFREG},
+    },
+    prelude::*,
+    user_buffer::UserBufferMut,
+};
+
+/// The `/proc/meminfo` file.
+pub(super) struct MemInfoFile {}
+
+impl MemInfoFile {
+    pub fn new() -> MemInfoFile {
+        MemInfoFile {}
+    }
+}
+
+impl fmt::Debug for MemInfoFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemInfo").finish()
+    }
+}
+
+impl FileLike for MemInfoFile {
+    fn stat(&self) -> Result<Stat> {
+        Ok(Stat {
+            inode_no: INodeNo::new(3),
+            mode: FileMode::new(S_IFREG | 0o444),
+            ..Stat::zeroed()
+        })
+    }
+
+    fn read(&self, offset: usize, buf: UserBufferMut<'_>, _options: &OpenOptions) -> Result<usize> {
+        use core::fmt::Write;
+
+        let allocator_stats = read_allocator_stats();
+        let heap_stats = read_kernel_heap_stats();
+        let total_kb = allocator_stats.num_total_pages * PAGE_SIZE / 1024;
+        let free_kb = allocator_stats.num_free_pages * PAGE_SIZE / 1024;
+        // The kernel heap is carved out from the page allocator: its unused
+        // part is not available to the page allocator.
+        let heap_kb = heap_stats.allocated_bytes / 1024;
+
+        let mut contents = String::new();
+        let _ = write!(
+            contents,
+            concat!(
+                "MemTotal:       {total_kb:>8} kB\n",
+                "MemFree:        {free_kb:>8} kB\n",
+                "MemAvailable:   {free_kb:>8} kB\n",
+                "Buffers:        {zero:>8} kB\n",
+                "Cached:         {zero:>8} kB\n",
+                "Shmem:          {zero:>8} kB\n",
+                "Slab:           {heap_kb:>8} kB\n",
+                "SReclaimable:   {zero:>8} kB\n",
+                "SUnreclaim:     {heap_kb:>8} kB\n",
+                "SwapTotal:      {zero:>8} kB\n",
+                "SwapFree:       {zero:>8} kB\n",
+                "KernelHeapTotal:{heap_total_kb:>8} kB\n",
+            ),
+            total_kb = total_kb,
+            free_kb = free_kb,
+            heap_kb = heap_kb,
+            heap_total_kb = heap_stats.total_bytes / 1024,
+            zero = 0,
+        );
+
+        read_rendered(&contents, offset, buf)
+    }
+}
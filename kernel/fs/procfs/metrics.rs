@@ -2,6 +2,7 @@ use core::fmt;
 
 use kerla_runtime::page_allocator::read_allocator_stats;
 
+use super::read_rendered;
 use crate::{
     fs::{
         inode::{FileLike, INodeNo},
@@ -9,14 +10,23 @@ use crate::{
         stat::{FileMode, Stat, S_IFCHR},
     },
     net::read_tcp_stats,
+    prelude::*,
     process::read_process_stats,
-    result::Result,
+    syscalls::stats::{read_errno_stats, read_syscall_stats, LATENCY_BUCKETS},
     timer::read_monotonic_clock,
-    user_buffer::UserBufferMut,
-    user_buffer::{UserBufWriter, UserBuffer},
+    user_buffer::{UserBuffer, UserBufferMut},
 };
 
+fn metrics_stat() -> Result<Stat> {
+    Ok(Stat {
+        inode_no: INodeNo::new(2),
+        mode: FileMode::new(S_IFCHR | 0o666),
+        ..Stat::zeroed()
+    })
+}
+
 /// The `/proc/metrics` file. It returns the metrics of the kernel in Prometheus format.
+/// Each `open(2)` creates a `MetricsSnapshot`.
 pub(super) struct MetricsFile {}
 
 impl MetricsFile {
@@ -31,63 +41,165 @@ impl fmt::Debug for MetricsFile {
     }
 }
 
+fn write_syscall_metrics(w: &mut String) -> fmt::Result {
+    use core::fmt::Write;
+
+    let syscall_stats = read_syscall_stats();
+
+    writeln!(
+        w,
+        "# HELP: syscall_calls_total The total # of system calls."
+    )?;
+    writeln!(w, "# TYPE: syscall_calls_total counter")?;
+    for stats in &syscall_stats {
+        writeln!(
+            w,
+            "syscall_calls_total{{syscall=\"{}\"}} {}",
+            stats.name, stats.calls
+        )?;
+    }
+
+    writeln!(
+        w,
+        "# HELP: syscall_errors_total The total # of failed system calls."
+    )?;
+    writeln!(w, "# TYPE: syscall_errors_total counter")?;
+    for stats in read_errno_stats() {
+        writeln!(
+            w,
+            "syscall_errors_total{{nr=\"{}\",syscall=\"{}\",errno=\"{:?}\"}} {}",
+            stats.nr, stats.name, stats.errno, stats.count
+        )?;
+    }
+
+    writeln!(
+        w,
+        "# HELP: syscall_enosys_total The total # of ENOSYS returned from system calls."
+    )?;
+    writeln!(w, "# TYPE: syscall_enosys_total counter")?;
+    for stats in syscall_stats.iter().filter(|stats| stats.enosys > 0) {
+        writeln!(
+            w,
+            "syscall_enosys_total{{nr=\"{}\",syscall=\"{}\"}} {}",
+            stats.nr, stats.name, stats.enosys
+        )?;
+    }
+
+    writeln!(
+        w,
+        "# HELP: syscall_latency_cycles The latency of system calls in clock counter cycles."
+    )?;
+    writeln!(w, "# TYPE: syscall_latency_cycles histogram")?;
+    for stats in &syscall_stats {
+        for (le, count) in LATENCY_BUCKETS.iter().zip(stats.latency_buckets.iter()) {
+            writeln!(
+                w,
+                "syscall_latency_cycles_bucket{{syscall=\"{}\",le=\"{}\"}} {}",
+                stats.name, le, count
+            )?;
+        }
+        writeln!(
+            w,
+            "syscall_latency_cycles_bucket{{syscall=\"{}\",le=\"+Inf\"}} {}",
+            stats.name, stats.calls
+        )?;
+        writeln!(
+            w,
+            "syscall_latency_cycles_sum{{syscall=\"{}\"}} {}",
+            stats.name, stats.latency_sum
+        )?;
+        writeln!(
+            w,
+            "syscall_latency_cycles_count{{syscall=\"{}\"}} {}",
+            stats.name, stats.calls
+        )?;
+    }
+
+    Ok(())
+}
+
+fn render_metrics() -> String {
+    use core::fmt::Write;
+
+    let process_metrics = read_process_stats();
+    let allocator_metrics = read_allocator_stats();
+    let tcp_metrics = read_tcp_stats();
+
+    let mut metrics = String::new();
+    let _ = write!(
+        metrics,
+        concat!(
+            "# HELP: clock_monotonic The monotonic clock in milliseconds.\n",
+            "# TYPE: clock_monotonic_ms counter\n",
+            "clock_monotonic_ms {clock_monotonic_ms}\n",
+            "# HELP: process_fork_total The total # of process forks.\n",
+            "# TYPE: process_fork_total counter\n",
+            "process_fork_total {fork_total}\n",
+            "# HELP: memory_pages_total The total # of pages can be allocated.\n",
+            "# TYPE: memory_pages_total gauge\n",
+            "memory_pages_total {num_free_pages}\n",
+            "# HELP: memory_pages_free The total # of pages can be allocated.\n",
+            "# TYPE: memory_pages_free gauge\n",
+            "memory_pages_free {num_total_pages}\n",
+            "# HELP: passive_opens_total The total # of established passive TCP opens.\n",
+            "# TYPE: passive_opens_total counter\n",
+            "passive_opens_total {passive_opens_total}\n",
+            "# HELP: tcp_read_bytes_total The total bytes read from TCP socket buffers.\n",
+            "# TYPE: tcp_read_bytes_total counter\n",
+            "tcp_read_bytes_total {tcp_read_bytes_total}\n",
+            "# HELP: tcp_written_bytes_total The total bytes written into TCP socket buffers.\n",
+            "# TYPE: tcp_written_bytes_total counter\n",
+            "tcp_written_bytes_total {tcp_written_bytes_total}\n",
+        ),
+        clock_monotonic_ms = read_monotonic_clock().msecs(),
+        fork_total = process_metrics.fork_total,
+        num_free_pages = allocator_metrics.num_free_pages,
+        num_total_pages = allocator_metrics.num_total_pages,
+        passive_opens_total = tcp_metrics.passive_opens_total,
+        tcp_read_bytes_total = tcp_metrics.read_bytes_total,
+        tcp_written_bytes_total = tcp_metrics.written_bytes_total,
+    );
+    let _ = write_syscall_metrics(&mut metrics);
+    metrics
+}
+
 impl FileLike for MetricsFile {
+    fn open(&self, _options: &OpenOptions) -> Result<Option<Arc<dyn FileLike>>> {
+        Ok(Some(Arc::new(MetricsSnapshot {
+            metrics: render_metrics(),
+        }) as Arc<dyn FileLike>))
+    }
+
     fn stat(&self) -> Result<Stat> {
-        Ok(Stat {
-            inode_no: INodeNo::new(2),
-            mode: FileMode::new(S_IFCHR | 0o666),
-            ..Stat::zeroed()
-        })
+        metrics_stat()
     }
 
-    fn read(&self, offset: usize, buf: UserBufferMut<'_>, _options: &OpenOptions) -> Result<usize> {
-        use core::fmt::Write;
+    fn write(&self, _offset: usize, buf: UserBuffer<'_>, _options: &OpenOptions) -> Result<usize> {
+        Ok(buf.len())
+    }
+}
 
-        if offset > 0 {
-            // EOF. I guess there's a better way to do this.
-            return Ok(0);
-        }
+/// An opened `/proc/metrics`. The metrics are rendered once in `open(2)` so
+/// that reads at different offsets return parts of the same snapshot.
+struct MetricsSnapshot {
+    metrics: String,
+}
+
+impl fmt::Debug for MetricsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MetricsSnapshot")
+            .field("len", &self.metrics.len())
+            .finish()
+    }
+}
 
-        let process_metrics = read_process_stats();
-        let allocator_metrics = read_allocator_stats();
-        let tcp_metrics = read_tcp_stats();
-
-        let mut writer = UserBufWriter::from(buf);
-        let _ = write!(
-            writer,
-            concat!(
-                "# HELP: clock_monotonic The monotonic clock in milliseconds.\n",
-                "# TYPE: clock_monotonic_ms counter\n",
-                "clock_monotonic_ms {clock_monotonic_ms}\n",
-                "# HELP: process_fork_total The total # of process forks.\n",
-                "# TYPE: process_fork_total counter\n",
-                "process_fork_total {fork_total}\n",
-                "# HELP: memory_pages_total The total # of pages can be allocated.\n",
-                "# TYPE: memory_pages_total gauge\n",
-                "memory_pages_total {num_free_pages}\n",
-                "# HELP: memory_pages_free The total # of pages can be allocated.\n",
-                "# TYPE: memory_pages_free gauge\n",
-                "memory_pages_free {num_total_pages}\n",
-                "# HELP: passive_opens_total The total # of established passive TCP opens.\n",
-                "# TYPE: passive_opens_total counter\n",
-                "passive_opens_total {passive_opens_total}\n",
-                "# HELP: tcp_read_bytes_total The total bytes read from TCP socket buffers.\n",
-                "# TYPE: tcp_read_bytes_total counter\n",
-                "tcp_read_bytes_total {tcp_read_bytes_total}\n",
-                "# HELP: tcp_written_bytes_total The total bytes written into TCP socket buffers.\n",
-                "# TYPE: tcp_written_bytes_total counter\n",
-                "tcp_written_bytes_total {tcp_written_bytes_total}\n",
-            ),
-            clock_monotonic_ms = read_monotonic_clock().msecs(),
-            fork_total = process_metrics.fork_total,
-            num_free_pages = allocator_metrics.num_free_pages,
-            num_total_pages = allocator_metrics.num_total_pages,
-            passive_opens_total = tcp_metrics.passive_opens_total,
-            tcp_read_bytes_total = tcp_metrics.read_bytes_total,
-            tcp_written_bytes_total = tcp_metrics.written_bytes_total,
-        );
-
-        Ok(writer.written_len())
+impl FileLike for MetricsSnapshot {
+    fn stat(&self) -> Result<Stat> {
+        metrics_stat()
+    }
+
+    fn read(&self, offset: usize, buf: UserBufferMut<'_>, _options: &OpenOptions) -> Result<usize> {
+        read_rendered(&self.metrics, offset, buf)
     }
 
     fn write(&self, _offset: usize, buf: UserBuffer<'_>, _options: &OpenOptions) -> Result<usize> {
@@ -1,18 +1,39 @@
 use crate::{
     fs::{
-        file_system::FileSystem,
+        file_system::{register_file_system_type, FileSystem, FileSystemType, FsStat},
         inode::{Directory, FileLike},
     },
     result::Result,
+    user_buffer::{UserBufWriter, UserBufferMut},
 };
 use alloc::sync::Arc;
 use kerla_utils::once::Once;
 
-use self::metrics::MetricsFile;
+use self::{
+    core_pattern::CorePatternFile,
+    cpuinfo::CpuInfoFile,
+    filesystems::FileSystemsFile,
+    loadavg::LoadAvgFile,
+    meminfo::MemInfoFile,
+    metrics::MetricsFile,
+    mounts::{MountInfoFile, MountsFile},
+    stat::StatFile,
+    uptime::UptimeFile,
+};
 
 use super::tmpfs::TmpFs;
 
+mod core_pattern;
+mod cpuinfo;
+mod filesystems;
+mod loadavg;
+mod meminfo;
 mod metrics;
+mod mounts;
+mod stat;
+mod uptime;
+
+const PROC_SUPER_MAGIC: u64 = 0x9fa0;
 
 pub static PROC_FS: Once<Arc<ProcFs>> = Once::new();
 static METRICS_FILE: Once<Arc<dyn FileLike>> = Once::new();
@@ -27,17 +48,53 @@ impl ProcFs {
         METRICS_FILE.init(|| Arc::new(MetricsFile::new()) as Arc<dyn FileLike>);
 
         root_dir.add_file("metrics", METRICS_FILE.clone());
+        root_dir.add_file("meminfo", Arc::new(MemInfoFile::new()));
+        root_dir.add_file("cpuinfo", Arc::new(CpuInfoFile::new()));
+        root_dir.add_file("uptime", Arc::new(UptimeFile::new()));
+        root_dir.add_file("loadavg", Arc::new(LoadAvgFile::new()));
+        root_dir.add_file("mounts", Arc::new(MountsFile::new()));
+        root_dir.add_file("filesystems", Arc::new(FileSystemsFile::new()));
+        root_dir.add_file("stat", Arc::new(StatFile::new()));
+
+        let sys_kernel_dir = root_dir.add_dir("sys").add_dir("kernel");
+        sys_kernel_dir.add_file("core_pattern", Arc::new(CorePatternFile::new()));
+
+        // TODO: `self` should be a symlink to the current process's directory.
+        let self_dir = root_dir.add_dir("self");
+        self_dir.add_file("mountinfo", Arc::new(MountInfoFile::new()));
 
         ProcFs(tmpfs)
     }
 }
 
 impl FileSystem for ProcFs {
+    fn name(&self) -> &'static str {
+        "proc"
+    }
+
     fn root_dir(&self) -> Result<Arc<dyn Directory>> {
         self.0.root_dir()
     }
+
+    fn statfs(&self) -> Result<FsStat> {
+        Ok(FsStat::empty(PROC_SUPER_MAGIC))
+    }
+}
+
+/// Copies `contents` starting at `offset` into `buf`. Files are rendered as a
+/// whole on each read: a read at a non-zero offset continues a previous one.
+fn read_rendered(contents: &str, offset: usize, buf: UserBufferMut<'_>) -> Result<usize> {
+    match contents.as_bytes().get(offset..) {
+        Some(remaining) => UserBufWriter::from(buf).write_bytes(remaining),
+        None => Ok(0),
+    }
 }
 
 pub fn init() {
     PROC_FS.init(|| Arc::new(ProcFs::new()));
+    register_file_system_type(FileSystemType {
+        name: "proc",
+        requires_device: false,
+        mount: |_source, _data| Ok(PROC_FS.clone()),
+    });
 }
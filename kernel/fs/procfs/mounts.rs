@@ -0,0 +1,108 @@
+use core::fmt;
+
+use super::read_rendered;
+use crate::{
+    fs::{
+        inode::{FileLike, INodeNo},
+        opened_file::OpenOptions,
+        stat::{FileMode, Stat, S_IFREG},
+    },
+    prelude::*,
+    process::current_process,
+    user_buffer::UserBufferMut,
+};
+
+/// The `/proc/mounts` file.
+pub(super) struct MountsFile {}
+
+impl MountsFile {
+    pub fn new() -> MountsFile {
+        MountsFile {}
+    }
+}
+
+impl fmt::Debug for MountsFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mounts").finish()
+    }
+}
+
+impl FileLike for MountsFile {
+    fn stat(&self) -> Result<Stat> {
+        Ok(Stat {
+            inode_no: INodeNo::new(7),
+            mode: FileMode::new(S_IFREG | 0o444),
+            ..Stat::zeroed()
+        })
+    }
+
+    fn read(&self, offset: usize, buf: UserBufferMut<'_>, _options: &OpenOptions) -> Result<usize> {
+        use core::fmt::Write;
+
+        let root_fs = current_process().root_fs().lock();
+        let mut contents = String::new();
+        for mount_point in root_fs.mount_points() {
+            let _ = writeln!(
+                contents,
+                "{} {} {} {} 0 0",
+                mount_point.source(),
+                mount_point.path().as_str(),
+                mount_point.fs().name(),
+                mount_point.options()
+            );
+        }
+
+        read_rendered(&contents, offset, buf)
+    }
+}
+
+/// The `/proc/self/mountinfo` file.
+pub(super) struct MountInfoFile {}
+
+impl MountInfoFile {
+    pub fn new() -> MountInfoFile {
+        MountInfoFile {}
+    }
+}
+
+impl fmt::Debug for MountInfoFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MountInfo").finish()
+    }
+}
+
+impl FileLike for MountInfoFile {
+    fn stat(&self) -> Result<Stat> {
+        Ok(Stat {
+            inode_no: INodeNo::new(8),
+            mode: FileMode::new(S_IFREG | 0o444),
+            ..Stat::zeroed()
+        })
+    }
+
+    fn read(&self, offset: usize, buf: UserBufferMut<'_>, _options: &OpenOptions) -> Result<usize> {
+        use core::fmt::Write;
+
+        let root_fs = current_process().root_fs().lock();
+        let mut contents = String::new();
+        for mount_point in root_fs.mount_points() {
+            let options = mount_point.options();
+            let _ = writeln!(
+                contents,
+                "{} {} 0:{} {} {} {} - {} {} {}",
+                mount_point.id(),
+                mount_point.parent_id(),
+                mount_point.id(),
+                mount_point.root().as_str(),
+                mount_point.path().as_str(),
+                options,
+                mount_point.fs().name(),
+                mount_point.source(),
+                // Per-superblock options are not distinguished.
+                options
+            );
+        }
+
+        read_rendered(&contents, offset, buf)
+    }
+}
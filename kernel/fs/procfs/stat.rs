@@ -0,0 +1,79 @@
+use core::fmt;
+
+use kerla_runtime::arch::TICK_HZ;
+
+use super::read_rendered;
+use crate::{
+    fs::{
+        inode::{FileLike, INodeNo},
+        opened_file::OpenOptions,
+        stat::{FileMode, Stat, S_IFREG},
+    },
+    prelude::*,
+    process::{num_running_processes, read_process_stats},
+    timer::{read_cpu_ticks, read_monotonic_clock, read_wall_clock},
+    user_buffer::UserBufferMut,
+};
+
+/// The unit of time values in `/proc/stat` (`USER_HZ`).
+const USER_HZ: usize = 100;
+
+/// The `/proc/stat` file.
+pub(super) struct StatFile {}
+
+impl StatFile {
+    pub fn new() -> StatFile {
+        StatFile {}
+    }
+}
+
+impl fmt::Debug for StatFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Stat").finish()
+    }
+}
+
+impl FileLike for StatFile {
+    fn stat(&self) -> Result<Stat> {
+        Ok(Stat {
+            inode_no: INodeNo::new(9),
+            mode: FileMode::new(S_IFREG | 0o444),
+            ..Stat::zeroed()
+        })
+    }
+
+    fn read(&self, offset: usize, buf: UserBufferMut<'_>, _options: &OpenOptions) -> Result<usize> {
+        use core::fmt::Write;
+
+        // We don't support SMP yet: the system-wide values are same as cpu0's.
+        let cpu_ticks = read_cpu_ticks();
+        let user = cpu_ticks.user * USER_HZ / TICK_HZ;
+        let system = cpu_ticks.system * USER_HZ / TICK_HZ;
+        let idle = cpu_ticks.idle * USER_HZ / TICK_HZ;
+        let btime = read_wall_clock()
+            .secs_from_epoch()
+            .saturating_sub(read_monotonic_clock().secs());
+        let process_stats = read_process_stats();
+
+        let mut contents = String::new();
+        let _ = write!(
+            contents,
+            concat!(
+                "cpu  {user} 0 {system} {idle} 0 0 0 0 0 0\n",
+                "cpu0 {user} 0 {system} {idle} 0 0 0 0 0 0\n",
+                "btime {btime}\n",
+                "processes {processes}\n",
+                "procs_running {procs_running}\n",
+                "procs_blocked 0\n",
+            ),
+            user = user,
+            system = system,
+            idle = idle,
+            btime = btime,
+            processes = process_stats.fork_total,
+            procs_running = num_running_processes(),
+        );
+
+        read_rendered(&contents, offset, buf)
+    }
+}
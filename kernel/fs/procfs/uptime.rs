@@ -0,0 +1,59 @@
+use core::fmt;
+
+use kerla_runtime::arch::TICK_HZ;
+
+use super::read_rendered;
+use crate::{
+    fs::{
+        inode::{FileLike, INodeNo},
+        opened_file::OpenOptions,
+        stat::{FileMode, Stat, S_IFREG},
+    },
+    prelude::*,
+    timer::{read_cpu_ticks, read_monotonic_clock},
+    user_buffer::UserBufferMut,
+};
+
+/// The `/proc/uptime` file.
+pub(super) struct UptimeFile {}
+
+impl UptimeFile {
+    pub fn new() -> UptimeFile {
+        UptimeFile {}
+    }
+}
+
+impl fmt::Debug for UptimeFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Uptime").finish()
+    }
+}
+
+impl FileLike for UptimeFile {
+    fn stat(&self) -> Result<Stat> {
+        Ok(Stat {
+            inode_no: INodeNo::new(5),
+            mode: FileMode::new(S_IFREG | 0o444),
+            ..Stat::zeroed()
+        })
+    }
+
+    fn read(&self, offset: usize, buf: UserBufferMut<'_>, _options: &OpenOptions) -> Result<usize> {
+        use core::fmt::Write;
+
+        let uptime_ms = read_monotonic_clock().msecs();
+        let idle_ms = read_cpu_ticks().idle * 1000 / TICK_HZ;
+
+        let mut contents = String::new();
+        let _ = writeln!(
+            contents,
+            "{}.{:02} {}.{:02}",
+            uptime_ms / 1000,
+            (uptime_ms % 1000) / 10,
+            idle_ms / 1000,
+            (idle_ms % 1000) / 10,
+        );
+
+        read_rendered(&contents, offset, buf)
+    }
+}
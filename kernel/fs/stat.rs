@@ -1,14 +1,22 @@
 use crate::fs::inode::INodeNo;
+use crate::timer::read_wall_clock;
 
 /// The device file's ID.
 #[derive(Debug, Copy, Clone)]
 #[repr(transparent)]
 pub struct DevId(usize);
 
+impl DevId {
+    /// Encodes the major and minor numbers like `makedev(3)`.
+    pub fn new(major: usize, minor: usize) -> DevId {
+        DevId(((major & 0xfff) << 8) | (minor & 0xff) | ((minor & !0xff) << 12))
+    }
+}
+
 /// The number of hard links.
 #[derive(Debug, Copy, Clone)]
 #[repr(transparent)]
-pub struct NLink(usize);
+pub struct NLink(pub usize);
 
 /// The file size in bytes.
 #[derive(Debug, Copy, Clone)]
@@ -18,33 +26,58 @@ pub struct FileSize(pub isize);
 /// The user ID.
 #[derive(Debug, Copy, Clone)]
 #[repr(transparent)]
-pub struct UId(u32);
+pub struct UId(pub u32);
 
 /// The Group ID.
 #[derive(Debug, Copy, Clone)]
 #[repr(transparent)]
-pub struct GId(u32);
+pub struct GId(pub u32);
 
 /// The size in bytes of a block file file system I/O operations.
 #[derive(Debug, Copy, Clone)]
 #[repr(transparent)]
-pub struct BlockSize(isize);
+pub struct BlockSize(pub isize);
 
 /// The number of blocks.
 #[derive(Debug, Copy, Clone)]
 #[repr(transparent)]
-pub struct BlockCount(isize);
+pub struct BlockCount(pub isize);
+
+/// A timestamp (`struct timespec`) from the epoch. It can be negative.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub struct Time {
+    pub secs: isize,
+    pub nanosecs: isize,
+}
 
-/// The file size in bytes.
-#[derive(Debug, Copy, Clone)]
-#[repr(transparent)]
-pub struct Time(isize);
+impl Time {
+    pub const fn new(secs: isize, nanosecs: isize) -> Time {
+        Time { secs, nanosecs }
+    }
+
+    pub const fn from_secs(secs: isize) -> Time {
+        Time::new(secs, 0)
+    }
+
+    /// The current wall-clock time.
+    pub fn now() -> Time {
+        let nanosecs = read_wall_clock().nanosecs_from_epoch();
+        Time::new(
+            (nanosecs / 1_000_000_000) as isize,
+            (nanosecs % 1_000_000_000) as isize,
+        )
+    }
+}
 
 pub const S_IFMT: u32 = 0o170000;
+pub const S_IFIFO: u32 = 0o010000;
 pub const S_IFCHR: u32 = 0o020000;
 pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFBLK: u32 = 0o060000;
 pub const S_IFREG: u32 = 0o100000;
 pub const S_IFLNK: u32 = 0o120000;
+pub const S_IFSOCK: u32 = 0o140000;
 
 pub const O_ACCMODE: u32 = 0o3;
 
@@ -63,6 +96,10 @@ impl FileMode {
         FileMode(value)
     }
 
+    pub fn as_u32(self) -> u32 {
+        self.0
+    }
+
     pub fn access_mode(self) -> u32 {
         self.0 & O_ACCMODE
     }
@@ -97,6 +134,7 @@ pub struct Stat {
     pub atime: Time,
     pub mtime: Time,
     pub ctime: Time,
+    pub unused: [isize; 3],
 }
 
 impl Stat {
@@ -113,9 +151,10 @@ impl Stat {
             size: FileSize(0),
             blksize: BlockSize(0),
             blocks: BlockCount(0),
-            atime: Time(0),
-            mtime: Time(0),
-            ctime: Time(0),
+            atime: Time::from_secs(0),
+            mtime: Time::from_secs(0),
+            ctime: Time::from_secs(0),
+            unused: [0; 3],
         }
     }
 }
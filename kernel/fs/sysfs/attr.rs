@@ -0,0 +1,65 @@
+use alloc::boxed::Box;
+use core::fmt;
+
+use crate::{
+    fs::{
+        inode::{FileLike, INodeNo},
+        opened_file::OpenOptions,
+        stat::{FileMode, Stat, S_IFREG},
+        tmpfs::alloc_inode_no,
+    },
+    prelude::*,
+    user_buffer::{UserBufWriter, UserBufferMut},
+};
+
+/// A read-only sysfs attribute file. Its contents are generated by `show`
+/// every time it's read.
+pub(super) struct AttrFile {
+    inode_no: INodeNo,
+    show: Box<dyn Fn() -> String + Send + Sync>,
+}
+
+impl AttrFile {
+    pub fn new<F>(show: F) -> Arc<AttrFile>
+    where
+        F: Fn() -> String + Send + Sync + 'static,
+    {
+        Arc::new(AttrFile {
+            inode_no: alloc_inode_no(),
+            show: Box::new(show),
+        })
+    }
+
+    /// Creates an attribute file with the fixed contents.
+    pub fn new_static(value: String) -> Arc<AttrFile> {
+        AttrFile::new(move || value.clone())
+    }
+}
+
+impl fmt::Debug for AttrFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AttrFile")
+            .field("inode_no", &self.inode_no)
+            .finish()
+    }
+}
+
+impl FileLike for AttrFile {
+    fn stat(&self) -> Result<Stat> {
+        Ok(Stat {
+            inode_no: self.inode_no,
+            mode: FileMode::new(S_IFREG | 0o444),
+            ..Stat::zeroed()
+        })
+    }
+
+    fn read(&self, offset: usize, buf: UserBufferMut<'_>, _options: &OpenOptions) -> Result<usize> {
+        let contents = (self.show)();
+        if offset >= contents.len() {
+            return Ok(0);
+        }
+
+        let mut writer = UserBufWriter::from(buf);
+        writer.write_bytes(&contents.as_bytes()[offset..])
+    }
+}
@@ -0,0 +1,247 @@
+//! The sysfs: exports the kernel device model to the userspace.
+use crate::{
+    block::{block_devices, BlockDevice},
+    fs::{
+        file_system::{register_file_system_type, FileSystem, FileSystemType, FsStat},
+        inode::{Directory, FileLike},
+        tmpfs::{Dir, TmpFs},
+    },
+    net::{read_device_stats, use_ethernet_driver, DeviceStats},
+    prelude::*,
+};
+use kerla_api::driver::device::{devices, drivers, Bus, Device};
+use kerla_utils::once::Once;
+
+use self::attr::AttrFile;
+
+mod attr;
+
+const SYSFS_MAGIC: u64 = 0x62656572;
+
+pub static SYS_FS: Once<Arc<SysFs>> = Once::new();
+
+pub struct SysFs(TmpFs);
+
+impl SysFs {
+    pub fn new() -> SysFs {
+        let tmpfs = TmpFs::new();
+        let root_dir = tmpfs.root_tmpfs_dir();
+
+        let bus_dir = root_dir.add_dir("bus");
+        for bus in [Bus::Pci, Bus::Platform] {
+            let bus_dir = bus_dir.add_dir(bus.name());
+            let devices_dir = bus_dir.add_dir("devices");
+            for device in devices().iter().filter(|device| device.bus() == bus) {
+                add_device_dir(&devices_dir, device);
+            }
+
+            // Drivers probe devices on all buses.
+            let drivers_dir = bus_dir.add_dir("drivers");
+            for driver in drivers() {
+                add_driver_dir(&drivers_dir, driver, bus);
+            }
+        }
+
+        let class_dir = root_dir.add_dir("class");
+        let net_dir = class_dir.add_dir("net");
+        add_net_device_dir(&net_dir, "eth0");
+        let block_dir = class_dir.add_dir("block");
+        for device in block_devices() {
+            add_block_device_dir(&block_dir, &device);
+        }
+
+        SysFs(tmpfs)
+    }
+}
+
+impl FileSystem for SysFs {
+    fn name(&self) -> &'static str {
+        "sysfs"
+    }
+
+    fn root_dir(&self) -> Result<Arc<dyn Directory>> {
+        self.0.root_dir()
+    }
+
+    fn statfs(&self) -> Result<FsStat> {
+        Ok(FsStat::empty(SYSFS_MAGIC))
+    }
+}
+
+fn add_attr_file(dir: &Dir, name: &str, file: Arc<AttrFile>) {
+    dir.add_file(name, file as Arc<dyn FileLike>);
+}
+
+/// Adds `/sys/bus/<bus>/drivers/<driver>`.
+fn add_driver_dir(drivers_dir: &Dir, driver: &'static str, bus: Bus) {
+    let driver_dir = drivers_dir.add_dir(driver);
+    add_attr_file(
+        &driver_dir,
+        "devices",
+        AttrFile::new(move || {
+            let mut names = String::new();
+            for device in devices() {
+                if device.bus() == bus && device.driver() == Some(driver) {
+                    names += device.name();
+                    names += "\n";
+                }
+            }
+            names
+        }),
+    );
+}
+
+/// Adds `/sys/bus/<bus>/devices/<device>`.
+fn add_device_dir(devices_dir: &Dir, device: &Arc<Device>) {
+    let device_dir = devices_dir.add_dir(device.name());
+    for (name, value) in device.attrs() {
+        let value = if value.ends_with('\n') {
+            value.clone()
+        } else {
+            format!("{}\n", value)
+        };
+
+        add_attr_file(&device_dir, name, AttrFile::new_static(value));
+    }
+
+    let dev = device.clone();
+    add_attr_file(
+        &device_dir,
+        "driver",
+        AttrFile::new(move || match dev.driver() {
+            Some(driver) => format!("{}\n", driver),
+            None => String::new(),
+        }),
+    );
+
+    let dev = device.clone();
+    add_attr_file(&device_dir, "uevent", AttrFile::new(move || uevent(&dev)));
+}
+
+/// Generates the contents of the `uevent` file of a device.
+fn uevent(device: &Device) -> String {
+    let mut uevent = String::new();
+    if let Some(driver) = device.driver() {
+        uevent += &format!("DRIVER={}\n", driver);
+    }
+
+    if device.bus() == Bus::Pci {
+        let hex = |name| {
+            device
+                .attr(name)
+                .and_then(|value| value.strip_prefix("0x"))
+                .unwrap_or("0")
+                .to_uppercase()
+        };
+
+        uevent += &format!(
+            "PCI_CLASS={}\nPCI_ID={}:{}\nPCI_SUBSYS_ID={}:{}\nPCI_SLOT_NAME={}\n",
+            hex("class"),
+            hex("vendor"),
+            hex("device"),
+            hex("subsystem_vendor"),
+            hex("subsystem_device"),
+            device.name()
+        );
+    }
+
+    uevent
+}
+
+/// Adds `/sys/class/net/<name>`.
+fn add_net_device_dir(net_dir: &Dir, name: &str) {
+    let mac = use_ethernet_driver(|driver| driver.mac_addr()).as_array();
+    let iface_dir = net_dir.add_dir(name);
+    add_attr_file(
+        &iface_dir,
+        "address",
+        AttrFile::new_static(format!(
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}\n",
+            mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]
+        )),
+    );
+    add_attr_file(&iface_dir, "mtu", AttrFile::new_static("1500\n".to_owned()));
+    add_attr_file(&iface_dir, "type", AttrFile::new_static("1\n".to_owned()));
+    add_attr_file(
+        &iface_dir,
+        "operstate",
+        AttrFile::new_static("up\n".to_owned()),
+    );
+
+    let stats_dir = iface_dir.add_dir("statistics");
+    type StatReader = fn(&DeviceStats) -> usize;
+    let stats: [(&str, StatReader); 5] = [
+        ("rx_packets", |stats| stats.rx_packets),
+        ("rx_bytes", |stats| stats.rx_bytes),
+        ("rx_dropped", |stats| stats.rx_dropped),
+        ("tx_packets", |stats| stats.tx_packets),
+        ("tx_bytes", |stats| stats.tx_bytes),
+    ];
+    for (name, read) in stats {
+        add_attr_file(
+            &stats_dir,
+            name,
+            AttrFile::new(move || format!("{}\n", read(&read_device_stats()))),
+        );
+    }
+}
+
+/// Adds `/sys/class/block/<name>`.
+fn add_block_device_dir(block_dir: &Dir, device: &BlockDevice) {
+    let device_dir = block_dir.add_dir(device.name());
+    add_attr_file(
+        &device_dir,
+        "size",
+        AttrFile::new_static(format!("{}\n", device.num_sectors())),
+    );
+    add_attr_file(
+        &device_dir,
+        "ro",
+        AttrFile::new_static(format!("{}\n", device.is_read_only() as u8)),
+    );
+    add_attr_file(
+        &device_dir,
+        "dev",
+        AttrFile::new_static(format!("{}:{}\n", device.major(), device.minor())),
+    );
+
+    let mut uevent = format!(
+        "MAJOR={}\nMINOR={}\nDEVNAME={}\n",
+        device.major(),
+        device.minor(),
+        device.name()
+    );
+    match device.partition_number() {
+        Some(number) => {
+            add_attr_file(
+                &device_dir,
+                "partition",
+                AttrFile::new_static(format!("{}\n", number)),
+            );
+            add_attr_file(
+                &device_dir,
+                "start",
+                AttrFile::new_static(format!("{}\n", device.start_sector())),
+            );
+
+            uevent += &format!("DEVTYPE=partition\nPARTN={}\n", number);
+            if let Some(label) = device.partlabel() {
+                uevent += &format!("PARTNAME={}\n", label);
+            }
+        }
+        None => {
+            uevent += "DEVTYPE=disk\n";
+        }
+    }
+
+    add_attr_file(&device_dir, "uevent", AttrFile::new_static(uevent));
+}
+
+pub fn init() {
+    SYS_FS.init(|| Arc::new(SysFs::new()));
+    register_file_system_type(FileSystemType {
+        name: "sysfs",
+        requires_device: false,
+        mount: |_source, _data| Ok(SYS_FS.clone()),
+    });
+}
@@ -1,17 +1,23 @@
 use crate::{
+    pipe::PipeBuffer,
     prelude::*,
     user_buffer::{UserBufReader, UserBufWriter},
 };
+use alloc::collections::{btree_map::Entry, BTreeMap};
 use core::{
+    cmp::{max, min},
     fmt,
     sync::atomic::{AtomicUsize, Ordering},
 };
 
 use super::{
-    file_system::FileSystem,
-    inode::{DirEntry, Directory, FileLike, FileType, INode, INodeNo},
+    file_system::{register_file_system_type, FileSystem, FileSystemType, FsStat},
+    inode::{
+        DirEntry, Directory, FileLike, FileType, INode, INodeNo, RenameFlags, SetAttr, Symlink,
+    },
     opened_file::OpenOptions,
-    stat::{FileMode, Stat, S_IFDIR, S_IFREG},
+    path::PathBuf,
+    stat::{BlockCount, FileMode, FileSize, NLink, Stat, Time, S_IFDIR, S_IFLNK, S_IFREG},
 };
 use crate::{
     result::{Errno, Error, Result},
@@ -19,67 +25,319 @@ use crate::{
     user_buffer::UserBufferMut,
 };
 use hashbrown::HashMap;
-use kerla_runtime::spinlock::SpinLock;
-use kerla_utils::{downcast::downcast, once::Once};
+use kerla_runtime::{arch::PAGE_SIZE, page_allocator::read_allocator_stats, spinlock::SpinLock};
+use kerla_utils::{
+    alignment::align_up,
+    downcast::{downcast, downcast_arc},
+    once::Once,
+};
 
 pub static TMP_FS: Once<Arc<TmpFs>> = Once::new();
 
-fn alloc_inode_no() -> INodeNo {
+const TMPFS_MAGIC: u64 = 0x01021994;
+
+pub(super) fn alloc_inode_no() -> INodeNo {
     // Inode #1 is reserved for the root dir.
     static NEXT_INODE_NO: AtomicUsize = AtomicUsize::new(2);
 
     INodeNo::new(NEXT_INODE_NO.fetch_add(1, Ordering::SeqCst))
 }
 
+/// The attributes of a new inode. `mode` includes the file type.
+fn new_stat(inode_no: INodeNo, mode: u32) -> SpinLock<Stat> {
+    let now = Time::now();
+    SpinLock::new(Stat {
+        inode_no,
+        mode: FileMode::new(mode),
+        atime: now,
+        mtime: now,
+        ctime: now,
+        ..Stat::zeroed()
+    })
+}
+
+/// The size and inode limits of a tmpfs instance (`size=` and `nr_inodes=`).
+/// Shared by all inodes in the file system: they release what they use when
+/// dropped.
+struct Capacity {
+    /// The maximum number of pages. `None` means unlimited.
+    max_pages: Option<usize>,
+    /// The maximum number of inodes. `None` means unlimited.
+    max_inodes: Option<usize>,
+    used_pages: AtomicUsize,
+    used_inodes: AtomicUsize,
+}
+
+impl Capacity {
+    fn new(max_pages: Option<usize>, max_inodes: Option<usize>) -> Capacity {
+        Capacity {
+            max_pages,
+            max_inodes,
+            used_pages: AtomicUsize::new(0),
+            used_inodes: AtomicUsize::new(0),
+        }
+    }
+
+    /// Adds `n` to `used` or returns `ENOSPC` if it exceeds `max`.
+    fn reserve(used: &AtomicUsize, max: Option<usize>, n: usize) -> Result<()> {
+        used.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| match max {
+            Some(max) if used + n > max => None,
+            _ => Some(used + n),
+        })
+        .map(|_| ())
+        .map_err(|_| Error::new(Errno::ENOSPC))
+    }
+
+    /// Reserves an inode. The caller must pass the capacity to the inode
+    /// object, which releases it when dropped.
+    fn alloc_inode(&self) -> Result<()> {
+        Capacity::reserve(&self.used_inodes, self.max_inodes, 1)
+    }
+
+    /// Reserves an inode ignoring the limit. Used for directories created by
+    /// the kernel (e.g. in procfs).
+    fn force_alloc_inode(&self) {
+        self.used_inodes.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn free_inode(&self) {
+        self.used_inodes.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    fn alloc_page(&self) -> Result<()> {
+        Capacity::reserve(&self.used_pages, self.max_pages, 1)
+    }
+
+    fn free_pages(&self, num_pages: usize) {
+        self.used_pages.fetch_sub(num_pages, Ordering::SeqCst);
+    }
+}
+
 pub struct TmpFs {
     root_dir: Arc<Dir>,
+    capacity: Arc<Capacity>,
 }
 
 impl TmpFs {
+    /// Creates a tmpfs without size and inode limits. Used by the kernel
+    /// itself (e.g. procfs).
     pub fn new() -> TmpFs {
+        TmpFs::with_limits(None, None)
+    }
+
+    fn with_limits(max_pages: Option<usize>, max_inodes: Option<usize>) -> TmpFs {
+        let capacity = Arc::new(Capacity::new(max_pages, max_inodes));
+        capacity.force_alloc_inode();
         TmpFs {
-            root_dir: Arc::new(Dir::new(INodeNo::new(1))),
+            root_dir: Arc::new(Dir::new(INodeNo::new(1), 0o755, capacity.clone())),
+            capacity,
         }
     }
 
+    /// Creates a tmpfs from the mount options (e.g. `size=16m,nr_inodes=1k`).
+    /// Like Linux, both limits default to the half of the RAM pages and `0`
+    /// means unlimited.
+    pub fn from_options(data: &str) -> Result<TmpFs> {
+        let total_pages = read_allocator_stats().num_total_pages;
+        let mut max_pages = Some(total_pages / 2);
+        let mut max_inodes = Some(total_pages / 2);
+        for option in data.split(',') {
+            match option.split_once('=') {
+                Some(("size", size)) => {
+                    let pages = match size.strip_suffix('%') {
+                        Some(percent) => percent
+                            .parse::<usize>()
+                            .ok()
+                            .and_then(|percent| total_pages.checked_mul(percent))
+                            .map(|pages| pages / 100),
+                        // Round up to the page size.
+                        None => parse_size(size)?
+                            .checked_add(PAGE_SIZE - 1)
+                            .map(|size| size / PAGE_SIZE),
+                    };
+
+                    max_pages = Some(pages.ok_or_else(|| Error::new(Errno::EINVAL))?);
+                }
+                Some(("nr_inodes", num)) => max_inodes = Some(parse_size(num)?),
+                // The root directory's permissions and owner are not
+                // configurable.
+                Some(("mode" | "uid" | "gid", _)) => {}
+                None if option.is_empty() => {}
+                _ => return Err(Errno::EINVAL.into()),
+            }
+        }
+
+        Ok(TmpFs::with_limits(
+            max_pages.filter(|max| *max > 0),
+            max_inodes.filter(|max| *max > 0),
+        ))
+    }
+
     pub fn root_tmpfs_dir(&self) -> &Arc<Dir> {
         &self.root_dir
     }
 }
 
 impl FileSystem for TmpFs {
+    fn name(&self) -> &'static str {
+        "tmpfs"
+    }
+
     fn root_dir(&self) -> Result<Arc<dyn Directory>> {
         Ok(self.root_dir.clone())
     }
+
+    fn statfs(&self) -> Result<FsStat> {
+        let capacity = &self.capacity;
+        // Unlimited ones are reported as zero as Linux does.
+        let free_pages = capacity.max_pages.map_or(0, |max| {
+            max.saturating_sub(capacity.used_pages.load(Ordering::SeqCst))
+        });
+        let free_inodes = capacity.max_inodes.map_or(0, |max| {
+            max.saturating_sub(capacity.used_inodes.load(Ordering::SeqCst))
+        });
+
+        Ok(FsStat {
+            total_blocks: capacity.max_pages.unwrap_or(0) as u64,
+            free_blocks: free_pages as u64,
+            available_blocks: free_pages as u64,
+            total_inodes: capacity.max_inodes.unwrap_or(0) as u64,
+            free_inodes: free_inodes as u64,
+            ..FsStat::empty(TMPFS_MAGIC)
+        })
+    }
 }
 
+/// Parses a number with an optional `k`, `m`, or `g` suffix.
+fn parse_size(s: &str) -> Result<usize> {
+    let (num, unit) = match s.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&s[..i], 1024),
+        Some((i, 'm' | 'M')) => (&s[..i], 1024 * 1024),
+        Some((i, 'g' | 'G')) => (&s[..i], 1024 * 1024 * 1024),
+        _ => (s, 1),
+    };
+
+    num.parse::<usize>()
+        .ok()
+        .and_then(|num| num.checked_mul(unit))
+        .ok_or_else(|| Error::new(Errno::EINVAL))
+}
+
+#[derive(Clone)]
 enum TmpFsINode {
     File(Arc<dyn FileLike>),
     Directory(Arc<Dir>),
+    Symlink(Arc<SymbolicLink>),
 }
 
+impl TmpFsINode {
+    fn to_inode(&self) -> INode {
+        match self {
+            TmpFsINode::File(file) => file.clone().into(),
+            TmpFsINode::Directory(dir) => (dir.clone() as Arc<dyn Directory>).into(),
+            TmpFsINode::Symlink(symlink) => (symlink.clone() as Arc<dyn Symlink>).into(),
+        }
+    }
+
+    fn is_dir(&self) -> bool {
+        matches!(self, TmpFsINode::Directory(_))
+    }
+
+    /// Returns `true` if both entries point to the same inode (i.e. hard links).
+    fn ptr_eq(&self, other: &TmpFsINode) -> bool {
+        match (self, other) {
+            (TmpFsINode::File(a), TmpFsINode::File(b)) => Arc::ptr_eq(a, b),
+            (TmpFsINode::Directory(a), TmpFsINode::Directory(b)) => Arc::ptr_eq(a, b),
+            (TmpFsINode::Symlink(a), TmpFsINode::Symlink(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// The link counter of the inode. Files not owned by tmpfs (e.g. device
+    /// files in devfs) and directories don't have one.
+    fn nlink(&self) -> Option<&AtomicUsize> {
+        match self {
+            TmpFsINode::File(file) => downcast::<_, File>(file).map(|file| &file.nlink),
+            TmpFsINode::Symlink(symlink) => Some(&symlink.nlink),
+            TmpFsINode::Directory(_) => None,
+        }
+    }
+}
+
+/// Serializes `rename(2)` and `rmdir(2)`: operations which need to look into
+/// more than one directory.
+static TREE_LOCK: SpinLock<()> = SpinLock::new(());
+
 struct DirInner {
     files: HashMap<String, TmpFsINode>,
-    stat: Stat,
+    /// Set when the directory is removed. No new entries can be added to it
+    /// anymore.
+    removed: bool,
 }
 
-pub struct Dir(SpinLock<DirInner>);
+impl DirInner {
+    fn insert(&mut self, name: &str, entry: TmpFsINode) -> Result<()> {
+        if self.removed {
+            return Err(Errno::ENOENT.into());
+        }
+
+        if let Some(nlink) = entry.nlink() {
+            nlink.fetch_add(1, Ordering::SeqCst);
+        }
+
+        if let Some(old) = self.files.insert(name.to_owned(), entry) {
+            if let Some(nlink) = old.nlink() {
+                nlink.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes an entry. The inode (and its data) is freed once no one
+    /// references it, i.e. when the last link is removed and the last opened
+    /// file is closed.
+    fn remove(&mut self, name: &str) -> Option<TmpFsINode> {
+        let entry = self.files.remove(name)?;
+        if let Some(nlink) = entry.nlink() {
+            nlink.fetch_sub(1, Ordering::SeqCst);
+        }
+
+        Some(entry)
+    }
+
+    fn create(&mut self, name: &str, entry: TmpFsINode) -> Result<()> {
+        if self.files.contains_key(name) {
+            return Err(Errno::EEXIST.into());
+        }
+
+        self.insert(name, entry)
+    }
+}
+
+pub struct Dir {
+    stat: SpinLock<Stat>,
+    inner: SpinLock<DirInner>,
+    capacity: Arc<Capacity>,
+}
 
 impl Dir {
-    pub fn new(inode_no: INodeNo) -> Dir {
-        Dir(SpinLock::new(DirInner {
-            files: HashMap::new(),
-            stat: Stat {
-                inode_no,
-                mode: FileMode::new(S_IFDIR | 0o755),
-                ..Stat::zeroed()
-            },
-        }))
+    /// Creates a directory. The inode must be reserved in `capacity`.
+    fn new(inode_no: INodeNo, perm: u32, capacity: Arc<Capacity>) -> Dir {
+        Dir {
+            stat: new_stat(inode_no, S_IFDIR | (perm & 0o7777)),
+            inner: SpinLock::new(DirInner {
+                files: HashMap::new(),
+                removed: false,
+            }),
+            capacity,
+        }
     }
 
     pub fn add_dir(&self, name: &str) -> Arc<Dir> {
-        let dir = Arc::new(Dir::new(alloc_inode_no()));
-        self.0
+        self.capacity.force_alloc_inode();
+        let dir = Arc::new(Dir::new(alloc_inode_no(), 0o755, self.capacity.clone()));
+        self.inner
             .lock()
             .files
             .insert(name.to_owned(), TmpFsINode::Directory(dir.clone()));
@@ -87,28 +345,95 @@ impl Dir {
     }
 
     pub fn add_file(&self, name: &str, file: Arc<dyn FileLike>) {
-        self.0
+        self.inner
             .lock()
             .files
             .insert(name.to_owned(), TmpFsINode::File(file));
     }
-}
 
-impl Directory for Dir {
-    fn lookup(&self, name: &str) -> Result<INode> {
-        self.0
+    fn get(&self, name: &str) -> Result<TmpFsINode> {
+        self.inner
             .lock()
             .files
             .get(name)
-            .map(|tmpfs_inode| match tmpfs_inode {
-                TmpFsINode::File(file) => file.clone().into(),
-                TmpFsINode::Directory(dir) => (dir.clone() as Arc<dyn Directory>).into(),
-            })
+            .cloned()
             .ok_or_else(|| Error::new(Errno::ENOENT))
     }
 
+    /// Returns `true` if `dir` is this directory or one of its descendants.
+    fn contains_dir(&self, dir: &Dir) -> bool {
+        if core::ptr::eq(self, dir) {
+            return true;
+        }
+
+        let subdirs: Vec<Arc<Dir>> = self
+            .inner
+            .lock()
+            .files
+            .values()
+            .filter_map(|entry| match entry {
+                TmpFsINode::Directory(subdir) => Some(subdir.clone()),
+                _ => None,
+            })
+            .collect();
+
+        subdirs.iter().any(|subdir| subdir.contains_dir(dir))
+    }
+
+    /// Marks the directory as removed if it's empty. The caller must hold
+    /// `TREE_LOCK`.
+    fn mark_removed(&self) -> Result<()> {
+        let mut inner = self.inner.lock();
+        if !inner.files.is_empty() {
+            return Err(Errno::ENOTEMPTY.into());
+        }
+
+        inner.removed = true;
+        Ok(())
+    }
+
+    fn exchange(&self, old_name: &str, new_dir: &Dir, new_name: &str) -> Result<()> {
+        let old = self.get(old_name)?;
+        let new = new_dir.get(new_name)?;
+
+        // "EINVAL - The new pathname contained a path prefix of the old, or,
+        // more generally, an attempt was made to make a directory a
+        // subdirectory of itself." -- rename(2)
+        if let TmpFsINode::Directory(dir) = &old {
+            if dir.contains_dir(new_dir) {
+                return Err(Errno::EINVAL.into());
+            }
+        }
+        if let TmpFsINode::Directory(dir) = &new {
+            if dir.contains_dir(self) {
+                return Err(Errno::EINVAL.into());
+            }
+        }
+
+        if core::ptr::eq(self, new_dir) {
+            let mut inner = self.inner.lock();
+            inner.insert(old_name, new)?;
+            inner.insert(new_name, old)?;
+        } else {
+            // No one else locks two directories at once since we hold
+            // `TREE_LOCK`.
+            let mut old_inner = self.inner.lock();
+            let mut new_inner = new_dir.inner.lock();
+            old_inner.insert(old_name, new)?;
+            new_inner.insert(new_name, old)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Directory for Dir {
+    fn lookup(&self, name: &str) -> Result<INode> {
+        self.get(name).map(|entry| entry.to_inode())
+    }
+
     fn readdir(&self, index: usize) -> Result<Option<DirEntry>> {
-        let dir_lock = self.0.lock();
+        let dir_lock = self.inner.lock();
         let (name, inode) = match dir_lock.files.iter().nth(index) {
             Some(entry) => entry,
             None => {
@@ -117,65 +442,194 @@ impl Directory for Dir {
         };
 
         let entry = match inode {
-            TmpFsINode::Directory(dir) => {
-                let dir = dir.0.lock();
-                DirEntry {
-                    inode_no: dir.stat.inode_no,
-                    file_type: FileType::Directory,
-                    name: name.clone(),
-                }
-            }
+            TmpFsINode::Directory(dir) => DirEntry {
+                inode_no: dir.stat.lock().inode_no,
+                file_type: FileType::Directory,
+                name: name.clone(),
+            },
             TmpFsINode::File(file) => DirEntry {
                 inode_no: file.stat()?.inode_no,
                 file_type: FileType::Regular,
                 name: name.clone(),
             },
+            TmpFsINode::Symlink(symlink) => DirEntry {
+                inode_no: symlink.stat.lock().inode_no,
+                file_type: FileType::Link,
+                name: name.clone(),
+            },
         };
 
         Ok(Some(entry))
     }
 
     fn stat(&self) -> Result<Stat> {
-        Ok(self.0.lock().stat)
+        let inner = self.inner.lock();
+        let num_subdirs = inner.files.values().filter(|entry| entry.is_dir()).count();
+        let nlink = if inner.removed { 0 } else { 2 + num_subdirs };
+        Ok(Stat {
+            nlink: NLink(nlink),
+            ..*self.stat.lock()
+        })
+    }
+
+    fn set_attr(&self, attr: &SetAttr) -> Result<()> {
+        attr.apply(&mut self.stat.lock());
+        Ok(())
     }
 
     fn link(&self, name: &str, link_to: &INode) -> Result<()> {
         let tmpfs_inode = match link_to {
             INode::FileLike(file_like) => TmpFsINode::File(file_like.clone()),
-            INode::Directory(dir) => {
-                let dir: &Arc<Dir> = downcast(dir).unwrap();
-                TmpFsINode::Directory(dir.clone())
+            // "EPERM - oldpath is a directory." -- link(2)
+            INode::Directory(_) => return Err(Errno::EPERM.into()),
+            INode::Symlink(symlink) => {
+                let symlink = downcast_arc(symlink).ok_or_else(|| Error::new(Errno::EXDEV))?;
+                TmpFsINode::Symlink(symlink)
             }
-            INode::Symlink(_) => unreachable!(), /* symblic links are not supported yet */
         };
 
-        self.0.lock().files.insert(name.to_owned(), tmpfs_inode);
-        Ok(())
+        self.inner.lock().create(name, tmpfs_inode)
     }
 
-    fn create_file(&self, name: &str, _mode: FileMode) -> Result<INode> {
-        let mut dir_lock = self.0.lock();
-        if dir_lock.files.contains_key(name) {
-            return Err(Errno::EEXIST.into());
-        }
-
-        let inode = Arc::new(File::new(alloc_inode_no()));
-        dir_lock
-            .files
-            .insert(name.to_owned(), TmpFsINode::File(inode.clone()));
+    fn create_file(&self, name: &str, mode: FileMode) -> Result<INode> {
+        self.capacity.alloc_inode()?;
+        let inode = Arc::new(File::new(
+            alloc_inode_no(),
+            mode.as_u32(),
+            self.capacity.clone(),
+        ));
+        self.inner
+            .lock()
+            .create(name, TmpFsINode::File(inode.clone()))?;
 
         Ok((inode as Arc<dyn FileLike>).into())
     }
 
-    fn create_dir(&self, name: &str, _mode: FileMode) -> Result<INode> {
-        let inode = Arc::new(Dir::new(alloc_inode_no()));
-        self.0
+    fn create_dir(&self, name: &str, mode: FileMode) -> Result<INode> {
+        self.capacity.alloc_inode()?;
+        let inode = Arc::new(Dir::new(
+            alloc_inode_no(),
+            mode.as_u32(),
+            self.capacity.clone(),
+        ));
+        self.inner
             .lock()
-            .files
-            .insert(name.to_owned(), TmpFsINode::Directory(inode.clone()));
+            .create(name, TmpFsINode::Directory(inode.clone()))?;
 
         Ok((inode as Arc<dyn Directory>).into())
     }
+
+    fn create_symlink(&self, name: &str, target: &str) -> Result<INode> {
+        self.capacity.alloc_inode()?;
+        let inode = Arc::new(SymbolicLink::new(
+            alloc_inode_no(),
+            target,
+            self.capacity.clone(),
+        ));
+        self.inner
+            .lock()
+            .create(name, TmpFsINode::Symlink(inode.clone()))?;
+
+        Ok((inode as Arc<dyn Symlink>).into())
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        let mut dir_lock = self.inner.lock();
+        match dir_lock.files.get(name) {
+            Some(TmpFsINode::Directory(_)) => return Err(Errno::EISDIR.into()),
+            Some(_) => {}
+            None => return Err(Errno::ENOENT.into()),
+        }
+
+        dir_lock.remove(name);
+        Ok(())
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        let _tree_lock = TREE_LOCK.lock();
+        match self.get(name)? {
+            TmpFsINode::Directory(dir) => dir.mark_removed()?,
+            _ => return Err(Errno::ENOTDIR.into()),
+        }
+
+        self.inner.lock().remove(name);
+        Ok(())
+    }
+
+    fn rename(
+        &self,
+        old_name: &str,
+        new_dir: &Arc<dyn Directory>,
+        new_name: &str,
+        flags: RenameFlags,
+    ) -> Result<()> {
+        let new_dir: &Dir = downcast(new_dir).ok_or_else(|| Error::new(Errno::EXDEV))?;
+        if flags.contains(RenameFlags::RENAME_WHITEOUT) {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let _tree_lock = TREE_LOCK.lock();
+        if new_dir.inner.lock().removed {
+            return Err(Errno::ENOENT.into());
+        }
+
+        if flags.contains(RenameFlags::RENAME_EXCHANGE) {
+            return self.exchange(old_name, new_dir, new_name);
+        }
+
+        let old = self.get(old_name)?;
+        let new = new_dir.get(new_name).ok();
+        if let Some(new) = &new {
+            if flags.contains(RenameFlags::RENAME_NOREPLACE) {
+                return Err(Errno::EEXIST.into());
+            }
+
+            // "If oldpath and newpath are existing hard links referring to the
+            // same file, then rename() does nothing, and returns a success
+            // status." -- rename(2)
+            if old.ptr_eq(new) {
+                return Ok(());
+            }
+
+            match (old.is_dir(), new.is_dir()) {
+                (true, false) => return Err(Errno::ENOTDIR.into()),
+                (false, true) => return Err(Errno::EISDIR.into()),
+                _ => {}
+            }
+        }
+
+        if let TmpFsINode::Directory(dir) = &old {
+            if dir.contains_dir(new_dir) {
+                return Err(Errno::EINVAL.into());
+            }
+        }
+
+        // Everything looks good. Replace the destination directory (if any).
+        if let Some(TmpFsINode::Directory(dir)) = &new {
+            dir.mark_removed()?;
+        }
+
+        if core::ptr::eq(self, new_dir) {
+            let mut inner = self.inner.lock();
+            let entry = inner.remove(old_name).unwrap();
+            inner.insert(new_name, entry)?;
+        } else {
+            // No one else locks two directories at once since we hold
+            // `TREE_LOCK`.
+            let mut old_inner = self.inner.lock();
+            let mut new_inner = new_dir.inner.lock();
+            let entry = old_inner.remove(old_name).unwrap();
+            new_inner.insert(new_name, entry)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Dir {
+    fn drop(&mut self) {
+        self.capacity.free_inode();
+    }
 }
 
 impl fmt::Debug for Dir {
@@ -184,44 +638,201 @@ impl fmt::Debug for Dir {
     }
 }
 
+/// The contents of a file. Pages which have never been written are holes:
+/// they're not allocated and read as zeroes.
+struct FileData {
+    /// Allocated pages (each of `PAGE_SIZE` bytes) keyed by the page index.
+    /// They're shared with pipes by `splice(2)` and copied on write.
+    pages: BTreeMap<usize, Arc<Vec<u8>>>,
+    size: usize,
+}
+
 struct File {
-    data: SpinLock<Vec<u8>>,
-    stat: Stat,
+    data: SpinLock<FileData>,
+    stat: SpinLock<Stat>,
+    nlink: AtomicUsize,
+    capacity: Arc<Capacity>,
 }
 
 impl File {
-    pub fn new(inode_no: INodeNo) -> File {
+    /// Creates a file. The inode must be reserved in `capacity`.
+    fn new(inode_no: INodeNo, perm: u32, capacity: Arc<Capacity>) -> File {
         File {
-            data: SpinLock::new(Vec::new()),
-            stat: Stat {
-                inode_no,
-                mode: FileMode::new(S_IFREG | 0o644),
-                ..Stat::zeroed()
-            },
+            data: SpinLock::new(FileData {
+                pages: BTreeMap::new(),
+                size: 0,
+            }),
+            stat: new_stat(inode_no, S_IFREG | (perm & 0o7777)),
+            nlink: AtomicUsize::new(0),
+            capacity,
         }
     }
+
+    /// Updates the modification and the status change time.
+    fn touch(&self) {
+        let now = Time::now();
+        let mut stat = self.stat.lock();
+        stat.mtime = now;
+        stat.ctime = now;
+    }
 }
 
 impl FileLike for File {
     fn stat(&self) -> Result<Stat> {
-        Ok(self.stat)
+        let data = self.data.lock();
+        Ok(Stat {
+            nlink: NLink(self.nlink.load(Ordering::SeqCst)),
+            size: FileSize(data.size as isize),
+            blocks: BlockCount((data.pages.len() * PAGE_SIZE / 512) as isize),
+            ..*self.stat.lock()
+        })
+    }
+
+    fn set_attr(&self, attr: &SetAttr) -> Result<()> {
+        attr.apply(&mut self.stat.lock());
+        Ok(())
     }
 
     fn read(&self, offset: usize, buf: UserBufferMut<'_>, _options: &OpenOptions) -> Result<usize> {
         let data = self.data.lock();
-        if offset > data.len() {
+        if offset >= data.size {
             return Ok(0);
         }
 
         let mut writer = UserBufWriter::from(buf);
-        writer.write_bytes(&data[offset..])
+        let end = offset + min(writer.remaining_len(), data.size - offset);
+        let mut pos = offset;
+        while pos < end {
+            let offset_in_page = pos % PAGE_SIZE;
+            let copy_len = min(PAGE_SIZE - offset_in_page, end - pos);
+            match data.pages.get(&(pos / PAGE_SIZE)) {
+                Some(page) => {
+                    writer.write_bytes(&page[offset_in_page..(offset_in_page + copy_len)])?;
+                }
+                None => {
+                    writer.fill(0, copy_len)?;
+                }
+            }
+
+            pos += copy_len;
+        }
+
+        Ok(end - offset)
     }
 
     fn write(&self, offset: usize, buf: UserBuffer<'_>, _options: &OpenOptions) -> Result<usize> {
         let mut data = self.data.lock();
         let mut reader = UserBufReader::from(buf);
-        data.resize(offset + reader.remaining_len(), 0);
-        reader.read_bytes(&mut data[offset..])
+        let end = offset + reader.remaining_len();
+        let mut pos = offset;
+        while pos < end {
+            let page = match data.pages.entry(pos / PAGE_SIZE) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                // If the file system is full, return a short write if we've
+                // written something.
+                Entry::Vacant(entry) => match self.capacity.alloc_page() {
+                    Ok(()) => entry.insert(Arc::new(vec![0; PAGE_SIZE])),
+                    Err(err) if pos == offset => return Err(err),
+                    Err(_) => break,
+                },
+            };
+
+            let offset_in_page = pos % PAGE_SIZE;
+            let copy_len = min(PAGE_SIZE - offset_in_page, end - pos);
+            let page = Arc::make_mut(page);
+            reader.read_bytes(&mut page[offset_in_page..(offset_in_page + copy_len)])?;
+            pos += copy_len;
+        }
+
+        data.size = max(data.size, pos);
+        self.touch();
+        Ok(pos - offset)
+    }
+
+    fn truncate(&self, length: usize) -> Result<()> {
+        let mut data = self.data.lock();
+        // Free pages beyond the new end and zero the tail of the last page so
+        // that extending the file later reads zeroes.
+        let freed = data
+            .pages
+            .split_off(&(align_up(length, PAGE_SIZE) / PAGE_SIZE));
+        self.capacity.free_pages(freed.len());
+        let offset_in_page = length % PAGE_SIZE;
+        if offset_in_page > 0 {
+            if let Some(page) = data.pages.get_mut(&(length / PAGE_SIZE)) {
+                Arc::make_mut(page)[offset_in_page..].fill(0);
+            }
+        }
+
+        data.size = length;
+        self.touch();
+        Ok(())
+    }
+
+    fn splice_read(
+        &self,
+        offset: usize,
+        len: usize,
+        _options: &OpenOptions,
+    ) -> Result<Vec<PipeBuffer>> {
+        let data = self.data.lock();
+        if offset >= data.size {
+            return Ok(Vec::new());
+        }
+
+        let end = offset + min(len, data.size - offset);
+        let mut bufs = Vec::new();
+        let mut pos = offset;
+        while pos < end {
+            let offset_in_page = pos % PAGE_SIZE;
+            let chunk_len = min(PAGE_SIZE - offset_in_page, end - pos);
+            let buf = match data.pages.get(&(pos / PAGE_SIZE)) {
+                Some(page) => {
+                    PipeBuffer::new(page.clone(), offset_in_page..(offset_in_page + chunk_len))
+                }
+                None => PipeBuffer::from(vec![0; chunk_len]),
+            };
+
+            bufs.push(buf);
+            pos += chunk_len;
+        }
+
+        Ok(bufs)
+    }
+
+    fn next_data(&self, offset: usize) -> Result<usize> {
+        let data = self.data.lock();
+        if offset >= data.size {
+            return Err(Error::new(Errno::ENXIO));
+        }
+
+        match data.pages.range((offset / PAGE_SIZE)..).next() {
+            Some((&index, _)) if index * PAGE_SIZE < data.size => {
+                Ok(max(offset, index * PAGE_SIZE))
+            }
+            _ => Err(Error::new(Errno::ENXIO)),
+        }
+    }
+
+    fn next_hole(&self, offset: usize) -> Result<usize> {
+        let data = self.data.lock();
+        if offset >= data.size {
+            return Err(Error::new(Errno::ENXIO));
+        }
+
+        let mut index = offset / PAGE_SIZE;
+        while data.pages.contains_key(&index) {
+            index += 1;
+        }
+
+        Ok(min(max(offset, index * PAGE_SIZE), data.size))
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        self.capacity.free_pages(self.data.lock().pages.len());
+        self.capacity.free_inode();
     }
 }
 
@@ -231,6 +842,62 @@ impl fmt::Debug for File {
     }
 }
 
+pub struct SymbolicLink {
+    stat: SpinLock<Stat>,
+    target: PathBuf,
+    nlink: AtomicUsize,
+    capacity: Arc<Capacity>,
+}
+
+impl SymbolicLink {
+    fn new(inode_no: INodeNo, target: &str, capacity: Arc<Capacity>) -> SymbolicLink {
+        SymbolicLink {
+            stat: new_stat(inode_no, S_IFLNK | 0o777),
+            target: PathBuf::from(target),
+            nlink: AtomicUsize::new(0),
+            capacity,
+        }
+    }
+}
+
+impl Symlink for SymbolicLink {
+    fn stat(&self) -> Result<Stat> {
+        Ok(Stat {
+            nlink: NLink(self.nlink.load(Ordering::SeqCst)),
+            size: FileSize(self.target.as_str().len() as isize),
+            ..*self.stat.lock()
+        })
+    }
+
+    fn set_attr(&self, attr: &SetAttr) -> Result<()> {
+        attr.apply(&mut self.stat.lock());
+        Ok(())
+    }
+
+    fn linked_to(&self) -> Result<PathBuf> {
+        Ok(self.target.clone())
+    }
+}
+
+impl Drop for SymbolicLink {
+    fn drop(&mut self) {
+        self.capacity.free_inode();
+    }
+}
+
+impl fmt::Debug for SymbolicLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TmpFsSymlink")
+            .field("target", &self.target)
+            .finish()
+    }
+}
+
 pub fn init() {
-    TMP_FS.init(|| Arc::new(TmpFs::new()));
+    TMP_FS.init(|| Arc::new(TmpFs::from_options("").unwrap()));
+    register_file_system_type(FileSystemType {
+        name: "tmpfs",
+        requires_device: false,
+        mount: |_source, data| Ok(Arc::new(TmpFs::from_options(data)?)),
+    });
 }
@@ -3,7 +3,7 @@
 use alloc::{boxed::Box, vec::Vec};
 use kerla_runtime::{arch::enable_irq, spinlock::SpinLock};
 
-use crate::{deferred_job::run_deferred_jobs, interval_work};
+use crate::{interval_work, random::add_interrupt_randomness};
 
 type IrqHandler = dyn FnMut() + Send + Sync;
 const NUM_IRQ_NUMBERS: usize = 256;
@@ -39,23 +39,20 @@ pub fn attach_irq(irq: u8, f: Box<IrqHandler>) {
 }
 
 pub fn handle_irq(irq: u8) {
+    add_interrupt_randomness(irq);
+
     {
         debug_assert!((irq as usize) < NUM_IRQ_NUMBERS);
         let mut vectors = IRQ_VECTORS.lock();
         for handler in vectors[irq as usize].handlers_mut() {
             handler();
         }
-
-        // `vectors` is dropped here to release IRQ_HANDLERS's lock since
-        // we re-enable interrupts just before running deferred jobs.
     }
 
-    // TODO: Re-enable interrupts to make deferred jobs preemptive.
-
-    // So-called "bottom half" in Linux kernel. Execute time-consuming but
-    // non-critical work like processing packets.
+    // Time-consuming but non-critical work like processing packets (so-called
+    // "bottom half" in Linux kernel) is done in the kworker thread. See
+    // `workqueue`.
     interval_work();
-    run_deferred_jobs();
 }
 
 pub fn init() {
@@ -1,15 +1,184 @@
 use alloc::boxed::Box;
+use kerla_runtime::arch::nanosecs_since_boot;
 use kerla_runtime::print::{get_debug_printer, set_debug_printer, Printer};
 use kerla_utils::ring_buffer::RingBuffer;
 
 use crate::lang_items::PANICKED;
-use core::sync::atomic::Ordering;
+use crate::poll::POLL_WAIT_QUEUE;
+use crate::process::WaitQueue;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 pub const KERNEL_LOG_BUF_SIZE: usize = 8192;
+/// The number of records kept for `/dev/kmsg`.
+const LOG_RECORDS_MAX: usize = 128;
+/// The maximum length of a message in a record. Longer lines are truncated.
+pub const LOG_MESSAGE_LEN_MAX: usize = 256;
+/// The syslog level of lines printed without a color (e.g. `trace!`).
+const DEFAULT_LOG_LEVEL: u8 = 6;
+
 // We use spin::Mutex here because SpinLock's debugging features may cause a
 // problem (capturing a backtrace requires memory allocation).
-pub static KERNEL_LOG_BUF: spin::Mutex<RingBuffer<u8, KERNEL_LOG_BUF_SIZE>> =
-    spin::Mutex::new(RingBuffer::new());
+pub static KERNEL_LOG_BUF: spin::Mutex<KernelLogBuf> = spin::Mutex::new(KernelLogBuf::new());
+/// Processes waiting for a new log record in `/dev/kmsg`.
+pub static LOG_WAIT_QUEUE: WaitQueue = WaitQueue::new();
+/// Set when a new record is added. Readers are woken up in `wake_log_readers`
+/// because the printer can be called in any context, e.g. with the scheduler
+/// locked.
+static LOG_UPDATED: AtomicBool = AtomicBool::new(false);
+
+/// A line in the kernel log.
+#[derive(Clone, Copy)]
+pub struct LogRecord {
+    seq: u64,
+    /// Microseconds since the boot.
+    timestamp: u64,
+    /// The syslog level: from 0 (`LOG_EMERG`) to 7 (`LOG_DEBUG`).
+    level: u8,
+    len: usize,
+    message: [u8; LOG_MESSAGE_LEN_MAX],
+}
+
+impl LogRecord {
+    const fn empty() -> LogRecord {
+        LogRecord {
+            seq: 0,
+            timestamp: 0,
+            level: DEFAULT_LOG_LEVEL,
+            len: 0,
+            message: [0; LOG_MESSAGE_LEN_MAX],
+        }
+    }
+
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    pub fn message(&self) -> &[u8] {
+        &self.message[..self.len]
+    }
+}
+
+/// The kernel log: the raw output for syslog(2) and crash dumps, and the
+/// sequence-numbered records for `/dev/kmsg`.
+pub struct KernelLogBuf {
+    bytes: RingBuffer<u8, KERNEL_LOG_BUF_SIZE>,
+    /// The latest `LOG_RECORDS_MAX` records. `records[seq % LOG_RECORDS_MAX]`.
+    records: [LogRecord; LOG_RECORDS_MAX],
+    next_seq: u64,
+    /// The line being printed.
+    line: LogRecord,
+    /// The ANSI escape sequence being parsed, if any.
+    escape: Option<([u8; 8], usize)>,
+}
+
+impl KernelLogBuf {
+    const fn new() -> KernelLogBuf {
+        KernelLogBuf {
+            bytes: RingBuffer::new(),
+            records: [LogRecord::empty(); LOG_RECORDS_MAX],
+            next_seq: 0,
+            line: LogRecord::empty(),
+            escape: None,
+        }
+    }
+
+    /// Consumes the raw output.
+    pub fn pop_slice(&mut self, len: usize) -> Option<&[u8]> {
+        self.bytes.pop_slice(len)
+    }
+
+    /// The sequence number of the oldest record available.
+    pub fn first_seq(&self) -> u64 {
+        self.next_seq.saturating_sub(LOG_RECORDS_MAX as u64)
+    }
+
+    /// The sequence number of the next record to be added.
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    pub fn get_record(&self, seq: u64) -> Option<&LogRecord> {
+        if seq < self.first_seq() || seq >= self.next_seq {
+            return None;
+        }
+
+        Some(&self.records[(seq % LOG_RECORDS_MAX as u64) as usize])
+    }
+
+    /// Adds a record with the given level.
+    pub fn push_record(&mut self, level: u8, message: &[u8]) {
+        let len = message.len().min(LOG_MESSAGE_LEN_MAX);
+        let record = &mut self.records[(self.next_seq % LOG_RECORDS_MAX as u64) as usize];
+        record.seq = self.next_seq;
+        record.timestamp = (nanosecs_since_boot() / 1000) as u64;
+        record.level = level;
+        record.len = len;
+        record.message[..len].copy_from_slice(&message[..len]);
+        self.next_seq += 1;
+        LOG_UPDATED.store(true, Ordering::Release);
+    }
+
+    fn push_bytes(&mut self, s: &[u8]) {
+        self.bytes.push_slice(s);
+
+        for &byte in s {
+            if let Some((ref mut escape, ref mut len)) = self.escape {
+                // The log level is determined by the color of the line (see
+                // `kerla_runtime::logger`).
+                if byte.is_ascii_alphabetic() {
+                    if self.line.len == 0 {
+                        self.line.level = match &escape[..*len] {
+                            b"[1;31" => 3,
+                            b"[33" | b"[1;33" => 4,
+                            b"[36" => 6,
+                            _ => self.line.level,
+                        };
+                    }
+
+                    self.escape = None;
+                } else if *len < escape.len() {
+                    escape[*len] = byte;
+                    *len += 1;
+                }
+
+                continue;
+            }
+
+            match byte {
+                0x1b => {
+                    self.escape = Some(([0; 8], 0));
+                }
+                b'\n' => {
+                    let line = self.line;
+                    self.push_record(line.level, line.message());
+                    self.line = LogRecord::empty();
+                }
+                _ if self.line.len < LOG_MESSAGE_LEN_MAX => {
+                    self.line.message[self.line.len] = byte;
+                    self.line.len += 1;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Wakes up processes waiting for new log records. Called from contexts where
+/// it's safe to wake up processes.
+pub fn wake_log_readers() {
+    if LOG_UPDATED.swap(false, Ordering::AcqRel) {
+        LOG_WAIT_QUEUE.wake_all();
+        POLL_WAIT_QUEUE.wake_all();
+    }
+}
 
 pub struct LoggedPrinter {
     inner: &'static dyn Printer,
@@ -36,7 +205,7 @@ impl Printer for LoggedPrinter {
             }
         }
 
-        KERNEL_LOG_BUF.lock().push_slice(s);
+        KERNEL_LOG_BUF.lock().push_bytes(s);
     }
 }
 
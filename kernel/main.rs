@@ -24,8 +24,8 @@ mod result;
 mod arch;
 #[macro_use]
 mod user_buffer;
+mod block;
 mod ctypes;
-mod deferred_job;
 mod fs;
 mod interrupt;
 mod lang_items;
@@ -40,17 +40,24 @@ mod syscalls;
 mod test_runner;
 mod timer;
 mod tty;
+mod workqueue;
 
 use crate::{
     fs::{devfs::SERIAL_TTY, tmpfs},
     fs::{
         devfs::{self, DEV_FS},
+        ext2::{self, Ext2Fs},
+        file_lock,
+        file_system::FileSystem,
         initramfs::{self, INITRAM_FS},
-        mount::RootFs,
+        mount::{MountFlags, RootFs},
+        overlayfs::{self, OverlayFs},
+        p9::{self, P9Fs},
         path::Path,
         procfs::{self, PROC_FS},
+        sysfs::{self, SYS_FS},
     },
-    process::{switch, Process},
+    process::{switch, Mutex, Process},
     syscalls::SyscallHandler,
 };
 use alloc::{boxed::Box, sync::Arc};
@@ -60,11 +67,10 @@ use kerla_runtime::{
     arch::{idle, PageFaultReason, PtRegs},
     bootinfo::BootInfo,
     profile::StopWatch,
-    spinlock::SpinLock,
 };
 use kerla_utils::once::Once;
 use net::register_ethernet_driver;
-use tmpfs::TMP_FS;
+use tmpfs::{TmpFs, TMP_FS};
 
 #[cfg(test)]
 use crate::test_runner::end_tests;
@@ -80,17 +86,17 @@ impl kerla_runtime::Handler for Handler {
         crate::interrupt::handle_irq(irq);
     }
 
-    fn handle_timer_irq(&self) {
-        crate::timer::handle_timer_irq();
+    fn handle_timer_irq(&self, in_user: bool) {
+        crate::timer::handle_timer_irq(in_user);
     }
 
     fn handle_page_fault(
         &self,
         unaligned_vaddr: Option<kerla_runtime::address::UserVAddr>,
-        ip: usize,
+        frame: &PtRegs,
         reason: PageFaultReason,
     ) {
-        crate::mm::page_fault::handle_page_fault(unaligned_vaddr, ip, reason);
+        crate::mm::page_fault::handle_page_fault(unaligned_vaddr, frame, reason);
     }
 
     fn handle_syscall(
@@ -134,9 +140,25 @@ impl KernelOps for ApiOps {
     fn receive_etherframe_packet(&self, pkt: &[u8]) {
         net::receive_ethernet_frame(pkt);
     }
+
+    fn register_rng_driver(&self, driver: Box<dyn kerla_api::driver::rng::RngDriver>) {
+        random::register_rng_driver(driver)
+    }
+
+    fn receive_randomness(&self, buf: &[u8]) {
+        random::add_hardware_randomness(buf);
+    }
+
+    fn register_block_driver(&self, driver: Box<dyn kerla_api::driver::block::BlockDriver>) {
+        block::register_block_driver(driver)
+    }
+
+    fn register_p9_transport(&self, transport: Box<dyn kerla_api::driver::p9::P9Transport>) {
+        p9::register_p9_transport(transport)
+    }
 }
 
-pub static INITIAL_ROOT_FS: Once<Arc<SpinLock<RootFs>>> = Once::new();
+pub static INITIAL_ROOT_FS: Once<Arc<Mutex<RootFs>>> = Once::new();
 
 #[no_mangle]
 #[cfg_attr(test, allow(unreachable_code))]
@@ -159,8 +181,14 @@ pub fn boot_kernel(#[cfg_attr(debug_assertions, allow(unused))] bootinfo: &BootI
     }
 
     // Initialize kernel subsystems.
+    timer::init();
+    profiler.lap_time("timer init");
+    random::init();
+    profiler.lap_time("random init");
     pipe::init();
     profiler.lap_time("pipe init");
+    file_lock::init();
+    profiler.lap_time("file lock init");
     poll::init();
     profiler.lap_time("poll init");
     procfs::init();
@@ -171,6 +199,12 @@ pub fn boot_kernel(#[cfg_attr(debug_assertions, allow(unused))] bootinfo: &BootI
     profiler.lap_time("tmpfs init");
     initramfs::init();
     profiler.lap_time("initramfs init");
+    ext2::init();
+    profiler.lap_time("ext2 init");
+    p9::init();
+    profiler.lap_time("p9 init");
+    overlayfs::init();
+    profiler.lap_time("overlayfs init");
     kerla_api::kernel_ops::init(&ApiOps);
     profiler.lap_time("kerla_api init");
 
@@ -178,6 +212,15 @@ pub fn boot_kernel(#[cfg_attr(debug_assertions, allow(unused))] bootinfo: &BootI
     info!("kext: Loading virtio_net...");
     virtio_net::init();
     profiler.lap_time("virtio_net init");
+    info!("kext: Loading virtio_rng...");
+    virtio_rng::init();
+    profiler.lap_time("virtio_rng init");
+    info!("kext: Loading virtio_blk...");
+    virtio_blk::init();
+    profiler.lap_time("virtio_blk init");
+    info!("kext: Loading virtio_9p...");
+    virtio_9p::init();
+    profiler.lap_time("virtio_9p init");
 
     // Initialize device drivers.
     kerla_api::kernel_ops::init_drivers(
@@ -187,30 +230,39 @@ pub fn boot_kernel(#[cfg_attr(debug_assertions, allow(unused))] bootinfo: &BootI
     );
     profiler.lap_time("drivers init");
 
+    // Look for partitions in the disks.
+    block::init();
+    profiler.lap_time("block init");
+
     // Connect to the network.
     net::init_and_start_dhcp_discover(bootinfo);
     profiler.lap_time("net init");
 
+    // Export the devices found above.
+    sysfs::init();
+
     // Prepare the root file system.
-    let mut root_fs = RootFs::new(INITRAM_FS.clone()).unwrap();
-    let proc_dir = root_fs
-        .lookup_dir(Path::new("/proc"))
-        .expect("failed to locate /dev");
-    let dev_dir = root_fs
-        .lookup_dir(Path::new("/dev"))
-        .expect("failed to locate /dev");
-    let tmp_dir = root_fs
-        .lookup_dir(Path::new("/tmp"))
-        .expect("failed to locate /tmp");
+    let mut root = root_file_system(bootinfo);
+    if bootinfo.overlay_root {
+        root = overlay_root_file_system(root);
+    }
+
+    let mut root_fs = RootFs::new(root).unwrap();
     root_fs
-        .mount(proc_dir, PROC_FS.clone())
+        .mount(Path::new("/proc"), PROC_FS.clone())
         .expect("failed to mount procfs");
     root_fs
-        .mount(dev_dir, DEV_FS.clone())
+        .mount(Path::new("/dev"), DEV_FS.clone())
         .expect("failed to mount devfs");
     root_fs
-        .mount(tmp_dir, TMP_FS.clone())
+        .mount(Path::new("/tmp"), TMP_FS.clone())
         .expect("failed to mount tmpfs");
+    if let Err(err) = root_fs.mount(Path::new("/sys"), SYS_FS.clone()) {
+        warn!("failed to mount sysfs on /sys: {:?}", err);
+    }
+    if let Some(virtfs) = &bootinfo.virtfs {
+        mount_virtfs(&mut root_fs, virtfs);
+    }
 
     // Open /dev/console for the init process.
     let console = root_fs
@@ -228,13 +280,17 @@ pub fn boot_kernel(#[cfg_attr(debug_assertions, allow(unused))] bootinfo: &BootI
         .expect("failed to open the init executable");
 
     // We cannot initialize the process subsystem until INITIAL_ROOT_FS is initialized.
-    INITIAL_ROOT_FS.init(|| Arc::new(SpinLock::new(root_fs)));
+    INITIAL_ROOT_FS.init(|| Arc::new(Mutex::new(root_fs)));
 
     profiler.lap_time("root fs init");
 
     process::init();
     profiler.lap_time("process init");
 
+    // Start the kworker thread to run works queued so far.
+    workqueue::init();
+    profiler.lap_time("workqueue init");
+
     // Create the init process.
     if let Some(script) = option_env!("INIT_SCRIPT") {
         let argv = &[b"sh", b"-c", script.as_bytes()];
@@ -261,13 +317,100 @@ pub fn boot_kernel(#[cfg_attr(debug_assertions, allow(unused))] bootinfo: &BootI
     idle_thread();
 }
 
+/// Mounts the block device specified in the `root` kernel parameter. If it's
+/// not given or not available, the initramfs is used instead.
+fn root_file_system(bootinfo: &BootInfo) -> Arc<dyn FileSystem> {
+    let root = match &bootinfo.root {
+        Some(root) => root,
+        None => return INITRAM_FS.clone(),
+    };
+
+    let device = match block::lookup_block_device(root) {
+        Some(device) => device,
+        None => {
+            warn!("root device not found: {}, using initramfs", root);
+            return INITRAM_FS.clone();
+        }
+    };
+
+    match Ext2Fs::mount(device.clone()) {
+        Ok(fs) => {
+            info!("mounted {} as the root file system", device.name());
+            fs
+        }
+        Err(err) => {
+            warn!(
+                "failed to mount {} as the root file system: {:?}, using initramfs",
+                device.name(),
+                err
+            );
+            INITRAM_FS.clone()
+        }
+    }
+}
+
+/// Overlays a tmpfs on the root file system to make it writable. Changes are
+/// lost on reboot.
+fn overlay_root_file_system(lower: Arc<dyn FileSystem>) -> Arc<dyn FileSystem> {
+    let lower_dir = match lower.root_dir() {
+        Ok(lower_dir) => lower_dir,
+        Err(err) => {
+            warn!("failed to overlay the root file system: {:?}", err);
+            return lower;
+        }
+    };
+
+    // Apply the default size limit of tmpfs: writes to the root file system
+    // consume the RAM.
+    let upper_fs = Arc::new(TmpFs::from_options("").unwrap());
+    let upper_dir = upper_fs.root_tmpfs_dir().clone();
+    info!(
+        "overlaid a tmpfs on the root file system ({})",
+        lower.name()
+    );
+    Arc::new(OverlayFs::new(lower_dir, upper_fs, upper_dir))
+}
+
+/// Mounts the 9P file system specified in the `virtfs` kernel parameter
+/// (`<mount_tag>:<path>`).
+fn mount_virtfs(root_fs: &mut RootFs, virtfs: &str) {
+    let (mount_tag, path) = match virtfs.split_once(':') {
+        Some((mount_tag, path)) => (mount_tag, Path::new(path)),
+        None => {
+            warn!("virtfs: expected <mount_tag>:<path>: {}", virtfs);
+            return;
+        }
+    };
+
+    let fs = match P9Fs::mount(mount_tag) {
+        Ok(fs) => fs,
+        Err(err) => {
+            warn!("virtfs: failed to mount \"{}\": {:?}", mount_tag, err);
+            return;
+        }
+    };
+
+    if let Err(err) = root_fs.mount_fs(path, mount_tag, fs, MountFlags::empty()) {
+        warn!(
+            "virtfs: failed to mount \"{}\" on {}: {:?}",
+            mount_tag, path, err
+        );
+    }
+}
+
 pub fn interval_work() {
     process::gc_exited_processes();
+    logger::wake_log_readers();
 }
 
 fn idle_thread() -> ! {
     loop {
         interval_work();
+        timer::stop_tick();
         idle();
+        timer::restart_tick();
+        // Run threads woken up by the interrupt (e.g. kworker) immediately
+        // instead of waiting for the next preemption.
+        switch();
     }
 }
@@ -1,46 +1,54 @@
+use alloc::format;
 use kerla_utils::alignment::align_down;
 
 use super::vm::VmAreaType;
 use crate::{
+    arch::vdso_area_page,
     fs::opened_file::OpenOptions,
-    process::{
-        current_process,
-        signal::{self, SIGSEGV},
-        Process,
-    },
+    process::{current_process, signal::SIGSEGV, Process},
 };
 use core::cmp::min;
 use core::slice;
 use kerla_runtime::{
     address::UserVAddr,
-    arch::{PageFaultReason, PAGE_SIZE},
+    arch::{PageFaultReason, PtRegs, PAGE_SIZE},
     page_allocator::{alloc_pages, AllocPageFlags},
 };
 
-pub fn handle_page_fault(unaligned_vaddr: Option<UserVAddr>, ip: usize, _reason: PageFaultReason) {
+/// Prints a segfault message and kills the current process by SIGSEGV.
+fn segfault(frame: &PtRegs, reason: &str) -> ! {
+    let current = current_process();
+    let ip = frame.rip;
+    let sp = frame.rsp;
+    warn!(
+        "{} ({:?}): segfault: {} (ip={:x}, sp={:x})",
+        current.cmdline().argv0(),
+        current.pid(),
+        reason,
+        ip,
+        sp
+    );
+    Process::exit_by_signal(SIGSEGV, frame);
+}
+
+pub fn handle_page_fault(
+    unaligned_vaddr: Option<UserVAddr>,
+    frame: &PtRegs,
+    reason: PageFaultReason,
+) {
     let unaligned_vaddr = match unaligned_vaddr {
         Some(unaligned_vaddr) => unaligned_vaddr,
-        None => {
-            debug_warn!(
-                "null pointer access (ip={:x}), killing the current process...",
-                ip
-            );
-            Process::exit_by_signal(signal::SIGSEGV);
-        }
+        None => segfault(frame, "null pointer access"),
     };
 
     let current = current_process();
     let aligned_vaddr = match UserVAddr::new_nonnull(align_down(unaligned_vaddr.value(), PAGE_SIZE))
     {
         Ok(uaddr) => uaddr,
-        _ => {
-            debug_warn!(
-                "invalid memory access at {} (ip={:x}), killing the current process...",
-                unaligned_vaddr,
-                ip
-            );
-            Process::exit_by_signal(SIGSEGV);
-        }
+        _ => segfault(
+            frame,
+            &format!("invalid memory access at {}", unaligned_vaddr),
+        ),
     };
 
     // Look for the associated vma area.
@@ -53,16 +61,30 @@ pub fn handle_page_fault(unaligned_vaddr: Option<UserVAddr>, ip: usize, _reason:
     {
         Some(vma) => vma,
         None => {
-            debug_warn!(
-                "no VMAs for address {}, killing the current process...",
-                unaligned_vaddr
-            );
             drop(vm);
             drop(vm_ref);
-            Process::exit_by_signal(SIGSEGV);
+            segfault(frame, &format!("no VMAs for address {}", unaligned_vaddr));
         }
     };
 
+    // The page is already mapped: the access violates its protection (e.g.
+    // writing into the vDSO).
+    if reason.contains(PageFaultReason::PRESENT) {
+        drop(vm);
+        drop(vm_ref);
+        segfault(
+            frame,
+            &format!("protection violation at {}", unaligned_vaddr),
+        );
+    }
+
+    if let VmAreaType::Vdso = vma.area_type() {
+        let paddr = vdso_area_page(vma.offset_in_vma(aligned_vaddr));
+        vm.page_table_mut()
+            .map_shared_user_page(aligned_vaddr, paddr);
+        return;
+    }
+
     // Allocate and fill the page.
     let paddr = alloc_pages(1, AllocPageFlags::USER).expect("failed to allocate an anonymous page");
     unsafe {
@@ -70,10 +92,12 @@ pub fn handle_page_fault(unaligned_vaddr: Option<UserVAddr>, ip: usize, _reason:
     }
     match vma.area_type() {
         VmAreaType::Anonymous => { /* The page is already filled with zeros. Nothing to do. */ }
+        VmAreaType::Vdso => unreachable!(),
         VmAreaType::File {
             file,
             offset,
             file_size,
+            ..
         } => {
             let buf = unsafe { slice::from_raw_parts_mut(paddr.as_mut_ptr(), PAGE_SIZE) };
             let offset_in_page;
@@ -1,6 +1,7 @@
-use crate::fs::inode::FileLike;
+use crate::fs::{inode::FileLike, opened_file::PathComponent};
 use crate::{
-    arch::{USER_STACK_TOP, USER_VALLOC_BASE, USER_VALLOC_END},
+    arch::{vdso_area_len, USER_STACK_TOP, USER_VALLOC_BASE, USER_VALLOC_END, VDSO_IMAGE_OFFSET},
+    ctypes::MMapProt,
     result::{Errno, Result},
 };
 use alloc::sync::Arc;
@@ -16,9 +17,13 @@ pub enum VmAreaType {
     Anonymous,
     File {
         file: Arc<dyn FileLike>,
+        /// The path to `file`. Used for core dumps.
+        path: Arc<PathComponent>,
         offset: usize,
         file_size: usize,
     },
+    /// The vvar page and the vDSO image. They're shared among all processes.
+    Vdso,
 }
 
 #[derive(Clone)]
@@ -26,6 +31,9 @@ pub struct VmArea {
     start: UserVAddr,
     len: usize,
     area_type: VmAreaType,
+    /// The protection requested in `mmap(2)` or by the ELF program header.
+    /// Not enforced by the page table yet.
+    prot: MMapProt,
 }
 
 impl VmArea {
@@ -33,6 +41,10 @@ impl VmArea {
         &self.area_type
     }
 
+    pub fn prot(&self) -> MMapProt {
+        self.prot
+    }
+
     pub fn start(&self) -> UserVAddr {
         self.start
     }
@@ -59,6 +71,9 @@ pub struct Vm {
     page_table: PageTable,
     vm_areas: Vec<VmArea>,
     valloc_next: UserVAddr,
+    /// The auxiliary vector (pairs of `AT_*` and its value) passed to the
+    /// program.
+    auxv: Vec<(usize, usize)>,
 }
 
 impl Vm {
@@ -70,12 +85,14 @@ impl Vm {
             start: stack_bottom,
             len: USER_STACK_TOP.value() - stack_bottom.value(),
             area_type: VmAreaType::Anonymous,
+            prot: MMapProt::PROT_READ | MMapProt::PROT_WRITE,
         };
 
         let heap_vma = VmArea {
             start: heap_bottom,
             len: 0,
             area_type: VmAreaType::Anonymous,
+            prot: MMapProt::PROT_READ | MMapProt::PROT_WRITE,
         };
 
         Ok(Vm {
@@ -84,6 +101,7 @@ impl Vm {
             // and `heap_vma_mut` depends on it.
             vm_areas: vec![stack_vma, heap_vma],
             valloc_next: USER_VALLOC_BASE,
+            auxv: Vec::new(),
         })
     }
 
@@ -99,6 +117,14 @@ impl Vm {
         &self.vm_areas
     }
 
+    pub fn auxv(&self) -> &[(usize, usize)] {
+        &self.auxv
+    }
+
+    pub fn set_auxv(&mut self, auxv: Vec<(usize, usize)>) {
+        self.auxv = auxv;
+    }
+
     fn stack_vma(&self) -> &VmArea {
         &self.vm_areas[0]
     }
@@ -116,6 +142,7 @@ impl Vm {
         start: UserVAddr,
         len: usize,
         area_type: VmAreaType,
+        prot: MMapProt,
     ) -> Result<()> {
         start.access_ok(len)?;
 
@@ -127,11 +154,26 @@ impl Vm {
             start,
             len,
             area_type,
+            prot,
         });
 
         Ok(())
     }
 
+    /// Reserves the vDSO area and returns the address of the vDSO image. Its
+    /// pages are mapped on demand.
+    pub fn add_vdso_area(&mut self) -> Result<UserVAddr> {
+        let len = vdso_area_len();
+        let start = self.alloc_vaddr_range(len)?;
+        self.add_vm_area(
+            start,
+            len,
+            VmAreaType::Vdso,
+            MMapProt::PROT_READ | MMapProt::PROT_EXEC,
+        )?;
+        Ok(start.add(VDSO_IMAGE_OFFSET))
+    }
+
     pub fn heap_end(&self) -> UserVAddr {
         self.heap_vma().end()
     }
@@ -164,6 +206,7 @@ impl Vm {
             page_table: PageTable::duplicate_from(&self.page_table)?,
             vm_areas: self.vm_areas.clone(),
             valloc_next: self.valloc_next,
+            auxv: self.auxv.clone(),
         })
     }
 
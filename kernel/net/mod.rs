@@ -1,11 +1,14 @@
-use crate::deferred_job::DeferredJob;
+use crate::workqueue::Work;
 use crate::{
-    poll::POLL_WAIT_QUEUE, process::WaitQueue, timer::read_monotonic_clock, timer::MonotonicClock,
+    poll::POLL_WAIT_QUEUE,
+    process::WaitQueue,
+    timer::{read_monotonic_clock, MonotonicClock, Timer},
 };
 use alloc::boxed::Box;
 use alloc::collections::BTreeMap;
 use alloc::vec::Vec;
 use atomic_refcell::AtomicRefCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use crossbeam::queue::ArrayQueue;
 use kerla_api::driver::net::EthernetDriver;
 use kerla_runtime::bootinfo::BootInfo;
@@ -37,16 +40,46 @@ pub use tcp_socket::*;
 pub use udp_socket::*;
 pub use unix_socket::*;
 
-static PACKET_PROCESS_JOB: DeferredJob = DeferredJob::new("net_packet_process");
+static PACKET_PROCESS_WORK: Work = Work::new("net_packet_process");
 static RX_PACKET_QUEUE: Once<SpinLock<ArrayQueue<Vec<u8>>>> = Once::new();
 
+static RX_PACKETS_TOTAL: AtomicUsize = AtomicUsize::new(0);
+static RX_BYTES_TOTAL: AtomicUsize = AtomicUsize::new(0);
+static RX_DROPPED_TOTAL: AtomicUsize = AtomicUsize::new(0);
+static TX_PACKETS_TOTAL: AtomicUsize = AtomicUsize::new(0);
+static TX_BYTES_TOTAL: AtomicUsize = AtomicUsize::new(0);
+
+/// Statistics of the network device.
+#[derive(Debug)]
+pub struct DeviceStats {
+    pub rx_packets: usize,
+    pub rx_bytes: usize,
+    pub rx_dropped: usize,
+    pub tx_packets: usize,
+    pub tx_bytes: usize,
+}
+
+pub fn read_device_stats() -> DeviceStats {
+    DeviceStats {
+        rx_packets: RX_PACKETS_TOTAL.load(Ordering::Relaxed),
+        rx_bytes: RX_BYTES_TOTAL.load(Ordering::Relaxed),
+        rx_dropped: RX_DROPPED_TOTAL.load(Ordering::Relaxed),
+        tx_packets: TX_PACKETS_TOTAL.load(Ordering::Relaxed),
+        tx_bytes: TX_BYTES_TOTAL.load(Ordering::Relaxed),
+    }
+}
+
 pub fn receive_ethernet_frame(frame: &[u8]) {
     if RX_PACKET_QUEUE.lock().push(frame.to_vec()).is_err() {
         // TODO: Introduce warn_once! macro
         warn!("the rx packet queue is full; dropping an incoming packet");
+        RX_DROPPED_TOTAL.fetch_add(1, Ordering::Relaxed);
+    } else {
+        RX_PACKETS_TOTAL.fetch_add(1, Ordering::Relaxed);
+        RX_BYTES_TOTAL.fetch_add(frame.len(), Ordering::Relaxed);
     }
 
-    PACKET_PROCESS_JOB.run_later(|| {
+    PACKET_PROCESS_WORK.queue(|| {
         process_packets();
     });
 }
@@ -63,6 +96,8 @@ static INTERFACE: Once<SpinLock<EthernetInterface<OurDevice>>> = Once::new();
 static DHCP_CLIENT: Once<SpinLock<Dhcpv4Client>> = Once::new();
 static DHCP_ENABLED: Once<bool> = Once::new();
 static SOCKET_WAIT_QUEUE: Once<WaitQueue> = Once::new();
+/// The timer to call `process_packets` when smoltcp needs to be polled next time.
+static POLL_TIMER: SpinLock<Option<Timer>> = SpinLock::new(None);
 
 pub fn process_packets() {
     let mut sockets = SOCKETS.lock();
@@ -105,14 +140,26 @@ pub fn process_packets() {
         }
     }
 
-    if *DHCP_ENABLED {
-        let dhcp = DHCP_CLIENT.lock();
-        dhcp.next_poll(timestamp);
-    }
-
-    if let Some(_timeout) = iface.poll_delay(&sockets, timestamp) {
-        // TODO: Use timeout
-    }
+    // Schedule the next poll for TCP retransmissions, DHCP renewals, etc.
+    let dhcp_delay = if *DHCP_ENABLED {
+        Some(DHCP_CLIENT.lock().next_poll(timestamp))
+    } else {
+        None
+    };
+    let delay = iface
+        .poll_delay(&sockets, timestamp)
+        .into_iter()
+        .chain(dhcp_delay)
+        .min();
+    *POLL_TIMER.lock() = delay.map(|delay| {
+        let deadline =
+            read_monotonic_clock().add_nanosecs(delay.total_millis() as usize * 1_000_000);
+        Timer::new(deadline, || {
+            PACKET_PROCESS_WORK.queue(|| {
+                process_packets();
+            });
+        })
+    });
 
     SOCKET_WAIT_QUEUE.wake_all();
     POLL_WAIT_QUEUE.wake_all();
@@ -142,6 +189,8 @@ impl TxToken for OurTxToken {
         let return_value = f(&mut buffer)?;
         if EthernetFrame::new_checked(&mut buffer).is_ok() {
             use_ethernet_driver(|driver| driver.transmit(&buffer));
+            TX_PACKETS_TOTAL.fetch_add(1, Ordering::Relaxed);
+            TX_BYTES_TOTAL.fetch_add(buffer.len(), Ordering::Relaxed);
         }
 
         Ok(return_value)
@@ -98,6 +98,10 @@ impl TcpSocket {
 }
 
 impl FileLike for TcpSocket {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
     fn listen(&self, backlog: i32) -> Result<()> {
         let mut backlogs = self.backlogs.lock();
 
@@ -32,6 +32,10 @@ impl UdpSocket {
 }
 
 impl FileLike for UdpSocket {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
     fn bind(&self, sockaddr: SockAddr) -> Result<()> {
         let mut endpoint: IpEndpoint = sockaddr.try_into()?;
         // TODO: Reject if the endpoint is already in use -- IIUC smoltcp
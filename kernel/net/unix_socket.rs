@@ -17,6 +17,10 @@ impl UnixSocket {
 }
 
 impl FileLike for UnixSocket {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
     fn connect(&self, _endpoint: SockAddr, _options: &OpenOptions) -> Result<()> {
         Err(Errno::EACCES.into())
     }
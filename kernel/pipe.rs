@@ -1,8 +1,9 @@
 //! Unnamed pipe (`pipe(2)`).
-use core::fmt;
+use alloc::collections::VecDeque;
+use core::{cmp::min, fmt, ops::Range};
 
-use kerla_runtime::spinlock::SpinLock;
-use kerla_utils::{once::Once, ring_buffer::RingBuffer};
+use kerla_runtime::{arch::PAGE_SIZE, spinlock::SpinLock};
+use kerla_utils::{downcast::downcast, once::Once};
 
 use crate::{
     fs::{
@@ -19,18 +20,201 @@ const PIPE_SIZE: usize = 4096;
 // TODO: Fine-granined wait queue, say, embed a queue in every pipes.
 static PIPE_WAIT_QUEUE: Once<WaitQueue> = Once::new();
 
+/// A reference to a part of a (possibly shared) page.
+///
+/// Pipes hold their contents in this form so that `splice(2)` and `tee(2)`
+/// can pass file pages and pipe contents around without copying them.
+#[derive(Clone)]
+pub struct PipeBuffer {
+    page: Arc<Vec<u8>>,
+    range: Range<usize>,
+}
+
+impl PipeBuffer {
+    pub fn new(page: Arc<Vec<u8>>, range: Range<usize>) -> PipeBuffer {
+        debug_assert!(range.end <= page.len());
+        PipeBuffer { page, range }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.page[self.range.clone()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.range.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.range.is_empty()
+    }
+
+    /// Drops the first `len` bytes.
+    fn advance(&mut self, len: usize) {
+        self.range.start += len;
+    }
+
+    /// Drops bytes after the first `len` bytes.
+    fn truncate(&mut self, len: usize) {
+        self.range.end = min(self.range.end, self.range.start + len);
+    }
+}
+
+impl From<Vec<u8>> for PipeBuffer {
+    fn from(data: Vec<u8>) -> PipeBuffer {
+        let len = data.len();
+        PipeBuffer::new(Arc::new(data), 0..len)
+    }
+}
+
+impl fmt::Debug for PipeBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PipeBuffer")
+            .field("range", &self.range)
+            .finish()
+    }
+}
+
 struct PipeInner {
-    buf: RingBuffer<u8, PIPE_SIZE>,
+    bufs: VecDeque<PipeBuffer>,
+    /// The total length of `bufs`.
+    len: usize,
+    /// The space reserved by `PipeWriter::reserve_space` and `PipeInner::take`.
+    reserved: usize,
     closed_by_reader: bool,
     closed_by_writer: bool,
 }
 
+impl PipeInner {
+    fn free_space(&self) -> usize {
+        PIPE_SIZE - self.len - self.reserved
+    }
+
+    /// Copies bytes from `reader` into the pipe. Returns the copied length.
+    fn push_bytes(&mut self, reader: &mut UserBufReader<'_>) -> Result<usize> {
+        let mut written_len = 0;
+        loop {
+            let max_len = min(self.free_space(), reader.remaining_len());
+            if max_len == 0 {
+                break;
+            }
+
+            // Append to the last buffer if it's not shared with others.
+            let appendable = match self.bufs.back_mut() {
+                Some(buf) => {
+                    let end = buf.range.end;
+                    matches!(Arc::get_mut(&mut buf.page),
+                             Some(page) if end == page.len() && page.len() < PAGE_SIZE)
+                }
+                None => false,
+            };
+
+            if !appendable {
+                self.bufs
+                    .push_back(PipeBuffer::from(Vec::with_capacity(PAGE_SIZE)));
+            }
+
+            let buf = self.bufs.back_mut().unwrap();
+            let page = Arc::get_mut(&mut buf.page).unwrap();
+            let start = page.len();
+            let copy_len = min(max_len, PAGE_SIZE - start);
+            page.resize(start + copy_len, 0);
+            reader.read_bytes(&mut page[start..])?;
+            buf.range.end += copy_len;
+            self.len += copy_len;
+            written_len += copy_len;
+        }
+
+        Ok(written_len)
+    }
+
+    /// Appends `buf` as far as the pipe has room. Returns the appended length.
+    fn push_buffer(&mut self, mut buf: PipeBuffer) -> usize {
+        buf.truncate(self.free_space());
+        let len = buf.len();
+        if len > 0 {
+            self.len += len;
+            self.bufs.push_back(buf);
+        }
+
+        len
+    }
+
+    /// Returns references to the first `len` bytes without consuming them.
+    fn peek(&self, len: usize) -> Vec<PipeBuffer> {
+        let mut bufs = Vec::new();
+        let mut remaining = len;
+        for buf in &self.bufs {
+            if remaining == 0 {
+                break;
+            }
+
+            let mut buf = buf.clone();
+            buf.truncate(remaining);
+            remaining -= buf.len();
+            bufs.push(buf);
+        }
+
+        bufs
+    }
+
+    /// Removes the first `len` bytes and returns references to them. Their
+    /// space stays reserved until they're passed to `put_back`.
+    fn take(&mut self, len: usize) -> Vec<PipeBuffer> {
+        let bufs = self.peek(len);
+        let taken_len = bufs.iter().map(PipeBuffer::len).sum();
+        self.consume(taken_len);
+        self.reserved += taken_len;
+        bufs
+    }
+
+    /// Puts `bufs` returned by `take` back except the first `consumed_len`
+    /// bytes and releases the space reserved for them.
+    fn put_back(&mut self, bufs: Vec<PipeBuffer>, consumed_len: usize) {
+        let mut skip = consumed_len;
+        let mut unconsumed = Vec::new();
+        for mut buf in bufs {
+            self.reserved -= buf.len();
+            if skip >= buf.len() {
+                skip -= buf.len();
+                continue;
+            }
+
+            buf.advance(skip);
+            skip = 0;
+            unconsumed.push(buf);
+        }
+
+        for buf in unconsumed.into_iter().rev() {
+            self.len += buf.len();
+            self.bufs.push_front(buf);
+        }
+    }
+
+    /// Drops the first `len` bytes.
+    fn consume(&mut self, len: usize) {
+        let mut remaining = min(len, self.len);
+        self.len -= remaining;
+        while remaining > 0 {
+            let front = self.bufs.front_mut().unwrap();
+            if front.len() > remaining {
+                front.advance(remaining);
+                break;
+            }
+
+            remaining -= front.len();
+            self.bufs.pop_front();
+        }
+    }
+}
+
 pub struct Pipe(Arc<SpinLock<PipeInner>>);
 
 impl Pipe {
     pub fn new() -> Pipe {
         Pipe(Arc::new(SpinLock::new(PipeInner {
-            buf: RingBuffer::new(),
+            bufs: VecDeque::new(),
+            len: 0,
+            reserved: 0,
             closed_by_reader: false,
             closed_by_writer: false,
         })))
@@ -47,32 +231,114 @@ impl Pipe {
 
 pub struct PipeWriter(Arc<SpinLock<PipeInner>>);
 
-impl FileLike for PipeWriter {
-    fn write(&self, _offset: usize, buf: UserBuffer<'_>, options: &OpenOptions) -> Result<usize> {
-        let ret_value = PIPE_WAIT_QUEUE.sleep_signalable_until(|| {
+impl PipeWriter {
+    /// Waits until the pipe has room and returns its free space. Returns
+    /// `EAGAIN` if `nonblock` is set and the pipe is full.
+    fn wait_for_space(&self, nonblock: bool) -> Result<usize> {
+        PIPE_WAIT_QUEUE.sleep_signalable_until(|| {
+            let pipe = self.0.lock();
+            if pipe.closed_by_reader {
+                // TODO: SIGPIPE?
+                return Err(Errno::EPIPE.into());
+            }
+
+            match pipe.free_space() {
+                0 if nonblock => Err(Errno::EAGAIN.into()),
+                0 => Ok(None),
+                free => Ok(Some(free)),
+            }
+        })
+    }
+
+    /// Waits until the pipe has room and reserves up to `len` bytes of it so
+    /// that other writers can't take the space while the lock is not held.
+    /// Returns the reserved length, which must be passed to `push_buffers`
+    /// or `release_space`.
+    fn reserve_space(&self, len: usize, nonblock: bool) -> Result<usize> {
+        PIPE_WAIT_QUEUE.sleep_signalable_until(|| {
             let mut pipe = self.0.lock();
             if pipe.closed_by_reader {
                 // TODO: SIGPIPE?
                 return Err(Errno::EPIPE.into());
             }
 
-            let mut written_len = 0;
-            let mut reader = UserBufReader::from(buf.clone());
-            loop {
-                let mut tmp = [0; 512];
-                let copied_len = reader.read_bytes(&mut tmp)?;
-                if copied_len == 0 {
-                    break;
+            match pipe.free_space() {
+                0 if nonblock => Err(Errno::EAGAIN.into()),
+                0 => Ok(None),
+                free => {
+                    let reserved = min(len, free);
+                    pipe.reserved += reserved;
+                    Ok(Some(reserved))
                 }
+            }
+        })
+    }
 
-                match pipe.buf.push_slice(&tmp[..copied_len]) {
-                    0 => break,
-                    len => {
-                        written_len += len;
-                    }
-                }
+    fn release_space(&self, reserved: usize) {
+        self.0.lock().reserved -= reserved;
+        PIPE_WAIT_QUEUE.wake_all();
+    }
+
+    /// Appends `bufs` as far as the pipe has room, using the space reserved
+    /// by `reserve_space` (if any). Returns the appended length.
+    fn push_buffers(&self, bufs: Vec<PipeBuffer>, reserved: usize) -> usize {
+        let mut pipe = self.0.lock();
+        pipe.reserved -= reserved;
+        let mut written_len = 0;
+        for buf in bufs {
+            let len = buf.len();
+            let pushed_len = pipe.push_buffer(buf);
+            written_len += pushed_len;
+            if pushed_len < len {
+                break;
             }
+        }
+
+        drop(pipe);
+
+        // Try waking readers...
+        PIPE_WAIT_QUEUE.wake_all();
+        written_len
+    }
+
+    /// `splice(2)` from a file into the pipe: moves up to `len` bytes at
+    /// `offset` in `src` into the pipe by reference where `src` allows it.
+    pub fn splice_from(
+        &self,
+        src: &Arc<dyn FileLike>,
+        offset: usize,
+        len: usize,
+        src_options: &OpenOptions,
+        options: &OpenOptions,
+    ) -> Result<usize> {
+        // Data read from `src` can't be put back if it's not seekable (e.g. a
+        // socket): reserve the space so that all of it fits in the pipe.
+        let reserved = self.reserve_space(len, options.nonblock)?;
+        match src.splice_read(offset, reserved, src_options) {
+            Ok(bufs) => Ok(self.push_buffers(bufs, reserved)),
+            Err(err) => {
+                self.release_space(reserved);
+                Err(err)
+            }
+        }
+    }
+}
 
+impl FileLike for PipeWriter {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
+    fn write(&self, _offset: usize, buf: UserBuffer<'_>, options: &OpenOptions) -> Result<usize> {
+        let ret_value = PIPE_WAIT_QUEUE.sleep_signalable_until(|| {
+            let mut pipe = self.0.lock();
+            if pipe.closed_by_reader {
+                // TODO: SIGPIPE?
+                return Err(Errno::EPIPE.into());
+            }
+
+            let mut reader = UserBufReader::from(buf.clone());
+            let written_len = pipe.push_bytes(&mut reader)?;
             if written_len > 0 {
                 Ok(Some(written_len))
             } else if options.nonblock {
@@ -100,7 +366,7 @@ impl FileLike for PipeWriter {
         let mut status = PollStatus::empty();
         let inner = self.0.lock();
 
-        if inner.buf.is_writable() {
+        if inner.free_space() > 0 {
             status |= PollStatus::POLLOUT;
         }
 
@@ -123,7 +389,122 @@ impl Drop for PipeWriter {
 
 pub struct PipeReader(Arc<SpinLock<PipeInner>>);
 
+impl PipeReader {
+    /// Waits until the pipe has data and returns references to up to `len`
+    /// bytes. If `take` is set, they're removed from the pipe and must be
+    /// passed to `put_back`. Returns an empty `Vec` at EOF, or `EAGAIN` if
+    /// `nonblock` is set and the pipe is empty.
+    fn wait_for_data(&self, len: usize, nonblock: bool, take: bool) -> Result<Vec<PipeBuffer>> {
+        PIPE_WAIT_QUEUE.sleep_signalable_until(|| {
+            let mut pipe = self.0.lock();
+            if pipe.len > 0 && take {
+                Ok(Some(pipe.take(len)))
+            } else if pipe.len > 0 {
+                Ok(Some(pipe.peek(len)))
+            } else if pipe.closed_by_writer {
+                Ok(Some(Vec::new()))
+            } else if nonblock {
+                Err(Errno::EAGAIN.into())
+            } else {
+                Ok(None)
+            }
+        })
+    }
+
+    /// Consumes the first `consumed_len` bytes of `bufs` taken by
+    /// `wait_for_data` and puts back the rest.
+    fn put_back(&self, bufs: Vec<PipeBuffer>, consumed_len: usize) {
+        self.0.lock().put_back(bufs, consumed_len);
+
+        // Try waking writers...
+        PIPE_WAIT_QUEUE.wake_all();
+    }
+
+    /// `splice(2)` from the pipe into a file: writes up to `len` bytes into
+    /// `dst` at `offset` directly from the pipe buffers.
+    pub fn splice_to(
+        &self,
+        dst: &Arc<dyn FileLike>,
+        offset: usize,
+        len: usize,
+        options: &OpenOptions,
+        dst_options: &OpenOptions,
+    ) -> Result<usize> {
+        if let Some(writer) = downcast::<_, PipeWriter>(dst) {
+            return self.transfer(writer, len, options.nonblock || dst_options.nonblock, true);
+        }
+
+        // Take the data out of the pipe under the lock so that concurrent
+        // readers don't get the same bytes. The pipe lock is not held while
+        // writing into `dst`: put back what `dst` doesn't accept.
+        let bufs = self.wait_for_data(len, options.nonblock, true)?;
+        let mut written_len = 0;
+        let mut error = None;
+        for buf in &bufs {
+            let result = dst.write(
+                offset + written_len,
+                UserBuffer::from(buf.as_bytes()),
+                dst_options,
+            );
+
+            match result {
+                Ok(len) => {
+                    written_len += len;
+                    if len < buf.len() {
+                        break;
+                    }
+                }
+                Err(_) if written_len > 0 => break,
+                Err(err) => {
+                    error = Some(err);
+                    break;
+                }
+            }
+        }
+
+        self.put_back(bufs, written_len);
+        match error {
+            Some(err) => Err(err),
+            None => Ok(written_len),
+        }
+    }
+
+    /// `tee(2)`: duplicates up to `len` bytes into `dst` without consuming
+    /// them.
+    pub fn tee(&self, dst: &PipeWriter, len: usize, nonblock: bool) -> Result<usize> {
+        self.transfer(dst, len, nonblock, false)
+    }
+
+    /// Passes pipe buffers to another pipe by reference.
+    fn transfer(
+        &self,
+        dst: &PipeWriter,
+        len: usize,
+        nonblock: bool,
+        consume: bool,
+    ) -> Result<usize> {
+        if Arc::ptr_eq(&self.0, &dst.0) {
+            return Err(Errno::EINVAL.into());
+        }
+
+        // Avoid holding both locks at once: another process could be moving
+        // data in the opposite direction.
+        let free = dst.wait_for_space(nonblock)?;
+        let bufs = self.wait_for_data(min(len, free), nonblock, consume)?;
+        let written_len = dst.push_buffers(bufs.clone(), 0);
+        if consume {
+            self.put_back(bufs, written_len);
+        }
+
+        Ok(written_len)
+    }
+}
+
 impl FileLike for PipeReader {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
     fn write(&self, _offset: usize, _buf: UserBuffer<'_>, _options: &OpenOptions) -> Result<usize> {
         Err(Errno::EINVAL.into())
     }
@@ -133,12 +514,19 @@ impl FileLike for PipeReader {
         let ret_value = PIPE_WAIT_QUEUE.sleep_signalable_until(|| {
             let mut pipe = self.0.lock();
 
-            while let Some(src) = pipe.buf.pop_slice(writer.remaining_len()) {
-                writer.write_bytes(src)?;
+            let mut read_len = 0;
+            while let Some(buf) = pipe.bufs.front() {
+                let copied_len = writer.write_bytes(buf.as_bytes())?;
+                if copied_len == 0 {
+                    break;
+                }
+
+                pipe.consume(copied_len);
+                read_len += copied_len;
             }
 
-            if writer.written_len() > 0 {
-                Ok(Some(writer.written_len()))
+            if read_len > 0 {
+                Ok(Some(read_len))
             } else if options.nonblock || pipe.closed_by_writer {
                 Ok(Some(0))
             } else {
@@ -155,7 +543,7 @@ impl FileLike for PipeReader {
         let mut status = PollStatus::empty();
         let inner = self.0.lock();
 
-        if inner.buf.is_readable() {
+        if inner.len > 0 {
             status |= PollStatus::POLLIN;
         }
 
@@ -171,7 +559,7 @@ impl fmt::Debug for PipeReader {
 
 impl Drop for PipeReader {
     fn drop(&mut self) {
-        self.0.lock().closed_by_reader = false;
+        self.0.lock().closed_by_reader = true;
         PIPE_WAIT_QUEUE.wake_all();
     }
 }
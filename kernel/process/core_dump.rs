@@ -0,0 +1,565 @@
+//! ELF core dumps.
+//!
+//! The layout is compatible with Linux's so that core files can be loaded into
+//! gdb: a `PT_NOTE` segment (`NT_PRSTATUS`, `NT_PRPSINFO`, `NT_AUXV`, and
+//! `NT_FILE`) followed by a `PT_LOAD` segment for each `VmArea`.
+//!
+//! `VmArea`s are not necessarily page-aligned (the ELF loader passes `p_vaddr`
+//! as is), so each `PT_LOAD` segment covers the pages overlapping the area.
+use core::cmp::min;
+use core::mem::size_of;
+use core::slice;
+
+use goblin::elf64::{
+    header::{
+        Header, EI_CLASS, EI_DATA, EI_VERSION, ELFCLASS64, ELFDATA2LSB, ELFMAG, EM_X86_64, ET_CORE,
+        EV_CURRENT, SIZEOF_EHDR,
+    },
+    program_header::{ProgramHeader, PF_R, PF_W, PF_X, PT_LOAD, PT_NOTE, SIZEOF_PHDR},
+};
+use kerla_runtime::{
+    arch::{PtRegs, PAGE_SIZE},
+    spinlock::SpinLock,
+};
+use kerla_utils::alignment::{align_down, align_up};
+
+use crate::{
+    ctypes::MMapProt,
+    fs::{
+        inode::FileLike,
+        opened_file::OpenOptions,
+        path::{Path, PathBuf},
+        stat::{FileMode, S_IFREG},
+    },
+    mm::vm::VmAreaType,
+    prelude::*,
+    process::{current_process, rlimit::RLIMIT_CORE, signal::Signal},
+    timer::read_wall_clock,
+    user_buffer::UserBuffer,
+};
+
+const NT_PRSTATUS: u32 = 1;
+const NT_PRPSINFO: u32 = 3;
+const NT_AUXV: u32 = 6;
+const NT_FILE: u32 = 0x4649_4c45;
+
+/// The maximum length of the process name in `NT_PRPSINFO` (`TASK_COMM_LEN`).
+const COMM_LEN: usize = 16;
+/// The maximum length of the command line in `NT_PRPSINFO` (`ELF_PRARGSZ`).
+const PSARGS_LEN: usize = 80;
+
+/// The template of core file paths. See [`expand_core_pattern`].
+static CORE_PATTERN: SpinLock<String> = SpinLock::new(String::new());
+
+/// The default value of `/proc/sys/kernel/core_pattern`.
+const DEFAULT_CORE_PATTERN: &str = "core";
+
+/// `struct elf_prstatus` on x86_64.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct PrStatus {
+    si_signo: i32,
+    si_code: i32,
+    si_errno: i32,
+    cursig: i16,
+    _pad1: i16,
+    sigpend: u64,
+    sighold: u64,
+    pid: i32,
+    ppid: i32,
+    pgrp: i32,
+    sid: i32,
+    utime: [u64; 2],
+    stime: [u64; 2],
+    cutime: [u64; 2],
+    cstime: [u64; 2],
+    /// `struct user_regs_struct`.
+    regs: [u64; 27],
+    fpvalid: i32,
+    _pad2: i32,
+}
+
+/// `struct elf_prpsinfo` on x86_64.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct PrPsInfo {
+    state: u8,
+    sname: u8,
+    zomb: u8,
+    nice: i8,
+    _pad: u32,
+    flag: u64,
+    uid: u32,
+    gid: u32,
+    pid: i32,
+    ppid: i32,
+    pgrp: i32,
+    sid: i32,
+    fname: [u8; COMM_LEN],
+    psargs: [u8; PSARGS_LEN],
+}
+
+fn as_bytes<T: Copy>(value: &T) -> &[u8] {
+    unsafe { slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+}
+
+/// Returns the current `core_pattern`.
+pub fn core_pattern() -> String {
+    let pattern = CORE_PATTERN.lock();
+    if pattern.is_empty() {
+        DEFAULT_CORE_PATTERN.to_owned()
+    } else {
+        pattern.clone()
+    }
+}
+
+/// Updates `core_pattern`. Trailing newlines (e.g. `echo` into
+/// `/proc/sys/kernel/core_pattern`) are removed.
+pub fn set_core_pattern(pattern: &str) {
+    *CORE_PATTERN.lock() = pattern.trim_end_matches('\n').to_owned();
+}
+
+/// The process name reported as `%e` and in `NT_PRPSINFO`: the basename of
+/// argv[0] truncated to 15 bytes like Linux's `comm`. It's truncated at a
+/// character boundary so that it's still a valid UTF-8 string.
+fn comm() -> String {
+    let cmdline = current_process().cmdline();
+    let argv0 = cmdline.argv0();
+    let basename = argv0.rsplit('/').next().unwrap_or(argv0);
+    let mut len = min(basename.len(), COMM_LEN - 1);
+    while !basename.is_char_boundary(len) {
+        len -= 1;
+    }
+
+    basename[..len].to_owned()
+}
+
+/// Expands `%` specifiers in `core_pattern`:
+///
+/// - `%%`: `%`
+/// - `%p`: the process ID
+/// - `%e`: the process name
+/// - `%s`: the signal number
+/// - `%t`: the time of the dump in seconds since the Epoch
+fn expand_core_pattern(pattern: &str, signal: Signal) -> PathBuf {
+    use core::fmt::Write;
+
+    let mut path = String::new();
+    let mut chars = pattern.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            path.push(ch);
+            continue;
+        }
+
+        let _ = match chars.next() {
+            Some('%') => write!(path, "%"),
+            Some('p') => write!(path, "{}", current_process().pid().as_i32()),
+            Some('e') => write!(path, "{}", comm()),
+            Some('s') => write!(path, "{}", signal),
+            Some('t') => write!(path, "{}", read_wall_clock().secs_from_epoch()),
+            // Unknown specifiers are dropped as Linux does.
+            Some(_) | None => Ok(()),
+        };
+    }
+
+    Path::new(&path).to_path_buf()
+}
+
+/// Creates the core file or truncates the existing one.
+fn create_core_file(path: &Path) -> Result<Arc<dyn FileLike>> {
+    let (parent_dir, name) = path
+        .parent_and_basename()
+        .ok_or_else::<Error, _>(|| Errno::EISDIR.into())?;
+
+    let root_fs = current_process().root_fs().lock();
+    match root_fs
+        .lookup_writable_dir(parent_dir)?
+        .create_file(name, FileMode::new(S_IFREG | 0o600))
+    {
+        Ok(inode) => inode.as_file().map(Clone::clone),
+        Err(err) if err.errno() == Errno::EEXIST => {
+            // Like Linux, refuse to overwrite anything other than a regular
+            // file and discard the stale contents of the old core file.
+            let file = root_fs.lookup_file(path)?;
+            if !file.stat()?.mode.is_regular_file() {
+                return Err(Errno::EACCES.into());
+            }
+
+            file.truncate(0)?;
+            Ok(file)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Writes a core file sequentially. Data beyond RLIMIT_CORE is silently
+/// discarded.
+struct CoreWriter {
+    file: Arc<dyn FileLike>,
+    offset: usize,
+    limit: usize,
+}
+
+impl CoreWriter {
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        let len = core::cmp::min(data.len(), self.limit.saturating_sub(self.offset));
+        if len > 0 {
+            self.file.write(
+                self.offset,
+                UserBuffer::from(&data[..len]),
+                &OpenOptions::readwrite(),
+            )?;
+        }
+
+        self.offset += data.len();
+        Ok(())
+    }
+
+    fn fill_zeroes_until(&mut self, offset: usize) -> Result<()> {
+        debug_assert!(self.offset <= offset);
+        if self.offset >= self.limit {
+            self.offset = offset;
+            return Ok(());
+        }
+
+        let zeroes = [0; 512];
+        while self.offset < offset {
+            let len = core::cmp::min(zeroes.len(), offset - self.offset);
+            self.write(&zeroes[..len])?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Appends an ELF note named `CORE`.
+fn push_note(buf: &mut Vec<u8>, note_type: u32, desc: &[u8]) {
+    const NAME: &[u8] = b"CORE\0";
+    buf.extend_from_slice(&(NAME.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&(desc.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&note_type.to_le_bytes());
+    buf.extend_from_slice(NAME);
+    buf.resize(align_up(buf.len(), 4), 0);
+    buf.extend_from_slice(desc);
+    buf.resize(align_up(buf.len(), 4), 0);
+}
+
+fn build_prstatus(signal: Signal, frame: &PtRegs) -> PrStatus {
+    let current = current_process();
+    let pgid = current.process_group().lock().pgid().as_i32();
+    PrStatus {
+        si_signo: signal,
+        si_code: 0,
+        si_errno: 0,
+        cursig: signal as i16,
+        _pad1: 0,
+        sigpend: 0,
+        sighold: 0,
+        pid: current.pid().as_i32(),
+        ppid: current.ppid().as_i32(),
+        pgrp: pgid,
+        sid: pgid,
+        utime: [0; 2],
+        stime: [0; 2],
+        cutime: [0; 2],
+        cstime: [0; 2],
+        regs: [
+            frame.r15,
+            frame.r14,
+            frame.r13,
+            frame.r12,
+            frame.rbp,
+            frame.rbx,
+            frame.r11,
+            frame.r10,
+            frame.r9,
+            frame.r8,
+            frame.rax,
+            frame.rcx,
+            frame.rdx,
+            frame.rsi,
+            frame.rdi,
+            frame.orig_rax,
+            frame.rip,
+            frame.cs,
+            frame.rflags,
+            frame.rsp,
+            frame.ss,
+            current.arch().fsbase(),
+            0, /* gs_base */
+            0, /* ds */
+            0, /* es */
+            0, /* fs */
+            0, /* gs */
+        ],
+        fpvalid: 0,
+        _pad2: 0,
+    }
+}
+
+fn build_prpsinfo() -> PrPsInfo {
+    let current = current_process();
+    let pgid = current.process_group().lock().pgid().as_i32();
+    let mut info = PrPsInfo {
+        state: 0,
+        sname: b'R',
+        zomb: 0,
+        nice: 0,
+        _pad: 0,
+        flag: 0,
+        uid: 0,
+        gid: 0,
+        pid: current.pid().as_i32(),
+        ppid: current.ppid().as_i32(),
+        pgrp: pgid,
+        sid: pgid,
+        fname: [0; COMM_LEN],
+        psargs: [0; PSARGS_LEN],
+    };
+
+    let comm = comm();
+    info.fname[..comm.len()].copy_from_slice(comm.as_bytes());
+    let cmdline = current.cmdline();
+    let psargs = cmdline.as_str().as_bytes();
+    let psargs_len = core::cmp::min(psargs.len(), PSARGS_LEN - 1);
+    info.psargs[..psargs_len].copy_from_slice(&psargs[..psargs_len]);
+    info
+}
+
+/// Fills `page` (mapped at `vaddr`) with the contents of a file-backed
+/// `VmArea` starting at `area_start`.
+fn read_file_page(
+    page: &mut [u8],
+    vaddr: usize,
+    area_start: usize,
+    file: &Arc<dyn FileLike>,
+    offset: usize,
+    file_size: usize,
+) -> Result<()> {
+    let copy_start = core::cmp::max(vaddr, area_start);
+    let copy_end = core::cmp::min(vaddr + PAGE_SIZE, area_start + file_size);
+    if copy_start >= copy_end {
+        return Ok(());
+    }
+
+    file.read(
+        offset + (copy_start - area_start),
+        (&mut page[(copy_start - vaddr)..(copy_end - vaddr)]).into(),
+        &OpenOptions::readwrite(),
+    )?;
+    Ok(())
+}
+
+/// Writes a core dump of the **current** process. Returns `Ok(false)` if it is
+/// disabled by RLIMIT_CORE.
+pub fn dump_core(signal: Signal, frame: &PtRegs) -> Result<bool> {
+    let current = current_process();
+    let limit = current.rlimits().get(RLIMIT_CORE)?.cur;
+    if limit == 0 {
+        return Ok(false);
+    }
+
+    let vm_ref = current.vm();
+    let vm = match vm_ref.as_ref() {
+        Some(vm) => vm.clone(),
+        None => return Ok(false),
+    };
+    drop(vm_ref);
+
+    let pattern = core_pattern();
+    if pattern.starts_with('|') {
+        warn!("core_pattern: piping a core dump into a program is not supported");
+        return Ok(false);
+    }
+
+    let path = expand_core_pattern(&pattern, signal);
+    let file = create_core_file(&path)?;
+
+    // Collect VM areas and build notes.
+    let (areas, auxv) = {
+        let vm_lock = vm.lock();
+        let areas: Vec<_> = vm_lock
+            .vm_areas()
+            .iter()
+            .map(|area| {
+                (
+                    area.start(),
+                    area.end(),
+                    area.area_type().clone(),
+                    area.prot(),
+                )
+            })
+            .collect();
+        (areas, vm_lock.auxv().to_vec())
+    };
+
+    let mut notes = Vec::new();
+    push_note(
+        &mut notes,
+        NT_PRSTATUS,
+        as_bytes(&build_prstatus(signal, frame)),
+    );
+    push_note(&mut notes, NT_PRPSINFO, as_bytes(&build_prpsinfo()));
+
+    let mut auxv_desc = Vec::with_capacity(auxv.len() * 2 * size_of::<u64>());
+    for (auxv_type, value) in auxv {
+        auxv_desc.extend_from_slice(&(auxv_type as u64).to_le_bytes());
+        auxv_desc.extend_from_slice(&(value as u64).to_le_bytes());
+    }
+    push_note(&mut notes, NT_AUXV, &auxv_desc);
+
+    let mut file_entries = Vec::new();
+    let mut file_names = Vec::new();
+    for (start, end, area_type, _) in &areas {
+        if let VmAreaType::File { path, offset, .. } = area_type {
+            file_entries.push((
+                align_down(start.value(), PAGE_SIZE),
+                align_up(end.value(), PAGE_SIZE),
+                offset / PAGE_SIZE,
+            ));
+            file_names.extend_from_slice(path.resolve_absolute_path().as_str().as_bytes());
+            file_names.push(0);
+        }
+    }
+    let mut file_desc = Vec::new();
+    file_desc.extend_from_slice(&(file_entries.len() as u64).to_le_bytes());
+    file_desc.extend_from_slice(&(PAGE_SIZE as u64).to_le_bytes());
+    for (start, end, page_offset) in file_entries {
+        file_desc.extend_from_slice(&(start as u64).to_le_bytes());
+        file_desc.extend_from_slice(&(end as u64).to_le_bytes());
+        file_desc.extend_from_slice(&(page_offset as u64).to_le_bytes());
+    }
+    file_desc.extend_from_slice(&file_names);
+    push_note(&mut notes, NT_FILE, &file_desc);
+
+    // Determine the layout: the ELF header, program headers, notes, and then
+    // page-aligned memory contents.
+    let phnum = 1 + areas.len();
+    let notes_offset = SIZEOF_EHDR + phnum * SIZEOF_PHDR;
+    let mut data_offset = align_up(notes_offset + notes.len(), PAGE_SIZE);
+
+    let mut e_ident = [0; 16];
+    e_ident[..4].copy_from_slice(ELFMAG);
+    e_ident[EI_CLASS] = ELFCLASS64;
+    e_ident[EI_DATA] = ELFDATA2LSB;
+    e_ident[EI_VERSION] = EV_CURRENT;
+    let header = Header {
+        e_ident,
+        e_type: ET_CORE,
+        e_machine: EM_X86_64,
+        e_version: EV_CURRENT as u32,
+        e_entry: 0,
+        e_phoff: SIZEOF_EHDR as u64,
+        e_shoff: 0,
+        e_flags: 0,
+        e_ehsize: SIZEOF_EHDR as u16,
+        e_phentsize: SIZEOF_PHDR as u16,
+        e_phnum: phnum as u16,
+        e_shentsize: 0,
+        e_shnum: 0,
+        e_shstrndx: 0,
+    };
+
+    let mut phdrs = Vec::with_capacity(phnum);
+    phdrs.push(ProgramHeader {
+        p_type: PT_NOTE,
+        p_flags: 0,
+        p_offset: notes_offset as u64,
+        p_vaddr: 0,
+        p_paddr: 0,
+        p_filesz: notes.len() as u64,
+        p_memsz: 0,
+        p_align: 4,
+    });
+    for (start, end, _, prot) in &areas {
+        let vaddr = align_down(start.value(), PAGE_SIZE);
+        let len = (align_up(end.value(), PAGE_SIZE) - vaddr) as u64;
+        let mut p_flags = 0;
+        if prot.contains(MMapProt::PROT_READ) {
+            p_flags |= PF_R;
+        }
+        if prot.contains(MMapProt::PROT_WRITE) {
+            p_flags |= PF_W;
+        }
+        if prot.contains(MMapProt::PROT_EXEC) {
+            p_flags |= PF_X;
+        }
+
+        phdrs.push(ProgramHeader {
+            p_type: PT_LOAD,
+            p_flags,
+            p_offset: data_offset as u64,
+            p_vaddr: vaddr as u64,
+            p_paddr: 0,
+            p_filesz: len,
+            p_memsz: len,
+            p_align: PAGE_SIZE as u64,
+        });
+        data_offset += len as usize;
+    }
+
+    let mut writer = CoreWriter {
+        file,
+        offset: 0,
+        limit: limit as usize,
+    };
+    writer.write(as_bytes(&header))?;
+    for phdr in &phdrs {
+        writer.write(as_bytes(phdr))?;
+    }
+    writer.write(&notes)?;
+
+    // Copy memory contents page by page. Pages not yet populated by the page
+    // fault handler are read from the backing file (as the page fault handler
+    // would do) or filled with zeroes.
+    let mut page = vec![0; PAGE_SIZE];
+    for (phdr, (start, _, area_type, _)) in phdrs.iter().skip(1).zip(&areas) {
+        writer.fill_zeroes_until(phdr.p_offset as usize)?;
+        for i in 0..(phdr.p_filesz as usize / PAGE_SIZE) {
+            if writer.offset >= writer.limit {
+                break;
+            }
+
+            let vaddr = start.sub(start.value() % PAGE_SIZE).add(i * PAGE_SIZE);
+            // Don't hold the VM lock while reading the file: it may sleep.
+            let paddr = vm.lock().page_table().lookup_paddr(vaddr);
+            match paddr {
+                Some(paddr) => {
+                    let src = unsafe { slice::from_raw_parts(paddr.as_ptr::<u8>(), PAGE_SIZE) };
+                    page.copy_from_slice(src);
+                }
+                None => {
+                    page.fill(0);
+                    if let VmAreaType::File {
+                        file,
+                        offset,
+                        file_size,
+                        ..
+                    } = area_type
+                    {
+                        read_file_page(
+                            &mut page,
+                            vaddr.value(),
+                            start.value(),
+                            file,
+                            *offset,
+                            *file_size,
+                        )?;
+                    }
+                }
+            }
+
+            writer.write(&page)?;
+        }
+    }
+
+    info!(
+        "{:?}: dumped core to {} ({:?})",
+        current.pid(),
+        path.as_str(),
+        signal
+    );
+
+    Ok(true)
+}
@@ -20,6 +20,8 @@ pub enum Auxv {
     Pagesz(usize),
     /// 16 random bytes. Used for stack canary.
     Random([u8; 16]),
+    /// The address of the vDSO image.
+    SysinfoEhdr(UserVAddr),
 }
 
 fn push_bytes_to_stack(sp: &mut VAddr, stack_bottom: VAddr, buf: &[u8]) -> Result<()> {
@@ -52,12 +54,14 @@ fn push_aux_data_to_stack(sp: &mut VAddr, stack_bottom: VAddr, auxv: &Auxv) -> R
     Ok(())
 }
 
+/// Pushes an auxiliary vector entry and returns the pushed pair of its type
+/// and value.
 fn push_auxv_entry_to_stack(
     sp: &mut VAddr,
     stack_bottom: VAddr,
     auxv: &Auxv,
     data_ptr: Option<UserVAddr>,
-) -> Result<()> {
+) -> Result<(usize, usize)> {
     let (auxv_type, value) = match auxv {
         Auxv::Null => (0, 0),
         Auxv::Phdr(uaddr) => (3, uaddr.value()),
@@ -65,11 +69,12 @@ fn push_auxv_entry_to_stack(
         Auxv::Phnum(value) => (5, *value),
         Auxv::Pagesz(value) => (6, *value),
         Auxv::Random(_) => (25, data_ptr.unwrap().as_isize() as usize),
+        Auxv::SysinfoEhdr(uaddr) => (33, uaddr.value()),
     };
 
     push_usize_to_stack(sp, stack_bottom, value)?;
     push_usize_to_stack(sp, stack_bottom, auxv_type)?;
-    Ok(())
+    Ok((auxv_type, value))
 }
 
 pub(super) fn estimate_user_init_stack_size(
@@ -85,7 +90,12 @@ pub(super) fn estimate_user_init_stack_size(
 
     let aux_data_len = auxv.iter().fold(0, |l, aux| {
         l + match aux {
-            Auxv::Null | Auxv::Phdr(_) | Auxv::Phent(_) | Auxv::Phnum(_) | Auxv::Pagesz(_) => 0,
+            Auxv::Null
+            | Auxv::Phdr(_)
+            | Auxv::Phent(_)
+            | Auxv::Phnum(_)
+            | Auxv::Pagesz(_)
+            | Auxv::SysinfoEhdr(_) => 0,
             Auxv::Random(_) => 16,
         }
     });
@@ -97,6 +107,9 @@ pub(super) fn estimate_user_init_stack_size(
 }
 
 /// Initializes a user stack. See "Initial Process Stack" in <https://uclibc.org/docs/psABI-x86_64.pdf>.
+///
+/// Returns the initial user stack pointer and the auxiliary vector entries
+/// written into the stack.
 pub(super) fn init_user_stack(
     user_stack_top: UserVAddr,
     stack_top: VAddr,
@@ -104,7 +117,7 @@ pub(super) fn init_user_stack(
     argv: &[&[u8]],
     envp: &[&[u8]],
     auxv: &[Auxv],
-) -> Result<UserVAddr> {
+) -> Result<(UserVAddr, Vec<(usize, usize)>)> {
     let mut sp = stack_top;
     let kernel_sp_to_user_sp = |sp: VAddr| {
         let offset = stack_top.value() - sp.value();
@@ -138,10 +151,18 @@ pub(super) fn init_user_stack(
     sp = sp.align_down(size_of::<usize>());
 
     // Push auxiliary vector entries.
-    push_auxv_entry_to_stack(&mut sp, stack_bottom, &Auxv::Null, None)?;
+    let mut auxv_entries = Vec::with_capacity(auxv.len() + 1);
+    auxv_entries.push(push_auxv_entry_to_stack(
+        &mut sp,
+        stack_bottom,
+        &Auxv::Null,
+        None,
+    )?);
     for (aux, data) in auxv.iter().zip(auxv_ptrs) {
-        push_auxv_entry_to_stack(&mut sp, stack_bottom, aux, data)?;
+        auxv_entries.push(push_auxv_entry_to_stack(&mut sp, stack_bottom, aux, data)?);
     }
+    // The entries are pushed from the end of the vector.
+    auxv_entries.reverse();
 
     // Push environment pointers (`const char **envp`).
     push_usize_to_stack(&mut sp, stack_bottom, 0)?;
@@ -158,5 +179,5 @@ pub(super) fn init_user_stack(
     // Push argc.
     push_usize_to_stack(&mut sp, stack_bottom, argv.len())?;
 
-    Ok(kernel_sp_to_user_sp(sp))
+    Ok((kernel_sp_to_user_sp(sp), auxv_entries))
 }
@@ -0,0 +1,116 @@
+//! The load average calculation (the same fixed-point algorithm as Linux).
+use core::{
+    fmt,
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use kerla_runtime::arch::TICK_HZ;
+
+use super::{current_process, SCHEDULER};
+
+/// The number of fractional bits in a load average value.
+const FSHIFT: usize = 11;
+/// 1.0 in the fixed-point representation.
+const FIXED_1: usize = 1 << FSHIFT;
+/// The interval between samples: 5 seconds.
+const LOAD_FREQ: usize = 5 * TICK_HZ;
+/// `FIXED_1 / exp(5sec / 1min)`.
+const EXP_1: usize = 1884;
+/// `FIXED_1 / exp(5sec / 5min)`.
+const EXP_5: usize = 2014;
+/// `FIXED_1 / exp(5sec / 15min)`.
+const EXP_15: usize = 2037;
+
+static AVENRUN: [AtomicUsize; 3] = [
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+];
+
+/// A load average value in the fixed-point representation.
+#[derive(Debug, Copy, Clone)]
+pub struct LoadAverage(usize);
+
+impl fmt::Display for LoadAverage {
+    /// Formats the value like `0.42`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let int = self.0 >> FSHIFT;
+        let frac = ((self.0 & (FIXED_1 - 1)) * 100) >> FSHIFT;
+        write!(f, "{}.{:02}", int, frac)
+    }
+}
+
+/// Returns the 1, 5, and 15 minutes load averages.
+pub fn read_load_average() -> [LoadAverage; 3] {
+    [
+        LoadAverage(AVENRUN[0].load(Ordering::Relaxed)),
+        LoadAverage(AVENRUN[1].load(Ordering::Relaxed)),
+        LoadAverage(AVENRUN[2].load(Ordering::Relaxed)),
+    ]
+}
+
+/// Returns the number of runnable processes including the current one.
+pub fn num_running_processes() -> usize {
+    let running = if current_process().is_idle() { 0 } else { 1 };
+    SCHEDULER.lock().num_runnable() + running
+}
+
+fn calc_load(load: usize, exp: usize, active: usize) -> usize {
+    let new_load = load * exp + active * (FIXED_1 - exp);
+    // Round up if the load is increasing.
+    let new_load = if active >= load {
+        new_load + (FIXED_1 - 1)
+    } else {
+        new_load
+    };
+
+    new_load >> FSHIFT
+}
+
+/// Computes `x^n` in the fixed-point representation.
+fn fixed_power(mut x: usize, mut n: usize) -> usize {
+    let mut result = FIXED_1;
+    while n > 0 {
+        if n & 1 != 0 {
+            result = (result * x + FIXED_1 / 2) >> FSHIFT;
+        }
+
+        n >>= 1;
+        x = (x * x + FIXED_1 / 2) >> FSHIFT;
+    }
+
+    result
+}
+
+/// Samples the length of the run queue. Called from the timer interrupt handler
+/// every tick.
+pub fn sample_load_average(ticks: usize) {
+    if ticks % LOAD_FREQ != 0 {
+        return;
+    }
+
+    let active = num_running_processes() * FIXED_1;
+    for (avenrun, exp) in AVENRUN.iter().zip([EXP_1, EXP_5, EXP_15]) {
+        let load = avenrun.load(Ordering::Relaxed);
+        avenrun.store(calc_load(load, exp, active), Ordering::Relaxed);
+    }
+}
+
+/// Decays the load averages for the samples in the idle `ticks` (the ticks
+/// skipped while the periodic tick is stopped) in one step, as if no processes
+/// were running.
+pub fn decay_load_average(ticks: Range<usize>) {
+    let samples = ticks.end.div_ceil(LOAD_FREQ) - ticks.start.div_ceil(LOAD_FREQ);
+    if samples == 0 {
+        return;
+    }
+
+    for (avenrun, exp) in AVENRUN.iter().zip([EXP_1, EXP_5, EXP_15]) {
+        let load = avenrun.load(Ordering::Relaxed);
+        avenrun.store(
+            calc_load(load, fixed_power(exp, samples), 0),
+            Ordering::Relaxed,
+        );
+    }
+}
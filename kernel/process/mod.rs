@@ -6,17 +6,27 @@ use kerla_utils::lazy::Lazy;
 use kerla_utils::once::Once;
 
 mod cmdline;
+pub mod core_dump;
 mod elf;
 mod init_stack;
+mod loadavg;
+mod mutex;
 #[allow(clippy::module_inception)]
 mod process;
 pub mod process_group;
+pub mod rlimit;
 mod scheduler;
 pub mod signal;
 mod switch;
 mod wait_queue;
 
-pub use process::{gc_exited_processes, read_process_stats, PId, Process, ProcessState};
+pub use loadavg::{
+    decay_load_average, num_running_processes, read_load_average, sample_load_average,
+};
+pub use mutex::{Mutex, MutexGuard};
+pub use process::{
+    gc_exited_processes, list_processes, read_process_stats, PId, Process, ProcessState,
+};
 pub use switch::switch;
 pub use wait_queue::WaitQueue;
 
@@ -38,6 +48,19 @@ pub fn current_process() -> &'static Arc<Process> {
     CURRENT.get()
 }
 
+/// Returns `true` if the current context can sleep on a wait queue, that is,
+/// we're neither in the boot code nor in the idle thread.
+pub fn can_sleep() -> bool {
+    CURRENT.get().is_initialized() && current_process().pid() != PId::new(0)
+}
+
+/// Returns `true` if the current process has the superuser privileges (e.g.
+/// `CAP_SYS_TIME`). Always `true` for now: we don't support users and all
+/// processes run as root.
+pub fn is_privileged() -> bool {
+    true
+}
+
 pub fn init() {
     JOIN_WAIT_QUEUE.init(WaitQueue::new);
     SCHEDULER.init(|| SpinLock::new(Scheduler::new()));
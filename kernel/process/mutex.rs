@@ -0,0 +1,73 @@
+//! A sleeping lock. Unlike `SpinLock`, the owner may sleep while holding it
+//! (e.g. to wait for disk I/O).
+use super::{can_sleep, WaitQueue};
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+pub struct Mutex<T: ?Sized> {
+    locked: AtomicBool,
+    wait_queue: WaitQueue,
+    value: UnsafeCell<T>,
+}
+
+impl<T> Mutex<T> {
+    pub const fn new(value: T) -> Mutex<T> {
+        Mutex {
+            locked: AtomicBool::new(false),
+            wait_queue: WaitQueue::new(),
+            value: UnsafeCell::new(value),
+        }
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    fn try_lock(&self) -> Option<()> {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| ())
+    }
+
+    /// Acquires the lock. Sleeps (non-signalable) until it gets released.
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        if can_sleep() {
+            self.wait_queue.sleep_until(|| self.try_lock());
+        } else {
+            // We're in the boot code: no one else can hold the lock.
+            self.try_lock()
+                .expect("Mutex: already locked in the boot code");
+        }
+
+        MutexGuard { mutex: self }
+    }
+}
+
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+
+pub struct MutexGuard<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+}
+
+impl<'a, T: ?Sized> Drop for MutexGuard<'a, T> {
+    fn drop(&mut self) {
+        self.mutex.locked.store(false, Ordering::Release);
+        self.mutex.wait_queue.wake_all();
+    }
+}
+
+impl<'a, T: ?Sized> Deref for MutexGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for MutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
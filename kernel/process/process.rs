@@ -3,6 +3,7 @@ use crate::{
     ctypes::*,
     fs::{
         devfs::SERIAL_TTY,
+        file_lock,
         inode::FileLike,
         mount::RootFs,
         opened_file::{Fd, OpenFlags, OpenOptions, OpenedFile, OpenedFileTable, PathComponent},
@@ -12,14 +13,22 @@ use crate::{
     prelude::*,
     process::{
         cmdline::Cmdline,
+        core_dump::dump_core,
         current_process,
         elf::{Elf, ProgramHeader},
         init_stack::{estimate_user_init_stack_size, init_user_stack, Auxv},
+        mutex::Mutex,
         process_group::{PgId, ProcessGroup},
-        signal::{SigAction, SigSet, Signal, SignalDelivery, SignalMask, SIGCHLD, SIGKILL},
-        switch, UserVAddr, JOIN_WAIT_QUEUE, SCHEDULER,
+        rlimit::ResourceLimits,
+        signal::{
+            SigAction, SigSet, Signal, SignalDelivery, SignalMask, DEFAULT_ACTIONS, SIGCHLD,
+            SIGKILL,
+        },
+        switch,
+        switch::current_time_slice_nanosecs,
+        UserVAddr, JOIN_WAIT_QUEUE, SCHEDULER,
     },
-    random::read_secure_random,
+    random::read_insecure_random,
     result::Errno,
     INITIAL_ROOT_FS,
 };
@@ -31,8 +40,9 @@ use core::mem::size_of;
 use core::sync::atomic::{AtomicI32, Ordering};
 use core::{cmp::max, sync::atomic::AtomicUsize};
 use crossbeam::atomic::AtomicCell;
-use goblin::elf64::program_header::PT_LOAD;
+use goblin::elf64::program_header::{PF_R, PF_W, PF_X, PT_LOAD};
 use kerla_runtime::{
+    address::VAddr,
     arch::{PtRegs, PAGE_SIZE},
     page_allocator::{alloc_pages, AllocPageFlags},
     spinlock::{SpinLock, SpinLockGuard},
@@ -46,18 +56,28 @@ pub(super) static PROCESSES: SpinLock<ProcessTable> = SpinLock::new(BTreeMap::ne
 pub(super) static EXITED_PROCESSES: SpinLock<Vec<Arc<Process>>> = SpinLock::new(Vec::new());
 
 static FORK_TOTAL: AtomicUsize = AtomicUsize::new(0);
+static LAST_PID: AtomicI32 = AtomicI32::new(1);
 
 #[derive(Debug)]
 pub struct Stats {
     pub fork_total: usize,
+    pub num_processes: usize,
+    pub last_pid: PId,
 }
 
 pub fn read_process_stats() -> Stats {
     Stats {
         fork_total: FORK_TOTAL.load(Ordering::SeqCst),
+        num_processes: PROCESSES.lock().len(),
+        last_pid: PId::new(LAST_PID.load(Ordering::SeqCst)),
     }
 }
 
+/// Returns all processes in the process table.
+pub fn list_processes() -> Vec<Arc<Process>> {
+    PROCESSES.lock().values().cloned().collect()
+}
+
 /// Returns an unused PID. Note that this function does not reserve the PID:
 /// keep the process table locked until you insert the process into the table!
 pub(super) fn alloc_pid(table: &mut ProcessTable) -> Result<PId> {
@@ -72,6 +92,7 @@ pub(super) fn alloc_pid(table: &mut ProcessTable) -> Result<PId> {
         }
 
         if !table.contains_key(&PId::new(pid)) {
+            LAST_PID.store(pid, Ordering::SeqCst);
             return Ok(PId::new(pid));
         }
 
@@ -101,7 +122,10 @@ pub enum ProcessState {
     Runnable,
     /// The process is sleeping. It can be resumed by signals.
     BlockedSignalable,
-    /// The process has exited.
+    /// The process is sleeping. Signals are delivered after it's resumed.
+    Blocked,
+    /// The process has exited. The value is the status reported by wait4(2)
+    /// (e.g. `WEXITSTATUS` and `WTERMSIG`).
     ExitedWith(c_int),
 }
 
@@ -117,10 +141,13 @@ pub struct Process {
     children: SpinLock<Vec<Arc<Process>>>,
     vm: AtomicRefCell<Option<Arc<SpinLock<Vm>>>>,
     opened_files: Arc<SpinLock<OpenedFileTable>>,
-    root_fs: Arc<SpinLock<RootFs>>,
+    root_fs: Arc<Mutex<RootFs>>,
     signals: Arc<SpinLock<SignalDelivery>>,
     signaled_frame: AtomicCell<Option<PtRegs>>,
     sigset: SpinLock<SigSet>,
+    rlimits: SpinLock<ResourceLimits>,
+    /// Nanoseconds spent on CPUs, excluding the current time slice.
+    cpu_time: AtomicUsize,
 }
 
 impl Process {
@@ -145,15 +172,51 @@ impl Process {
             signals: Arc::new(SpinLock::new(SignalDelivery::new())),
             signaled_frame: AtomicCell::new(None),
             sigset: SpinLock::new(SigSet::ZERO),
+            rlimits: SpinLock::new(ResourceLimits::new()),
+            cpu_time: AtomicUsize::new(0),
         });
 
         process_group.lock().add(Arc::downgrade(&proc));
         Ok(proc)
     }
 
+    /// Creates a kernel thread.
+    ///
+    /// A kernel thread is scheduled like user processes but it does not have
+    /// its own address space (`Vm`) and always runs in the kernel mode with
+    /// interrupts enabled. `entry` must never return.
+    pub fn new_kthread(name: &str, entry: extern "C" fn() -> !) -> Result<Arc<Process>> {
+        let mut process_table = PROCESSES.lock();
+        let pid = alloc_pid(&mut process_table)?;
+        let process_group = ProcessGroup::find_or_create_by_pgid(PgId::new(0));
+        let process = Arc::new(Process {
+            is_idle: false,
+            process_group: AtomicRefCell::new(Arc::downgrade(&process_group)),
+            pid,
+            parent: Weak::new(),
+            children: SpinLock::new(Vec::new()),
+            state: AtomicCell::new(ProcessState::Runnable),
+            cmdline: AtomicRefCell::new(Cmdline::from_argv(&[name.as_bytes()])),
+            arch: arch::Process::new_kthread(VAddr::new(entry as usize)),
+            vm: AtomicRefCell::new(None),
+            opened_files: Arc::new(SpinLock::new(OpenedFileTable::new())),
+            root_fs: INITIAL_ROOT_FS.clone(),
+            signals: Arc::new(SpinLock::new(SignalDelivery::new())),
+            signaled_frame: AtomicCell::new(None),
+            sigset: SpinLock::new(SigSet::ZERO),
+            rlimits: SpinLock::new(ResourceLimits::new()),
+            cpu_time: AtomicUsize::new(0),
+        });
+
+        process_group.lock().add(Arc::downgrade(&process));
+        process_table.insert(pid, process.clone());
+        SCHEDULER.lock().enqueue(pid);
+        Ok(process)
+    }
+
     /// Creates the initial process (PID=1).
     pub fn new_init_process(
-        root_fs: Arc<SpinLock<RootFs>>,
+        root_fs: Arc<Mutex<RootFs>>,
         executable_path: Arc<PathComponent>,
         console: Arc<PathComponent>,
         argv: &[&[u8]],
@@ -206,6 +269,8 @@ impl Process {
             signals: Arc::new(SpinLock::new(SignalDelivery::new())),
             signaled_frame: AtomicCell::new(None),
             sigset: SpinLock::new(SigSet::ZERO),
+            rlimits: SpinLock::new(ResourceLimits::new()),
+            cpu_time: AtomicUsize::new(0),
         });
 
         process_group.lock().add(Arc::downgrade(&process));
@@ -226,6 +291,11 @@ impl Process {
         self.is_idle
     }
 
+    /// Returns true if the process is a kernel thread (including idle threads).
+    pub fn is_kernel_thread(&self) -> bool {
+        self.vm.borrow().is_none()
+    }
+
     /// The process ID.
     pub fn pid(&self) -> PId {
         self.pid
@@ -267,7 +337,7 @@ impl Process {
     }
 
     /// The process's path resolution info.
-    pub fn root_fs(&self) -> &Arc<SpinLock<RootFs>> {
+    pub fn root_fs(&self) -> &Arc<Mutex<RootFs>> {
         &self.root_fs
     }
 
@@ -286,6 +356,26 @@ impl Process {
         &self.signals
     }
 
+    /// Resource limits.
+    pub fn rlimits(&self) -> SpinLockGuard<'_, ResourceLimits> {
+        self.rlimits.lock()
+    }
+
+    /// Nanoseconds the process has spent on CPUs.
+    pub fn cpu_time_nanosecs(&self) -> usize {
+        let cpu_time = self.cpu_time.load(Ordering::Relaxed);
+        if core::ptr::eq(self, &**current_process()) {
+            cpu_time + current_time_slice_nanosecs()
+        } else {
+            cpu_time
+        }
+    }
+
+    /// Accounts the time slice the process has just used up.
+    pub(super) fn add_cpu_time(&self, nanosecs: usize) {
+        self.cpu_time.fetch_add(nanosecs, Ordering::Relaxed);
+    }
+
     /// Changes the process group.
     pub fn set_process_group(&self, pg: Weak<SpinLock<ProcessGroup>>) {
         *self.process_group.borrow_mut() = pg;
@@ -312,7 +402,9 @@ impl Process {
         self.state.store(new_state);
         match new_state {
             ProcessState::Runnable => {}
-            ProcessState::BlockedSignalable | ProcessState::ExitedWith(_) => {
+            ProcessState::BlockedSignalable
+            | ProcessState::Blocked
+            | ProcessState::ExitedWith(_) => {
                 scheduler.remove(self.pid);
             }
         }
@@ -338,12 +430,26 @@ impl Process {
 
     /// Terminates the **current** process.
     pub fn exit(status: c_int) -> ! {
+        Process::exit_with_wait_status((status & 0xff) << 8)
+    }
+
+    fn exit_with_wait_status(wait_status: c_int) -> ! {
         let current = current_process();
         if current.pid == PId::new(1) {
             panic!("init (pid=0) tried to exit")
         }
 
-        current.set_state(ProcessState::ExitedWith(status));
+        // Close opened files here instead of in Drop::drop because `proc` is
+        // not dropped until it's joined by the parent process. Drop them to
+        // make pipes closed.
+        //
+        // Drop them after unlocking the table and before changing the state:
+        // closing a file may sleep (e.g. inotify events look up the inode).
+        let closed_files = current.opened_files.lock().close_all();
+        drop(closed_files);
+        file_lock::release_process_locks(current.pid);
+
+        current.set_state(ProcessState::ExitedWith(wait_status));
         if let Some(parent) = current.parent.upgrade() {
             if parent.signals().lock().get_action(SIGCHLD) == SigAction::Ignore {
                 // If the parent process is not waiting for a child,
@@ -358,11 +464,6 @@ impl Process {
             }
         }
 
-        // Close opened files here instead of in Drop::drop because `proc` is
-        // not dropped until it's joined by the parent process. Drop them to
-        // make pipes closed.
-        current.opened_files.lock().close_all();
-
         PROCESSES.lock().remove(&current.pid);
         JOIN_WAIT_QUEUE.wake_all();
         switch();
@@ -375,15 +476,38 @@ impl Process {
         Process::exit(status)
     }
 
-    /// Terminates the **current** process by a signal.
-    pub fn exit_by_signal(_signal: Signal) -> ! {
-        Process::exit(1 /* FIXME: how should we compute the exit status? */);
+    /// Terminates the **current** process by a signal. If the default action
+    /// of the signal is to dump core, it writes a core file before exiting.
+    pub fn exit_by_signal(signal: Signal, frame: &PtRegs) -> ! {
+        let mut wait_status = signal & 0x7f;
+        if DEFAULT_ACTIONS.get(signal as usize) == Some(&SigAction::CoreDump) {
+            match dump_core(signal, frame) {
+                Ok(true) => wait_status |= 0x80,
+                Ok(false) => {}
+                Err(err) => {
+                    warn!(
+                        "{:?}: failed to write a core dump: {:?}",
+                        current_process().pid,
+                        err
+                    );
+                }
+            }
+        }
+
+        Process::exit_with_wait_status(wait_status)
     }
 
     /// Sends a signal.
     pub fn send_signal(&self, signal: Signal) {
+        if self.is_kernel_thread() {
+            // Kernel threads ignore signals as Linux does.
+            return;
+        }
+
         self.signals.lock().signal(signal);
-        self.resume();
+        if self.state() != ProcessState::Blocked {
+            self.resume();
+        }
     }
 
     /// Returns `true` if there's a pending signal.
@@ -424,14 +548,20 @@ impl Process {
     /// address and stack pointer) to call the registered user's signal handler.
     pub fn try_delivering_signal(frame: &mut PtRegs) -> Result<()> {
         let current = current_process();
-        if let Some((signal, sigaction)) = current.signals.lock().pop_pending() {
-            let sigset = current.sigset.lock();
-            if !sigset.get(signal as usize).as_deref().unwrap_or(&true) {
+        let pending = current.signals.lock().pop_pending();
+        if let Some((signal, sigaction)) = pending {
+            let blocked = *current
+                .sigset
+                .lock()
+                .get(signal as usize)
+                .as_deref()
+                .unwrap_or(&true);
+            if !blocked {
                 match sigaction {
                     SigAction::Ignore => {}
-                    SigAction::Terminate => {
+                    SigAction::Terminate | SigAction::CoreDump => {
                         trace!("terminating {:?} by {:?}", current.pid, signal,);
-                        Process::exit(1 /* FIXME: */);
+                        Process::exit_by_signal(signal, frame);
                     }
                     SigAction::Handler { handler } => {
                         trace!("delivering {:?} to {:?}", signal, current.pid,);
@@ -457,7 +587,7 @@ impl Process {
         } else {
             // The user intentionally called sigreturn(2) while it is not signaled.
             // TODO: Should we ignore instead of the killing the process?
-            Process::exit_by_signal(SIGKILL);
+            Process::exit_by_signal(SIGKILL, current_frame);
         }
     }
 
@@ -474,7 +604,11 @@ impl Process {
         envp: &[&[u8]],
     ) -> Result<()> {
         let current = current_process();
-        current.opened_files.lock().close_cloexec_files();
+        let closed_files = current.opened_files.lock().close_cloexec_files();
+        for closed_file in closed_files {
+            file_lock::release_posix_locks(current.pid, &closed_file);
+        }
+
         current.cmdline.borrow_mut().set_by_argv(argv);
 
         let entry = setup_userspace(executable_path, argv, envp, &current.root_fs)?;
@@ -519,6 +653,8 @@ impl Process {
             signals: Arc::new(SpinLock::new(SignalDelivery::new())), // TODO: #88 has to address this
             signaled_frame: AtomicCell::new(None),
             sigset: SpinLock::new(*sig_set),
+            rlimits: SpinLock::new(parent.rlimits().clone()),
+            cpu_time: AtomicUsize::new(0),
         });
 
         process_group.lock().add(Arc::downgrade(&child));
@@ -556,7 +692,7 @@ fn setup_userspace(
     executable_path: Arc<PathComponent>,
     argv: &[&[u8]],
     envp: &[&[u8]],
-    root_fs: &Arc<SpinLock<RootFs>>,
+    root_fs: &Arc<Mutex<RootFs>>,
 ) -> Result<UserspaceEntry> {
     do_setup_userspace(executable_path, argv, envp, root_fs, true)
 }
@@ -565,7 +701,7 @@ fn do_script_binfmt(
     executable_path: &Arc<PathComponent>,
     script_argv: &[&[u8]],
     envp: &[&[u8]],
-    root_fs: &Arc<SpinLock<RootFs>>,
+    root_fs: &Arc<Mutex<RootFs>>,
     buf: &[u8],
 ) -> Result<UserspaceEntry> {
     // Set up argv[] with the interpreter and its arguments from the shebang line.
@@ -597,6 +733,7 @@ fn do_script_binfmt(
 }
 
 fn do_elf_binfmt(
+    executable_path: &Arc<PathComponent>,
     executable: &Arc<dyn FileLike>,
     argv: &[&[u8]],
     envp: &[&[u8]],
@@ -615,7 +752,22 @@ fn do_elf_binfmt(
     }
 
     let mut random_bytes = [0u8; 16];
-    read_secure_random(((&mut random_bytes) as &mut [u8]).into())?;
+    // Don't block in execve(2) even if the CRNG is not yet initialized.
+    read_insecure_random(((&mut random_bytes) as &mut [u8]).into())?;
+
+    const USER_STACK_LEN: usize = 128 * 1024; // TODO: Implement rlimit
+    let init_stack_top = file_header_top.sub(buf.len());
+    let user_stack_bottom = init_stack_top.sub(USER_STACK_LEN).value();
+    let user_heap_bottom = align_up(end_of_image, PAGE_SIZE);
+    if user_heap_bottom >= user_stack_bottom {
+        return Err(Errno::E2BIG.into());
+    }
+
+    let mut vm = Vm::new(
+        UserVAddr::new(user_stack_bottom).unwrap(),
+        UserVAddr::new(user_heap_bottom).unwrap(),
+    )?;
+    let vdso = vm.add_vdso_area()?;
 
     // Set up the user stack.
     let auxv = &[
@@ -628,18 +780,15 @@ fn do_elf_binfmt(
         Auxv::Phent(size_of::<ProgramHeader>()),
         Auxv::Pagesz(PAGE_SIZE),
         Auxv::Random(random_bytes),
+        Auxv::SysinfoEhdr(vdso),
     ];
-    const USER_STACK_LEN: usize = 128 * 1024; // TODO: Implement rlimit
-    let init_stack_top = file_header_top.sub(buf.len());
-    let user_stack_bottom = init_stack_top.sub(USER_STACK_LEN).value();
-    let user_heap_bottom = align_up(end_of_image, PAGE_SIZE);
     let init_stack_len = align_up(estimate_user_init_stack_size(argv, envp, auxv), PAGE_SIZE);
-    if user_heap_bottom >= user_stack_bottom || init_stack_len >= USER_STACK_LEN {
+    if init_stack_len >= USER_STACK_LEN {
         return Err(Errno::E2BIG.into());
     }
 
     let init_stack_pages = alloc_pages(init_stack_len / PAGE_SIZE, AllocPageFlags::KERNEL)?;
-    let user_sp = init_user_stack(
+    let (user_sp, auxv_entries) = init_user_stack(
         init_stack_top,
         init_stack_pages.as_vaddr().add(init_stack_len),
         init_stack_pages.as_vaddr(),
@@ -648,10 +797,7 @@ fn do_elf_binfmt(
         auxv,
     )?;
 
-    let mut vm = Vm::new(
-        UserVAddr::new(user_stack_bottom).unwrap(),
-        UserVAddr::new(user_heap_bottom).unwrap(),
-    )?;
+    vm.set_auxv(auxv_entries);
     for i in 0..(buf.len() / PAGE_SIZE) {
         vm.page_table_mut().map_user_page(
             file_header_top.sub(((buf.len() / PAGE_SIZE) - i) * PAGE_SIZE),
@@ -675,6 +821,7 @@ fn do_elf_binfmt(
         let area_type = if phdr.p_filesz > 0 {
             VmAreaType::File {
                 file: executable.clone(),
+                path: executable_path.clone(),
                 offset: phdr.p_offset as usize,
                 file_size: phdr.p_filesz as usize,
             }
@@ -682,10 +829,16 @@ fn do_elf_binfmt(
             VmAreaType::Anonymous
         };
 
+        let mut prot = MMapProt::empty();
+        prot.set(MMapProt::PROT_READ, phdr.p_flags & PF_R != 0);
+        prot.set(MMapProt::PROT_WRITE, phdr.p_flags & PF_W != 0);
+        prot.set(MMapProt::PROT_EXEC, phdr.p_flags & PF_X != 0);
+
         vm.add_vm_area(
             UserVAddr::new_nonnull(phdr.p_vaddr as usize)?,
             phdr.p_memsz as usize,
             area_type,
+            prot,
         )?;
     }
 
@@ -698,7 +851,7 @@ fn do_setup_userspace(
     executable_path: Arc<PathComponent>,
     argv: &[&[u8]],
     envp: &[&[u8]],
-    root_fs: &Arc<SpinLock<RootFs>>,
+    root_fs: &Arc<Mutex<RootFs>>,
     handle_shebang: bool,
 ) -> Result<UserspaceEntry> {
     // Read the ELF header in the executable file.
@@ -714,7 +867,14 @@ fn do_setup_userspace(
         return do_script_binfmt(&executable_path, argv, envp, root_fs, buf);
     }
 
-    do_elf_binfmt(executable, argv, envp, file_header_pages, buf)
+    do_elf_binfmt(
+        &executable_path,
+        executable,
+        argv,
+        envp,
+        file_header_pages,
+        buf,
+    )
 }
 
 pub fn gc_exited_processes() {
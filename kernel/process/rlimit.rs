@@ -0,0 +1,113 @@
+use crate::{ctypes::c_int, prelude::*};
+
+pub type Resource = c_int;
+#[allow(unused)]
+pub const RLIMIT_CPU: Resource = 0;
+#[allow(unused)]
+pub const RLIMIT_FSIZE: Resource = 1;
+#[allow(unused)]
+pub const RLIMIT_DATA: Resource = 2;
+#[allow(unused)]
+pub const RLIMIT_STACK: Resource = 3;
+pub const RLIMIT_CORE: Resource = 4;
+#[allow(unused)]
+pub const RLIMIT_RSS: Resource = 5;
+#[allow(unused)]
+pub const RLIMIT_NPROC: Resource = 6;
+#[allow(unused)]
+pub const RLIMIT_NOFILE: Resource = 7;
+#[allow(unused)]
+pub const RLIMIT_MEMLOCK: Resource = 8;
+#[allow(unused)]
+pub const RLIMIT_AS: Resource = 9;
+#[allow(unused)]
+pub const RLIMIT_LOCKS: Resource = 10;
+#[allow(unused)]
+pub const RLIMIT_SIGPENDING: Resource = 11;
+#[allow(unused)]
+pub const RLIMIT_MSGQUEUE: Resource = 12;
+#[allow(unused)]
+pub const RLIMIT_NICE: Resource = 13;
+#[allow(unused)]
+pub const RLIMIT_RTPRIO: Resource = 14;
+#[allow(unused)]
+pub const RLIMIT_RTTIME: Resource = 15;
+
+const RLIM_NLIMITS: usize = 16;
+
+pub const RLIM_INFINITY: u64 = !0;
+
+/// `struct rlimit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct RLimit {
+    /// The soft limit.
+    pub cur: u64,
+    /// The hard limit (the ceiling for `cur`).
+    pub max: u64,
+}
+
+impl RLimit {
+    pub const fn new(cur: u64, max: u64) -> RLimit {
+        RLimit { cur, max }
+    }
+
+    pub const fn infinity() -> RLimit {
+        RLimit::new(RLIM_INFINITY, RLIM_INFINITY)
+    }
+}
+
+/// The initial limits of the init process. Mostly the same as Linux's `INIT_RLIMITS`.
+const DEFAULT_LIMITS: [RLimit; RLIM_NLIMITS] = [
+    /* RLIMIT_CPU */ RLimit::infinity(),
+    /* RLIMIT_FSIZE */ RLimit::infinity(),
+    /* RLIMIT_DATA */ RLimit::infinity(),
+    /* RLIMIT_STACK */ RLimit::new(8 * 1024 * 1024, RLIM_INFINITY),
+    /* RLIMIT_CORE */ RLimit::new(0, RLIM_INFINITY),
+    /* RLIMIT_RSS */ RLimit::infinity(),
+    /* RLIMIT_NPROC */ RLimit::infinity(),
+    /* RLIMIT_NOFILE */ RLimit::new(1024, 4096),
+    /* RLIMIT_MEMLOCK */ RLimit::new(64 * 1024, 64 * 1024),
+    /* RLIMIT_AS */ RLimit::infinity(),
+    /* RLIMIT_LOCKS */ RLimit::infinity(),
+    /* RLIMIT_SIGPENDING */ RLimit::infinity(),
+    /* RLIMIT_MSGQUEUE */ RLimit::new(819200, 819200),
+    /* RLIMIT_NICE */ RLimit::new(0, 0),
+    /* RLIMIT_RTPRIO */ RLimit::new(0, 0),
+    /* RLIMIT_RTTIME */ RLimit::infinity(),
+];
+
+/// Per-process resource limits. Inherited by child processes.
+#[derive(Clone)]
+pub struct ResourceLimits {
+    limits: [RLimit; RLIM_NLIMITS],
+}
+
+impl ResourceLimits {
+    pub fn new() -> ResourceLimits {
+        ResourceLimits {
+            limits: DEFAULT_LIMITS,
+        }
+    }
+
+    pub fn get(&self, resource: Resource) -> Result<RLimit> {
+        self.limits
+            .get(resource as usize)
+            .copied()
+            .ok_or_else(|| Error::new(Errno::EINVAL))
+    }
+
+    pub fn set(&mut self, resource: Resource, new_limit: RLimit) -> Result<()> {
+        if new_limit.cur > new_limit.max {
+            return Err(Errno::EINVAL.into());
+        }
+
+        // TODO: Return EPERM if an unprivileged process raises the hard limit.
+        let limit = self
+            .limits
+            .get_mut(resource as usize)
+            .ok_or_else(|| Error::new(Errno::EINVAL))?;
+        *limit = new_limit;
+        Ok(())
+    }
+}
@@ -30,6 +30,11 @@ impl Scheduler {
         self.run_queue.lock().pop_front()
     }
 
+    /// Returns the number of processes in the runqueue.
+    pub fn num_runnable(&self) -> usize {
+        self.run_queue.lock().len()
+    }
+
     /// Removes the process from the runqueue.
     pub fn remove(&self, pid: PId) {
         self.run_queue.lock().retain(|p| *p != pid);
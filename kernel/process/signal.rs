@@ -75,7 +75,11 @@ pub const SIG_IGN: usize = 1;
 pub enum SigAction {
     Ignore,
     Terminate,
-    Handler { handler: UserVAddr },
+    /// Terminates the process and writes a core dump.
+    CoreDump,
+    Handler {
+        handler: UserVAddr,
+    },
 }
 
 // TODO: Fill correct default actions
@@ -83,15 +87,15 @@ pub const DEFAULT_ACTIONS: [SigAction; SIGMAX as usize] = [
     /* (unused) */ SigAction::Ignore,
     /* SIGHUP */ SigAction::Ignore,
     /* SIGINT */ SigAction::Terminate,
-    /* SIGQUIT */ SigAction::Ignore,
-    /* SIGILL */ SigAction::Ignore,
-    /* SIGTRAP */ SigAction::Ignore,
-    /* SIGABRT */ SigAction::Ignore,
-    /* SIGBUS */ SigAction::Ignore,
-    /* SIGFPE */ SigAction::Ignore,
+    /* SIGQUIT */ SigAction::CoreDump,
+    /* SIGILL */ SigAction::CoreDump,
+    /* SIGTRAP */ SigAction::CoreDump,
+    /* SIGABRT */ SigAction::CoreDump,
+    /* SIGBUS */ SigAction::CoreDump,
+    /* SIGFPE */ SigAction::CoreDump,
     /* SIGKILL */ SigAction::Ignore,
     /* SIGUSR1 */ SigAction::Ignore,
-    /* SIGSEGV */ SigAction::Ignore,
+    /* SIGSEGV */ SigAction::CoreDump,
     /* SIGUSR2 */ SigAction::Ignore,
     /* SIGPIPE */ SigAction::Ignore,
     /* SIGALRM */ SigAction::Ignore,
@@ -104,14 +108,14 @@ pub const DEFAULT_ACTIONS: [SigAction; SIGMAX as usize] = [
     /* SIGTTIN */ SigAction::Ignore,
     /* SIGTTOU */ SigAction::Ignore,
     /* SIGURG */ SigAction::Ignore,
-    /* SIGXCPU */ SigAction::Ignore,
-    /* SIGXFSZ */ SigAction::Ignore,
+    /* SIGXCPU */ SigAction::CoreDump,
+    /* SIGXFSZ */ SigAction::CoreDump,
     /* SIGVTALRM */ SigAction::Ignore,
     /* SIGPROF */ SigAction::Ignore,
     /* SIGWINCH */ SigAction::Ignore,
     /* SIGIO */ SigAction::Ignore,
     /* SIGPWR */ SigAction::Ignore,
-    /* SIGSYS */ SigAction::Ignore,
+    /* SIGSYS */ SigAction::CoreDump,
 ];
 
 pub struct SignalDelivery {
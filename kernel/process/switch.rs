@@ -3,12 +3,24 @@ use crate::process::PId;
 use crate::{
     arch::{self},
     process::process::PROCESSES,
+    timer::read_monotonic_clock,
 };
 
 use alloc::sync::Arc;
 
 use core::mem::{self};
 
+cpu_local! {
+    // The monotonic clock when the current process started running.
+    static ref TIME_SLICE_STARTED_AT: usize = 0;
+}
+
+/// Returns nanoseconds the current process has been running since it's
+/// switched to.
+pub fn current_time_slice_nanosecs() -> usize {
+    read_monotonic_clock().nanosecs() - TIME_SLICE_STARTED_AT.get()
+}
+
 /// Yields execution to another thread.
 pub fn switch() {
     let prev = current_process().clone();
@@ -59,6 +71,10 @@ pub fn switch() {
         Arc::decrement_strong_count(Arc::as_ptr(&next));
     }
 
+    let now = read_monotonic_clock().nanosecs();
+    prev.add_cpu_time(now - TIME_SLICE_STARTED_AT.get());
+    TIME_SLICE_STARTED_AT.set(now);
+
     // Switch into the next thread.
     CURRENT.as_mut().set(next.clone());
     arch::switch_thread(prev.arch(), next.arch());
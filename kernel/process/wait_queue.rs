@@ -1,16 +1,16 @@
-use super::{current_process, switch, Process, ProcessState};
+use super::{can_sleep, current_process, switch, Process, ProcessState};
 use crate::result::Errno;
 use crate::result::Result;
 
 use alloc::{collections::VecDeque, sync::Arc};
-use kerla_runtime::spinlock::SpinLock;
+use kerla_runtime::{arch::idle, spinlock::SpinLock};
 
 pub struct WaitQueue {
     queue: SpinLock<VecDeque<Arc<Process>>>,
 }
 
 impl WaitQueue {
-    pub fn new() -> WaitQueue {
+    pub const fn new() -> WaitQueue {
         WaitQueue {
             queue: SpinLock::new(VecDeque::new()),
         }
@@ -69,6 +69,53 @@ impl WaitQueue {
         }
     }
 
+    /// Sleeps on the wait queue until `sleep_if_none` returns `Some`. Unlike
+    /// `sleep_signalable_until`, signals don't interrupt the sleep: use this
+    /// only for operations that will complete soon (e.g. disk I/O).
+    pub fn sleep_until<F, R>(&self, mut sleep_if_none: F) -> R
+    where
+        F: FnMut() -> Option<R>,
+    {
+        loop {
+            // See `sleep_signalable_until` for why we enqueue the current
+            // process first.
+            current_process().set_state(ProcessState::Blocked);
+            self.queue.lock().push_back(current_process().clone());
+
+            if let Some(ret_value) = sleep_if_none() {
+                current_process().resume();
+                self.queue
+                    .lock()
+                    .retain(|proc| !Arc::ptr_eq(proc, current_process()));
+                return ret_value;
+            }
+
+            switch();
+        }
+    }
+
+    /// Waits for `is_done` to return `Some` like `sleep_until`. If the current
+    /// context can't sleep (the boot code or the idle thread), it halts the
+    /// CPU until an interrupt arrives instead. Use this to wait for devices.
+    pub fn sleep_or_idle_until<F, R>(&self, mut is_done: F) -> R
+    where
+        F: FnMut() -> Option<R>,
+    {
+        if can_sleep() {
+            return self.sleep_until(is_done);
+        }
+
+        // Interrupts are disabled here. `idle` enables interrupts only while
+        // halting the CPU so we won't miss the interrupt.
+        loop {
+            if let Some(ret_value) = is_done() {
+                return ret_value;
+            }
+
+            idle();
+        }
+    }
+
     pub fn _wake_one(&self) {
         let mut queue = self.queue.lock();
         if let Some(process) = queue.pop_front() {
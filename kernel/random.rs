@@ -1,22 +1,458 @@
-use crate::user_buffer::UserBufferMut;
-use crate::{prelude::*, user_buffer::UserBufWriter};
-use x86::random::rdrand_slice;
+//! The kernel random number generator.
+//!
+//! Entropy sources (RDSEED/RDRAND, TSC jitter, interrupt timings, and hardware
+//! RNG devices like virtio-rng) are mixed into the input pool. The CRNG, a
+//! ChaCha20-based generator, is seeded from the pool once it has collected
+//! enough entropy, and reseeded from it periodically.
+//!
+//! Each read derives a one-time key from the CRNG key and replaces the CRNG key
+//! at the same time (so-called fast key erasure): a leaked CRNG state doesn't
+//! reveal outputs generated before.
+use crate::{
+    prelude::*,
+    process::WaitQueue,
+    user_buffer::{UserBufReader, UserBufWriter, UserBuffer, UserBufferMut},
+};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cmp::min;
+use core::sync::atomic::{AtomicBool, Ordering};
+use kerla_api::driver::rng::RngDriver;
+use kerla_runtime::{arch::nanosecs_since_boot, spinlock::SpinLock};
+use x86::{
+    cpuid::CpuId,
+    random::{rdrand64, rdseed64},
+    time::rdtsc,
+};
 
-pub fn read_secure_random(buf: UserBufferMut<'_>) -> Result<usize> {
-    // TODO: Implement arch-agnostic CRNG which does not fully depends on RDRAND.
+/// The entropy (in bits) required to initialize the CRNG.
+const CRNG_SEED_BITS: usize = 256;
+/// How often the CRNG is reseeded from the input pool in nanoseconds.
+const CRNG_RESEED_INTERVAL: usize = 60 * 1_000_000_000;
+/// The maximum entropy (in bits) the input pool can hold.
+const POOL_MAX_BITS: usize = 512;
+/// The number of words absorbed into the input pool per permutation.
+const POOL_RATE_WORDS: usize = 8;
+/// The number of interrupts mixed into the fast pool before it's flushed into
+/// the input pool with 1 bit of entropy credit.
+const INTERRUPTS_PER_FLUSH: usize = 64;
+/// The number of TSC jitter samples collected at the boot.
+const JITTER_SAMPLES: usize = 4096;
+/// Varying TSC jitter samples needed for 1 bit of entropy credit.
+const JITTER_SAMPLES_PER_BIT: usize = 64;
+/// The maximum entropy credited from TSC jitter. It's hard to estimate (e.g.
+/// a coarse TSC in VMs), so jitter alone never initializes the CRNG.
+const JITTER_MAX_CREDIT_BITS: usize = CRNG_SEED_BITS / 4;
+/// "expand 32-byte k"
+const CHACHA20_CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+static INPUT_POOL: SpinLock<InputPool> = SpinLock::new(InputPool::new());
+static FAST_POOL: SpinLock<FastPool> = SpinLock::new(FastPool::new());
+static CRNG: SpinLock<Crng> = SpinLock::new(Crng::new());
+static CRNG_READY: AtomicBool = AtomicBool::new(false);
+static CRNG_READY_WAIT_QUEUE: WaitQueue = WaitQueue::new();
+static RNG_DRIVERS: SpinLock<Vec<Box<dyn RngDriver>>> = SpinLock::new(Vec::new());
+
+fn quarter_round(x: &mut [u32], a: usize, b: usize, c: usize, d: usize) {
+    x[a] = x[a].wrapping_add(x[b]);
+    x[d] = (x[d] ^ x[a]).rotate_left(16);
+    x[c] = x[c].wrapping_add(x[d]);
+    x[b] = (x[b] ^ x[c]).rotate_left(12);
+    x[a] = x[a].wrapping_add(x[b]);
+    x[d] = (x[d] ^ x[a]).rotate_left(8);
+    x[c] = x[c].wrapping_add(x[d]);
+    x[b] = (x[b] ^ x[c]).rotate_left(7);
+}
+
+/// The ChaCha20 block function: 20 rounds and the feed-forward addition.
+fn chacha20_rounds(state: &[u32; 16]) -> [u32; 16] {
+    let mut x = *state;
+    for _ in 0..10 {
+        quarter_round(&mut x, 0, 4, 8, 12);
+        quarter_round(&mut x, 1, 5, 9, 13);
+        quarter_round(&mut x, 2, 6, 10, 14);
+        quarter_round(&mut x, 3, 7, 11, 15);
+        quarter_round(&mut x, 0, 5, 10, 15);
+        quarter_round(&mut x, 1, 6, 11, 12);
+        quarter_round(&mut x, 2, 7, 8, 13);
+        quarter_round(&mut x, 3, 4, 9, 14);
+    }
+
+    for (word, initial) in x.iter_mut().zip(state.iter()) {
+        *word = word.wrapping_add(*initial);
+    }
+
+    x
+}
+
+/// Computes a ChaCha20 keystream block (the original 64-bit counter and
+/// 64-bit nonce variant).
+fn chacha20_block(key: &[u32; 8], counter: u64, nonce: u64) -> [u32; 16] {
+    let mut state = [0; 16];
+    state[..4].copy_from_slice(&CHACHA20_CONSTANTS);
+    state[4..12].copy_from_slice(key);
+    state[12] = counter as u32;
+    state[13] = (counter >> 32) as u32;
+    state[14] = nonce as u32;
+    state[15] = (nonce >> 32) as u32;
+    chacha20_rounds(&state)
+}
+
+/// Collects entropy from sources. Inputs are absorbed into the state and
+/// stirred by the ChaCha20 block function.
+struct InputPool {
+    state: [u32; 16],
+    pos: usize,
+    /// The estimated entropy in bits.
+    entropy_bits: usize,
+}
+
+impl InputPool {
+    const fn new() -> InputPool {
+        InputPool {
+            state: [0; 16],
+            pos: 0,
+            entropy_bits: 0,
+        }
+    }
+
+    fn stir(&mut self) {
+        self.state = chacha20_rounds(&self.state);
+        self.pos = 0;
+    }
+
+    /// Absorbs `data` into the pool. It doesn't credit any entropy.
+    fn mix(&mut self, data: &[u8]) {
+        for chunk in data.chunks(4) {
+            let mut word = [0; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.state[self.pos] ^= u32::from_le_bytes(word);
+            self.pos += 1;
+            if self.pos == POOL_RATE_WORDS {
+                self.stir();
+            }
+        }
+    }
+
+    fn credit(&mut self, bits: usize) {
+        self.entropy_bits = min(self.entropy_bits + bits, POOL_MAX_BITS);
+    }
+
+    /// Extracts a 256-bit seed and resets the entropy estimate.
+    fn extract(&mut self) -> [u32; 8] {
+        self.stir();
+        let mut seed = [0; 8];
+        seed.copy_from_slice(&self.state[..8]);
+        // Stir again so that the seed can't be recovered from the pool state.
+        self.stir();
+        self.entropy_bits = 0;
+        seed
+    }
+}
+
+/// Accumulates interrupt timings cheaply before they are mixed into the input
+/// pool.
+struct FastPool {
+    state: [u32; 4],
+    count: usize,
+}
+
+impl FastPool {
+    const fn new() -> FastPool {
+        FastPool {
+            state: [0; 4],
+            count: 0,
+        }
+    }
+}
+
+struct Crng {
+    key: [u32; 8],
+    /// Incremented on every key derivation. Used as the ChaCha20 nonce.
+    generation: u64,
+    /// The monotonic clock when the CRNG was reseeded last time.
+    reseeded_at: usize,
+}
+
+impl Crng {
+    const fn new() -> Crng {
+        Crng {
+            key: [0; 8],
+            generation: 0,
+            reseeded_at: 0,
+        }
+    }
+
+    fn reseed(&mut self, pool: &mut InputPool) {
+        mix_arch_randomness(pool);
+        let seed = pool.extract();
+        for (key, seed) in self.key.iter_mut().zip(seed.iter()) {
+            *key ^= *seed;
+        }
+
+        self.reseeded_at = nanosecs_since_boot();
+    }
+}
+
+/// Mixes outputs from RDSEED (or RDRAND if RDSEED is not available) into the
+/// pool. Returns `true` if the CPU has filled the whole seed.
+fn mix_arch_randomness(pool: &mut InputPool) -> bool {
+    let cpuid = CpuId::new();
+    let has_rdseed = cpuid
+        .get_extended_feature_info()
+        .is_some_and(|info| info.has_rdseed());
+    let has_rdrand = cpuid
+        .get_feature_info()
+        .is_some_and(|info| info.has_rdrand());
+
+    let mut filled = true;
+    for _ in 0..(CRNG_SEED_BITS / 64) {
+        let mut value = 0;
+        let valid =
+            unsafe { (has_rdseed && rdseed64(&mut value)) || (has_rdrand && rdrand64(&mut value)) };
 
-    UserBufWriter::from(buf).write_with(|slice| {
-        let valid = unsafe { rdrand_slice(slice) };
         if valid {
-            Ok(slice.len())
+            pool.mix(&value.to_le_bytes());
         } else {
-            warn_once!("RDRAND returned invalid data");
-            Ok(0)
+            filled = false;
         }
+    }
+
+    filled
+}
+
+/// Mixes timing variations of the CPU (caches, pipelines, and SMIs) measured
+/// by the TSC into the pool. Returns the number of samples whose delta
+/// differs from the previous one.
+fn collect_jitter_entropy(pool: &mut InputPool) -> usize {
+    let mut prev = unsafe { rdtsc() };
+    let mut prev_delta = 0;
+    let mut varying_samples = 0;
+    for _ in 0..JITTER_SAMPLES {
+        let now = unsafe { rdtsc() };
+        let delta = now.wrapping_sub(prev);
+        // Mixing into the pool itself is the workload we measure.
+        pool.mix(&delta.to_le_bytes());
+        if delta != prev_delta {
+            varying_samples += 1;
+        }
+
+        prev = now;
+        prev_delta = delta;
+    }
+
+    varying_samples
+}
+
+pub fn is_crng_ready() -> bool {
+    CRNG_READY.load(Ordering::Acquire)
+}
+
+/// Initializes the CRNG if the input pool has enough entropy.
+fn try_initialize_crng() {
+    {
+        let mut crng = CRNG.lock();
+        let mut pool = INPUT_POOL.lock();
+        if is_crng_ready() || pool.entropy_bits < CRNG_SEED_BITS {
+            return;
+        }
+
+        crng.reseed(&mut pool);
+        CRNG_READY.store(true, Ordering::Release);
+    }
+
+    info!("random: crng initialized");
+    CRNG_READY_WAIT_QUEUE.wake_all();
+}
+
+/// Mixes `data` into the input pool and credits `credit_bits` bits of entropy.
+fn add_entropy(data: &[u8], credit_bits: usize) {
+    let seedable = {
+        let mut pool = INPUT_POOL.lock();
+        pool.mix(data);
+        pool.credit(credit_bits);
+        pool.entropy_bits >= CRNG_SEED_BITS
+    };
+
+    if seedable && !is_crng_ready() {
+        try_initialize_crng();
+    }
+}
+
+/// Mixes the timing of an interrupt. Called on every device interrupt.
+pub fn add_interrupt_randomness(irq: u8) {
+    let tsc = unsafe { rdtsc() };
+    let state = {
+        let mut fast_pool = FAST_POOL.lock();
+        fast_pool.state[0] ^= tsc as u32;
+        fast_pool.state[1] ^= (tsc >> 32) as u32;
+        fast_pool.state[2] ^= irq as u32;
+        quarter_round(&mut fast_pool.state, 0, 1, 2, 3);
+        fast_pool.count += 1;
+        if fast_pool.count < INTERRUPTS_PER_FLUSH {
+            return;
+        }
+
+        fast_pool.count = 0;
+        fast_pool.state
+    };
+
+    let mut bytes = [0; 16];
+    for (chunk, word) in bytes.chunks_mut(4).zip(state.iter()) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+
+    add_entropy(&bytes, 1);
+}
+
+/// Mixes random bytes from a hardware RNG device. They're credited as full
+/// entropy.
+pub fn add_hardware_randomness(buf: &[u8]) {
+    add_entropy(buf, buf.len() * 8);
+}
+
+/// Mixes bytes written into `/dev/random` or `/dev/urandom`. No entropy is
+/// credited.
+pub fn add_user_randomness(buf: UserBuffer<'_>) -> Result<usize> {
+    let mut reader = UserBufReader::from(buf);
+    let mut chunk = [0; 64];
+    let mut total_len = 0;
+    loop {
+        let read_len = reader.read_bytes(&mut chunk)?;
+        if read_len == 0 {
+            return Ok(total_len);
+        }
+
+        add_entropy(&chunk[..read_len], 0);
+        total_len += read_len;
+    }
+}
+
+pub fn register_rng_driver(driver: Box<dyn RngDriver>) {
+    driver.request_randomness();
+    RNG_DRIVERS.lock().push(driver);
+}
+
+/// Derives a one-time key and replaces the CRNG key. Reseeds the CRNG first
+/// if it's time to do so.
+fn derive_key() -> [u32; 8] {
+    let mut key = [0; 8];
+    let reseeded = {
+        let mut crng = CRNG.lock();
+        let reseed =
+            is_crng_ready() && nanosecs_since_boot() - crng.reseeded_at >= CRNG_RESEED_INTERVAL;
+        if reseed {
+            crng.reseed(&mut INPUT_POOL.lock());
+        }
+
+        let block = chacha20_block(&crng.key, 0, crng.generation);
+        crng.generation += 1;
+        crng.key.copy_from_slice(&block[..8]);
+        key.copy_from_slice(&block[8..]);
+        reseed
+    };
+
+    if reseeded {
+        // Ask hardware RNGs for fresh entropy for the next reseed.
+        for driver in RNG_DRIVERS.lock().iter() {
+            driver.request_randomness();
+        }
+    }
+
+    key
+}
+
+fn fill_random(buf: UserBufferMut<'_>) -> Result<usize> {
+    let key = derive_key();
+    let mut counter = 0;
+    UserBufWriter::from(buf).write_with(|slice| {
+        for chunk in slice.chunks_mut(64) {
+            let block = chacha20_block(&key, counter, 0);
+            counter += 1;
+            for (bytes, word) in chunk.chunks_mut(4).zip(block.iter()) {
+                bytes.copy_from_slice(&word.to_le_bytes()[..bytes.len()]);
+            }
+        }
+
+        Ok(slice.len())
     })
 }
 
+/// Fills `buf` with random bytes. It blocks until the CRNG is initialized.
+pub fn read_secure_random(buf: UserBufferMut<'_>) -> Result<usize> {
+    CRNG_READY_WAIT_QUEUE.sleep_signalable_until(|| {
+        if is_crng_ready() {
+            Ok(Some(()))
+        } else {
+            Ok(None)
+        }
+    })?;
+
+    fill_random(buf)
+}
+
+/// Fills `buf` with random bytes. Returns `EAGAIN` if the CRNG is not yet
+/// initialized.
+pub fn try_read_secure_random(buf: UserBufferMut<'_>) -> Result<usize> {
+    if !is_crng_ready() {
+        return Err(Errno::EAGAIN.into());
+    }
+
+    fill_random(buf)
+}
+
+/// Fills `buf` with random bytes without waiting for the CRNG to be
+/// initialized: outputs might be predictable in the early boot stage.
 pub fn read_insecure_random(buf: UserBufferMut<'_>) -> Result<usize> {
-    // TODO:
-    read_secure_random(buf)
+    fill_random(buf)
+}
+
+pub fn init() {
+    let mut crng = CRNG.lock();
+    let mut pool = INPUT_POOL.lock();
+    if mix_arch_randomness(&mut pool) {
+        pool.credit(CRNG_SEED_BITS);
+    }
+
+    let varying_samples = collect_jitter_entropy(&mut pool);
+    pool.credit(min(
+        varying_samples / JITTER_SAMPLES_PER_BIT,
+        JITTER_MAX_CREDIT_BITS,
+    ));
+
+    // Seed the CRNG even if we don't have enough entropy yet so that insecure
+    // reads don't return the same bytes on every boot.
+    let ready = pool.entropy_bits >= CRNG_SEED_BITS;
+    crng.reseed(&mut pool);
+    if ready {
+        CRNG_READY.store(true, Ordering::Release);
+        info!("random: crng initialized");
+    } else {
+        warn!("random: not enough entropy; waiting for more");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The test vector in RFC 8439 section 2.3.2.
+    #[test_case]
+    fn chacha20_block_function() {
+        let key = [
+            0x03020100, 0x07060504, 0x0b0a0908, 0x0f0e0d0c, 0x13121110, 0x17161514, 0x1b1a1918,
+            0x1f1e1d1c,
+        ];
+        // The RFC uses a 32-bit counter (1) and a 96-bit nonce
+        // (00:00:00:09:00:00:00:4a:00:00:00:00).
+        let counter = 1 | (0x0900_0000 << 32);
+        let nonce = 0x4a00_0000;
+        let expected = [
+            0xe4e7f110, 0x15593bd1, 0x1fdd0f50, 0xc47120a3, 0xc7f4d1c7, 0x0368c033, 0x9aaa2204,
+            0x4e6cd4c3, 0x466482d2, 0x09aa9f07, 0x05d7c214, 0xa2028bd9, 0xd19c12b5, 0xb94e16de,
+            0xe883d0cb, 0x4e3c50a2,
+        ];
+
+        assert_eq!(chacha20_block(&key, counter, nonce), expected);
+    }
 }
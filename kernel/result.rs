@@ -1,5 +1,6 @@
 use core::fmt;
 
+use kerla_api::driver::block::BlockIoError;
 use kerla_runtime::{
     address::{AccessError, NullUserPointerError},
     page_allocator::PageAllocError,
@@ -47,10 +48,14 @@ pub enum Errno {
     EPIPE = 32,
     EDOM = 33,
     ERANGE = 34,
-
+    EDEADLK = 35,
+    ENAMETOOLONG = 36,
     ENOSYS = 38,
+    ENOTEMPTY = 39,
     ELOOP = 40,
 
+    EOVERFLOW = 75,
+
     EADDRINUSE = 98,
     EADDRNOTAVAIL = 99,
     ENETDOWN = 100,
@@ -175,6 +180,17 @@ impl From<NullUserPointerError> for Error {
     }
 }
 
+impl From<BlockIoError> for Error {
+    fn from(error: BlockIoError) -> Error {
+        match error {
+            BlockIoError::IoError => Error::new(Errno::EIO),
+            BlockIoError::Unsupported => Error::with_message(Errno::EIO, "unsupported block I/O"),
+            BlockIoError::ReadOnly => Error::new(Errno::EROFS),
+            BlockIoError::OutOfRange => Error::new(Errno::ENXIO),
+        }
+    }
+}
+
 impl From<smoltcp::Error> for Error {
     fn from(error: smoltcp::Error) -> Error {
         match error {
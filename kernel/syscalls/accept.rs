@@ -21,6 +21,9 @@ impl<'a> SyscallHandler<'a> {
         let options = OpenOptions {
             nonblock: false,
             close_on_exec: false,
+            append: false,
+            readable: true,
+            writable: true,
         };
         let fd = current_process()
             .opened_files()
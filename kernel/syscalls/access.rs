@@ -0,0 +1,9 @@
+use crate::fs::path::Path;
+use crate::result::Result;
+use crate::syscalls::{faccessat2::AccessMode, AtFlags, CwdOrFd, SyscallHandler};
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_access(&mut self, path: &Path, mode: AccessMode) -> Result<isize> {
+        self.sys_faccessat2(CwdOrFd::AtCwd, path, mode, AtFlags::empty())
+    }
+}
@@ -0,0 +1,219 @@
+use crate::ctypes::*;
+use crate::prelude::*;
+use crate::process::is_privileged;
+use crate::syscalls::SyscallHandler;
+use crate::timer::{
+    read_wall_clock, remaining_wall_clock_slew, set_wall_clock_freq, slew_wall_clock,
+    step_wall_clock, wall_clock_freq,
+};
+use kerla_runtime::address::UserVAddr;
+use kerla_runtime::spinlock::SpinLock;
+
+const ADJ_OFFSET: c_uint = 0x0001;
+const ADJ_FREQUENCY: c_uint = 0x0002;
+const ADJ_MAXERROR: c_uint = 0x0004;
+const ADJ_ESTERROR: c_uint = 0x0008;
+const ADJ_STATUS: c_uint = 0x0010;
+const ADJ_TIMECONST: c_uint = 0x0020;
+const ADJ_SETOFFSET: c_uint = 0x0100;
+const ADJ_MICRO: c_uint = 0x1000;
+const ADJ_NANO: c_uint = 0x2000;
+const ADJ_TICK: c_uint = 0x4000;
+/// `adjtime(3)`-compatible mode: the offset is always in microseconds.
+const ADJ_OFFSET_SINGLESHOT: c_uint = 0x8001;
+/// Reads the remaining offset of `ADJ_OFFSET_SINGLESHOT`.
+const ADJ_OFFSET_SS_READ: c_uint = 0xa001;
+
+const STA_UNSYNC: c_int = 0x0040;
+const STA_NANO: c_int = 0x2000;
+/// Status bits which can be changed by `ADJ_STATUS`.
+const STA_RW: c_int = 0x00ff;
+
+const TIME_OK: isize = 0;
+const TIME_ERROR: isize = 5;
+
+/// The maximum frequency adjustment: 500 ppm.
+const MAX_FREQ: c_long = 500 << 16;
+/// The maximum offset in `ADJ_OFFSET`: 0.5 seconds.
+const MAX_PHASE: i64 = 500_000_000;
+/// The nominal `tick` value: microseconds per `USER_HZ` (100Hz) tick.
+const NOMINAL_TICK: c_long = 10000;
+
+/// `struct timex`
+#[allow(unused)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct Timex {
+    modes: c_uint,
+    offset: c_long,
+    freq: c_long,
+    maxerror: c_long,
+    esterror: c_long,
+    status: c_int,
+    constant: c_long,
+    precision: c_long,
+    tolerance: c_long,
+    /// `tv_usec` is in nanoseconds if `STA_NANO` is set.
+    time: [c_long; 2],
+    tick: c_long,
+    ppsfreq: c_long,
+    jitter: c_long,
+    shift: c_int,
+    stabil: c_long,
+    jitcnt: c_long,
+    calcnt: c_long,
+    errcnt: c_long,
+    stbcnt: c_long,
+    tai: c_int,
+    _reserved: [c_int; 11],
+}
+
+/// The NTP parameters which don't affect the clock itself.
+struct NtpState {
+    status: c_int,
+    maxerror: c_long,
+    esterror: c_long,
+    constant: c_long,
+    tick: c_long,
+}
+
+static NTP_STATE: SpinLock<NtpState> = SpinLock::new(NtpState {
+    status: STA_UNSYNC,
+    maxerror: 16_000_000,
+    esterror: 16_000_000,
+    constant: 2,
+    tick: NOMINAL_TICK,
+});
+
+/// Converts the frequency and the tick into the frequency adjustment of the
+/// wall clock. Each microsecond in `tick` is 100 ppm.
+fn effective_freq(freq: c_long, tick: c_long) -> i64 {
+    freq + (((tick - NOMINAL_TICK) * 100) << 16)
+}
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_adjtimex(&mut self, buf: UserVAddr) -> Result<isize> {
+        let mut timex = buf.read::<Timex>()?;
+        let modes = timex.modes;
+        if modes != 0 && modes != ADJ_OFFSET_SS_READ && !is_privileged() {
+            return Err(Errno::EPERM.into());
+        }
+
+        let mut ntp = NTP_STATE.lock();
+        let freq = wall_clock_freq() - effective_freq(0, ntp.tick);
+        if modes == ADJ_OFFSET_SINGLESHOT || modes == ADJ_OFFSET_SS_READ {
+            // adjtime(3): returns the remaining offset in microseconds.
+            let remaining = remaining_wall_clock_slew() / 1000;
+            if modes == ADJ_OFFSET_SINGLESHOT {
+                let offset = timex
+                    .offset
+                    .checked_mul(1000)
+                    .ok_or_else(|| Error::new(Errno::EINVAL))?;
+                slew_wall_clock(offset);
+            }
+
+            timex.offset = remaining;
+        } else {
+            if modes & ADJ_TICK != 0 && !(9000..=11000).contains(&timex.tick) {
+                return Err(Errno::EINVAL.into());
+            }
+
+            // The offset is in the unit after this call (`ADJ_MICRO` wins).
+            let nano =
+                (ntp.status & STA_NANO != 0 || modes & ADJ_NANO != 0) && modes & ADJ_MICRO == 0;
+            let offset = if modes & ADJ_OFFSET != 0 {
+                let unit = if nano { 1 } else { 1000 };
+                let offset = timex
+                    .offset
+                    .checked_mul(unit)
+                    .ok_or_else(|| Error::new(Errno::EINVAL))?;
+                Some(offset)
+            } else {
+                None
+            };
+
+            if modes & ADJ_SETOFFSET != 0 {
+                let [sec, subsec] = timex.time;
+                let unit = if modes & ADJ_NANO != 0 { 1 } else { 1000 };
+                if !(0..(1_000_000_000 / unit)).contains(&subsec) {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let delta = sec
+                    .checked_mul(1_000_000_000)
+                    .and_then(|nanosecs| nanosecs.checked_add(subsec * unit))
+                    .ok_or_else(|| Error::new(Errno::EINVAL))?;
+                step_wall_clock(delta);
+            }
+
+            if modes & ADJ_STATUS != 0 {
+                ntp.status = (ntp.status & !STA_RW) | (timex.status & STA_RW);
+            }
+
+            if modes & ADJ_NANO != 0 {
+                ntp.status |= STA_NANO;
+            }
+
+            if modes & ADJ_MICRO != 0 {
+                ntp.status &= !STA_NANO;
+            }
+
+            if modes & ADJ_MAXERROR != 0 {
+                ntp.maxerror = timex.maxerror;
+            }
+
+            if modes & ADJ_ESTERROR != 0 {
+                ntp.esterror = timex.esterror;
+            }
+
+            if modes & ADJ_TIMECONST != 0 {
+                ntp.constant = timex.constant;
+            }
+
+            let unit = if ntp.status & STA_NANO != 0 { 1 } else { 1000 };
+            if let Some(offset) = offset {
+                // We don't implement the NTP PLL: just slew the clock.
+                slew_wall_clock(offset.clamp(-MAX_PHASE, MAX_PHASE));
+            }
+
+            if modes & ADJ_TICK != 0 {
+                ntp.tick = timex.tick;
+            }
+
+            if modes & (ADJ_FREQUENCY | ADJ_TICK) != 0 {
+                let freq = if modes & ADJ_FREQUENCY != 0 {
+                    timex.freq.clamp(-MAX_FREQ, MAX_FREQ)
+                } else {
+                    freq
+                };
+
+                set_wall_clock_freq(effective_freq(freq, ntp.tick));
+            }
+
+            timex.offset = remaining_wall_clock_slew() / unit;
+        }
+
+        let now = read_wall_clock().nanosecs_from_epoch() as c_long;
+        let subsec = now % 1_000_000_000;
+        timex.time = if ntp.status & STA_NANO != 0 {
+            [now / 1_000_000_000, subsec]
+        } else {
+            [now / 1_000_000_000, subsec / 1000]
+        };
+        timex.freq = wall_clock_freq() - effective_freq(0, ntp.tick);
+        timex.maxerror = ntp.maxerror;
+        timex.esterror = ntp.esterror;
+        timex.status = ntp.status;
+        timex.constant = ntp.constant;
+        timex.precision = 1;
+        timex.tolerance = MAX_FREQ;
+        timex.tick = ntp.tick;
+        buf.write::<Timex>(&timex)?;
+
+        if ntp.status & STA_UNSYNC != 0 {
+            Ok(TIME_ERROR)
+        } else {
+            Ok(TIME_OK)
+        }
+    }
+}
@@ -1,14 +1,9 @@
 use crate::fs::{path::Path, stat::FileMode};
 use crate::result::Result;
-use crate::{process::current_process, syscalls::SyscallHandler};
+use crate::syscalls::{CwdOrFd, SyscallHandler};
 
 impl<'a> SyscallHandler<'a> {
     pub fn sys_chmod(&mut self, path: &Path, mode: FileMode) -> Result<isize> {
-        current_process()
-            .root_fs()
-            .lock()
-            .lookup(path)?
-            .chmod(mode)?;
-        Ok(0)
+        self.sys_fchmodat(CwdOrFd::AtCwd, path, mode)
     }
 }
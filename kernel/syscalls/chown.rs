@@ -0,0 +1,12 @@
+use crate::fs::{
+    path::Path,
+    stat::{GId, UId},
+};
+use crate::result::Result;
+use crate::syscalls::{AtFlags, CwdOrFd, SyscallHandler};
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_chown(&mut self, path: &Path, uid: UId, gid: GId) -> Result<isize> {
+        self.sys_fchownat(CwdOrFd::AtCwd, path, uid, gid, AtFlags::empty())
+    }
+}
@@ -0,0 +1,30 @@
+use kerla_runtime::address::UserVAddr;
+
+use crate::ctypes::*;
+use crate::prelude::*;
+use crate::syscalls::{clock_gettime::COARSE_CLOCK_RESOLUTION, SyscallHandler};
+use crate::timer::Timespec;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_clock_getres(&mut self, clock: c_clockid, res: Option<UserVAddr>) -> Result<isize> {
+        let resolution = match clock {
+            CLOCK_REALTIME
+            | CLOCK_MONOTONIC
+            | CLOCK_MONOTONIC_RAW
+            | CLOCK_BOOTTIME
+            | CLOCK_PROCESS_CPUTIME_ID
+            | CLOCK_THREAD_CPUTIME_ID => 1,
+            CLOCK_REALTIME_COARSE | CLOCK_MONOTONIC_COARSE => COARSE_CLOCK_RESOLUTION,
+            _ => {
+                debug_warn!("clock_getres: unsupported clock id: {}", clock);
+                return Err(Errno::EINVAL.into());
+            }
+        };
+
+        if let Some(res) = res {
+            res.write::<Timespec>(&Timespec::from_nanosecs(resolution))?;
+        }
+
+        Ok(0)
+    }
+}
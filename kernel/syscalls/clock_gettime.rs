@@ -1,35 +1,39 @@
-use kerla_runtime::address::UserVAddr;
+use kerla_runtime::{address::UserVAddr, arch::TICK_HZ};
 
-use crate::result::{Errno, Result};
-use crate::user_buffer::UserBufWriter;
-use crate::{
-    ctypes::{c_clockid, c_long, c_time, CLOCK_MONOTONIC, CLOCK_REALTIME},
-    timer::read_wall_clock,
-};
-use crate::{syscalls::SyscallHandler, timer::read_monotonic_clock};
-use core::{convert::TryInto, mem::size_of};
+use crate::ctypes::*;
+use crate::prelude::*;
+use crate::process::current_process;
+use crate::syscalls::SyscallHandler;
+use crate::timer::{read_monotonic_clock, read_wall_clock, Timespec};
 
-impl<'a> SyscallHandler<'a> {
-    pub fn sys_clock_gettime(&mut self, clock: c_clockid, buf: UserVAddr) -> Result<isize> {
-        let (tv_sec, tv_nsec) = match clock {
-            CLOCK_REALTIME => {
-                let now = read_wall_clock();
-                (now.secs_from_epoch(), now.nanosecs_from_epoch())
-            }
-            CLOCK_MONOTONIC => {
-                let now = read_monotonic_clock();
-                (now.secs(), now.nanosecs())
-            }
-            _ => {
-                debug_warn!("clock_gettime: unsupported clock id: {}", clock);
-                return Err(Errno::ENOSYS.into());
-            }
-        };
+/// The resolution of `CLOCK_*_COARSE` clocks: they're updated only at ticks.
+pub const COARSE_CLOCK_RESOLUTION: usize = 1_000_000_000 / TICK_HZ;
+
+/// Reads `clock` in nanoseconds.
+fn read_clock(clock: c_clockid) -> Result<usize> {
+    let coarse = |nanosecs: usize| nanosecs - nanosecs % COARSE_CLOCK_RESOLUTION;
+    let nanosecs = match clock {
+        CLOCK_REALTIME => read_wall_clock().nanosecs_from_epoch(),
+        CLOCK_REALTIME_COARSE => coarse(read_wall_clock().nanosecs_from_epoch()),
+        // We don't slew the clock nor suspend the system: they are
+        // identical to CLOCK_MONOTONIC.
+        CLOCK_MONOTONIC | CLOCK_MONOTONIC_RAW | CLOCK_BOOTTIME => read_monotonic_clock().nanosecs(),
+        CLOCK_MONOTONIC_COARSE => coarse(read_monotonic_clock().nanosecs()),
+        // A process consists of only one thread.
+        CLOCK_PROCESS_CPUTIME_ID | CLOCK_THREAD_CPUTIME_ID => current_process().cpu_time_nanosecs(),
+        _ => {
+            debug_warn!("clock_gettime: unsupported clock id: {}", clock);
+            return Err(Errno::EINVAL.into());
+        }
+    };
 
-        let mut writer = UserBufWriter::from_uaddr(buf, size_of::<c_time>() + size_of::<c_long>());
-        writer.write::<c_time>(tv_sec.try_into().unwrap())?;
-        writer.write::<c_long>(tv_nsec.try_into().unwrap())?;
+    Ok(nanosecs)
+}
 
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_clock_gettime(&mut self, clock: c_clockid, buf: UserVAddr) -> Result<isize> {
+        let now = read_clock(clock)?;
+        buf.write::<Timespec>(&Timespec::from_nanosecs(now))?;
         Ok(0)
     }
 }
@@ -0,0 +1,27 @@
+use crate::ctypes::*;
+use crate::prelude::*;
+use crate::process::is_privileged;
+use crate::syscalls::SyscallHandler;
+use crate::timer::{set_wall_clock, Timespec};
+use kerla_runtime::address::UserVAddr;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_clock_settime(&mut self, clock: c_clockid, tp: UserVAddr) -> Result<isize> {
+        // Other clocks are not settable.
+        if clock != CLOCK_REALTIME {
+            return Err(Errno::EINVAL.into());
+        }
+
+        if !is_privileged() {
+            return Err(Errno::EPERM.into());
+        }
+
+        let tp = tp.read::<Timespec>()?;
+        if !tp.is_valid() {
+            return Err(Errno::EINVAL.into());
+        }
+
+        set_wall_clock(tp.as_nanosecs());
+        Ok(0)
+    }
+}
@@ -1,9 +1,11 @@
-use crate::{fs::opened_file::Fd, result::Result};
+use crate::{fs::file_lock, fs::opened_file::Fd, result::Result};
 use crate::{process::current_process, syscalls::SyscallHandler};
 
 impl<'a> SyscallHandler<'a> {
     pub fn sys_close(&mut self, fd: Fd) -> Result<isize> {
-        current_process().opened_files().lock().close(fd)?;
+        let current = current_process();
+        let opened_file = current.opened_files().lock().close(fd)?;
+        file_lock::release_posix_locks(current.pid(), &opened_file);
         Ok(0)
     }
 }
@@ -0,0 +1,75 @@
+use super::{read_offset, MAX_READ_WRITE_LEN};
+use crate::ctypes::{c_long, c_uint};
+use crate::fs::{inode::INode, opened_file::Fd, opened_file::OpenedFile};
+use crate::prelude::*;
+use crate::{process::current_process, syscalls::SyscallHandler};
+use core::cmp::min;
+use kerla_runtime::address::UserVAddr;
+
+/// Returns `EISDIR` for directories and `EINVAL` for files other than regular
+/// files.
+fn check_regular_file(opened_file: &OpenedFile) -> Result<()> {
+    match opened_file.inode() {
+        INode::Directory(_) => Err(Errno::EISDIR.into()),
+        inode if inode.stat()?.mode.is_regular_file() => Ok(()),
+        _ => Err(Errno::EINVAL.into()),
+    }
+}
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_copy_file_range(
+        &mut self,
+        fd_in: Fd,
+        off_in: Option<UserVAddr>,
+        fd_out: Fd,
+        off_out: Option<UserVAddr>,
+        len: usize,
+        flags: c_uint,
+    ) -> Result<isize> {
+        // "flags - is provided to allow for future extensions and currently
+        // must be set to 0." -- copy_file_range(2)
+        if flags != 0 {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let len = min(len, MAX_READ_WRITE_LEN);
+        let in_file = current_process().get_opened_file_by_fd(fd_in)?;
+        let out_file = current_process().get_opened_file_by_fd(fd_out)?;
+        if !in_file.options().readable || !out_file.options().writable {
+            return Err(Errno::EBADF.into());
+        }
+
+        check_regular_file(&in_file)?;
+        check_regular_file(&out_file)?;
+        if out_file.options().append {
+            return Err(Errno::EBADF.into());
+        }
+
+        let in_offset = read_offset(off_in)?;
+        let out_offset = read_offset(off_out)?;
+
+        // Overlapping ranges in the same file are not allowed.
+        let in_stat = in_file.inode().stat()?;
+        let out_stat = out_file.inode().stat()?;
+        let (in_inode_no, out_inode_no) = (in_stat.inode_no, out_stat.inode_no);
+        if in_file.path().mount_id == out_file.path().mount_id && in_inode_no == out_inode_no {
+            let in_pos = in_offset.unwrap_or_else(|| in_file.pos());
+            let out_pos = out_offset.unwrap_or_else(|| out_file.pos());
+            if in_pos < out_pos.saturating_add(len) && out_pos < in_pos.saturating_add(len) {
+                return Err(Errno::EINVAL.into());
+            }
+        }
+
+        let copied_len = in_file.splice_to(in_offset, &out_file, out_offset, len, false)?;
+        if let (Some(uaddr), Some(offset)) = (off_in, in_offset) {
+            uaddr.write::<c_long>(&((offset + copied_len) as c_long))?;
+        }
+
+        if let (Some(uaddr), Some(offset)) = (off_out, out_offset) {
+            uaddr.write::<c_long>(&((offset + copied_len) as c_long))?;
+        }
+
+        // MAX_READ_WRITE_LEN limit guarantees copied_len is in the range of isize.
+        Ok(copied_len as isize)
+    }
+}
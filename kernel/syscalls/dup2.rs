@@ -1,3 +1,4 @@
+use crate::fs::file_lock;
 use crate::fs::opened_file::{Fd, OpenOptions};
 use crate::prelude::*;
 use crate::process::current_process;
@@ -6,8 +7,16 @@ use crate::syscalls::SyscallHandler;
 impl<'a> SyscallHandler<'a> {
     pub fn sys_dup2(&mut self, old: Fd, new: Fd) -> Result<isize> {
         let current = current_process();
-        let mut opened_files = current.opened_files().lock();
-        opened_files.dup2(old, new, OpenOptions::new(false, false))?;
+        let closed_file =
+            current
+                .opened_files()
+                .lock()
+                .dup2(old, new, OpenOptions::new(false, false))?;
+
+        if let Some(closed_file) = closed_file {
+            file_lock::release_posix_locks(current.pid(), &closed_file);
+        }
+
         Ok(new.as_int() as isize)
     }
 }
@@ -19,7 +19,12 @@ impl<'a> SyscallHandler<'a> {
         envp_uaddr: UserVAddr,
     ) -> Result<isize> {
         let current = current_process();
-        let executable = current.root_fs().lock().lookup_path(path, true)?;
+        let executable = {
+            let root_fs = current.root_fs().lock();
+            let executable = root_fs.lookup_path(path, true)?;
+            root_fs.check_executable(&executable)?;
+            executable
+        };
 
         let mut argv = Vec::new();
         for i in 0..ARG_MAX {
@@ -0,0 +1,64 @@
+use crate::ctypes::c_int;
+use crate::fs::{path::Path, stat::Stat};
+use crate::prelude::*;
+use crate::{
+    process::current_process,
+    syscalls::{AtFlags, CwdOrFd, SyscallHandler},
+};
+use bitflags::bitflags;
+
+bitflags! {
+    /// `F_OK` is represented as the empty set.
+    pub struct AccessMode: c_int {
+        const X_OK = 1;
+        const W_OK = 2;
+        const R_OK = 4;
+    }
+}
+
+/// Checks the permission bits of the file. All processes run as the superuser
+/// since we don't support users yet: it can read and write any file, and
+/// execute a file if any of the execute bits is set.
+fn check_permission(stat: &Stat, mode: AccessMode) -> Result<()> {
+    let file_mode = stat.mode;
+    if mode.contains(AccessMode::X_OK)
+        && !file_mode.is_directory()
+        && file_mode.as_u32() & 0o111 == 0
+    {
+        return Err(Errno::EACCES.into());
+    }
+
+    Ok(())
+}
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_faccessat2(
+        &mut self,
+        dir: CwdOrFd,
+        path: &Path,
+        mode: AccessMode,
+        flags: AtFlags,
+    ) -> Result<isize> {
+        // The real and effective user IDs are always the same: we don't need
+        // to handle `AT_EACCESS`.
+        let current = current_process();
+        let root_fs = current.root_fs().lock();
+        let opened_files = current.opened_files();
+        let path_comp = root_fs.lookup_path_at_flags(opened_files, &dir, path, flags)?;
+        let stat = path_comp.inode.stat()?;
+
+        // Device files are writable even in a read-only mount.
+        let file_mode = stat.mode;
+        let on_fs = file_mode.is_regular_file() || file_mode.is_directory();
+        if mode.contains(AccessMode::W_OK) && on_fs {
+            root_fs.check_writable(&path_comp)?;
+        }
+
+        if mode.contains(AccessMode::X_OK) && file_mode.is_regular_file() {
+            root_fs.check_executable(&path_comp)?;
+        }
+
+        check_permission(&stat, mode)?;
+        Ok(0)
+    }
+}
@@ -0,0 +1,27 @@
+use crate::fs::{
+    inode::SetAttr,
+    inotify::{self, InotifyMask},
+    path::Path,
+    stat::FileMode,
+};
+use crate::result::Result;
+use crate::{
+    process::current_process,
+    syscalls::{CwdOrFd, SyscallHandler},
+};
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_fchmodat(&mut self, dir: CwdOrFd, path: &Path, mode: FileMode) -> Result<isize> {
+        let current = current_process();
+        let root_fs = current.root_fs().lock();
+        let opened_files = current.opened_files();
+        let path_comp = root_fs.lookup_path_at(opened_files, &dir, path, true)?;
+        root_fs.check_writable(&path_comp)?;
+        path_comp.inode.set_attr(&SetAttr {
+            mode: Some(mode),
+            ..Default::default()
+        })?;
+        inotify::notify_path(&path_comp, InotifyMask::IN_ATTRIB);
+        Ok(0)
+    }
+}
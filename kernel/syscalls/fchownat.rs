@@ -0,0 +1,36 @@
+use crate::fs::{
+    inode::SetAttr,
+    inotify::{self, InotifyMask},
+    path::Path,
+    stat::{GId, UId},
+};
+use crate::result::Result;
+use crate::{
+    process::current_process,
+    syscalls::{AtFlags, CwdOrFd, SyscallHandler},
+};
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_fchownat(
+        &mut self,
+        dir: CwdOrFd,
+        path: &Path,
+        uid: UId,
+        gid: GId,
+        flags: AtFlags,
+    ) -> Result<isize> {
+        let current = current_process();
+        let root_fs = current.root_fs().lock();
+        let opened_files = current.opened_files();
+        let path_comp = root_fs.lookup_path_at_flags(opened_files, &dir, path, flags)?;
+        root_fs.check_writable(&path_comp)?;
+        // -1 leaves the ID unchanged.
+        path_comp.inode.set_attr(&SetAttr {
+            uid: Some(uid).filter(|uid| uid.0 != u32::MAX),
+            gid: Some(gid).filter(|gid| gid.0 != u32::MAX),
+            ..Default::default()
+        })?;
+        inotify::notify_path(&path_comp, InotifyMask::IN_ATTRIB);
+        Ok(0)
+    }
+}
@@ -1,38 +1,220 @@
-use crate::fs::opened_file::{Fd, OpenFlags, OpenOptions};
+use crate::fs::{
+    file_lock::{self, LockKey, LockOwner, LockType, RecordLock},
+    opened_file::{Fd, OpenFlags, OpenOptions, OpenedFile},
+};
 use crate::result::{Errno, Result};
 use crate::syscalls::SyscallHandler;
 use crate::{ctypes::*, process::current_process};
+use kerla_runtime::address::UserVAddr;
 
-const _F_DUPFD: c_int = 0;
-const _F_GETFD: c_int = 1;
+const F_DUPFD: c_int = 0;
+const F_GETFD: c_int = 1;
 const F_SETFD: c_int = 2;
-const _F_GETFL: c_int = 3;
+const F_GETFL: c_int = 3;
 const F_SETFL: c_int = 4;
+const F_GETLK: c_int = 5;
+const F_SETLK: c_int = 6;
+const F_SETLKW: c_int = 7;
+
+// Open file description locks.
+const F_OFD_GETLK: c_int = 36;
+const F_OFD_SETLK: c_int = 37;
+const F_OFD_SETLKW: c_int = 38;
 
 // Linux-specific commands.
 const F_LINUX_SPECIFIC_BASE: c_int = 1024;
 const F_DUPFD_CLOEXEC: c_int = F_LINUX_SPECIFIC_BASE + 6;
 
+const FD_CLOEXEC: usize = 1;
+
+// `l_type` in `struct flock`.
+const F_RDLCK: c_short = 0;
+const F_WRLCK: c_short = 1;
+const F_UNLCK: c_short = 2;
+
+// `l_whence` in `struct flock`.
+const SEEK_SET: c_short = 0;
+const SEEK_CUR: c_short = 1;
+const SEEK_END: c_short = 2;
+
+/// `struct flock`.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+struct Flock {
+    l_type: c_short,
+    l_whence: c_short,
+    l_start: c_long,
+    l_len: c_long,
+    l_pid: c_int,
+}
+
 impl<'a> SyscallHandler<'a> {
     pub fn sys_fcntl(&mut self, fd: Fd, cmd: c_int, arg: usize) -> Result<isize> {
         let current = current_process();
-        let mut opened_files = current.opened_files().lock();
         match cmd {
+            F_DUPFD => {
+                let fd = current.opened_files().lock().dup(
+                    fd,
+                    Some(arg as i32),
+                    OpenOptions::new(false, false),
+                )?;
+                Ok(fd.as_int() as isize)
+            }
+            F_GETFD => {
+                let cloexec = current.opened_files().lock().get_cloexec(fd)?;
+                Ok(if cloexec { FD_CLOEXEC as isize } else { 0 })
+            }
             F_SETFD => {
-                opened_files.get(fd)?.set_cloexec(arg == 1);
+                current
+                    .opened_files()
+                    .lock()
+                    .set_cloexec(fd, arg & FD_CLOEXEC != 0)?;
                 Ok(0)
             }
+            F_GETFL => {
+                let options = current.get_opened_file_by_fd(fd)?.options();
+                let mut flags = match (options.readable, options.writable) {
+                    (true, true) => OpenFlags::O_RDWR,
+                    (false, true) => OpenFlags::O_WRONLY,
+                    _ => OpenFlags::O_RDONLY,
+                };
+
+                if options.nonblock {
+                    flags |= OpenFlags::O_NONBLOCK;
+                }
+
+                if options.append {
+                    flags |= OpenFlags::O_APPEND;
+                }
+
+                Ok(flags.bits() as isize)
+            }
             F_SETFL => {
-                opened_files
-                    .get(fd)?
+                current
+                    .get_opened_file_by_fd(fd)?
                     .set_flags(OpenFlags::from_bits_truncate(arg as i32))?;
                 Ok(0)
             }
+            F_GETLK | F_SETLK | F_SETLKW | F_OFD_GETLK | F_OFD_SETLK | F_OFD_SETLKW => {
+                // Don't hold the opened file table lock: F_SETLKW may sleep.
+                let opened_file = current.get_opened_file_by_fd(fd)?;
+                fcntl_lock(&opened_file, cmd, UserVAddr::new_nonnull(arg)?)?;
+                Ok(0)
+            }
             F_DUPFD_CLOEXEC => {
-                let fd = opened_files.dup(fd, Some(arg as i32), OpenOptions::new(false, true))?;
+                let fd = current.opened_files().lock().dup(
+                    fd,
+                    Some(arg as i32),
+                    OpenOptions::new(false, true),
+                )?;
                 Ok(fd.as_int() as isize)
             }
             _ => Err(Errno::ENOSYS.into()),
         }
     }
 }
+
+/// Handles `F_GETLK`, `F_SETLK`, `F_SETLKW`, and their OFD variants.
+fn fcntl_lock(opened_file: &OpenedFile, cmd: c_int, arg: UserVAddr) -> Result<()> {
+    let mut flock = arg.read::<Flock>()?;
+    let is_ofd = matches!(cmd, F_OFD_GETLK | F_OFD_SETLK | F_OFD_SETLKW);
+    if is_ofd && flock.l_pid != 0 {
+        return Err(Errno::EINVAL.into());
+    }
+
+    let owner = if is_ofd {
+        LockOwner::opened_file(opened_file)
+    } else {
+        LockOwner::Process(current_process().pid())
+    };
+
+    let options = opened_file.options();
+    let lock_type = match flock.l_type {
+        F_RDLCK if cmd == F_GETLK || cmd == F_OFD_GETLK || options.readable => Some(LockType::Read),
+        F_WRLCK if cmd == F_GETLK || cmd == F_OFD_GETLK || options.writable => {
+            Some(LockType::Write)
+        }
+        F_RDLCK | F_WRLCK => return Err(Errno::EBADF.into()),
+        F_UNLCK if cmd != F_GETLK && cmd != F_OFD_GETLK => None,
+        _ => return Err(Errno::EINVAL.into()),
+    };
+
+    let (start, end) = lock_range(opened_file, &flock)?;
+    let key = LockKey::new(opened_file);
+    match (cmd, lock_type) {
+        (F_GETLK | F_OFD_GETLK, Some(lock_type)) => {
+            let lock = RecordLock {
+                owner,
+                lock_type,
+                start,
+                end,
+            };
+
+            match file_lock::get_conflicting_lock(key, &lock) {
+                Some(conflicting) => {
+                    flock.l_type = match conflicting.lock_type {
+                        LockType::Read => F_RDLCK,
+                        LockType::Write => F_WRLCK,
+                    };
+                    flock.l_whence = SEEK_SET;
+                    flock.l_start = conflicting.start as c_long;
+                    flock.l_len = if conflicting.end == u64::MAX {
+                        0
+                    } else {
+                        (conflicting.end - conflicting.start) as c_long
+                    };
+                    flock.l_pid = match conflicting.owner {
+                        LockOwner::Process(pid) => pid.as_i32(),
+                        LockOwner::OpenedFile(_) => -1,
+                    };
+                }
+                None => {
+                    flock.l_type = F_UNLCK;
+                }
+            }
+
+            arg.write(&flock)?;
+        }
+        (_, Some(lock_type)) => {
+            let lock = RecordLock {
+                owner,
+                lock_type,
+                start,
+                end,
+            };
+
+            let wait = cmd == F_SETLKW || cmd == F_OFD_SETLKW;
+            file_lock::set_record_lock(key, lock, wait)?;
+        }
+        (_, None) => {
+            file_lock::unlock_record(key, owner, start, end);
+        }
+    }
+
+    Ok(())
+}
+
+/// Computes the range `[start, end)` of the lock. `end` is `u64::MAX` if the
+/// lock extends to the end of the file.
+fn lock_range(opened_file: &OpenedFile, flock: &Flock) -> Result<(u64, u64)> {
+    let base = match flock.l_whence {
+        SEEK_SET => 0,
+        SEEK_CUR => opened_file.pos() as i64,
+        SEEK_END => opened_file.path().inode.stat()?.size.0 as i64,
+        _ => return Err(Errno::EINVAL.into()),
+    };
+
+    let start = base.checked_add(flock.l_start).ok_or(Errno::EOVERFLOW)?;
+    let (start, end) = match flock.l_len {
+        0 => (start, None),
+        len if len > 0 => (start, Some(start.checked_add(len).ok_or(Errno::EOVERFLOW)?)),
+        // A negative length means the range before `start`.
+        len => (start.checked_add(len).ok_or(Errno::EOVERFLOW)?, Some(start)),
+    };
+
+    if start < 0 {
+        return Err(Errno::EINVAL.into());
+    }
+
+    Ok((start as u64, end.map_or(u64::MAX, |end| end as u64)))
+}
@@ -0,0 +1,29 @@
+use crate::fs::{
+    file_lock::{self, LockKey, LockOwner, LockType},
+    opened_file::Fd,
+};
+use crate::{ctypes::c_int, prelude::*};
+use crate::{process::current_process, syscalls::SyscallHandler};
+
+const LOCK_SH: c_int = 1;
+const LOCK_EX: c_int = 2;
+const LOCK_NB: c_int = 4;
+const LOCK_UN: c_int = 8;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_flock(&mut self, fd: Fd, operation: c_int) -> Result<isize> {
+        let lock_type = match operation & !LOCK_NB {
+            LOCK_SH => Some(LockType::Read),
+            LOCK_EX => Some(LockType::Write),
+            LOCK_UN => None,
+            _ => return Err(Errno::EINVAL.into()),
+        };
+
+        let opened_file = current_process().get_opened_file_by_fd(fd)?;
+        let key = LockKey::new(&opened_file);
+        let owner = LockOwner::opened_file(&opened_file);
+        let wait = operation & LOCK_NB == 0;
+        file_lock::flock(key, owner, lock_type, wait)?;
+        Ok(0)
+    }
+}
@@ -0,0 +1,13 @@
+use super::statfs::write_statfs;
+use crate::fs::opened_file::Fd;
+use crate::result::Result;
+use crate::{process::current_process, syscalls::SyscallHandler};
+use kerla_runtime::address::UserVAddr;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_fstatfs(&mut self, fd: Fd, buf: UserVAddr) -> Result<isize> {
+        let opened_file = current_process().get_opened_file_by_fd(fd)?;
+        write_statfs(opened_file.path(), buf)?;
+        Ok(0)
+    }
+}
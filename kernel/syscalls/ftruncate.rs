@@ -0,0 +1,24 @@
+use crate::fs::{
+    inotify::{self, InotifyMask},
+    opened_file::Fd,
+};
+use crate::prelude::*;
+use crate::{process::current_process, syscalls::SyscallHandler};
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_ftruncate(&mut self, fd: Fd, length: isize) -> Result<isize> {
+        if length < 0 {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let opened_file = current_process().get_opened_file_by_fd(fd)?;
+        // "EBADF or EINVAL - fd is not open for writing." -- truncate(2)
+        if !opened_file.options().writable {
+            return Err(Errno::EINVAL.into());
+        }
+
+        opened_file.as_file()?.truncate(length as usize)?;
+        inotify::notify_path(opened_file.path(), InotifyMask::IN_MODIFY);
+        Ok(0)
+    }
+}
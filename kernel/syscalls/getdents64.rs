@@ -9,9 +9,8 @@ use crate::user_buffer::UserBufWriter;
 
 impl<'a> SyscallHandler<'a> {
     pub fn sys_getdents64(&mut self, fd: Fd, dirp: UserVAddr, len: usize) -> Result<isize> {
-        let current = current_process();
-        let opened_files = current.opened_files().lock();
-        let dir = opened_files.get(fd)?;
+        // Don't hold the lock: readdir may sleep (e.g. ext2).
+        let dir = current_process().opened_files().lock().get(fd)?.clone();
         let mut writer = UserBufWriter::from_uaddr(dirp, len);
         while let Some(entry) = dir.readdir()? {
             let alignment = size_of::<u64>();
@@ -1,5 +1,5 @@
-use crate::random::{read_insecure_random, read_secure_random};
-use crate::result::Result;
+use crate::random::{read_insecure_random, read_secure_random, try_read_secure_random};
+use crate::result::{Errno, Result};
 use crate::syscalls::SyscallHandler;
 use crate::{ctypes::c_uint, user_buffer::UserBufferMut};
 use bitflags::bitflags;
@@ -12,6 +12,7 @@ bitflags! {
     pub struct GetRandomFlags: c_uint {
         const GRND_NONBLOCK = 0x1;
         const GRND_RANDOM   = 0x2;
+        const GRND_INSECURE = 0x4;
     }
 }
 
@@ -22,11 +23,19 @@ impl<'a> SyscallHandler<'a> {
         len: usize,
         flags: GetRandomFlags,
     ) -> Result<isize> {
-        let len = min(len, GETRANDOM_LEN_MAX);
-        let read_len = if flags.contains(GetRandomFlags::GRND_RANDOM) {
-            read_secure_random(UserBufferMut::from_uaddr(buf, len))?
+        if flags.contains(GetRandomFlags::GRND_INSECURE | GetRandomFlags::GRND_RANDOM) {
+            return Err(Errno::EINVAL.into());
+        }
+
+        // We don't have the blocking pool: GRND_RANDOM reads from the CRNG as
+        // well, like Linux 5.6 and later.
+        let buf = UserBufferMut::from_uaddr(buf, min(len, GETRANDOM_LEN_MAX));
+        let read_len = if flags.contains(GetRandomFlags::GRND_INSECURE) {
+            read_insecure_random(buf)?
+        } else if flags.contains(GetRandomFlags::GRND_NONBLOCK) {
+            try_read_secure_random(buf)?
         } else {
-            read_insecure_random(UserBufferMut::from_uaddr(buf, len))?
+            read_secure_random(buf)?
         };
 
         Ok(read_len as isize)
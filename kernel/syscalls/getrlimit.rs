@@ -0,0 +1,13 @@
+use crate::ctypes::c_int;
+use crate::process::{current_process, rlimit::RLimit};
+use crate::result::Result;
+use crate::syscalls::SyscallHandler;
+use kerla_runtime::address::UserVAddr;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_getrlimit(&mut self, resource: c_int, rlim: UserVAddr) -> Result<isize> {
+        let limit = current_process().rlimits().get(resource)?;
+        rlim.write::<RLimit>(&limit)?;
+        Ok(0)
+    }
+}
@@ -0,0 +1,25 @@
+use crate::ctypes::c_int;
+use crate::prelude::*;
+use crate::syscalls::SyscallHandler;
+use crate::timer::{read_wall_clock, Timeval};
+use kerla_runtime::address::UserVAddr;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_gettimeofday(
+        &mut self,
+        tv: Option<UserVAddr>,
+        tz: Option<UserVAddr>,
+    ) -> Result<isize> {
+        if let Some(tv) = tv {
+            let now = read_wall_clock().nanosecs_from_epoch();
+            tv.write::<Timeval>(&Timeval::from_nanosecs(now))?;
+        }
+
+        // The timezone is obsolete. Always return UTC.
+        if let Some(tz) = tz {
+            tz.write::<[c_int; 2]>(&[0, 0])?;
+        }
+
+        Ok(0)
+    }
+}
@@ -0,0 +1,31 @@
+use crate::fs::{
+    inotify::{Inotify, InotifyMask},
+    opened_file::Fd,
+    path::Path,
+};
+use crate::prelude::*;
+use crate::{
+    process::current_process,
+    syscalls::{CwdOrFd, SyscallHandler},
+};
+use kerla_utils::downcast::downcast;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_inotify_add_watch(&mut self, fd: Fd, path: &Path, mask: u32) -> Result<isize> {
+        let mask = InotifyMask::from_bits_truncate(mask);
+        let current = current_process();
+        let opened_file = current.get_opened_file_by_fd(fd)?;
+        let inotify: &Inotify =
+            downcast(opened_file.as_file()?).ok_or_else(|| Error::new(Errno::EINVAL))?;
+
+        let follow_symlink = !mask.contains(InotifyMask::IN_DONT_FOLLOW);
+        let path_comp = {
+            let root_fs = current.root_fs().lock();
+            let opened_files = current.opened_files();
+            root_fs.lookup_path_at(opened_files, &CwdOrFd::AtCwd, path, follow_symlink)?
+        };
+
+        let wd = inotify.add_watch(&path_comp, mask)?;
+        Ok(wd as isize)
+    }
+}
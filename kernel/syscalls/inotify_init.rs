@@ -0,0 +1,9 @@
+use super::inotify_init1::InotifyFlags;
+use crate::prelude::*;
+use crate::syscalls::SyscallHandler;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_inotify_init(&mut self) -> Result<isize> {
+        self.sys_inotify_init1(InotifyFlags::empty())
+    }
+}
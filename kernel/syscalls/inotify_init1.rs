@@ -0,0 +1,31 @@
+use crate::fs::{
+    inode::{FileLike, INode},
+    inotify::Inotify,
+    opened_file::{OpenOptions, PathComponent},
+};
+use crate::{ctypes::c_int, prelude::*};
+use crate::{process::current_process, syscalls::SyscallHandler};
+use bitflags::bitflags;
+
+bitflags! {
+    pub struct InotifyFlags: c_int {
+        const IN_NONBLOCK = 0o4000;
+        const IN_CLOEXEC = 0o2000000;
+    }
+}
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_inotify_init1(&mut self, flags: InotifyFlags) -> Result<isize> {
+        let options = OpenOptions::new(
+            flags.contains(InotifyFlags::IN_NONBLOCK),
+            flags.contains(InotifyFlags::IN_CLOEXEC),
+        );
+
+        let inotify = Inotify::new() as Arc<dyn FileLike>;
+        let fd = current_process().opened_files().lock().open(
+            PathComponent::new_anonymous(INode::FileLike(inotify)),
+            options,
+        )?;
+        Ok(fd.as_usize() as isize)
+    }
+}
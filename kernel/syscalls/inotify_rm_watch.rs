@@ -0,0 +1,14 @@
+use crate::fs::{inotify::Inotify, opened_file::Fd};
+use crate::{ctypes::c_int, prelude::*};
+use crate::{process::current_process, syscalls::SyscallHandler};
+use kerla_utils::downcast::downcast;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_inotify_rm_watch(&mut self, fd: Fd, wd: c_int) -> Result<isize> {
+        let opened_file = current_process().get_opened_file_by_fd(fd)?;
+        let inotify: &Inotify =
+            downcast(opened_file.as_file()?).ok_or_else(|| Error::new(Errno::EINVAL))?;
+        inotify.rm_watch(wd)?;
+        Ok(0)
+    }
+}
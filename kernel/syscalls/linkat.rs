@@ -1,5 +1,8 @@
-use crate::fs::path::Path;
-use crate::result::Result;
+use crate::fs::{
+    inotify::{self, InotifyMask},
+    path::Path,
+};
+use crate::result::{Errno, Result};
 use crate::{
     process::current_process,
     syscalls::{AtFlags, CwdOrFd, SyscallHandler},
@@ -16,16 +19,25 @@ impl<'a> SyscallHandler<'a> {
     ) -> Result<isize> {
         let current = current_process();
         let root_fs = current.root_fs().lock();
-        let opened_files = current.opened_files().lock();
+        let opened_files = current.opened_files();
         let src = root_fs.lookup_path_at(
-            &opened_files,
+            opened_files,
             &src_dir,
             src_path,
             flags.contains(AtFlags::AT_SYMLINK_FOLLOW),
         )?;
         let (parent_dir, dst_name) =
-            root_fs.lookup_parent_path_at(&opened_files, &dst_dir, dst_path, true)?;
+            root_fs.lookup_parent_path_at(opened_files, &dst_dir, dst_path, true)?;
+
+        // "EXDEV - oldpath and newpath are not on the same mounted
+        // filesystem." -- link(2)
+        if src.mount_id != parent_dir.mount_id {
+            return Err(Errno::EXDEV.into());
+        }
+
+        root_fs.check_writable(&parent_dir)?;
         parent_dir.inode.as_dir()?.link(dst_name, &src.inode)?;
+        inotify::notify_entry(&parent_dir, dst_name, InotifyMask::IN_CREATE, false);
         Ok(0)
     }
 }
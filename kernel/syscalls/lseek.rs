@@ -0,0 +1,26 @@
+use crate::fs::opened_file::{Fd, SeekWhence};
+use crate::{ctypes::c_int, prelude::*};
+use crate::{process::current_process, syscalls::SyscallHandler};
+
+const SEEK_SET: c_int = 0;
+const SEEK_CUR: c_int = 1;
+const SEEK_END: c_int = 2;
+const SEEK_DATA: c_int = 3;
+const SEEK_HOLE: c_int = 4;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_lseek(&mut self, fd: Fd, offset: isize, whence: c_int) -> Result<isize> {
+        let whence = match whence {
+            SEEK_SET => SeekWhence::Set,
+            SEEK_CUR => SeekWhence::Cur,
+            SEEK_END => SeekWhence::End,
+            SEEK_DATA => SeekWhence::Data,
+            SEEK_HOLE => SeekWhence::Hole,
+            _ => return Err(Errno::EINVAL.into()),
+        };
+
+        let opened_file = current_process().get_opened_file_by_fd(fd)?;
+        let pos = opened_file.lseek(offset, whence)?;
+        Ok(pos as isize)
+    }
+}
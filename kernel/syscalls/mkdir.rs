@@ -1,19 +1,9 @@
 use crate::fs::{path::Path, stat::FileMode};
-use crate::prelude::*;
-use crate::{process::current_process, syscalls::SyscallHandler};
+use crate::result::Result;
+use crate::syscalls::{CwdOrFd, SyscallHandler};
 
 impl<'a> SyscallHandler<'a> {
     pub fn sys_mkdir(&mut self, path: &Path, mode: FileMode) -> Result<isize> {
-        let (parent_dir, name) = path
-            .parent_and_basename()
-            .ok_or_else::<Error, _>(|| Errno::EEXIST.into())?;
-
-        current_process()
-            .root_fs()
-            .lock()
-            .lookup_dir(parent_dir)?
-            .create_dir(name, mode)?;
-
-        Ok(0)
+        self.sys_mkdirat(CwdOrFd::AtCwd, path, mode)
     }
 }
@@ -0,0 +1,22 @@
+use crate::fs::{
+    inotify::{self, InotifyMask},
+    path::Path,
+    stat::FileMode,
+};
+use crate::prelude::*;
+use crate::{
+    process::current_process,
+    syscalls::{CwdOrFd, SyscallHandler},
+};
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_mkdirat(&mut self, dir: CwdOrFd, path: &Path, mode: FileMode) -> Result<isize> {
+        let current = current_process();
+        let root_fs = current.root_fs().lock();
+        let opened_files = current.opened_files();
+        let (parent_dir, name) = root_fs.lookup_writable_parent_at(opened_files, &dir, path)?;
+        parent_dir.inode.as_dir()?.create_dir(name, mode)?;
+        inotify::notify_entry(&parent_dir, name, InotifyMask::IN_CREATE, true);
+        Ok(0)
+    }
+}
@@ -11,12 +11,12 @@ impl<'a> SyscallHandler<'a> {
         &mut self,
         addr_hint: Option<UserVAddr>,
         len: c_size,
-        _prot: MMapProt,
+        prot: MMapProt,
         flags: MMapFlags,
         fd: Fd,
         offset: c_off,
     ) -> Result<isize> {
-        // TODO: Respect `prot`.
+        // TODO: Enforce `prot` in the page table.
 
         if !is_aligned(len as usize, PAGE_SIZE) {
             return Err(Errno::EINVAL.into());
@@ -29,15 +29,10 @@ impl<'a> SyscallHandler<'a> {
         let area_type = if flags.contains(MMapFlags::MAP_ANONYMOUS) {
             VmAreaType::Anonymous
         } else {
-            let file = current_process()
-                .opened_files()
-                .lock()
-                .get(fd)?
-                .as_file()?
-                .clone();
-
+            let opened_file = current_process().opened_files().lock().get(fd)?.clone();
             VmAreaType::File {
-                file,
+                file: opened_file.as_file()?.clone(),
+                path: opened_file.path().clone(),
                 offset: offset as usize,
                 file_size: len as usize,
             }
@@ -60,7 +55,7 @@ impl<'a> SyscallHandler<'a> {
             None => vm.alloc_vaddr_range(len as usize)?,
         };
 
-        vm.add_vm_area(mapped_uaddr, len as usize, area_type)?;
+        vm.add_vm_area(mapped_uaddr, len as usize, area_type, prot)?;
         Ok(mapped_uaddr.value() as isize)
     }
 }
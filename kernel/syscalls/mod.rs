@@ -2,37 +2,59 @@ use crate::{
     ctypes::*,
     fs::path::PathBuf,
     fs::{
-        opened_file::{Fd, OpenFlags},
+        inode::RenameFlags,
+        opened_file::{Fd, OpenFlags, RwFlags},
         path::Path,
-        stat::FileMode,
+        stat::{FileMode, GId, UId},
     },
     net::{RecvFromFlags, SendToFlags},
     process::{current_process, process_group::PgId, PId, Process},
     result::{Errno, Error, Result},
-    syscalls::{getrandom::GetRandomFlags, wait4::WaitOptions},
+    syscalls::{
+        faccessat2::AccessMode, getrandom::GetRandomFlags, inotify_init1::InotifyFlags,
+        splice::SpliceFlags, umount2::UmountFlags, wait4::WaitOptions,
+    },
     timer::Timeval,
     user_buffer::UserCStr,
 };
+use alloc::vec::Vec;
 use bitflags::bitflags;
-use kerla_runtime::{address::UserVAddr, arch::PtRegs};
+use core::{cmp::min, mem::size_of};
+use kerla_runtime::{
+    address::UserVAddr,
+    arch::{read_clock_counter, PtRegs},
+};
 
 mod accept;
+mod access;
+mod adjtimex;
 mod arch_prctl;
 mod bind;
 mod brk;
 mod chdir;
 mod chmod;
+mod chown;
+mod clock_getres;
 mod clock_gettime;
+mod clock_settime;
 mod close;
 mod connect;
+mod copy_file_range;
 mod dup2;
 mod execve;
 mod exit;
 mod exit_group;
+mod faccessat;
+mod faccessat2;
+mod fchmodat;
+mod fchownat;
 mod fcntl;
+mod flock;
 mod fork;
 mod fstat;
+mod fstatfs;
 mod fsync;
+mod ftruncate;
 mod getcwd;
 mod getdents64;
 mod getpeername;
@@ -40,36 +62,77 @@ mod getpgid;
 mod getpid;
 mod getppid;
 mod getrandom;
+mod getrlimit;
 mod getsockname;
 mod getsockopt;
 mod gettid;
+mod gettimeofday;
+mod inotify_add_watch;
+mod inotify_init;
+mod inotify_init1;
+mod inotify_rm_watch;
 mod ioctl;
 mod kill;
 mod link;
 mod linkat;
 mod listen;
+mod lseek;
 mod lstat;
 mod mkdir;
+mod mkdirat;
 mod mmap;
+mod mount;
+mod nanosleep;
+mod newfstatat;
 mod open;
+mod openat;
 mod pipe;
 mod poll;
+mod pread64;
+mod preadv;
+mod preadv2;
+mod prlimit64;
+mod pwrite64;
+mod pwritev;
+mod pwritev2;
 mod read;
 mod readlink;
+mod readlinkat;
+mod readv;
 mod reboot;
 mod recvfrom;
+mod rename;
+mod renameat;
+mod renameat2;
+mod rmdir;
 mod rt_sigaction;
 mod rt_sigprocmask;
 mod rt_sigreturn;
 mod select;
+mod sendfile;
 mod sendto;
 mod set_tid_address;
 mod setpgid;
+mod setrlimit;
+mod settimeofday;
 mod shutdown;
 mod socket;
+mod splice;
 mod stat;
+mod statfs;
+pub mod stats;
+mod symlink;
+mod symlinkat;
+mod sync;
 mod syslog;
+mod tee;
+mod time;
+mod truncate;
+mod umount2;
 mod uname;
+mod unlink;
+mod unlinkat;
+mod utimensat;
 mod utimes;
 mod wait4;
 mod write;
@@ -92,7 +155,13 @@ impl CwdOrFd {
 
 bitflags! {
     pub struct AtFlags: c_int {
+        const AT_SYMLINK_NOFOLLOW = 0x100;
+        const AT_REMOVEDIR = 0x200;
+        /// The same value as `AT_REMOVEDIR`: used only in `faccessat2(2)`.
+        const AT_EACCESS = 0x200;
         const AT_SYMLINK_FOLLOW = 0x400;
+        const AT_NO_AUTOMOUNT = 0x800;
+        const AT_EMPTY_PATH = 0x1000;
     }
 }
 
@@ -105,6 +174,46 @@ struct IoVec {
     len: usize,
 }
 
+/// Reads `struct iovec` entries from the userspace. Their lengths are clamped
+/// so that the total length does not exceed `MAX_READ_WRITE_LEN`.
+fn read_iovecs(iov_base: UserVAddr, iov_count: usize) -> Result<Vec<IoVec>> {
+    let iov_count = min(iov_count, IOV_MAX);
+
+    let mut iovecs = Vec::with_capacity(iov_count);
+    let mut total_len: usize = 0;
+    for i in 0..iov_count {
+        // Read an entry from the userspace.
+        let mut iov: IoVec = iov_base.add(i * size_of::<IoVec>()).read()?;
+
+        // Handle the case when total_len exceed the limit.
+        match total_len.checked_add(iov.len) {
+            Some(len) if len > MAX_READ_WRITE_LEN => {
+                iov.len = MAX_READ_WRITE_LEN - total_len;
+            }
+            None => {
+                iov.len = MAX_READ_WRITE_LEN - total_len;
+            }
+            _ => {}
+        }
+
+        total_len += iov.len;
+        iovecs.push(iov);
+    }
+
+    Ok(iovecs)
+}
+
+/// Reads an optional `loff_t *` argument (e.g. `sendfile(2)`).
+fn read_offset(uaddr: Option<UserVAddr>) -> Result<Option<usize>> {
+    match uaddr {
+        Some(uaddr) => match uaddr.read::<c_long>()? {
+            offset if offset < 0 => Err(Errno::EINVAL.into()),
+            offset => Ok(Some(offset as usize)),
+        },
+        None => Ok(None),
+    }
+}
+
 const SYS_READ: usize = 0;
 const SYS_WRITE: usize = 1;
 const SYS_OPEN: usize = 2;
@@ -113,17 +222,24 @@ const SYS_STAT: usize = 4;
 const SYS_FSTAT: usize = 5;
 const SYS_LSTAT: usize = 6;
 const SYS_POLL: usize = 7;
+const SYS_LSEEK: usize = 8;
 const SYS_MMAP: usize = 9;
 const SYS_BRK: usize = 12;
 const SYS_RT_SIGACTION: usize = 13;
 const SYS_RT_SIGPROCMASK: usize = 14;
 const SYS_RT_SIGRETURN: usize = 15;
 const SYS_IOCTL: usize = 16;
+const SYS_PREAD64: usize = 17;
+const SYS_PWRITE64: usize = 18;
+const SYS_READV: usize = 19;
 const SYS_WRITEV: usize = 20;
+const SYS_ACCESS: usize = 21;
 const SYS_PIPE: usize = 22;
 const SYS_SELECT: usize = 23;
 const SYS_DUP2: usize = 33;
+const SYS_NANOSLEEP: usize = 35;
 const SYS_GETPID: usize = 39;
+const SYS_SENDFILE: usize = 40;
 const SYS_SOCKET: usize = 41;
 const SYS_CONNECT: usize = 42;
 const SYS_ACCEPT: usize = 43;
@@ -142,14 +258,23 @@ const SYS_WAIT4: usize = 61;
 const SYS_KILL: usize = 62;
 const SYS_UNAME: usize = 63;
 const SYS_FCNTL: usize = 72;
+const SYS_FLOCK: usize = 73;
 const SYS_FSYNC: usize = 74;
+const SYS_TRUNCATE: usize = 76;
+const SYS_FTRUNCATE: usize = 77;
 const SYS_GETCWD: usize = 79;
 const SYS_CHDIR: usize = 80;
+const SYS_RENAME: usize = 82;
 const SYS_MKDIR: usize = 83;
+const SYS_RMDIR: usize = 84;
 const SYS_LINK: usize = 86;
+const SYS_UNLINK: usize = 87;
+const SYS_SYMLINK: usize = 88;
 const SYS_READLINK: usize = 89;
 const SYS_CHMOD: usize = 90;
 const SYS_CHOWN: usize = 92;
+const SYS_GETTIMEOFDAY: usize = 96;
+const SYS_GETRLIMIT: usize = 97;
 const SYS_GETUID: usize = 102;
 const SYS_SYSLOG: usize = 103;
 const SYS_SETUID: usize = 105;
@@ -159,16 +284,52 @@ const SYS_SETPGID: usize = 109;
 const SYS_GETPPID: usize = 110;
 const SYS_GETPGID: usize = 121;
 const SYS_SETGROUPS: usize = 116;
+const SYS_STATFS: usize = 137;
+const SYS_FSTATFS: usize = 138;
 const SYS_ARCH_PRCTL: usize = 158;
+const SYS_ADJTIMEX: usize = 159;
+const SYS_SETRLIMIT: usize = 160;
+const SYS_SYNC: usize = 162;
+const SYS_SETTIMEOFDAY: usize = 164;
+const SYS_MOUNT: usize = 165;
+const SYS_UMOUNT2: usize = 166;
 const SYS_REBOOT: usize = 169;
 const SYS_GETTID: usize = 186;
+const SYS_TIME: usize = 201;
 const SYS_GETDENTS64: usize = 217;
 const SYS_SET_TID_ADDRESS: usize = 218;
+const SYS_CLOCK_SETTIME: usize = 227;
 const SYS_CLOCK_GETTIME: usize = 228;
+const SYS_CLOCK_GETRES: usize = 229;
 const SYS_EXIT_GROUP: usize = 231;
 const SYS_UTIMES: usize = 235;
+const SYS_INOTIFY_INIT: usize = 253;
+const SYS_INOTIFY_ADD_WATCH: usize = 254;
+const SYS_INOTIFY_RM_WATCH: usize = 255;
+const SYS_OPENAT: usize = 257;
+const SYS_MKDIRAT: usize = 258;
+const SYS_FCHOWNAT: usize = 260;
+const SYS_NEWFSTATAT: usize = 262;
+const SYS_UNLINKAT: usize = 263;
+const SYS_RENAMEAT: usize = 264;
 const SYS_LINKAT: usize = 265;
+const SYS_SYMLINKAT: usize = 266;
+const SYS_READLINKAT: usize = 267;
+const SYS_FCHMODAT: usize = 268;
+const SYS_FACCESSAT: usize = 269;
+const SYS_SPLICE: usize = 275;
+const SYS_TEE: usize = 276;
+const SYS_UTIMENSAT: usize = 280;
+const SYS_INOTIFY_INIT1: usize = 294;
+const SYS_PREADV: usize = 295;
+const SYS_PWRITEV: usize = 296;
+const SYS_PRLIMIT64: usize = 302;
+const SYS_RENAMEAT2: usize = 316;
 const SYS_GETRANDOM: usize = 318;
+const SYS_COPY_FILE_RANGE: usize = 326;
+const SYS_PREADV2: usize = 327;
+const SYS_PWRITEV2: usize = 328;
+const SYS_FACCESSAT2: usize = 439;
 
 fn resolve_path(uaddr: usize) -> Result<PathBuf> {
     const PATH_MAX: usize = 512;
@@ -216,10 +377,12 @@ impl<'a> SyscallHandler<'a> {
             );
         }
 
+        let started_at = read_clock_counter();
         let ret = self.do_dispatch(a1, a2, a3, a4, a5, a6, n).map_err(|err| {
             debug_warn!("{}: error: {:?}", syscall_name_by_number(n), err);
             err
         });
+        stats::record_syscall(n, &ret, read_clock_counter().wrapping_sub(started_at));
 
         if let Err(err) = Process::try_delivering_signal(self.frame) {
             debug_warn!("failed to setup the signal stack: {:?}", err);
@@ -248,7 +411,76 @@ impl<'a> SyscallHandler<'a> {
             SYS_CLOSE => self.sys_close(Fd::new(a1 as i32)),
             SYS_READ => self.sys_read(Fd::new(a1 as i32), UserVAddr::new_nonnull(a2)?, a3),
             SYS_WRITE => self.sys_write(Fd::new(a1 as i32), UserVAddr::new_nonnull(a2)?, a3),
+            SYS_LSEEK => self.sys_lseek(Fd::new(a1 as i32), a2 as isize, a3 as c_int),
+            SYS_PREAD64 => self.sys_pread64(
+                Fd::new(a1 as i32),
+                UserVAddr::new_nonnull(a2)?,
+                a3,
+                a4 as isize,
+            ),
+            SYS_PWRITE64 => self.sys_pwrite64(
+                Fd::new(a1 as i32),
+                UserVAddr::new_nonnull(a2)?,
+                a3,
+                a4 as isize,
+            ),
+            SYS_SENDFILE => self.sys_sendfile(
+                Fd::new(a1 as i32),
+                Fd::new(a2 as i32),
+                UserVAddr::new(a3),
+                a4,
+            ),
+            SYS_SPLICE => self.sys_splice(
+                Fd::new(a1 as i32),
+                UserVAddr::new(a2),
+                Fd::new(a3 as i32),
+                UserVAddr::new(a4),
+                a5,
+                bitflags_from_user!(SpliceFlags, a6 as c_uint)?,
+            ),
+            SYS_TEE => self.sys_tee(
+                Fd::new(a1 as i32),
+                Fd::new(a2 as i32),
+                a3,
+                bitflags_from_user!(SpliceFlags, a4 as c_uint)?,
+            ),
+            SYS_COPY_FILE_RANGE => self.sys_copy_file_range(
+                Fd::new(a1 as i32),
+                UserVAddr::new(a2),
+                Fd::new(a3 as i32),
+                UserVAddr::new(a4),
+                a5,
+                a6 as c_uint,
+            ),
+            SYS_READV => self.sys_readv(Fd::new(a1 as i32), UserVAddr::new_nonnull(a2)?, a3),
             SYS_WRITEV => self.sys_writev(Fd::new(a1 as i32), UserVAddr::new_nonnull(a2)?, a3),
+            // The high 32 bits of the offset (a5) are unused on 64-bit architectures.
+            SYS_PREADV => self.sys_preadv(
+                Fd::new(a1 as i32),
+                UserVAddr::new_nonnull(a2)?,
+                a3,
+                a4 as isize,
+            ),
+            SYS_PWRITEV => self.sys_pwritev(
+                Fd::new(a1 as i32),
+                UserVAddr::new_nonnull(a2)?,
+                a3,
+                a4 as isize,
+            ),
+            SYS_PREADV2 => self.sys_preadv2(
+                Fd::new(a1 as i32),
+                UserVAddr::new_nonnull(a2)?,
+                a3,
+                a4 as isize,
+                bitflags_from_user!(RwFlags, a6 as c_int)?,
+            ),
+            SYS_PWRITEV2 => self.sys_pwritev2(
+                Fd::new(a1 as i32),
+                UserVAddr::new_nonnull(a2)?,
+                a3,
+                a4 as isize,
+                bitflags_from_user!(RwFlags, a6 as c_int)?,
+            ),
             SYS_MMAP => self.sys_mmap(
                 UserVAddr::new(a1),
                 a2 as c_size,
@@ -257,11 +489,76 @@ impl<'a> SyscallHandler<'a> {
                 Fd::new(a5 as i32),
                 a6 as c_off,
             ),
+            SYS_OPENAT => self.sys_openat(
+                CwdOrFd::parse(a1 as c_int),
+                &resolve_path(a2)?,
+                bitflags_from_user!(OpenFlags, a3 as i32)?,
+                FileMode::new(a4 as u32),
+            ),
             SYS_STAT => self.sys_stat(&resolve_path(a1)?, UserVAddr::new_nonnull(a2)?),
             SYS_FSTAT => self.sys_fstat(Fd::new(a1 as c_int), UserVAddr::new_nonnull(a2)?),
             SYS_LSTAT => self.sys_lstat(&resolve_path(a1)?, UserVAddr::new_nonnull(a2)?),
+            SYS_NEWFSTATAT => self.sys_newfstatat(
+                CwdOrFd::parse(a1 as c_int),
+                &resolve_path(a2)?,
+                UserVAddr::new_nonnull(a3)?,
+                bitflags_from_user!(AtFlags, a4 as c_int)?,
+            ),
+            SYS_ACCESS => self.sys_access(
+                &resolve_path(a1)?,
+                bitflags_from_user!(AccessMode, a2 as c_int)?,
+            ),
+            SYS_FACCESSAT => self.sys_faccessat(
+                CwdOrFd::parse(a1 as c_int),
+                &resolve_path(a2)?,
+                bitflags_from_user!(AccessMode, a3 as c_int)?,
+            ),
+            SYS_FACCESSAT2 => self.sys_faccessat2(
+                CwdOrFd::parse(a1 as c_int),
+                &resolve_path(a2)?,
+                bitflags_from_user!(AccessMode, a3 as c_int)?,
+                bitflags_from_user!(AtFlags, a4 as c_int)?,
+            ),
             SYS_FCNTL => self.sys_fcntl(Fd::new(a1 as i32), a2 as c_int, a3),
+            SYS_FLOCK => self.sys_flock(Fd::new(a1 as i32), a2 as c_int),
+            SYS_INOTIFY_INIT => self.sys_inotify_init(),
+            SYS_INOTIFY_INIT1 => {
+                self.sys_inotify_init1(bitflags_from_user!(InotifyFlags, a1 as c_int)?)
+            }
+            SYS_INOTIFY_ADD_WATCH => {
+                self.sys_inotify_add_watch(Fd::new(a1 as i32), &resolve_path(a2)?, a3 as u32)
+            }
+            SYS_INOTIFY_RM_WATCH => self.sys_inotify_rm_watch(Fd::new(a1 as i32), a2 as c_int),
             SYS_LINK => self.sys_link(&resolve_path(a1)?, &resolve_path(a2)?),
+            SYS_UNLINK => self.sys_unlink(&resolve_path(a1)?),
+            SYS_UNLINKAT => self.sys_unlinkat(
+                CwdOrFd::parse(a1 as c_int),
+                &resolve_path(a2)?,
+                bitflags_from_user!(AtFlags, a3 as c_int)?,
+            ),
+            SYS_RMDIR => self.sys_rmdir(&resolve_path(a1)?),
+            SYS_RENAME => self.sys_rename(&resolve_path(a1)?, &resolve_path(a2)?),
+            SYS_RENAMEAT => self.sys_renameat(
+                CwdOrFd::parse(a1 as c_int),
+                &resolve_path(a2)?,
+                CwdOrFd::parse(a3 as c_int),
+                &resolve_path(a4)?,
+            ),
+            SYS_RENAMEAT2 => self.sys_renameat2(
+                CwdOrFd::parse(a1 as c_int),
+                &resolve_path(a2)?,
+                CwdOrFd::parse(a3 as c_int),
+                &resolve_path(a4)?,
+                bitflags_from_user!(RenameFlags, a5 as c_int)?,
+            ),
+            SYS_SYMLINK => self.sys_symlink(&resolve_path(a1)?, &resolve_path(a2)?),
+            SYS_SYMLINKAT => self.sys_symlinkat(
+                &resolve_path(a1)?,
+                CwdOrFd::parse(a2 as c_int),
+                &resolve_path(a3)?,
+            ),
+            SYS_TRUNCATE => self.sys_truncate(&resolve_path(a1)?, a2 as isize),
+            SYS_FTRUNCATE => self.sys_ftruncate(Fd::new(a1 as i32), a2 as isize),
             SYS_LINKAT => self.sys_linkat(
                 CwdOrFd::parse(a1 as c_int),
                 &resolve_path(a2)?,
@@ -270,10 +567,38 @@ impl<'a> SyscallHandler<'a> {
                 bitflags_from_user!(AtFlags, a5 as c_int)?,
             ),
             SYS_READLINK => self.sys_readlink(&resolve_path(a1)?, UserVAddr::new_nonnull(a2)?, a3),
+            SYS_READLINKAT => self.sys_readlinkat(
+                CwdOrFd::parse(a1 as c_int),
+                &resolve_path(a2)?,
+                UserVAddr::new_nonnull(a3)?,
+                a4,
+            ),
             SYS_CHMOD => self.sys_chmod(&resolve_path(a1)?, FileMode::new(a2 as u32)),
-            SYS_CHOWN => Ok(0), // TODO:
+            SYS_FCHMODAT => self.sys_fchmodat(
+                CwdOrFd::parse(a1 as c_int),
+                &resolve_path(a2)?,
+                FileMode::new(a3 as u32),
+            ),
+            SYS_CHOWN => self.sys_chown(&resolve_path(a1)?, UId(a2 as u32), GId(a3 as u32)),
+            SYS_FCHOWNAT => self.sys_fchownat(
+                CwdOrFd::parse(a1 as c_int),
+                &resolve_path(a2)?,
+                UId(a3 as u32),
+                GId(a4 as u32),
+                bitflags_from_user!(AtFlags, a5 as c_int)?,
+            ),
             SYS_FSYNC => self.sys_fsync(Fd::new(a1 as i32)),
             SYS_UTIMES => self.sys_utimes(&resolve_path(a1)?, UserVAddr::new(a2)),
+            SYS_UTIMENSAT => self.sys_utimensat(
+                CwdOrFd::parse(a1 as c_int),
+                match a2 {
+                    0 => None,
+                    _ => Some(resolve_path(a2)?),
+                }
+                .as_deref(),
+                UserVAddr::new(a3),
+                bitflags_from_user!(AtFlags, a4 as c_int)?,
+            ),
             SYS_GETDENTS64 => {
                 self.sys_getdents64(Fd::new(a1 as i32), UserVAddr::new_nonnull(a2)?, a3)
             }
@@ -288,9 +613,17 @@ impl<'a> SyscallHandler<'a> {
                     .transpose()?,
             ),
             SYS_DUP2 => self.sys_dup2(Fd::new(a1 as c_int), Fd::new(a2 as c_int)),
+            SYS_NANOSLEEP => self.sys_nanosleep(UserVAddr::new_nonnull(a1)?, UserVAddr::new(a2)),
             SYS_GETCWD => self.sys_getcwd(UserVAddr::new_nonnull(a1)?, a2 as c_size),
             SYS_CHDIR => self.sys_chdir(&resolve_path(a1)?),
             SYS_MKDIR => self.sys_mkdir(&resolve_path(a1)?, FileMode::new(a2 as u32)),
+            SYS_MKDIRAT => self.sys_mkdirat(
+                CwdOrFd::parse(a1 as c_int),
+                &resolve_path(a2)?,
+                FileMode::new(a3 as u32),
+            ),
+            SYS_STATFS => self.sys_statfs(&resolve_path(a1)?, UserVAddr::new_nonnull(a2)?),
+            SYS_FSTATFS => self.sys_fstatfs(Fd::new(a1 as c_int), UserVAddr::new_nonnull(a2)?),
             SYS_ARCH_PRCTL => self.sys_arch_prctl(a1 as i32, UserVAddr::new_nonnull(a2)?),
             SYS_BRK => self.sys_brk(UserVAddr::new(a1)),
             SYS_IOCTL => self.sys_ioctl(Fd::new(a1 as i32), a2, a3),
@@ -303,6 +636,14 @@ impl<'a> SyscallHandler<'a> {
             SYS_SETGROUPS => Ok(0), // TODO:
             SYS_SETPGID => self.sys_setpgid(PId::new(a1 as i32), PgId::new(a2 as i32)),
             SYS_GETPPID => self.sys_getppid(),
+            SYS_GETRLIMIT => self.sys_getrlimit(a1 as c_int, UserVAddr::new_nonnull(a2)?),
+            SYS_SETRLIMIT => self.sys_setrlimit(a1 as c_int, UserVAddr::new_nonnull(a2)?),
+            SYS_PRLIMIT64 => self.sys_prlimit64(
+                PId::new(a1 as i32),
+                a2 as c_int,
+                UserVAddr::new(a3),
+                UserVAddr::new(a4),
+            ),
             SYS_SET_TID_ADDRESS => self.sys_set_tid_address(UserVAddr::new_nonnull(a1)?),
             SYS_PIPE => self.sys_pipe(UserVAddr::new_nonnull(a1)?),
             SYS_RT_SIGACTION => self.sys_rt_sigaction(a1 as c_int, a2, UserVAddr::new(a3)),
@@ -367,12 +708,32 @@ impl<'a> SyscallHandler<'a> {
             SYS_CLOCK_GETTIME => {
                 self.sys_clock_gettime(a1 as c_clockid, UserVAddr::new_nonnull(a2)?)
             }
+            SYS_CLOCK_GETRES => self.sys_clock_getres(a1 as c_clockid, UserVAddr::new(a2)),
+            SYS_CLOCK_SETTIME => {
+                self.sys_clock_settime(a1 as c_clockid, UserVAddr::new_nonnull(a2)?)
+            }
+            SYS_GETTIMEOFDAY => self.sys_gettimeofday(UserVAddr::new(a1), UserVAddr::new(a2)),
+            SYS_SETTIMEOFDAY => self.sys_settimeofday(UserVAddr::new(a1)),
+            SYS_TIME => self.sys_time(UserVAddr::new(a1)),
+            SYS_ADJTIMEX => self.sys_adjtimex(UserVAddr::new_nonnull(a1)?),
             SYS_GETRANDOM => self.sys_getrandom(
                 UserVAddr::new_nonnull(a1)?,
                 a2,
                 bitflags_from_user!(GetRandomFlags, a3 as c_uint)?,
             ),
             SYS_SYSLOG => self.sys_syslog(a1 as c_int, UserVAddr::new(a2), a3 as c_int),
+            SYS_SYNC => self.sys_sync(),
+            SYS_MOUNT => self.sys_mount(
+                UserVAddr::new(a1),
+                &resolve_path(a2)?,
+                UserVAddr::new(a3),
+                a4,
+                UserVAddr::new(a5),
+            ),
+            SYS_UMOUNT2 => self.sys_umount2(
+                &resolve_path(a1)?,
+                bitflags_from_user!(UmountFlags, a2 as c_int)?,
+            ),
             SYS_REBOOT => self.sys_reboot(a1 as c_int, a2 as c_int, a3),
             SYS_GETTID => self.sys_gettid(),
             SYS_RT_SIGPROCMASK => {
@@ -0,0 +1,75 @@
+use crate::fs::{file_system::lookup_file_system_type, mount::MountFlags, path::Path};
+use crate::prelude::*;
+use crate::user_buffer::UserCStr;
+use crate::{process::current_process, syscalls::SyscallHandler};
+use kerla_runtime::{address::UserVAddr, arch::PAGE_SIZE};
+
+const SOURCE_LEN_MAX: usize = 512;
+const FS_TYPE_LEN_MAX: usize = 64;
+const DATA_LEN_MAX: usize = PAGE_SIZE;
+
+/// The magic number in the upper 16 bits of flags used by old programs.
+const MS_MGC_VAL: usize = 0xc0ed0000;
+const MS_MGC_MSK: usize = 0xffff0000;
+
+fn read_cstr(uaddr: Option<UserVAddr>, max_len: usize) -> Result<String> {
+    match uaddr {
+        Some(uaddr) => Ok(UserCStr::new(uaddr, max_len)?.as_str().to_owned()),
+        None => Ok(String::new()),
+    }
+}
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_mount(
+        &mut self,
+        source: Option<UserVAddr>,
+        target: &Path,
+        fs_type: Option<UserVAddr>,
+        flags: usize,
+        data: Option<UserVAddr>,
+    ) -> Result<isize> {
+        let flags = if flags & MS_MGC_MSK == MS_MGC_VAL {
+            flags & !MS_MGC_MSK
+        } else {
+            flags
+        };
+        let flags = MountFlags::from_bits_truncate(flags);
+        let root_fs = current_process().root_fs();
+
+        if flags.contains(MountFlags::MS_REMOUNT) {
+            root_fs.lock().remount(target, flags)?;
+            return Ok(0);
+        }
+
+        if flags.contains(MountFlags::MS_BIND) {
+            // TODO: Support MS_REC: mounts under the source are not bound.
+            let source = read_cstr(source, SOURCE_LEN_MAX)?;
+            root_fs
+                .lock()
+                .bind_mount(Path::new(&source), target, flags)?;
+            return Ok(0);
+        }
+
+        if flags.intersects(MountFlags::PROPAGATION) {
+            // Mount events are never propagated: all mounts are private.
+            root_fs.lock().lookup_dir(target)?;
+            return Ok(0);
+        }
+
+        if flags.contains(MountFlags::MS_MOVE) {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let source = read_cstr(source, SOURCE_LEN_MAX)?;
+        let fs_type = read_cstr(fs_type, FS_TYPE_LEN_MAX)?;
+        let data = read_cstr(data, DATA_LEN_MAX)?;
+        let fs_type = lookup_file_system_type(&fs_type)
+            .ok_or_else(|| Error::with_message(Errno::ENODEV, "unknown file system type"))?;
+
+        // Don't hold the lock while instantiating the file system: it may
+        // wait for the device.
+        let fs = (fs_type.mount)(&source, &data)?;
+        root_fs.lock().mount_fs(target, &source, fs, flags)?;
+        Ok(0)
+    }
+}
@@ -0,0 +1,29 @@
+use crate::prelude::*;
+use crate::syscalls::SyscallHandler;
+use crate::timer::{read_monotonic_clock, sleep_until, Timespec};
+use kerla_runtime::address::UserVAddr;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_nanosleep(&mut self, req: UserVAddr, rem: Option<UserVAddr>) -> Result<isize> {
+        let duration = req.read::<Timespec>()?;
+        if !duration.is_valid() {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let deadline = read_monotonic_clock().add_nanosecs(duration.as_nanosecs());
+        if let Err(err) = sleep_until(deadline) {
+            // Interrupted by a signal. Tell the caller how long it should
+            // sleep more.
+            if let Some(rem) = rem {
+                let remaining = deadline
+                    .nanosecs()
+                    .saturating_sub(read_monotonic_clock().nanosecs());
+                rem.write::<Timespec>(&Timespec::from_nanosecs(remaining))?;
+            }
+
+            return Err(err);
+        }
+
+        Ok(0)
+    }
+}
@@ -0,0 +1,27 @@
+use crate::fs::path::Path;
+use crate::result::Result;
+use crate::{
+    process::current_process,
+    syscalls::{AtFlags, CwdOrFd, SyscallHandler},
+};
+use kerla_runtime::address::UserVAddr;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_newfstatat(
+        &mut self,
+        dir: CwdOrFd,
+        path: &Path,
+        buf: UserVAddr,
+        flags: AtFlags,
+    ) -> Result<isize> {
+        let current = current_process();
+        let stat = current
+            .root_fs()
+            .lock()
+            .lookup_path_at_flags(current.opened_files(), &dir, path, flags)?
+            .inode
+            .stat()?;
+        buf.write(&stat)?;
+        Ok(0)
+    }
+}
@@ -0,0 +1,100 @@
+use super::CwdOrFd;
+use crate::fs::stat::{O_ACCMODE, O_RDWR, O_WRONLY};
+use crate::fs::{
+    inode::INode,
+    inotify::{self, InotifyMask},
+    opened_file::{OpenFlags, PathComponent},
+    path::Path,
+    stat::FileMode,
+};
+use crate::prelude::*;
+use crate::{process::current_process, syscalls::SyscallHandler};
+
+fn create_file(dir: &CwdOrFd, path: &Path, flags: OpenFlags, mode: FileMode) -> Result<INode> {
+    if flags.contains(OpenFlags::O_DIRECTORY) {
+        // A directory should be created through mkdir(2).
+        return Err(Errno::EINVAL.into());
+    }
+
+    let current = current_process();
+    let root_fs = current.root_fs().lock();
+    let opened_files = current.opened_files();
+    let (parent_dir, name) = root_fs.lookup_writable_parent_at(opened_files, dir, path)?;
+    let inode = parent_dir.inode.as_dir()?.create_file(name, mode)?;
+    inotify::notify_entry(&parent_dir, name, InotifyMask::IN_CREATE, false);
+    Ok(inode)
+}
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_openat(
+        &mut self,
+        dir: CwdOrFd,
+        path: &Path,
+        flags: OpenFlags,
+        mode: FileMode,
+    ) -> Result<isize> {
+        let current = current_process();
+        trace!(
+            "[{}:{}] open(\"{}\")",
+            current.pid().as_i32(),
+            current.cmdline().argv0(),
+            path.as_str()
+        );
+
+        if flags.contains(OpenFlags::O_CREAT) {
+            match create_file(&dir, path, flags, mode) {
+                Ok(_) => {}
+                Err(err) if !flags.contains(OpenFlags::O_EXCL) && err.errno() == Errno::EEXIST => {}
+                Err(err) => {
+                    return Err(err);
+                }
+            }
+        }
+
+        let root_fs = current.root_fs().lock();
+        let opened_files = current.opened_files();
+
+        let path_comp = root_fs.lookup_path_at(opened_files, &dir, path, true)?;
+        if flags.contains(OpenFlags::O_DIRECTORY) && !path_comp.inode.is_dir() {
+            return Err(Error::new(Errno::ENOTDIR));
+        }
+
+        let access_mode = flags.bits() as u32 & O_ACCMODE;
+        if path_comp.inode.is_dir() && (access_mode == O_WRONLY || access_mode == O_RDWR) {
+            return Err(Error::new(Errno::EISDIR));
+        }
+
+        // Device files are writable even in a read-only mount.
+        let writes = flags.intersects(OpenFlags::O_WRONLY | OpenFlags::O_RDWR | OpenFlags::O_TRUNC);
+        let is_regular_file = path_comp.inode.stat()?.mode.is_regular_file();
+        if writes && is_regular_file {
+            root_fs.check_writable(&path_comp)?;
+        }
+
+        if flags.contains(OpenFlags::O_TRUNC) && is_regular_file {
+            path_comp.inode.as_file()?.truncate(0)?;
+            inotify::notify_path(&path_comp, InotifyMask::IN_MODIFY);
+        }
+
+        // Replace inode if FileLike::open returned Some. Currently it's used
+        // for /dev/ptmx and copy-ups in overlayfs. It may sleep: call it
+        // before locking the opened file table.
+        let options = flags.into();
+        let new_inode = match &path_comp.inode {
+            INode::FileLike(file) => file.open(&options)?,
+            _ => None,
+        };
+        let path_comp = match new_inode {
+            Some(new_inode) => Arc::new(PathComponent {
+                name: path_comp.name.clone(),
+                parent_dir: path_comp.parent_dir.clone(),
+                inode: new_inode.into(),
+                mount_id: path_comp.mount_id,
+            }),
+            None => path_comp,
+        };
+
+        let fd = opened_files.lock().open(path_comp, options)?;
+        Ok(fd.as_usize() as isize)
+    }
+}
@@ -9,7 +9,7 @@ use crate::{
     prelude::*,
     process::current_process,
     syscalls::SyscallHandler,
-    timer::read_monotonic_clock,
+    timer::{read_monotonic_clock, Timer},
     user_buffer::UserBuffer,
 };
 
@@ -17,12 +17,14 @@ use crate::user_buffer::UserBufReader;
 
 impl<'a> SyscallHandler<'a> {
     pub fn sys_poll(&mut self, fds: UserVAddr, nfds: c_nfds, timeout: c_int) -> Result<isize> {
-        let started_at = read_monotonic_clock();
+        // A negative timeout means an infinite timeout.
+        let deadline = if timeout >= 0 {
+            Some(read_monotonic_clock().add_nanosecs(timeout as usize * 1_000_000))
+        } else {
+            None
+        };
+        let _timer = deadline.map(Timer::wakeup);
         POLL_WAIT_QUEUE.sleep_signalable_until(|| {
-            if timeout > 0 && started_at.elapsed_msecs() >= (timeout as usize) {
-                return Ok(Some(0));
-            }
-
             // Check the statuses of all specified files one by one.
             let mut ready_fds = 0;
             let fds_len = (nfds as usize) * (size_of::<Fd>() + 2 * size_of::<c_short>());
@@ -53,6 +55,8 @@ impl<'a> SyscallHandler<'a> {
 
             if ready_fds > 0 {
                 Ok(Some(ready_fds))
+            } else if matches!(deadline, Some(deadline) if read_monotonic_clock() >= deadline) {
+                Ok(Some(0))
             } else {
                 // Sleep until any changes in files or sockets occur...
                 Ok(None)
@@ -0,0 +1,31 @@
+use super::MAX_READ_WRITE_LEN;
+use crate::fs::opened_file::{Fd, RwFlags};
+use crate::{prelude::*, user_buffer::UserBufferMut};
+use crate::{process::current_process, syscalls::SyscallHandler};
+use core::cmp::min;
+use kerla_runtime::address::UserVAddr;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_pread64(
+        &mut self,
+        fd: Fd,
+        uaddr: UserVAddr,
+        len: usize,
+        offset: isize,
+    ) -> Result<isize> {
+        if offset < 0 {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let len = min(len, MAX_READ_WRITE_LEN);
+        let opened_file = current_process().get_opened_file_by_fd(fd)?;
+        let read_len = opened_file.read_at(
+            Some(offset as usize),
+            UserBufferMut::from_uaddr(uaddr, len),
+            RwFlags::empty(),
+        )?;
+
+        // MAX_READ_WRITE_LEN limit guarantees read_len is in the range of isize.
+        Ok(read_len as isize)
+    }
+}
@@ -0,0 +1,21 @@
+use crate::fs::opened_file::{Fd, RwFlags};
+use crate::prelude::*;
+use crate::syscalls::SyscallHandler;
+use kerla_runtime::address::UserVAddr;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_preadv(
+        &mut self,
+        fd: Fd,
+        iov_base: UserVAddr,
+        iov_count: usize,
+        offset: isize,
+    ) -> Result<isize> {
+        // Unlike preadv2(2), -1 does not mean the current file position.
+        if offset < 0 {
+            return Err(Errno::EINVAL.into());
+        }
+
+        self.sys_preadv2(fd, iov_base, iov_count, offset, RwFlags::empty())
+    }
+}
@@ -0,0 +1,48 @@
+use super::read_iovecs;
+use crate::fs::opened_file::{Fd, RwFlags};
+use crate::{prelude::*, user_buffer::UserBufferMut};
+use crate::{process::current_process, syscalls::SyscallHandler};
+use kerla_runtime::address::UserVAddr;
+
+impl<'a> SyscallHandler<'a> {
+    /// `preadv2(2)`. `offset` is -1 to read from (and advance) the current
+    /// file position.
+    pub fn sys_preadv2(
+        &mut self,
+        fd: Fd,
+        iov_base: UserVAddr,
+        iov_count: usize,
+        offset: isize,
+        flags: RwFlags,
+    ) -> Result<isize> {
+        let offset = match offset {
+            -1 => None,
+            _ if offset < 0 => return Err(Errno::EINVAL.into()),
+            _ => Some(offset as usize),
+        };
+
+        let opened_file = current_process().get_opened_file_by_fd(fd)?;
+        let mut total_len: usize = 0;
+        for iov in read_iovecs(iov_base, iov_count)? {
+            if iov.len == 0 {
+                continue;
+            }
+
+            let buf = UserBufferMut::from_uaddr(iov.base, iov.len);
+            let read_len = match opened_file.read_at(offset.map(|o| o + total_len), buf, flags) {
+                Ok(read_len) => read_len,
+                // Return the length read so far (e.g. EAGAIN with RWF_NOWAIT).
+                Err(_) if total_len > 0 => break,
+                Err(err) => return Err(err),
+            };
+
+            total_len += read_len;
+            if read_len < iov.len {
+                break;
+            }
+        }
+
+        // MAX_READ_WRITE_LEN limit guarantees total_len is in the range of isize.
+        Ok(total_len as isize)
+    }
+}
@@ -0,0 +1,47 @@
+use crate::ctypes::c_int;
+use crate::prelude::*;
+use crate::process::{current_process, is_privileged, rlimit::RLimit, PId, Process};
+use crate::syscalls::SyscallHandler;
+use kerla_runtime::address::UserVAddr;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_prlimit64(
+        &mut self,
+        pid: PId,
+        resource: c_int,
+        new_rlim: Option<UserVAddr>,
+        old_rlim: Option<UserVAddr>,
+    ) -> Result<isize> {
+        let current = current_process();
+        let process = if pid.as_i32() == 0 {
+            current.clone()
+        } else {
+            Process::find_by_pid(pid).ok_or_else(|| Error::new(Errno::ESRCH))?
+        };
+
+        // Only the superuser can read or modify limits of other processes.
+        if !Arc::ptr_eq(&process, current) && !is_privileged() {
+            return Err(Errno::EPERM.into());
+        }
+
+        // Read the new limit before updating anything so that an invalid
+        // pointer does not leave the old one half-written.
+        let new_limit = match new_rlim {
+            Some(new_rlim) => Some(new_rlim.read::<RLimit>()?),
+            None => None,
+        };
+
+        let mut rlimits = process.rlimits();
+        let old_limit = rlimits.get(resource)?;
+        if let Some(new_limit) = new_limit {
+            rlimits.set(resource, new_limit)?;
+        }
+        drop(rlimits);
+
+        if let Some(old_rlim) = old_rlim {
+            old_rlim.write::<RLimit>(&old_limit)?;
+        }
+
+        Ok(0)
+    }
+}
@@ -0,0 +1,31 @@
+use super::MAX_READ_WRITE_LEN;
+use crate::fs::opened_file::{Fd, RwFlags};
+use crate::{prelude::*, user_buffer::UserBuffer};
+use crate::{process::current_process, syscalls::SyscallHandler};
+use core::cmp::min;
+use kerla_runtime::address::UserVAddr;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_pwrite64(
+        &mut self,
+        fd: Fd,
+        uaddr: UserVAddr,
+        len: usize,
+        offset: isize,
+    ) -> Result<isize> {
+        if offset < 0 {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let len = min(len, MAX_READ_WRITE_LEN);
+        let opened_file = current_process().get_opened_file_by_fd(fd)?;
+        let written_len = opened_file.write_at(
+            Some(offset as usize),
+            UserBuffer::from_uaddr(uaddr, len),
+            RwFlags::empty(),
+        )?;
+
+        // MAX_READ_WRITE_LEN limit guarantees written_len is in the range of isize.
+        Ok(written_len as isize)
+    }
+}
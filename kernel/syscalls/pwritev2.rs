@@ -0,0 +1,49 @@
+use super::read_iovecs;
+use crate::fs::opened_file::{Fd, RwFlags};
+use crate::{prelude::*, user_buffer::UserBuffer};
+use crate::{process::current_process, syscalls::SyscallHandler};
+use kerla_runtime::address::UserVAddr;
+
+impl<'a> SyscallHandler<'a> {
+    /// `pwritev2(2)`. `offset` is -1 to write to (and advance) the current
+    /// file position.
+    pub fn sys_pwritev2(
+        &mut self,
+        fd: Fd,
+        iov_base: UserVAddr,
+        iov_count: usize,
+        offset: isize,
+        flags: RwFlags,
+    ) -> Result<isize> {
+        let offset = match offset {
+            -1 => None,
+            _ if offset < 0 => return Err(Errno::EINVAL.into()),
+            _ => Some(offset as usize),
+        };
+
+        let opened_file = current_process().get_opened_file_by_fd(fd)?;
+        let mut total_len: usize = 0;
+        for iov in read_iovecs(iov_base, iov_count)? {
+            if iov.len == 0 {
+                continue;
+            }
+
+            let buf = UserBuffer::from_uaddr(iov.base, iov.len);
+            let written_len = match opened_file.write_at(offset.map(|o| o + total_len), buf, flags)
+            {
+                Ok(written_len) => written_len,
+                // Return the length written so far (e.g. EAGAIN with RWF_NOWAIT).
+                Err(_) if total_len > 0 => break,
+                Err(err) => return Err(err),
+            };
+
+            total_len += written_len;
+            if written_len < iov.len {
+                break;
+            }
+        }
+
+        // MAX_READ_WRITE_LEN limit guarantees total_len is in the range of isize.
+        Ok(total_len as isize)
+    }
+}
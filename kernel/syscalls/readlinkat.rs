@@ -0,0 +1,72 @@
+use kerla_runtime::address::UserVAddr;
+
+use crate::prelude::*;
+use crate::syscalls::{CwdOrFd, SyscallHandler};
+use crate::{
+    fs::{
+        opened_file::Fd,
+        path::{Path, PathBuf},
+    },
+    process::current_process,
+};
+
+use crate::user_buffer::UserBufWriter;
+
+/// Returns the file descriptor if `path` (relative to `dir` unless it's an
+/// absolute path) is `/proc/self/fd/<fd>`.
+fn proc_self_fd(dir: &CwdOrFd, path: &Path) -> Result<Option<Fd>> {
+    let current = current_process();
+    let mut abs_path = match dir {
+        _ if path.is_absolute() => PathBuf::new(),
+        CwdOrFd::AtCwd => current.root_fs().lock().cwd_path().resolve_absolute_path(),
+        CwdOrFd::Fd(fd) => current
+            .get_opened_file_by_fd(*fd)?
+            .path()
+            .resolve_absolute_path(),
+    };
+    abs_path.push(path);
+
+    match abs_path.as_str().strip_prefix("/proc/self/fd/") {
+        Some(fd) => {
+            let fd = fd.parse().map_err(|_| Error::new(Errno::ENOENT))?;
+            Ok(Some(Fd::new(fd)))
+        }
+        None => Ok(None),
+    }
+}
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_readlinkat(
+        &mut self,
+        dir: CwdOrFd,
+        path: &Path,
+        buf: UserVAddr,
+        buf_size: usize,
+    ) -> Result<isize> {
+        let current = current_process();
+        let resolved_path = if let Some(fd) = proc_self_fd(&dir, path)? {
+            // TODO: Implement procfs
+            current
+                .opened_files()
+                .lock()
+                .get(fd)?
+                .path()
+                .resolve_absolute_path()
+        } else {
+            let root_fs = current.root_fs().lock();
+            let opened_files = current.opened_files();
+            root_fs
+                .lookup_path_at(opened_files, &dir, path, false)?
+                .inode
+                .readlink()?
+        };
+
+        // The contents are truncated if the buffer is too small. It's not
+        // null-terminated.
+        let bytes = resolved_path.as_str().as_bytes();
+        let len = core::cmp::min(bytes.len(), buf_size);
+        let mut writer = UserBufWriter::from_uaddr(buf, buf_size);
+        writer.write_bytes(&bytes[..len])?;
+        Ok(writer.pos() as isize)
+    }
+}
@@ -0,0 +1,10 @@
+use crate::fs::opened_file::{Fd, RwFlags};
+use crate::prelude::*;
+use crate::syscalls::SyscallHandler;
+use kerla_runtime::address::UserVAddr;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_readv(&mut self, fd: Fd, iov_base: UserVAddr, iov_count: usize) -> Result<isize> {
+        self.sys_preadv2(fd, iov_base, iov_count, -1, RwFlags::empty())
+    }
+}
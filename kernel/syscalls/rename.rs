@@ -0,0 +1,15 @@
+use crate::fs::{inode::RenameFlags, path::Path};
+use crate::result::Result;
+use crate::syscalls::{CwdOrFd, SyscallHandler};
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_rename(&mut self, old_path: &Path, new_path: &Path) -> Result<isize> {
+        self.sys_renameat2(
+            CwdOrFd::AtCwd,
+            old_path,
+            CwdOrFd::AtCwd,
+            new_path,
+            RenameFlags::empty(),
+        )
+    }
+}
@@ -0,0 +1,15 @@
+use crate::fs::{inode::RenameFlags, path::Path};
+use crate::result::Result;
+use crate::syscalls::{CwdOrFd, SyscallHandler};
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_renameat(
+        &mut self,
+        old_dir: CwdOrFd,
+        old_path: &Path,
+        new_dir: CwdOrFd,
+        new_path: &Path,
+    ) -> Result<isize> {
+        self.sys_renameat2(old_dir, old_path, new_dir, new_path, RenameFlags::empty())
+    }
+}
@@ -0,0 +1,72 @@
+use crate::fs::{inode::RenameFlags, inotify, path::Path};
+use crate::prelude::*;
+use crate::{
+    process::current_process,
+    syscalls::{CwdOrFd, SyscallHandler},
+};
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_renameat2(
+        &mut self,
+        old_dir: CwdOrFd,
+        old_path: &Path,
+        new_dir: CwdOrFd,
+        new_path: &Path,
+        flags: RenameFlags,
+    ) -> Result<isize> {
+        if flags.contains(RenameFlags::RENAME_NOREPLACE | RenameFlags::RENAME_EXCHANGE) {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let current = current_process();
+        let root_fs = current.root_fs().lock();
+        let opened_files = current.opened_files();
+        let (old_parent, old_name) =
+            root_fs.lookup_parent_path_at(opened_files, &old_dir, old_path, true)?;
+        let (new_parent, new_name) =
+            root_fs.lookup_parent_path_at(opened_files, &new_dir, new_path, true)?;
+        if matches!(old_name, "." | "..") || matches!(new_name, "." | "..") {
+            return Err(Errno::EBUSY.into());
+        }
+
+        // "EXDEV - oldpath and newpath are not on the same mounted
+        // filesystem." -- rename(2)
+        if old_parent.mount_id != new_parent.mount_id {
+            return Err(Errno::EXDEV.into());
+        }
+
+        root_fs.check_writable(&old_parent)?;
+        let old = root_fs.lookup_path_at(opened_files, &old_dir, old_path, false)?;
+        root_fs.check_not_mount_point(&old)?;
+        let new = root_fs.lookup_path_at(opened_files, &new_dir, new_path, false);
+        if let Ok(new) = &new {
+            root_fs.check_not_mount_point(new)?;
+        }
+
+        old_parent
+            .inode
+            .as_dir()?
+            .rename(old_name, new_parent.inode.as_dir()?, new_name, flags)?;
+
+        inotify::notify_move(
+            &old_parent,
+            old_name,
+            &new_parent,
+            new_name,
+            old.inode.is_dir(),
+        );
+        if flags.contains(RenameFlags::RENAME_EXCHANGE) {
+            if let Ok(new) = &new {
+                inotify::notify_move(
+                    &new_parent,
+                    new_name,
+                    &old_parent,
+                    old_name,
+                    new.inode.is_dir(),
+                );
+            }
+        }
+
+        Ok(0)
+    }
+}
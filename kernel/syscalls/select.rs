@@ -8,7 +8,7 @@ use crate::{
     prelude::*,
     process::current_process,
     syscalls::SyscallHandler,
-    timer::{read_monotonic_clock, Timeval},
+    timer::{read_monotonic_clock, Timer, Timeval},
 };
 
 fn check_fd_statuses<F>(max_fd: c_int, fds: UserVAddr, is_ready: F) -> Result<isize>
@@ -55,16 +55,14 @@ impl<'a> SyscallHandler<'a> {
         _errorfds: Option<UserVAddr>,
         timeout: Option<Timeval>,
     ) -> Result<isize> {
+        if matches!(timeout, Some(timeval) if !timeval.is_valid()) {
+            return Err(Errno::EINVAL.into());
+        }
+
         let started_at = read_monotonic_clock();
-        let timeout_ms = timeout.map(|timeval| timeval.as_msecs());
+        let deadline = timeout.map(|timeval| started_at.add_nanosecs(timeval.as_nanosecs()));
+        let _timer = deadline.map(Timer::wakeup);
         POLL_WAIT_QUEUE.sleep_signalable_until(|| {
-            match timeout_ms {
-                Some(timeout_ms) if started_at.elapsed_msecs() >= timeout_ms => {
-                    return Ok(Some(0));
-                }
-                _ => {}
-            }
-
             // Check the statuses of all specified files one by one.
             // TODO: Support errorfds
             let mut ready_fds = 0;
@@ -79,6 +77,8 @@ impl<'a> SyscallHandler<'a> {
 
             if ready_fds > 0 {
                 Ok(Some(ready_fds))
+            } else if matches!(deadline, Some(deadline) if read_monotonic_clock() >= deadline) {
+                Ok(Some(0))
             } else {
                 // Sleep until any changes in files or sockets occur...
                 Ok(None)
@@ -0,0 +1,34 @@
+use super::{read_offset, MAX_READ_WRITE_LEN};
+use crate::ctypes::c_long;
+use crate::fs::opened_file::Fd;
+use crate::prelude::*;
+use crate::{process::current_process, syscalls::SyscallHandler};
+use core::cmp::min;
+use kerla_runtime::address::UserVAddr;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_sendfile(
+        &mut self,
+        out_fd: Fd,
+        in_fd: Fd,
+        offset: Option<UserVAddr>,
+        count: usize,
+    ) -> Result<isize> {
+        let count = min(count, MAX_READ_WRITE_LEN);
+        let in_file = current_process().get_opened_file_by_fd(in_fd)?;
+        let out_file = current_process().get_opened_file_by_fd(out_fd)?;
+        if !in_file.options().readable || !out_file.options().writable {
+            return Err(Errno::EBADF.into());
+        }
+
+        // If offset is given, the file position of in_fd is left unchanged.
+        let in_offset = read_offset(offset)?;
+        let sent_len = in_file.splice_to(in_offset, &out_file, None, count, false)?;
+        if let (Some(uaddr), Some(in_offset)) = (offset, in_offset) {
+            uaddr.write::<c_long>(&((in_offset + sent_len) as c_long))?;
+        }
+
+        // MAX_READ_WRITE_LEN limit guarantees sent_len is in the range of isize.
+        Ok(sent_len as isize)
+    }
+}
@@ -0,0 +1,13 @@
+use crate::ctypes::c_int;
+use crate::process::{current_process, rlimit::RLimit};
+use crate::result::Result;
+use crate::syscalls::SyscallHandler;
+use kerla_runtime::address::UserVAddr;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_setrlimit(&mut self, resource: c_int, rlim: UserVAddr) -> Result<isize> {
+        let new_limit = rlim.read::<RLimit>()?;
+        current_process().rlimits().set(resource, new_limit)?;
+        Ok(0)
+    }
+}
@@ -0,0 +1,24 @@
+use crate::prelude::*;
+use crate::process::is_privileged;
+use crate::syscalls::SyscallHandler;
+use crate::timer::{set_wall_clock, Timeval};
+use kerla_runtime::address::UserVAddr;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_settimeofday(&mut self, tv: Option<UserVAddr>) -> Result<isize> {
+        if !is_privileged() {
+            return Err(Errno::EPERM.into());
+        }
+
+        if let Some(tv) = tv {
+            let tv = tv.read::<Timeval>()?;
+            if !tv.is_valid() {
+                return Err(Errno::EINVAL.into());
+            }
+
+            set_wall_clock(tv.as_nanosecs());
+        }
+
+        Ok(0)
+    }
+}
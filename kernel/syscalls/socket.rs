@@ -21,6 +21,9 @@ impl From<SocketFlags> for OpenOptions {
         OpenOptions {
             nonblock: flags.contains(SocketFlags::SOCK_NONBLOCK),
             close_on_exec: flags.contains(SocketFlags::SOCK_CLOEXEC),
+            append: false,
+            readable: true,
+            writable: true,
         }
     }
 }
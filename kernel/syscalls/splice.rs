@@ -0,0 +1,77 @@
+use super::{read_offset, MAX_READ_WRITE_LEN};
+use crate::ctypes::{c_long, c_uint};
+use crate::fs::{inode::INode, opened_file::Fd};
+use crate::pipe::{PipeReader, PipeWriter};
+use crate::prelude::*;
+use crate::{process::current_process, syscalls::SyscallHandler};
+use bitflags::bitflags;
+use core::cmp::min;
+use kerla_runtime::address::UserVAddr;
+use kerla_utils::downcast::downcast;
+
+bitflags! {
+    pub struct SpliceFlags: c_uint {
+        // Pages are always moved if possible.
+        const SPLICE_F_MOVE = 1;
+        const SPLICE_F_NONBLOCK = 2;
+        const SPLICE_F_MORE = 4;
+        const SPLICE_F_GIFT = 8;
+    }
+}
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_splice(
+        &mut self,
+        fd_in: Fd,
+        off_in: Option<UserVAddr>,
+        fd_out: Fd,
+        off_out: Option<UserVAddr>,
+        len: usize,
+        flags: SpliceFlags,
+    ) -> Result<isize> {
+        let len = min(len, MAX_READ_WRITE_LEN);
+        let in_file = current_process().get_opened_file_by_fd(fd_in)?;
+        let out_file = current_process().get_opened_file_by_fd(fd_out)?;
+        if !in_file.options().readable || !out_file.options().writable {
+            return Err(Errno::EBADF.into());
+        }
+
+        // One of the ends must be a pipe.
+        let in_is_pipe = matches!(
+            in_file.inode(),
+            INode::FileLike(file) if downcast::<_, PipeReader>(file).is_some()
+        );
+        let out_is_pipe = matches!(
+            out_file.inode(),
+            INode::FileLike(file) if downcast::<_, PipeWriter>(file).is_some()
+        );
+        if !in_is_pipe && !out_is_pipe {
+            return Err(Errno::EINVAL.into());
+        }
+
+        if (in_is_pipe && off_in.is_some()) || (out_is_pipe && off_out.is_some()) {
+            return Err(Errno::ESPIPE.into());
+        }
+
+        let in_offset = read_offset(off_in)?;
+        let out_offset = read_offset(off_out)?;
+        let moved_len = in_file.splice_to(
+            in_offset,
+            &out_file,
+            out_offset,
+            len,
+            flags.contains(SpliceFlags::SPLICE_F_NONBLOCK),
+        )?;
+
+        if let (Some(uaddr), Some(offset)) = (off_in, in_offset) {
+            uaddr.write::<c_long>(&((offset + moved_len) as c_long))?;
+        }
+
+        if let (Some(uaddr), Some(offset)) = (off_out, out_offset) {
+            uaddr.write::<c_long>(&((offset + moved_len) as c_long))?;
+        }
+
+        // MAX_READ_WRITE_LEN limit guarantees moved_len is in the range of isize.
+        Ok(moved_len as isize)
+    }
+}
@@ -0,0 +1,98 @@
+use crate::ctypes::*;
+use crate::fs::{
+    file_system::FsStat,
+    mount::MountFlags,
+    opened_file::{PathComponent, ANONYMOUS_MOUNT_ID},
+    path::Path,
+};
+use crate::result::Result;
+use crate::{process::current_process, syscalls::SyscallHandler};
+use kerla_runtime::address::UserVAddr;
+
+/// The file system magic of anonymous files (e.g. pipes and sockets).
+const ANON_INODE_FS_MAGIC: u64 = 0x09041934;
+
+// `f_flags` in `struct statfs`.
+const ST_RDONLY: c_long = 1;
+const ST_NOSUID: c_long = 2;
+const ST_NODEV: c_long = 4;
+const ST_NOEXEC: c_long = 8;
+const ST_VALID: c_long = 0x20;
+const ST_NOATIME: c_long = 1024;
+const ST_NODIRATIME: c_long = 2048;
+const ST_RELATIME: c_long = 4096;
+
+/// `struct statfs`.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+struct StatFs {
+    f_type: c_long,
+    f_bsize: c_long,
+    f_blocks: u64,
+    f_bfree: u64,
+    f_bavail: u64,
+    f_files: u64,
+    f_ffree: u64,
+    f_fsid: [c_int; 2],
+    f_namelen: c_long,
+    f_frsize: c_long,
+    f_flags: c_long,
+    f_spare: [c_long; 4],
+}
+
+/// Writes the statistics of the file system which `path_comp` belongs to.
+pub(super) fn write_statfs(path_comp: &PathComponent, buf: UserVAddr) -> Result<()> {
+    let (stat, flags) = if path_comp.mount_id == ANONYMOUS_MOUNT_ID {
+        (FsStat::empty(ANON_INODE_FS_MAGIC), MountFlags::empty())
+    } else {
+        let (fs, flags) = {
+            let root_fs = current_process().root_fs().lock();
+            let mount_point = root_fs.mount_point_of(path_comp)?;
+            (mount_point.fs().clone(), mount_point.flags())
+        };
+
+        // Don't block lookups in other processes while waiting for the
+        // device (e.g. ext2).
+        (fs.statfs()?, flags)
+    };
+
+    let mut f_flags = ST_VALID;
+    for (flag, st_flag) in [
+        (MountFlags::MS_RDONLY, ST_RDONLY),
+        (MountFlags::MS_NOSUID, ST_NOSUID),
+        (MountFlags::MS_NODEV, ST_NODEV),
+        (MountFlags::MS_NOEXEC, ST_NOEXEC),
+        (MountFlags::MS_NOATIME, ST_NOATIME),
+        (MountFlags::MS_NODIRATIME, ST_NODIRATIME),
+        (MountFlags::MS_RELATIME, ST_RELATIME),
+    ] {
+        if flags.contains(flag) {
+            f_flags |= st_flag;
+        }
+    }
+
+    buf.write(&StatFs {
+        f_type: stat.magic as c_long,
+        f_bsize: stat.block_size as c_long,
+        f_blocks: stat.total_blocks,
+        f_bfree: stat.free_blocks,
+        f_bavail: stat.available_blocks,
+        f_files: stat.total_inodes,
+        f_ffree: stat.free_inodes,
+        f_fsid: [0; 2],
+        f_namelen: stat.max_name_len as c_long,
+        f_frsize: stat.block_size as c_long,
+        f_flags,
+        f_spare: [0; 4],
+    })?;
+
+    Ok(())
+}
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_statfs(&mut self, path: &Path, buf: UserVAddr) -> Result<isize> {
+        let path_comp = current_process().root_fs().lock().lookup_path(path, true)?;
+        write_statfs(&path_comp, buf)?;
+        Ok(0)
+    }
+}
@@ -0,0 +1,149 @@
+//! Per-syscall statistics exposed in `/proc/metrics`.
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use alloc::collections::BTreeMap;
+use kerla_runtime::spinlock::SpinLock;
+
+use crate::prelude::*;
+
+use super::syscall_name_by_number;
+
+/// The number of system call numbers to be tracked.
+const NUM_SYSCALLS: usize = 512;
+
+/// The upper bounds (in clock counter cycles) of the latency histogram buckets.
+/// The last bucket (`+Inf`) is implicit.
+pub const LATENCY_BUCKETS: [u64; 9] = [
+    1 << 10,
+    1 << 12,
+    1 << 14,
+    1 << 16,
+    1 << 18,
+    1 << 20,
+    1 << 22,
+    1 << 24,
+    1 << 26,
+];
+
+struct SyscallCounters {
+    calls: AtomicUsize,
+    enosys: AtomicUsize,
+    latency_sum: AtomicU64,
+    /// The number of calls in each bucket (not cumulative). The last one is
+    /// for `+Inf`.
+    latency_buckets: [AtomicUsize; LATENCY_BUCKETS.len() + 1],
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicUsize = AtomicUsize::new(0);
+
+#[allow(clippy::declare_interior_mutable_const)]
+const INITIAL_COUNTERS: SyscallCounters = SyscallCounters {
+    calls: ZERO,
+    enosys: ZERO,
+    latency_sum: AtomicU64::new(0),
+    latency_buckets: [ZERO; LATENCY_BUCKETS.len() + 1],
+};
+
+static COUNTERS: [SyscallCounters; NUM_SYSCALLS] = [INITIAL_COUNTERS; NUM_SYSCALLS];
+/// The number of errors keyed by the syscall number and the errno.
+static ERRNO_COUNTS: SpinLock<BTreeMap<(usize, i32), (Errno, usize)>> =
+    SpinLock::new(BTreeMap::new());
+
+/// Statistics of a system call.
+#[derive(Debug)]
+pub struct SyscallStats {
+    pub nr: usize,
+    pub name: &'static str,
+    pub calls: usize,
+    pub enosys: usize,
+    /// The total latency in clock counter cycles.
+    pub latency_sum: u64,
+    /// The cumulative counts of [`LATENCY_BUCKETS`] and `+Inf`.
+    pub latency_buckets: [usize; LATENCY_BUCKETS.len() + 1],
+}
+
+/// The number of a specific error returned from a system call.
+#[derive(Debug)]
+pub struct ErrnoStats {
+    pub nr: usize,
+    pub name: &'static str,
+    pub errno: Errno,
+    pub count: usize,
+}
+
+/// Records a completed system call. `cycles` is the elapsed time in clock
+/// counter cycles.
+pub(super) fn record_syscall(n: usize, ret: &Result<isize>, cycles: u64) {
+    let counters = match COUNTERS.get(n) {
+        Some(counters) => counters,
+        None => return,
+    };
+
+    counters.calls.fetch_add(1, Ordering::Relaxed);
+    counters.latency_sum.fetch_add(cycles, Ordering::Relaxed);
+    let bucket = LATENCY_BUCKETS
+        .iter()
+        .position(|le| cycles <= *le)
+        .unwrap_or(LATENCY_BUCKETS.len());
+    counters.latency_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+
+    if let Err(err) = ret {
+        let errno = err.errno();
+        if errno == Errno::ENOSYS {
+            counters.enosys.fetch_add(1, Ordering::Relaxed);
+        }
+
+        ERRNO_COUNTS
+            .lock()
+            .entry((n, errno as i32))
+            .or_insert((errno, 0))
+            .1 += 1;
+    }
+}
+
+/// Returns the statistics of system calls which have been called at least once.
+pub fn read_syscall_stats() -> Vec<SyscallStats> {
+    let mut stats = Vec::new();
+    for (nr, counters) in COUNTERS.iter().enumerate() {
+        let calls = counters.calls.load(Ordering::Relaxed);
+        if calls == 0 {
+            continue;
+        }
+
+        let mut latency_buckets = [0; LATENCY_BUCKETS.len() + 1];
+        let mut cumulative = 0;
+        for (count, bucket) in latency_buckets
+            .iter_mut()
+            .zip(counters.latency_buckets.iter())
+        {
+            cumulative += bucket.load(Ordering::Relaxed);
+            *count = cumulative;
+        }
+
+        stats.push(SyscallStats {
+            nr,
+            name: syscall_name_by_number(nr),
+            calls,
+            enosys: counters.enosys.load(Ordering::Relaxed),
+            latency_sum: counters.latency_sum.load(Ordering::Relaxed),
+            latency_buckets,
+        });
+    }
+
+    stats
+}
+
+/// Returns the error counts by the system call and errno.
+pub fn read_errno_stats() -> Vec<ErrnoStats> {
+    ERRNO_COUNTS
+        .lock()
+        .iter()
+        .map(|(&(nr, _), &(errno, count))| ErrnoStats {
+            nr,
+            name: syscall_name_by_number(nr),
+            errno,
+            count,
+        })
+        .collect()
+}
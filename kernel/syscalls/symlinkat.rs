@@ -0,0 +1,34 @@
+use crate::fs::{
+    inotify::{self, InotifyMask},
+    path::Path,
+};
+use crate::prelude::*;
+use crate::{
+    process::current_process,
+    syscalls::{CwdOrFd, SyscallHandler},
+};
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_symlinkat(
+        &mut self,
+        target: &Path,
+        new_dir: CwdOrFd,
+        link_path: &Path,
+    ) -> Result<isize> {
+        if target.is_empty() {
+            return Err(Errno::ENOENT.into());
+        }
+
+        let current = current_process();
+        let root_fs = current.root_fs().lock();
+        let opened_files = current.opened_files();
+        let (parent_dir, name) =
+            root_fs.lookup_writable_parent_at(opened_files, &new_dir, link_path)?;
+        parent_dir
+            .inode
+            .as_dir()?
+            .create_symlink(name, target.as_str())?;
+        inotify::notify_entry(&parent_dir, name, InotifyMask::IN_CREATE, false);
+        Ok(0)
+    }
+}
@@ -0,0 +1,14 @@
+use crate::block::sync_all;
+use crate::result::Result;
+use crate::syscalls::SyscallHandler;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_sync(&mut self) -> Result<isize> {
+        // sync(2) always succeeds.
+        if let Err(err) = sync_all() {
+            warn!("sync: failed to write back buffers: {:?}", err);
+        }
+
+        Ok(0)
+    }
+}
@@ -0,0 +1,38 @@
+use super::{splice::SpliceFlags, MAX_READ_WRITE_LEN};
+use crate::fs::opened_file::Fd;
+use crate::pipe::{PipeReader, PipeWriter};
+use crate::prelude::*;
+use crate::{process::current_process, syscalls::SyscallHandler};
+use core::cmp::min;
+use kerla_utils::downcast::downcast;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_tee(
+        &mut self,
+        fd_in: Fd,
+        fd_out: Fd,
+        len: usize,
+        flags: SpliceFlags,
+    ) -> Result<isize> {
+        let len = min(len, MAX_READ_WRITE_LEN);
+        let in_file = current_process().get_opened_file_by_fd(fd_in)?;
+        let out_file = current_process().get_opened_file_by_fd(fd_out)?;
+        if !in_file.options().readable || !out_file.options().writable {
+            return Err(Errno::EBADF.into());
+        }
+
+        // Both ends must be pipes.
+        let reader = downcast::<_, PipeReader>(in_file.as_file()?)
+            .ok_or_else(|| Error::new(Errno::EINVAL))?;
+        let writer = downcast::<_, PipeWriter>(out_file.as_file()?)
+            .ok_or_else(|| Error::new(Errno::EINVAL))?;
+
+        let nonblock = in_file.options().nonblock
+            || out_file.options().nonblock
+            || flags.contains(SpliceFlags::SPLICE_F_NONBLOCK);
+        let copied_len = reader.tee(writer, len, nonblock)?;
+
+        // MAX_READ_WRITE_LEN limit guarantees copied_len is in the range of isize.
+        Ok(copied_len as isize)
+    }
+}
@@ -0,0 +1,16 @@
+use crate::ctypes::c_time;
+use crate::prelude::*;
+use crate::syscalls::SyscallHandler;
+use crate::timer::read_wall_clock;
+use kerla_runtime::address::UserVAddr;
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_time(&mut self, tloc: Option<UserVAddr>) -> Result<isize> {
+        let now = read_wall_clock().secs_from_epoch() as c_time;
+        if let Some(tloc) = tloc {
+            tloc.write::<c_time>(&now)?;
+        }
+
+        Ok(now as isize)
+    }
+}
@@ -0,0 +1,26 @@
+use crate::fs::{
+    inotify::{self, InotifyMask},
+    path::Path,
+};
+use crate::prelude::*;
+use crate::{process::current_process, syscalls::SyscallHandler};
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_truncate(&mut self, path: &Path, length: isize) -> Result<isize> {
+        if length < 0 {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let current = current_process();
+        let root_fs = current.root_fs().lock();
+        let path_comp = root_fs.lookup_path(path, true)?;
+        if path_comp.inode.is_dir() {
+            return Err(Errno::EISDIR.into());
+        }
+
+        root_fs.check_writable(&path_comp)?;
+        path_comp.inode.as_file()?.truncate(length as usize)?;
+        inotify::notify_path(&path_comp, InotifyMask::IN_MODIFY);
+        Ok(0)
+    }
+}
@@ -0,0 +1,39 @@
+use crate::block::sync_all;
+use crate::fs::path::Path;
+use crate::prelude::*;
+use crate::{ctypes::*, process::current_process, syscalls::SyscallHandler};
+
+use bitflags::bitflags;
+
+bitflags! {
+    pub struct UmountFlags: c_int {
+        const MNT_FORCE = 1;
+        const MNT_DETACH = 2;
+        const MNT_EXPIRE = 4;
+        const UMOUNT_NOFOLLOW = 8;
+    }
+}
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_umount2(&mut self, target: &Path, flags: UmountFlags) -> Result<isize> {
+        if flags.contains(UmountFlags::MNT_EXPIRE) {
+            // TODO: Support marking a mount as expired.
+            return Err(Errno::EINVAL.into());
+        }
+
+        // MNT_FORCE is accepted but doesn't abort ongoing operations: a
+        // busy mount is still not unmounted.
+        current_process().root_fs().lock().unmount(
+            target,
+            !flags.contains(UmountFlags::UMOUNT_NOFOLLOW),
+            flags.contains(UmountFlags::MNT_DETACH),
+        )?;
+
+        // Write back the changes in the file system.
+        if let Err(err) = sync_all() {
+            warn!("umount2: failed to write back buffers: {:?}", err);
+        }
+
+        Ok(0)
+    }
+}
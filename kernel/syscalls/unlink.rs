@@ -0,0 +1,9 @@
+use crate::fs::path::Path;
+use crate::result::Result;
+use crate::syscalls::{AtFlags, CwdOrFd, SyscallHandler};
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_unlink(&mut self, path: &Path) -> Result<isize> {
+        self.sys_unlinkat(CwdOrFd::AtCwd, path, AtFlags::empty())
+    }
+}
@@ -0,0 +1,45 @@
+use crate::fs::{
+    inotify::{self, InotifyMask},
+    path::Path,
+};
+use crate::prelude::*;
+use crate::{
+    process::current_process,
+    syscalls::{AtFlags, CwdOrFd, SyscallHandler},
+};
+
+impl<'a> SyscallHandler<'a> {
+    pub fn sys_unlinkat(&mut self, dir: CwdOrFd, path: &Path, flags: AtFlags) -> Result<isize> {
+        let remove_dir = flags.contains(AtFlags::AT_REMOVEDIR);
+        let (parent_path, name) = match path.parent_and_basename() {
+            Some(parent_and_name) => parent_and_name,
+            // The root directory.
+            None if remove_dir => return Err(Errno::EBUSY.into()),
+            None => return Err(Errno::EISDIR.into()),
+        };
+
+        let current = current_process();
+        let root_fs = current.root_fs().lock();
+        let opened_files = current.opened_files();
+        let parent_dir = root_fs.lookup_path_at(opened_files, &dir, parent_path, true)?;
+        root_fs.check_writable(&parent_dir)?;
+
+        if remove_dir {
+            match name {
+                "." => return Err(Errno::EINVAL.into()),
+                ".." => return Err(Errno::ENOTEMPTY.into()),
+                _ => {}
+            }
+
+            let target = root_fs.lookup_path_at(opened_files, &dir, path, false)?;
+            root_fs.check_not_mount_point(&target)?;
+            parent_dir.inode.as_dir()?.rmdir(name)?;
+        } else {
+            parent_dir.inode.as_dir()?.unlink(name)?;
+        }
+
+        inotify::notify_entry(&parent_dir, name, InotifyMask::IN_DELETE, remove_dir);
+
+        Ok(0)
+    }
+}
@@ -0,0 +1,84 @@
+use crate::ctypes::c_long;
+use crate::fs::{
+    inode::SetAttr,
+    inotify::{self, InotifyMask},
+    path::Path,
+    stat::Time,
+};
+use crate::prelude::*;
+use crate::timer::Timespec;
+use crate::{
+    process::current_process,
+    syscalls::{AtFlags, CwdOrFd, SyscallHandler},
+};
+use kerla_runtime::address::UserVAddr;
+
+/// Sets the timestamp to the current time.
+const UTIME_NOW: c_long = (1 << 30) - 1;
+/// Leaves the timestamp unchanged.
+const UTIME_OMIT: c_long = (1 << 30) - 2;
+
+/// Returns the new timestamp. `None` if it's `UTIME_OMIT`.
+fn resolve_time(time: &Timespec) -> Option<Time> {
+    match time.tv_nsec() {
+        UTIME_OMIT => None,
+        UTIME_NOW => Some(Time::now()),
+        _ => Some(Time::new(time.tv_sec() as isize, time.tv_nsec() as isize)),
+    }
+}
+
+impl<'a> SyscallHandler<'a> {
+    /// `path` is `None` if it's `NULL`: the file `dir` refers to is updated
+    /// (`futimens(3)`).
+    pub fn sys_utimensat(
+        &mut self,
+        dir: CwdOrFd,
+        path: Option<&Path>,
+        times: Option<UserVAddr>,
+        flags: AtFlags,
+    ) -> Result<isize> {
+        let (atime, mtime) = match times {
+            Some(times) => {
+                let [atime, mtime] = times.read::<[Timespec; 2]>()?;
+                // Timestamps before the epoch (negative tv_sec) are valid:
+                // only tv_nsec is range-checked.
+                for time in [atime, mtime] {
+                    let tv_nsec = time.tv_nsec();
+                    if !(0..1_000_000_000).contains(&tv_nsec)
+                        && !matches!(tv_nsec, UTIME_NOW | UTIME_OMIT)
+                    {
+                        return Err(Errno::EINVAL.into());
+                    }
+                }
+
+                (resolve_time(&atime), resolve_time(&mtime))
+            }
+            None => {
+                let now = Time::now();
+                (Some(now), Some(now))
+            }
+        };
+
+        let (path, flags) = match path {
+            Some(path) => (path, flags),
+            None => (Path::new(""), flags | AtFlags::AT_EMPTY_PATH),
+        };
+
+        let current = current_process();
+        let root_fs = current.root_fs().lock();
+        let opened_files = current.opened_files();
+        let path_comp = root_fs.lookup_path_at_flags(opened_files, &dir, path, flags)?;
+        if atime.is_none() && mtime.is_none() {
+            return Ok(0);
+        }
+
+        root_fs.check_writable(&path_comp)?;
+        path_comp.inode.set_attr(&SetAttr {
+            atime,
+            mtime,
+            ..Default::default()
+        })?;
+        inotify::notify_path(&path_comp, InotifyMask::IN_ATTRIB);
+        Ok(0)
+    }
+}
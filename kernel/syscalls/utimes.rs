@@ -1,14 +1,49 @@
 use kerla_runtime::address::UserVAddr;
 
-use crate::fs::path::Path;
+use crate::fs::{
+    inode::SetAttr,
+    inotify::{self, InotifyMask},
+    path::Path,
+    stat::Time,
+};
 use crate::prelude::*;
+use crate::timer::Timeval;
 use crate::{process::current_process, syscalls::SyscallHandler};
 
+/// Converts a `struct timeval`. Timestamps before the epoch (negative
+/// `tv_sec`) are valid.
+fn to_time(time: &Timeval) -> Result<Time> {
+    let tv_usec = time.tv_usec();
+    if !(0..1_000_000).contains(&tv_usec) {
+        return Err(Errno::EINVAL.into());
+    }
+
+    Ok(Time::new(time.tv_sec() as isize, tv_usec as isize * 1000))
+}
+
 impl<'a> SyscallHandler<'a> {
-    pub fn sys_utimes(&mut self, path: &Path, _times: Option<UserVAddr>) -> Result<isize> {
-        // TODO: Currently we don't modify the file metadata: Return ENOENT if
-        //       the file exists for touch(1).
-        current_process().root_fs().lock().lookup_file(path)?;
+    pub fn sys_utimes(&mut self, path: &Path, times: Option<UserVAddr>) -> Result<isize> {
+        let (atime, mtime) = match times {
+            Some(times) => {
+                let [atime, mtime] = times.read::<[Timeval; 2]>()?;
+                (to_time(&atime)?, to_time(&mtime)?)
+            }
+            None => {
+                let now = Time::now();
+                (now, now)
+            }
+        };
+
+        let current = current_process();
+        let root_fs = current.root_fs().lock();
+        let path_comp = root_fs.lookup_path(path, true)?;
+        root_fs.check_writable(&path_comp)?;
+        path_comp.inode.set_attr(&SetAttr {
+            atime: Some(atime),
+            mtime: Some(mtime),
+            ..Default::default()
+        })?;
+        inotify::notify_path(&path_comp, InotifyMask::IN_ATTRIB);
         Ok(0)
     }
 }
@@ -52,7 +52,6 @@ impl<'a> SyscallHandler<'a> {
         current_process().children().retain(|p| p.pid() != got_pid);
 
         if let Some(status) = status {
-            // FIXME: This is NOT the correct format of `status`.
             status.write::<c_int>(&status_value)?;
         }
         Ok(got_pid.as_i32() as isize)
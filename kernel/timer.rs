@@ -1,86 +1,340 @@
+//! Clocks and timers.
+//!
+//! Clocks are derived from the TSC clocksource. The wall clock is initialized
+//! from the RTC at the boot. The vDSO reads them without entering the kernel
+//! using the parameters published whenever the wall clock is adjusted.
+//!
+//! The local APIC timer is programmed in the one-shot mode for the next event:
+//! the next periodic tick or the earliest timer, whichever comes first. The
+//! periodic tick drives the CPU time accounting, the load average, and the
+//! preemption. It's stopped while the CPU is idle so that an idle CPU wakes up
+//! only when a timer expires or an interrupt arrives.
 use crate::{
+    arch::update_vvar,
     ctypes::*,
     prelude::*,
-    process::{self, current_process, Process, ProcessState},
+    process::{self, current_process, ProcessState},
+};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use core::ops::Range;
+use kerla_runtime::{
+    arch::{arm_timer, nanosecs_since_boot, read_rtc, TICK_HZ},
+    spinlock::SpinLock,
 };
-use core::sync::atomic::{AtomicUsize, Ordering};
-use kerla_runtime::{arch::TICK_HZ, spinlock::SpinLock};
 use process::switch;
 
 const PREEMPT_PER_TICKS: usize = 30;
-static MONOTONIC_TICKS: AtomicUsize = AtomicUsize::new(0);
-/// Ticks from the epoch (00:00:00 on 1 January 1970, UTC).
-static WALLCLOCK_TICKS: AtomicUsize = AtomicUsize::new(0);
-static TIMERS: SpinLock<Vec<Timer>> = SpinLock::new(Vec::new());
+const NANOSECS_PER_TICK: usize = 1_000_000_000 / TICK_HZ;
+/// The maximum rate of slewing the wall clock: 500 ppm, the same as Linux.
+const MAX_SLEW_PPM: i128 = 500;
+static WALLCLOCK: SpinLock<WallClockState> = SpinLock::new(WallClockState::new());
+static CLOCK: SpinLock<ClockEvents> = SpinLock::new(ClockEvents::new());
+
+cpu_local! {
+    static ref CPU_TICKS: CpuTicks = CpuTicks {
+        user: 0,
+        system: 0,
+        idle: 0,
+    };
+}
+
+/// The number of ticks a CPU has spent in each mode since the boot.
+#[derive(Debug, Copy, Clone)]
+pub struct CpuTicks {
+    pub user: usize,
+    pub system: usize,
+    pub idle: usize,
+}
+
+/// Returns the tick accounting of the current CPU.
+pub fn read_cpu_ticks() -> CpuTicks {
+    *CPU_TICKS.get()
+}
+
+pub trait TimerCallback = FnOnce() + Send + 'static;
+
+struct ClockEvents {
+    /// The monotonic clock (in nanoseconds) when the next periodic tick is due.
+    next_tick: usize,
+    /// The number of periodic ticks since the boot.
+    ticks: usize,
+    /// `true` while the periodic tick is stopped, i.e. the CPU is idle.
+    tick_stopped: bool,
+    /// Pending timers ordered by their deadlines. The second element of the
+    /// key distinguishes timers with the same deadline.
+    timers: BTreeMap<(usize, usize), Box<dyn TimerCallback>>,
+    next_timer_id: usize,
+}
+
+impl ClockEvents {
+    const fn new() -> ClockEvents {
+        ClockEvents {
+            next_tick: 0,
+            ticks: 0,
+            tick_stopped: false,
+            timers: BTreeMap::new(),
+            next_timer_id: 0,
+        }
+    }
+
+    /// Advances the periodic tick to `now` and returns the ticks passed.
+    fn advance_ticks(&mut self, now: usize) -> Range<usize> {
+        let start = self.ticks;
+        if !self.tick_stopped && now >= self.next_tick {
+            let passed = (now - self.next_tick) / NANOSECS_PER_TICK + 1;
+            self.next_tick += passed * NANOSECS_PER_TICK;
+            self.ticks += passed;
+        }
+
+        start..self.ticks
+    }
+
+    /// Removes expired timers and returns their callbacks.
+    fn pop_expired_timers(&mut self, now: usize) -> Vec<Box<dyn TimerCallback>> {
+        let mut expired = Vec::new();
+        while let Some(entry) = self.timers.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+
+            expired.push(entry.remove());
+        }
+
+        expired
+    }
+
+    /// Programs the timer for the next event.
+    fn rearm(&mut self, now: usize) {
+        let next_tick = if self.tick_stopped {
+            None
+        } else {
+            Some(self.next_tick)
+        };
+        let next_timer = self.timers.keys().next().map(|(deadline, _)| *deadline);
+        let next_event = next_tick
+            .into_iter()
+            .chain(next_timer)
+            .min()
+            .unwrap_or(usize::MAX);
+
+        arm_timer(next_event.saturating_sub(now));
+    }
+}
 
-struct Timer {
-    current: usize,
-    process: Arc<Process>,
+/// A one-shot timer. It's cancelled if it's dropped before expiring.
+pub struct Timer {
+    key: (usize, usize),
 }
 
-/// Suspends the current process at least `ms` milliseconds.
-pub fn _sleep_ms(ms: usize) {
-    TIMERS.lock().push(Timer {
-        current: ms * TICK_HZ / 1000,
-        process: current_process().clone(),
-    });
+impl Timer {
+    /// Calls `callback` from the timer interrupt handler once the monotonic
+    /// clock reaches `deadline`.
+    pub fn new<F: TimerCallback>(deadline: MonotonicClock, callback: F) -> Timer {
+        let mut clock = CLOCK.lock();
+        let key = (deadline.nanosecs, clock.next_timer_id);
+        clock.next_timer_id += 1;
+        clock.timers.insert(key, Box::new(callback));
 
-    current_process().set_state(ProcessState::BlockedSignalable);
-    switch();
+        // Reprogram the timer if the new one expires first.
+        if clock.timers.keys().next() == Some(&key) {
+            clock.rearm(nanosecs_since_boot());
+        }
+
+        Timer { key }
+    }
+
+    /// Resumes the current process at `deadline`. Useful for implementing
+    /// timeouts of blocking operations.
+    pub fn wakeup(deadline: MonotonicClock) -> Timer {
+        let current = current_process().clone();
+        Timer::new(deadline, move || current.resume())
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        CLOCK.lock().timers.remove(&self.key);
+    }
+}
+
+/// Suspends the current process until the monotonic clock reaches `deadline`.
+///
+/// If a signal is arrived, this function returns `Err(Errno::EINTR)`.
+pub fn sleep_until(deadline: MonotonicClock) -> Result<()> {
+    let _timer = Timer::wakeup(deadline);
+    loop {
+        // Mark the current process as blocked before checking the clock so
+        // that we don't miss the wakeup by the timer.
+        current_process().set_state(ProcessState::BlockedSignalable);
+
+        if read_monotonic_clock() >= deadline {
+            current_process().resume();
+            return Ok(());
+        }
+
+        if current_process().has_pending_signals() {
+            current_process().resume();
+            return Err(Errno::EINTR.into());
+        }
+
+        switch();
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct WallClock {
-    ticks_from_epoch: usize,
+    nanosecs_from_epoch: usize,
 }
 
 impl WallClock {
     pub fn secs_from_epoch(self) -> usize {
-        self.ticks_from_epoch / TICK_HZ
+        self.nanosecs_from_epoch / 1_000_000_000
     }
 
-    pub fn msecs_from_epoch(self) -> usize {
-        self.ticks_from_epoch / (TICK_HZ / 1000)
+    pub fn nanosecs_from_epoch(self) -> usize {
+        self.nanosecs_from_epoch
     }
+}
 
-    pub fn nanosecs_from_epoch(self) -> usize {
-        self.msecs_from_epoch() * 1_000_000
+/// The wall clock in nanoseconds from the epoch (00:00:00 on 1 January 1970,
+/// UTC), derived from the monotonic clock:
+///
+/// ```text
+/// wall = base + (1 + freq) * (mono - mono_base) + (slewed part of offset)
+/// ```
+struct WallClockState {
+    base: i64,
+    mono_base: usize,
+    /// The frequency adjustment in ppm with 16-bit fractional part (the same
+    /// format as `timex.freq`).
+    freq: i64,
+    /// The offset to be slewed gradually since `mono_base`.
+    offset: i64,
+}
+
+impl WallClockState {
+    const fn new() -> WallClockState {
+        WallClockState {
+            base: 0,
+            mono_base: 0,
+            freq: 0,
+            offset: 0,
+        }
     }
+
+    /// Returns the part of `offset` already slewed at `mono`.
+    fn slewed(&self, mono: usize) -> i64 {
+        let max_slew = ((mono - self.mono_base) as i128 * MAX_SLEW_PPM / 1_000_000) as i64;
+        self.offset.clamp(-max_slew, max_slew)
+    }
+
+    fn read(&self, mono: usize) -> i64 {
+        let elapsed = (mono - self.mono_base) as i128;
+        let adjusted = elapsed + elapsed * self.freq as i128 / (1_000_000 << 16);
+        self.base + adjusted as i64 + self.slewed(mono)
+    }
+
+    /// Moves the base to `mono` so that we can change the parameters without
+    /// affecting the clock before `mono`.
+    fn rebase(&mut self, mono: usize) {
+        self.base = self.read(mono);
+        self.offset -= self.slewed(mono);
+        self.mono_base = mono;
+    }
+}
+
+/// Publishes the wall clock parameters to the vDSO. It computes the wall clock
+/// in the same way as `WallClockState::read`.
+fn update_vdso(wallclock: &WallClockState) {
+    update_vvar(
+        wallclock.base,
+        wallclock.mono_base,
+        wallclock.freq,
+        wallclock.offset,
+    );
 }
 
 pub fn read_wall_clock() -> WallClock {
+    let wall = WALLCLOCK.lock().read(read_monotonic_clock().nanosecs());
     WallClock {
-        ticks_from_epoch: WALLCLOCK_TICKS.load(Ordering::Relaxed),
+        nanosecs_from_epoch: wall.max(0) as usize,
     }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+/// Sets the wall clock. Ongoing slewing is cancelled.
+pub fn set_wall_clock(nanosecs_from_epoch: usize) {
+    let mut wallclock = WALLCLOCK.lock();
+    wallclock.rebase(read_monotonic_clock().nanosecs());
+    wallclock.base = nanosecs_from_epoch as i64;
+    wallclock.offset = 0;
+    update_vdso(&wallclock);
+}
+
+/// Steps the wall clock by `delta` nanoseconds.
+pub fn step_wall_clock(delta: i64) {
+    let mut wallclock = WALLCLOCK.lock();
+    wallclock.rebase(read_monotonic_clock().nanosecs());
+    wallclock.base += delta;
+    update_vdso(&wallclock);
+}
+
+/// Starts slewing the wall clock by `offset` nanoseconds gradually. It
+/// replaces the ongoing slewing, if any.
+pub fn slew_wall_clock(offset: i64) {
+    let mut wallclock = WALLCLOCK.lock();
+    wallclock.rebase(read_monotonic_clock().nanosecs());
+    wallclock.offset = offset;
+    update_vdso(&wallclock);
+}
+
+/// Returns the offset (in nanoseconds) not yet slewed.
+pub fn remaining_wall_clock_slew() -> i64 {
+    let wallclock = WALLCLOCK.lock();
+    wallclock.offset - wallclock.slewed(read_monotonic_clock().nanosecs())
+}
+
+/// Sets the frequency adjustment of the wall clock in the `timex.freq` format.
+pub fn set_wall_clock_freq(freq: i64) {
+    let mut wallclock = WALLCLOCK.lock();
+    wallclock.rebase(read_monotonic_clock().nanosecs());
+    wallclock.freq = freq;
+    update_vdso(&wallclock);
+}
+
+pub fn wall_clock_freq() -> i64 {
+    WALLCLOCK.lock().freq
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct MonotonicClock {
-    ticks: usize,
+    nanosecs: usize,
 }
 
 impl MonotonicClock {
     pub fn secs(self) -> usize {
-        self.ticks / TICK_HZ
+        self.nanosecs / 1_000_000_000
     }
 
     pub fn msecs(self) -> usize {
-        self.ticks / (TICK_HZ / 1000)
+        self.nanosecs / 1_000_000
     }
 
     pub fn nanosecs(self) -> usize {
-        self.msecs() * 1_000_000
+        self.nanosecs
     }
 
-    pub fn elapsed_msecs(self) -> usize {
-        // FIXME: Consider wrapping.
-        (read_monotonic_clock().ticks - self.ticks) / (TICK_HZ / 1000)
+    /// Returns the time `nanosecs` nanoseconds after `self`.
+    pub fn add_nanosecs(self, nanosecs: usize) -> MonotonicClock {
+        MonotonicClock {
+            nanosecs: self.nanosecs.saturating_add(nanosecs),
+        }
     }
 }
 
 pub fn read_monotonic_clock() -> MonotonicClock {
     MonotonicClock {
-        ticks: MONOTONIC_TICKS.load(Ordering::Relaxed),
+        nanosecs: nanosecs_since_boot(),
     }
 }
 
@@ -93,32 +347,146 @@ pub struct Timeval {
 }
 
 impl Timeval {
-    pub fn as_msecs(&self) -> usize {
-        (self.tv_sec as usize) * 1000 + (self.tv_usec as usize) / 1000
+    pub fn from_nanosecs(nanosecs: usize) -> Timeval {
+        Timeval {
+            tv_sec: (nanosecs / 1_000_000_000) as c_time,
+            tv_usec: ((nanosecs % 1_000_000_000) / 1000) as c_suseconds,
+        }
+    }
+
+    /// Returns `false` if the fields are out of range.
+    pub fn is_valid(&self) -> bool {
+        let tv_usec = self.tv_usec;
+        self.tv_sec >= 0 && (0..1_000_000).contains(&tv_usec)
+    }
+
+    pub fn tv_sec(&self) -> c_time {
+        self.tv_sec
+    }
+
+    pub fn tv_usec(&self) -> c_suseconds {
+        self.tv_usec
+    }
+
+    pub fn as_nanosecs(&self) -> usize {
+        (self.tv_sec as usize)
+            .saturating_mul(1_000_000_000)
+            .saturating_add((self.tv_usec as usize).saturating_mul(1000))
     }
 }
 
-pub fn handle_timer_irq() {
-    {
-        let mut timers = TIMERS.lock();
-        for timer in timers.iter_mut() {
-            if timer.current > 0 {
-                timer.current -= 1;
-            }
+/// `struct timespec`
+#[derive(Debug, Copy, Clone)]
+#[repr(C, packed)]
+pub struct Timespec {
+    tv_sec: c_time,
+    tv_nsec: c_long,
+}
+
+impl Timespec {
+    pub fn from_nanosecs(nanosecs: usize) -> Timespec {
+        Timespec {
+            tv_sec: (nanosecs / 1_000_000_000) as c_time,
+            tv_nsec: (nanosecs % 1_000_000_000) as c_long,
         }
+    }
+
+    /// Returns `false` if the fields are out of range.
+    pub fn is_valid(&self) -> bool {
+        let tv_nsec = self.tv_nsec;
+        self.tv_sec >= 0 && (0..1_000_000_000).contains(&tv_nsec)
+    }
+
+    pub fn tv_sec(&self) -> c_time {
+        self.tv_sec
+    }
 
-        timers.retain(|timer| {
-            if timer.current == 0 {
-                timer.process.resume();
+    pub fn tv_nsec(&self) -> c_long {
+        self.tv_nsec
+    }
+
+    pub fn as_nanosecs(&self) -> usize {
+        (self.tv_sec as usize)
+            .saturating_mul(1_000_000_000)
+            .saturating_add(self.tv_nsec as usize)
+    }
+}
+
+/// Accounts ticks in `ticks` to the current CPU. Returns `true` if the current
+/// process should be preempted.
+fn account_ticks(ticks: Range<usize>, in_user: bool) -> bool {
+    let mut preempt = false;
+    for tick in ticks {
+        {
+            let cpu_ticks = CPU_TICKS.as_mut();
+            if current_process().is_idle() {
+                cpu_ticks.idle += 1;
+            } else if in_user {
+                cpu_ticks.user += 1;
+            } else {
+                cpu_ticks.system += 1;
             }
+        }
 
-            timer.current > 0
-        })
+        process::sample_load_average(tick);
+        preempt |= tick % PREEMPT_PER_TICKS == 0;
     }
 
-    WALLCLOCK_TICKS.fetch_add(1, Ordering::Relaxed);
-    let ticks = MONOTONIC_TICKS.fetch_add(1, Ordering::Relaxed);
-    if ticks % PREEMPT_PER_TICKS == 0 {
+    preempt
+}
+
+/// Stops the periodic tick. Called from the idle thread before halting the CPU.
+pub fn stop_tick() {
+    let mut clock = CLOCK.lock();
+    let now = nanosecs_since_boot();
+    clock.tick_stopped = true;
+    clock.rearm(now);
+}
+
+/// Restarts the periodic tick stopped by `stop_tick`. The ticks skipped while
+/// the CPU was idle are accounted as idle ones at once.
+pub fn restart_tick() {
+    let skipped = {
+        let mut clock = CLOCK.lock();
+        if !clock.tick_stopped {
+            return;
+        }
+
+        let now = nanosecs_since_boot();
+        clock.tick_stopped = false;
+        let skipped = clock.advance_ticks(now);
+        clock.rearm(now);
+        skipped
+    };
+
+    CPU_TICKS.as_mut().idle += skipped.len();
+    process::decay_load_average(skipped);
+}
+
+pub fn handle_timer_irq(in_user: bool) {
+    let (expired, ticks) = {
+        let mut clock = CLOCK.lock();
+        let now = nanosecs_since_boot();
+        let expired = clock.pop_expired_timers(now);
+        let ticks = clock.advance_ticks(now);
+        clock.rearm(now);
+        (expired, ticks)
+    };
+
+    for callback in expired {
+        callback();
+    }
+
+    if account_ticks(ticks, in_user) {
         process::switch();
     }
 }
+
+pub fn init() {
+    match read_rtc() {
+        Some(secs) => set_wall_clock(secs as usize * 1_000_000_000),
+        None => warn!("RTC is not available; the wall clock starts from the epoch"),
+    }
+
+    update_vdso(&WALLCLOCK.lock());
+}
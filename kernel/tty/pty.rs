@@ -59,6 +59,10 @@ impl Drop for PtyMaster {
 }
 
 impl FileLike for PtyMaster {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
     fn read(
         &self,
         _offset: usize,
@@ -154,6 +158,10 @@ impl PtySlave {
 }
 
 impl FileLike for PtySlave {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
     fn read(
         &self,
         _offset: usize,
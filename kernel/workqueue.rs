@@ -0,0 +1,72 @@
+//! A workqueue.
+//!
+//! When you want to run some time-consuming work outside of an interrupt
+//! handler, please consider using this mechanism. Works are executed in a
+//! kernel thread (`kworker`) with interrupts enabled, so unlike interrupt
+//! handlers, they may take long and may sleep.
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use kerla_runtime::spinlock::SpinLock;
+
+use crate::process::{Process, WaitQueue};
+
+pub trait WorkCallback = FnOnce() + Send + 'static;
+
+struct WorkQueue {
+    works: SpinLock<VecDeque<(&'static str, Box<dyn WorkCallback>)>>,
+    /// The queue where the kworker thread waits for new works.
+    wait_queue: WaitQueue,
+}
+
+impl WorkQueue {
+    const fn new() -> WorkQueue {
+        WorkQueue {
+            works: SpinLock::new(VecDeque::new()),
+            wait_queue: WaitQueue::new(),
+        }
+    }
+}
+
+// Works can be queued before the kworker thread starts (e.g. packets received
+// during the boot): they are executed once it starts.
+static SYSTEM_WORKQUEUE: WorkQueue = WorkQueue::new();
+
+pub struct Work {
+    name: &'static str,
+}
+
+impl Work {
+    pub const fn new(name: &'static str) -> Work {
+        Work { name }
+    }
+
+    /// Enqueues a work. `callback` will be run in the kworker thread sometime
+    /// later. It's safe to call from an interrupt context.
+    pub fn queue<F: WorkCallback>(&self, callback: F) {
+        SYSTEM_WORKQUEUE
+            .works
+            .lock()
+            .push_back((self.name, Box::new(callback)));
+        SYSTEM_WORKQUEUE.wait_queue.wake_all();
+    }
+}
+
+extern "C" fn kworker_main() -> ! {
+    loop {
+        let next = SYSTEM_WORKQUEUE
+            .wait_queue
+            .sleep_signalable_until(|| Ok(SYSTEM_WORKQUEUE.works.lock().pop_front()));
+
+        // Kernel threads never receive signals, i.e. `EINTR` won't be returned.
+        if let Ok((name, callback)) = next {
+            trace!("kworker: running {}", name);
+            callback();
+        }
+    }
+}
+
+/// Starts the kworker thread. Must be called after the process subsystem
+/// is initialized.
+pub fn init() {
+    Process::new_kthread("kworker", kworker_main).expect("failed to create kworker");
+}
@@ -0,0 +1,65 @@
+//! Block device APIs.
+use alloc::boxed::Box;
+
+use super::Driver;
+
+use crate::address::PAddr;
+use crate::kernel_ops::kernel_ops;
+
+/// The unit of addressing in block devices.
+pub const SECTOR_SIZE: usize = 512;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BlockIoError {
+    /// The device failed to process the request.
+    IoError,
+    /// The request is not supported by the device.
+    Unsupported,
+    /// Tried to write into a read-only device.
+    ReadOnly,
+    /// The request is beyond the end of the device.
+    OutOfRange,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BlockOp {
+    /// Reads sectors into the buffer.
+    Read,
+    /// Writes the buffer into sectors.
+    Write,
+    /// Writes back the device's volatile write cache.
+    Flush,
+}
+
+/// Called once the device has processed the request. It may be called in the
+/// interrupt context.
+pub type BlockCompletion = Box<dyn FnOnce(Result<(), BlockIoError>) + Send>;
+
+pub struct BlockRequest {
+    pub op: BlockOp,
+    /// The first sector to be read or written.
+    pub sector: u64,
+    /// The physically contiguous buffer. Ignored in [`BlockOp::Flush`].
+    pub buf: PAddr,
+    /// The length of the buffer in bytes. It must be a multiple of
+    /// [`SECTOR_SIZE`].
+    pub len: usize,
+    pub completion: BlockCompletion,
+}
+
+pub trait BlockDriver: Driver {
+    /// The size of the device in sectors.
+    fn num_sectors(&self) -> u64;
+    fn is_read_only(&self) -> bool;
+    /// The logical block size in bytes. Requests should be aligned to it.
+    fn block_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+    /// Queues a request. The driver calls `request.completion` once it's
+    /// done. Multiple requests may be in flight at once.
+    fn submit(&self, request: BlockRequest);
+}
+
+pub fn register_block_driver(driver: Box<dyn BlockDriver>) {
+    kernel_ops().register_block_driver(driver);
+}
@@ -0,0 +1,135 @@
+//! The device model: devices found on buses and drivers bound to them.
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use kerla_runtime::spinlock::SpinLock;
+
+static DEVICES: SpinLock<Vec<Arc<Device>>> = SpinLock::new(Vec::new());
+static DRIVERS: SpinLock<Vec<&'static str>> = SpinLock::new(Vec::new());
+
+/// The bus which a device is connected to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Bus {
+    Pci,
+    /// Devices which are not discoverable (e.g. virtio-mmio devices specified
+    /// in the kernel command line).
+    Platform,
+}
+
+impl Bus {
+    pub fn name(self) -> &'static str {
+        match self {
+            Bus::Pci => "pci",
+            Bus::Platform => "platform",
+        }
+    }
+}
+
+/// The reason why a driver is unbound from a device.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UnbindReason {
+    /// The driver recognized the device but failed to initialize it.
+    ProbeFailed,
+    /// The driver released the device by itself.
+    Released,
+    /// The device has been removed.
+    Removed,
+}
+
+/// A device found on a bus.
+pub struct Device {
+    name: String,
+    bus: Bus,
+    /// Static attributes of the device such as `vendor` and `irq`.
+    attrs: Vec<(&'static str, String)>,
+    driver: SpinLock<Option<&'static str>>,
+}
+
+impl Device {
+    /// The unique name of the device in the bus (e.g. `0000:00:03.0`).
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn bus(&self) -> Bus {
+        self.bus
+    }
+
+    pub fn attrs(&self) -> &[(&'static str, String)] {
+        &self.attrs
+    }
+
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The name of the driver bound to the device.
+    pub fn driver(&self) -> Option<&'static str> {
+        *self.driver.lock()
+    }
+}
+
+/// Registers a device found on a bus.
+pub fn register_device(name: String, bus: Bus, attrs: Vec<(&'static str, String)>) -> Arc<Device> {
+    let device = Arc::new(Device {
+        name,
+        bus,
+        attrs,
+        driver: SpinLock::new(None),
+    });
+
+    DEVICES.lock().push(device.clone());
+    device
+}
+
+/// Registers a driver.
+pub fn register_driver(name: &'static str) {
+    let mut drivers = DRIVERS.lock();
+    if !drivers.contains(&name) {
+        drivers.push(name);
+    }
+}
+
+/// Binds `driver` to `device`.
+pub fn bind_driver(device: &Device, driver: &'static str) {
+    info!("{} {}: bound to {}", device.bus.name(), device.name, driver);
+    *device.driver.lock() = Some(driver);
+}
+
+/// Unbinds the driver from `device`.
+pub fn unbind_driver(device: &Device, driver: &'static str, reason: UnbindReason) {
+    match reason {
+        UnbindReason::ProbeFailed => warn!(
+            "{} {}: failed to probe by {}",
+            device.bus.name(),
+            device.name,
+            driver
+        ),
+        _ => info!(
+            "{} {}: unbound from {} ({:?})",
+            device.bus.name(),
+            device.name,
+            driver,
+            reason
+        ),
+    }
+
+    let mut bound = device.driver.lock();
+    if *bound == Some(driver) {
+        *bound = None;
+    }
+}
+
+/// Returns the registered devices.
+pub fn devices() -> Vec<Arc<Device>> {
+    DEVICES.lock().clone()
+}
+
+/// Returns the registered drivers.
+pub fn drivers() -> Vec<&'static str> {
+    DRIVERS.lock().clone()
+}
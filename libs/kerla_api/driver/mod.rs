@@ -1,18 +1,34 @@
 //! Device driver APIs.
 use crate::kernel_ops::kernel_ops;
 
+use alloc::string::ToString;
 use alloc::vec::Vec;
+use alloc::{format, vec};
 
+pub mod block;
+pub mod device;
 pub mod ioport;
 pub mod net;
+pub mod p9;
 pub mod pci;
+pub mod rng;
 
 pub use kerla_runtime::bootinfo::VirtioMmioDevice;
 
 use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
 use kerla_runtime::{bootinfo::AllowedPciDevice, spinlock::SpinLock};
 
-use self::pci::PciDevice;
+use self::device::{
+    bind_driver, register_device, register_driver, unbind_driver, Bus, Device, UnbindReason,
+};
+use self::pci::{Bar, PciDevice};
+
+/// `IORESOURCE_IO` in Linux: the resource is an I/O port range.
+const IORESOURCE_IO: usize = 0x100;
+/// `IORESOURCE_MEM` in Linux: the resource is a memory-mapped range.
+const IORESOURCE_MEM: usize = 0x200;
 
 static DEVICE_PROBERS: SpinLock<Vec<Box<dyn DeviceProber>>> = SpinLock::new(Vec::new());
 
@@ -20,15 +36,107 @@ pub trait Driver: Send + Sync {
     fn name(&self) -> &str;
 }
 
+/// The result of [`DeviceProber`] methods.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProbeResult {
+    /// The driver has taken the device.
+    Bound,
+    /// The device is not supported by the driver.
+    NotSupported,
+    /// The device is supported by the driver but it failed to initialize.
+    Failed,
+}
+
 pub trait DeviceProber: Send + Sync {
-    fn probe_pci(&self, pci_device: &PciDevice);
-    fn probe_virtio_mmio(&self, mmio_device: &VirtioMmioDevice);
+    /// The name of the driver (e.g. `virtio-net`).
+    fn name(&self) -> &'static str;
+    fn probe_pci(&self, pci_device: &PciDevice) -> ProbeResult;
+    fn probe_virtio_mmio(&self, mmio_device: &VirtioMmioDevice) -> ProbeResult;
 }
 
 pub fn register_driver_prober(driver: Box<dyn DeviceProber>) {
+    register_driver(driver.name());
     DEVICE_PROBERS.lock().push(driver);
 }
 
+/// Tries probers one by one until a driver takes the device.
+fn probe_device<F>(device: &Device, probe: F)
+where
+    F: Fn(&dyn DeviceProber) -> ProbeResult,
+{
+    for prober in DEVICE_PROBERS.lock().iter() {
+        match probe(prober.as_ref()) {
+            ProbeResult::Bound => {
+                bind_driver(device, prober.name());
+                return;
+            }
+            ProbeResult::NotSupported => {}
+            ProbeResult::Failed => {
+                unbind_driver(device, prober.name(), UnbindReason::ProbeFailed);
+            }
+        }
+    }
+}
+
+fn register_pci_device(pci_device: &PciDevice) -> Arc<Device> {
+    let config = pci_device.config();
+    let mut resource = String::new();
+    for i in 0..6 {
+        let size = pci_device.bar_size(i);
+        let (start, flags) = match config.bar(i) {
+            _ if size == 0 => (0, 0),
+            Bar::IOMapped { port } => (port as usize, IORESOURCE_IO),
+            Bar::MemoryMapped { paddr } => (paddr.value(), IORESOURCE_MEM),
+        };
+        let end = if size == 0 { 0 } else { start + size - 1 };
+        resource += &format!("0x{:016x} 0x{:016x} 0x{:016x}\n", start, end, flags);
+    }
+
+    register_device(
+        pci_device.name(),
+        Bus::Pci,
+        vec![
+            ("vendor", format!("0x{:04x}", config.vendor_id())),
+            ("device", format!("0x{:04x}", config.device_id())),
+            (
+                "subsystem_vendor",
+                format!("0x{:04x}", config.subsystem_vendor_id()),
+            ),
+            (
+                "subsystem_device",
+                format!("0x{:04x}", config.subsystem_id()),
+            ),
+            ("class", format!("0x{:06x}", config.class_code())),
+            ("revision", format!("0x{:02x}", config.revision())),
+            ("irq", config.interrupt_line().to_string()),
+            ("resource", resource),
+        ],
+    )
+}
+
+fn register_virtio_mmio_device(mmio_device: &VirtioMmioDevice) -> Arc<Device> {
+    // The size of the virtio-mmio register space.
+    const VIRTIO_MMIO_SIZE: usize = 0x200;
+
+    let start = mmio_device.mmio_base.value();
+    register_device(
+        format!("{:x}.virtio_mmio", start),
+        Bus::Platform,
+        vec![
+            ("irq", mmio_device.irq.to_string()),
+            (
+                "resource",
+                format!(
+                    "0x{:016x} 0x{:016x} 0x{:016x}\n",
+                    start,
+                    start + VIRTIO_MMIO_SIZE - 1,
+                    IORESOURCE_MEM
+                ),
+            ),
+        ],
+    )
+}
+
 pub fn attach_irq<F: FnMut() + Send + Sync + 'static>(irq: u8, f: F) {
     kernel_ops().attach_irq(irq, Box::new(f))
 }
@@ -62,16 +170,14 @@ pub fn init(
                 device.config().interrupt_line()
             );
 
-            for prober in DEVICE_PROBERS.lock().iter() {
-                prober.probe_pci(&device);
-            }
+            let dev = register_pci_device(&device);
+            probe_device(&dev, |prober| prober.probe_pci(&device));
         }
     }
 
     // Register Virtio devices connected over MMIO.
     for device in mmio_devices {
-        for prober in DEVICE_PROBERS.lock().iter() {
-            prober.probe_virtio_mmio(device);
-        }
+        let dev = register_virtio_mmio_device(device);
+        probe_device(&dev, |prober| prober.probe_virtio_mmio(device));
     }
 }
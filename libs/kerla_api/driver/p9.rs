@@ -0,0 +1,42 @@
+//! 9P transport APIs (e.g. virtio-9p).
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use super::Driver;
+
+use crate::kernel_ops::kernel_ops;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum P9TransportError {
+    /// The device failed to process the request.
+    IoError,
+    /// The request is larger than [`P9Transport::max_message_size`].
+    TooLarge,
+}
+
+/// Called with the response message once the server has replied. It may be
+/// called in the interrupt context.
+pub type P9Completion = Box<dyn FnOnce(Result<Vec<u8>, P9TransportError>) + Send>;
+
+pub struct P9Request {
+    /// The whole T-message including the `size[4]` header.
+    pub message: Vec<u8>,
+    pub completion: P9Completion,
+}
+
+/// A channel to a 9P server.
+pub trait P9Transport: Driver {
+    /// The tag which identifies the exported file system (e.g. `mount_tag` in
+    /// QEMU's `-virtfs`).
+    fn mount_tag(&self) -> &str;
+    /// The maximum size of a message in bytes. It applies to both requests
+    /// and responses.
+    fn max_message_size(&self) -> usize;
+    /// Sends a request. The transport calls `request.completion` with the
+    /// corresponding R-message. Multiple requests may be in flight at once.
+    fn submit(&self, request: P9Request);
+}
+
+pub fn register_p9_transport(transport: Box<dyn P9Transport>) {
+    kernel_ops().register_p9_transport(transport);
+}
@@ -1,3 +1,5 @@
+use alloc::format;
+use alloc::string::String;
 use alloc::vec::Vec;
 use arrayvec::ArrayVec;
 use core::convert::TryInto;
@@ -56,7 +58,7 @@ pub struct PciCapability {
 
 impl PciConfig {
     pub fn bar(&self, index: usize) -> Bar {
-        assert!(index < 5);
+        assert!(index < 6);
         let bar = self.bar[index];
         if bar & 1 == 0 {
             Bar::MemoryMapped {
@@ -80,6 +82,24 @@ impl PciConfig {
     pub fn interrupt_line(&self) -> u8 {
         self.interrupt_line
     }
+
+    pub fn revision(&self) -> u8 {
+        self.revision
+    }
+
+    /// Returns the class code: the base class, subclass, and programming
+    /// interface (e.g. `0x020000` for an Ethernet controller).
+    pub fn class_code(&self) -> u32 {
+        ((self.class as u32) << 16) | ((self.subclass as u32) << 8) | (self.prog_if as u32)
+    }
+
+    pub fn subsystem_vendor_id(&self) -> VendorId {
+        self.subsystem_vendor
+    }
+
+    pub fn subsystem_id(&self) -> DeviceId {
+        self.subsystem
+    }
 }
 
 macro_rules! pci_config_offset {
@@ -122,6 +142,42 @@ impl PciDevice {
     pub fn capabilities(&self) -> &[PciCapability] {
         &self.capabilities
     }
+
+    /// Returns the size of the address range decoded by the BAR. Returns 0 if
+    /// the BAR is not implemented.
+    pub fn bar_size(&self, index: usize) -> usize {
+        assert!(index < 6);
+        let bus = PciBus {};
+        let offset = pci_config_offset!(bar) + (index * size_of::<u32>()) as u32;
+
+        // Disable the I/O and memory decoding while probing the size.
+        let command = bus.read32(self.bus, self.slot, pci_config_offset!(command));
+        bus.write32(
+            self.bus,
+            self.slot,
+            pci_config_offset!(command),
+            command & !0b11,
+        );
+
+        let original = bus.read32(self.bus, self.slot, offset);
+        bus.write32(self.bus, self.slot, offset, 0xffff_ffff);
+        let value = bus.read32(self.bus, self.slot, offset);
+        bus.write32(self.bus, self.slot, offset, original);
+        bus.write32(self.bus, self.slot, pci_config_offset!(command), command);
+
+        let mask = if original & 1 == 0 { !0b1111 } else { !0b11 };
+        let size_mask = value & mask;
+        if size_mask == 0 {
+            return 0;
+        }
+
+        (!size_mask).wrapping_add(1) as usize
+    }
+
+    /// Returns the name of the device in the sysfs (e.g. `0000:00:03.0`).
+    pub fn name(&self) -> String {
+        format!("0000:{:02x}:{:02x}.0", self.bus, self.slot)
+    }
 }
 
 #[derive(Copy, Clone)]
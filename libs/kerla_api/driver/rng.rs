@@ -0,0 +1,21 @@
+//! Hardware random number generator APIs.
+use alloc::boxed::Box;
+
+use super::Driver;
+
+use crate::kernel_ops::kernel_ops;
+
+pub trait RngDriver: Driver {
+    /// Asks the device for random bytes. The driver passes them to
+    /// [`receive_randomness`] once they are available.
+    fn request_randomness(&self);
+}
+
+pub fn register_rng_driver(driver: Box<dyn RngDriver>) {
+    kernel_ops().register_rng_driver(driver);
+}
+
+/// Mixes random bytes from the device into the kernel's entropy pool.
+pub fn receive_randomness(buf: &[u8]) {
+    kernel_ops().receive_randomness(buf);
+}
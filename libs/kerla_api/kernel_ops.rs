@@ -3,12 +3,18 @@ use alloc::boxed::Box;
 use kerla_runtime::bootinfo::{AllowedPciDevice, VirtioMmioDevice};
 use kerla_utils::static_cell::StaticCell;
 
-use crate::driver::{self, net::EthernetDriver};
+use crate::driver::{
+    self, block::BlockDriver, net::EthernetDriver, p9::P9Transport, rng::RngDriver,
+};
 
 pub trait KernelOps: Sync {
     fn receive_etherframe_packet(&self, pkt: &[u8]);
     fn register_ethernet_driver(&self, driver: Box<dyn EthernetDriver>);
     fn attach_irq(&self, irq: u8, f: Box<dyn FnMut() + Send + Sync + 'static>);
+    fn register_rng_driver(&self, driver: Box<dyn RngDriver>);
+    fn receive_randomness(&self, buf: &[u8]);
+    fn register_block_driver(&self, driver: Box<dyn BlockDriver>);
+    fn register_p9_transport(&self, transport: Box<dyn P9Transport>);
 }
 
 static OPS: StaticCell<&dyn KernelOps> = StaticCell::new(&NopOps);
@@ -19,6 +25,10 @@ impl KernelOps for NopOps {
     fn attach_irq(&self, _irq: u8, _f: Box<dyn FnMut() + Send + Sync + 'static>) {}
     fn register_ethernet_driver(&self, _driver: Box<dyn EthernetDriver>) {}
     fn receive_etherframe_packet(&self, _pkt: &[u8]) {}
+    fn register_rng_driver(&self, _driver: Box<dyn RngDriver>) {}
+    fn receive_randomness(&self, _buf: &[u8]) {}
+    fn register_block_driver(&self, _driver: Box<dyn BlockDriver>) {}
+    fn register_p9_transport(&self, _transport: Box<dyn P9Transport>) {}
 }
 
 pub(crate) fn kernel_ops() -> &'static dyn KernelOps {
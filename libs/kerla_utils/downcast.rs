@@ -3,18 +3,57 @@ use core::any::Any;
 
 pub trait Downcastable: Any + Send + Sync {
     fn as_any(&self) -> &dyn Any;
+    fn as_any_arc(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
 }
 
 impl<T: Any + Send + Sync> Downcastable for T {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn as_any_arc(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+/// Downcasts a trait object into a reference to the concrete type.
+pub fn downcast<S, T>(arc: &Arc<S>) -> Option<&T>
+where
+    S: Downcastable + ?Sized,
+    T: Send + Sync + 'static,
+{
+    // Call the method of `S` explicitly: `arc.as_any()` resolves into the
+    // implementation for `Arc<S>` itself.
+    Downcastable::as_any(&**arc).downcast_ref::<T>()
 }
 
-pub fn downcast<S, T>(arc: &Arc<S>) -> Option<&Arc<T>>
+/// Downcasts a trait object into an `Arc` of the concrete type.
+pub fn downcast_arc<S, T>(arc: &Arc<S>) -> Option<Arc<T>>
 where
     S: Downcastable + ?Sized,
     T: Send + Sync + 'static,
 {
-    arc.as_any().downcast_ref::<Arc<T>>()
+    Downcastable::as_any_arc(arc.clone()).downcast::<T>().ok()
+}
+
+#[cfg(all(test, not(feature = "no_std")))]
+mod tests {
+    use super::*;
+
+    trait Animal: Downcastable {}
+
+    struct Cat;
+    impl Animal for Cat {}
+
+    struct Dog;
+    impl Animal for Dog {}
+
+    #[test]
+    fn test_downcast() {
+        let cat: Arc<dyn Animal> = Arc::new(Cat);
+        assert!(downcast::<_, Cat>(&cat).is_some());
+        assert!(downcast::<_, Dog>(&cat).is_none());
+        assert!(downcast_arc::<_, Cat>(&cat).is_some());
+        assert!(downcast_arc::<_, Dog>(&cat).is_none());
+    }
 }
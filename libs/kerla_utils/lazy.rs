@@ -12,6 +12,10 @@ impl<T> Lazy<T> {
         Lazy { value: None }
     }
 
+    pub fn is_initialized(&self) -> bool {
+        self.value.is_some()
+    }
+
     pub fn get(&self) -> &T {
         self.value.as_ref().expect("not yet initialized")
     }
@@ -333,6 +333,19 @@ impl Virtio {
         features: u64,
         num_virtqueues: u16,
     ) -> Result<(), VirtioAttachError> {
+        self.initialize_with_optional_features(features, 0, num_virtqueues)?;
+        Ok(())
+    }
+
+    /// Initialize the virtio device like `initialize`, but also enables
+    /// `optional_features` supported by the device. Returns the negotiated
+    /// features.
+    pub fn initialize_with_optional_features(
+        &mut self,
+        features: u64,
+        optional_features: u64,
+        num_virtqueues: u16,
+    ) -> Result<u64, VirtioAttachError> {
         // "3.1.1 Driver Requirements: Device Initialization"
         self.transport.write_device_status(0); // Reset the device.
         self.transport
@@ -350,6 +363,7 @@ impl Virtio {
             return Err(VirtioAttachError::MissingFeatures);
         }
 
+        let features = features | (device_features & optional_features);
         self.transport.write_driver_features(features);
         self.transport
             .write_device_status(self.transport.read_device_status() | VIRTIO_STATUS_FEAT_OK);
@@ -368,7 +382,7 @@ impl Virtio {
         self.transport
             .write_device_status(self.transport.read_device_status() | VIRTIO_STATUS_DRIVER_OK);
 
-        Ok(())
+        Ok(features)
     }
 
     pub fn is_modern(&self) -> bool {
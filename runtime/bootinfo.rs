@@ -27,4 +27,10 @@ pub struct BootInfo {
     pub dhcp_enabled: bool,
     pub ip4: Option<ArrayString<18>>,
     pub gateway_ip4: Option<ArrayString<15>>,
+    /// The root block device (e.g. `PARTUUID=...`).
+    pub root: Option<ArrayString<64>>,
+    /// The 9P file system to be mounted (`<mount_tag>:<path>`).
+    pub virtfs: Option<ArrayString<64>>,
+    /// If it's true, a tmpfs is overlaid on the root file system.
+    pub overlay_root: bool,
 }
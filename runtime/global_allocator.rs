@@ -18,6 +18,23 @@ pub fn is_kernel_heap_enabled() -> bool {
     KERNEL_HEAP_ENABLED.load(Ordering::Acquire)
 }
 
+#[derive(Debug)]
+pub struct Stats {
+    /// The bytes added to the kernel heap.
+    pub total_bytes: usize,
+    /// The bytes allocated from the kernel heap (including the internal
+    /// fragmentation of the buddy allocator).
+    pub allocated_bytes: usize,
+}
+
+pub fn read_kernel_heap_stats() -> Stats {
+    let heap = ALLOCATOR.lock();
+    Stats {
+        total_bytes: heap.stats_total_bytes(),
+        allocated_bytes: heap.stats_alloc_actual(),
+    }
+}
+
 fn expand_kernel_heap(heap: &mut Heap<ORDER>, layout: &Layout) {
     if layout.size() > KERNEL_HEAP_CHUNK_SIZE {
         panic!(
@@ -24,9 +24,10 @@ mod x64;
 pub mod arch {
     #[cfg(target_arch = "x86_64")]
     pub use super::x64::{
-        enable_irq, halt, idle, read_clock_counter, semihosting_halt, x64_specific, Backtrace,
-        PageFaultReason, PageTable, PtRegs, SavedInterruptStatus, SemihostingExitStatus,
-        KERNEL_BASE_ADDR, KERNEL_STRAIGHT_MAP_PADDR_END, PAGE_SIZE, TICK_HZ,
+        arm_timer, enable_irq, halt, idle, nanosecs_since_boot, read_clock_counter, read_rtc,
+        semihosting_halt, tsc_frequency, tsc_params, x64_specific, Backtrace, PageFaultReason,
+        PageTable, PtRegs, SavedInterruptStatus, SemihostingExitStatus, KERNEL_BASE_ADDR,
+        KERNEL_STRAIGHT_MAP_PADDR_END, PAGE_SIZE, TICK_HZ,
     };
 }
 
@@ -36,11 +37,16 @@ use kerla_utils::static_cell::StaticCell;
 pub trait Handler: Sync {
     fn handle_console_rx(&self, char: u8);
     fn handle_irq(&self, irq: u8);
-    fn handle_timer_irq(&self);
+    /// Handles a timer interrupt. `in_user` is `true` if the CPU was running
+    /// in the user mode when the interrupt occurred.
+    ///
+    /// The timer is one-shot: the handler is responsible for programming the
+    /// next timer interrupt by `arch::arm_timer`.
+    fn handle_timer_irq(&self, in_user: bool);
     fn handle_page_fault(
         &self,
         unaligned_vaddr: Option<UserVAddr>,
-        ip: usize,
+        frame: &arch::PtRegs,
         _reason: arch::PageFaultReason,
     );
 
@@ -68,12 +74,12 @@ struct NopHandler;
 impl Handler for NopHandler {
     fn handle_console_rx(&self, _char: u8) {}
     fn handle_irq(&self, _irq: u8) {}
-    fn handle_timer_irq(&self) {}
+    fn handle_timer_irq(&self, _in_user: bool) {}
 
     fn handle_page_fault(
         &self,
         _unaligned_vaddr: Option<UserVAddr>,
-        _ip: usize,
+        _frame: &arch::PtRegs,
         _reason: arch::PageFaultReason,
     ) {
     }
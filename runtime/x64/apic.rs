@@ -1,19 +1,36 @@
+use super::{ioapic::VECTOR_IRQ_BASE, pit};
 use crate::address::PAddr;
 use crate::spinlock::SpinLock;
 use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{AtomicU64, Ordering};
 use x86::msr::{self, rdmsr, wrmsr};
 
 /// The base index of interrupt vectors.
 const APIC_BASE_EN: u64 = 1 << 11;
 const SIVR_SOFT_EN: u32 = 1 << 8;
+/// Masks the timer interrupt. The one-shot mode is used if it's not set.
+const LVT_TIMER_MASKED: u32 = 1 << 16;
+/// Divides the bus clock by 16.
+const TIMER_DIVIDE_BY_16: u32 = 0b0011;
+/// The timer interrupt vector. It's shared with the IRQ 0 (PIT), which is no
+/// longer enabled.
+const TIMER_VECTOR: u8 = VECTOR_IRQ_BASE;
+/// How long we measure the timer frequency against the PIT.
+const CALIBRATION_MS: u64 = 10;
 
 static APIC: SpinLock<LocalApic> = SpinLock::new(LocalApic::new(PAddr::new(0xfee0_0000)));
+/// The number of timer counts per second, measured in `init_timer`.
+static TIMER_FREQ: AtomicU64 = AtomicU64::new(0);
 
 #[derive(Debug, Copy, Clone)]
 #[repr(u32)]
 enum LocalApicReg {
     Eoi = 0xb0,
     SpuriousInterrupt = 0xf0,
+    LvtTimer = 0x320,
+    TimerInitialCount = 0x380,
+    TimerCurrentCount = 0x390,
+    TimerDivideConfig = 0x3e0,
 }
 
 struct LocalApic {
@@ -34,8 +51,15 @@ impl LocalApic {
         self.mmio_write(LocalApicReg::SpuriousInterrupt, value);
     }
 
+    /// Starts the timer in the one-shot mode. It counts down from `count` to
+    /// zero and then raises an interrupt.
+    pub unsafe fn start_timer(&self, count: u32) {
+        self.mmio_write(LocalApicReg::LvtTimer, TIMER_VECTOR as u32);
+        self.mmio_write(LocalApicReg::TimerInitialCount, count);
+    }
+
     #[inline(always)]
-    unsafe fn _mmio_read(&self, reg: LocalApicReg) -> u32 {
+    unsafe fn mmio_read(&self, reg: LocalApicReg) -> u32 {
         read_volatile(self.base.add(reg as usize).as_ptr())
     }
 
@@ -51,6 +75,38 @@ pub fn ack_interrupt() {
     }
 }
 
+/// Programs the local APIC timer to raise a timer interrupt once after
+/// `nanosecs` nanoseconds.
+///
+/// Too long durations are truncated to the maximum duration the timer supports
+/// (tens of seconds), so the timer interrupt handler should not assume that
+/// the given deadline has been reached.
+pub fn arm_timer(nanosecs: usize) {
+    let freq = TIMER_FREQ.load(Ordering::Relaxed) as u128;
+    let count = (nanosecs as u128 * freq / 1_000_000_000).clamp(1, u32::MAX as u128);
+    unsafe {
+        APIC.lock().start_timer(count as u32);
+    }
+}
+
+/// Measures the frequency of the local APIC timer and fires the first timer
+/// interrupt.
+pub unsafe fn init_timer() {
+    let apic = APIC.lock();
+    apic.mmio_write(LocalApicReg::TimerDivideConfig, TIMER_DIVIDE_BY_16);
+    apic.mmio_write(LocalApicReg::LvtTimer, LVT_TIMER_MASKED);
+    apic.mmio_write(LocalApicReg::TimerInitialCount, u32::MAX);
+    pit::busy_wait_ms(CALIBRATION_MS as u32);
+    let elapsed = u32::MAX - apic.mmio_read(LocalApicReg::TimerCurrentCount);
+
+    let freq = (elapsed as u64) * 1000 / CALIBRATION_MS;
+    trace!("LAPIC timer: {} counts/sec", freq);
+    TIMER_FREQ.store(freq, Ordering::Relaxed);
+
+    // The timer interrupt handler programs the next one.
+    apic.start_timer(1);
+}
+
 pub unsafe fn init() {
     // Activate Local APIC.
     let apic_base = rdmsr(msr::APIC_BASE);
@@ -1,4 +1,4 @@
-use super::{apic, bootinfo, cpu_local, gdt, idt, ioapic, pit, serial, syscall, tss, vga};
+use super::{apic, bootinfo, cpu_local, gdt, idt, ioapic, serial, syscall, tsc, tss, vga};
 use crate::address::{PAddr, VAddr};
 use crate::bootinfo::BootInfo;
 use crate::logger;
@@ -40,7 +40,8 @@ unsafe fn common_setup(cpu_local_area: VAddr) {
     gdt::init();
     tss::init();
     idt::init();
-    pit::init();
+    tsc::init();
+    apic::init_timer();
     syscall::init();
 }
 
@@ -132,6 +132,9 @@ struct Cmdline {
     pub dhcp_enabled: bool,
     pub ip4: Option<ArrayString<18>>,
     pub gateway_ip4: Option<ArrayString<15>>,
+    pub root: Option<ArrayString<64>>,
+    pub virtfs: Option<ArrayString<64>>,
+    pub overlay_root: bool,
     pub pci_allowlist: ArrayVec<AllowedPciDevice, 4>,
 }
 
@@ -148,6 +151,9 @@ impl Cmdline {
         let mut dhcp_enabled = true;
         let mut ip4 = None;
         let mut gateway_ip4 = None;
+        let mut root = None;
+        let mut virtfs = None;
+        let mut overlay_root = false;
         if !s.is_empty() {
             for config in s.split(' ') {
                 if config.is_empty() {
@@ -216,6 +222,28 @@ impl Cmdline {
                         }
                         gateway_ip4 = Some(s);
                     }
+                    (Some("root"), Some(value)) => {
+                        info!("bootinfo: root device = \"{}\"", value);
+                        let mut s = ArrayString::new();
+                        if s.try_push_str(value).is_err() {
+                            warn!("bootinfo: root is too long");
+                        } else {
+                            root = Some(s);
+                        }
+                    }
+                    (Some("virtfs"), Some(value)) => {
+                        info!("bootinfo: virtfs = \"{}\"", value);
+                        let mut s = ArrayString::new();
+                        if s.try_push_str(value).is_err() {
+                            warn!("bootinfo: virtfs is too long");
+                        } else {
+                            virtfs = Some(s);
+                        }
+                    }
+                    (Some("overlay"), Some("on")) => {
+                        info!("bootinfo: overlay root enabled");
+                        overlay_root = true;
+                    }
                     (Some(path), None) if path.starts_with('/') => {
                         // QEMU appends a kernel image path. Just ignore it.
                     }
@@ -235,6 +263,9 @@ impl Cmdline {
             dhcp_enabled,
             ip4,
             gateway_ip4,
+            root,
+            virtfs,
+            overlay_root,
         }
     }
 }
@@ -338,6 +369,9 @@ unsafe fn parse_multiboot2_info(header: &Multiboot2InfoHeader) -> BootInfo {
         dhcp_enabled: cmdline.dhcp_enabled,
         ip4: cmdline.ip4,
         gateway_ip4: cmdline.gateway_ip4,
+        root: cmdline.root,
+        virtfs: cmdline.virtfs,
+        overlay_root: cmdline.overlay_root,
     }
 }
 
@@ -383,6 +417,9 @@ unsafe fn parse_multiboot_legacy_info(info: &MultibootLegacyInfo) -> BootInfo {
         dhcp_enabled: cmdline.dhcp_enabled,
         ip4: cmdline.ip4,
         gateway_ip4: cmdline.gateway_ip4,
+        root: cmdline.root,
+        virtfs: cmdline.virtfs,
+        overlay_root: cmdline.overlay_root,
     }
 }
 
@@ -418,6 +455,9 @@ unsafe fn parse_linux_boot_params(boot_params: PAddr) -> BootInfo {
         dhcp_enabled: cmdline.dhcp_enabled,
         ip4: cmdline.ip4,
         gateway_ip4: cmdline.gateway_ip4,
+        root: cmdline.root,
+        virtfs: cmdline.virtfs,
+        overlay_root: cmdline.overlay_root,
     }
 }
 
@@ -2,7 +2,9 @@ use crate::{address::UserVAddr, handler};
 
 use core::fmt;
 
-use super::{apic::ack_interrupt, ioapic::VECTOR_IRQ_BASE, serial::SERIAL0_IRQ, PageFaultReason};
+use super::{
+    apic::ack_interrupt, ioapic::VECTOR_IRQ_BASE, serial::SERIAL0_IRQ, PageFaultReason, PtRegs,
+};
 use x86::{
     controlregs::cr2,
     current::rflags::{self, RFlags},
@@ -36,6 +38,36 @@ struct InterruptFrame {
     ss: u64,
 }
 
+impl InterruptFrame {
+    /// Converts into the same register layout as the one saved in a system
+    /// call entry.
+    fn to_pt_regs(self) -> PtRegs {
+        PtRegs {
+            r15: self.r15,
+            r14: self.r14,
+            r13: self.r13,
+            r12: self.r12,
+            rbp: self.rbp,
+            rbx: self.rbx,
+            r11: self.r11,
+            r10: self.r10,
+            r9: self.r9,
+            r8: self.r8,
+            rax: self.rax,
+            rcx: self.rcx,
+            rdx: self.rdx,
+            rsi: self.rsi,
+            rdi: self.rdi,
+            orig_rax: !0,
+            rip: self.rip,
+            cs: self.cs,
+            rflags: self.rflags,
+            rsp: self.rsp,
+            ss: self.ss,
+        }
+    }
+}
+
 impl fmt::Debug for InterruptFrame {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let rip = self.rip;
@@ -60,14 +92,9 @@ extern "C" {
 unsafe extern "C" fn x64_handle_interrupt(vec: u8, frame: *const InterruptFrame) {
     let frame = &*frame;
 
-    // FIXME: Check "Legacy replacement" mapping
+    // The local APIC timer (see `apic::init_timer`).
     const TIMER_IRQ: u8 = 0;
-    const TIMER_IRQ2: u8 = 2;
-    if vec != VECTOR_IRQ_BASE + TIMER_IRQ
-        && vec != VECTOR_IRQ_BASE + TIMER_IRQ2
-        && vec != 14
-        && vec != 36
-    {
+    if vec != VECTOR_IRQ_BASE + TIMER_IRQ && vec != 14 && vec != 36 {
         let rip = frame.rip;
         let rsp = frame.rsp;
         let error = frame.error;
@@ -87,8 +114,8 @@ unsafe extern "C" fn x64_handle_interrupt(vec: u8, frame: *const InterruptFrame)
 
             let irq = vec - VECTOR_IRQ_BASE;
             match irq {
-                TIMER_IRQ | TIMER_IRQ2 => {
-                    handler().handle_timer_irq();
+                TIMER_IRQ => {
+                    handler().handle_timer_irq(frame.cs & 3 == 3);
                 }
                 SERIAL0_IRQ => {
                     super::serial::serial0_irq_handler();
@@ -181,7 +208,7 @@ unsafe extern "C" fn x64_handle_interrupt(vec: u8, frame: *const InterruptFrame)
 
             // Abort if the virtual address points to out of the user's address space.
             let unaligned_vaddr = UserVAddr::new(cr2());
-            handler().handle_page_fault(unaligned_vaddr, frame.rip as usize, reason);
+            handler().handle_page_fault(unaligned_vaddr, &frame.to_pt_regs(), reason);
         }
         X87_FPU_VECTOR => {
             // TODO:
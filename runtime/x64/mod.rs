@@ -19,20 +19,25 @@ mod ioapic;
 mod paging;
 mod pit;
 mod profile;
+mod rtc;
 mod semihosting;
 mod serial;
 mod syscall;
+mod tsc;
 mod tss;
 mod vga;
 
+pub use apic::arm_timer;
 pub use backtrace::Backtrace;
 pub use idle::{halt, idle};
 pub use interrupt::SavedInterruptStatus;
 pub use ioapic::enable_irq;
 pub use paging::{PageFaultReason, PageTable};
 pub use profile::read_clock_counter;
+pub use rtc::read_rtc;
 pub use semihosting::{semihosting_halt, SemihostingExitStatus};
 pub use syscall::PtRegs;
+pub use tsc::{nanosecs_since_boot, tsc_frequency, tsc_params};
 
 pub mod x64_specific {
     pub use super::cpu_local::cpu_local_head;
@@ -16,6 +16,9 @@ bitflags! {
         const PRESENT = 1 << 0;
         const WRITABLE = 1 << 1;
         const USER = 1 << 2;
+        /// A software-defined bit (ignored by the CPU): the page is shared
+        /// among processes and must not be copied in fork(2).
+        const SHARED = 1 << 9;
     }
 }
 
@@ -100,7 +103,10 @@ fn duplicate_table(original_table_paddr: PAddr, level: usize) -> Result<PAddr, P
         }
 
         // Create a deep copy of the page table entry.
-        let new_paddr = if level == 1 {
+        let new_paddr = if level == 1 && entry & PageAttrs::SHARED.bits() != 0 {
+            // Shared pages (e.g. vDSO) are mapped as they are.
+            paddr
+        } else if level == 1 {
             // Copy a physical page referenced from the last-level page table.
             let new_paddr = alloc_pages(1, AllocPageFlags::KERNEL)?;
             unsafe {
@@ -184,9 +190,44 @@ impl PageTable {
         );
     }
 
+    /// Maps a read-only page shared among all processes. Unlike pages mapped
+    /// by `map_user_page`, it won't be copied in fork(2).
+    pub fn map_shared_user_page(&mut self, vaddr: UserVAddr, paddr: PAddr) {
+        self.map_page(
+            vaddr,
+            paddr,
+            PageAttrs::PRESENT | PageAttrs::USER | PageAttrs::SHARED,
+        );
+    }
+
+    /// Returns the physical address mapped at `vaddr`, or `None` if the page
+    /// is not mapped. Unlike `map_user_page`, it never modifies the table.
+    pub fn lookup_paddr(&self, vaddr: UserVAddr) -> Option<PAddr> {
+        debug_assert!(is_aligned(vaddr.value(), PAGE_SIZE));
+        let mut table = self.pml4.as_ptr::<PageTableEntry>();
+        for level in (2..=4).rev() {
+            let entry = unsafe { *table.offset(nth_level_table_index(vaddr, level)) };
+            if entry & PageAttrs::PRESENT.bits() == 0 {
+                return None;
+            }
+
+            table = entry_paddr(entry).as_ptr::<PageTableEntry>();
+        }
+
+        let entry = unsafe { *table.offset(nth_level_table_index(vaddr, 1)) };
+        if entry & PageAttrs::PRESENT.bits() == 0 {
+            return None;
+        }
+
+        Some(entry_paddr(entry))
+    }
+
     fn map_page(&mut self, vaddr: UserVAddr, paddr: PAddr, attrs: PageAttrs) {
         debug_assert!(is_aligned(vaddr.value(), PAGE_SIZE));
-        let mut entry = traverse(self.pml4, vaddr, true, attrs).unwrap();
+        // Intermediate tables are always writable: the effective permissions
+        // are determined by the last-level entry.
+        let table_attrs = PageAttrs::PRESENT | PageAttrs::USER | PageAttrs::WRITABLE;
+        let mut entry = traverse(self.pml4, vaddr, true, table_attrs).unwrap();
         unsafe {
             *entry.as_mut() = paddr.value() as u64 | attrs.bits();
         }
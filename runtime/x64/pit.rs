@@ -1,15 +1,30 @@
-use super::ioapic::enable_irq;
-use x86::io::outb;
+//! The PIT (i8254). It's used only for calibrating the local APIC timer.
+use core::hint::spin_loop;
+use x86::io::{inb, outb};
 
-const DIVISOR: u16 = (1193182u32 / 1000) as u16;
+/// The frequency of the PIT input clock.
+const PIT_HZ: u32 = 1193182;
 
-pub unsafe fn init() {
-    trace!("enabling PIT (i8254) timer: divisor={}", DIVISOR);
-    outb(0x43, 0x35);
-    outb(0x40, (DIVISOR & 0xff) as u8);
-    outb(0x40, (DIVISOR >> 8) as u8);
+/// Latches and reads the current count of the channel 0.
+unsafe fn read_count() -> u16 {
+    outb(0x43, 0x00);
+    let lo = inb(0x40) as u16;
+    let hi = inb(0x40) as u16;
+    (hi << 8) | lo
+}
+
+/// Busy-waits for `ms` milliseconds (up to 50 milliseconds).
+pub unsafe fn busy_wait_ms(ms: u32) {
+    let target = (PIT_HZ * ms / 1000) as u16;
+
+    // Channel 0, lobyte/hibyte, mode 0 (interrupt on terminal count). The IRQ
+    // is not enabled in the IO APIC so no interrupts occur.
+    outb(0x43, 0x30);
+    outb(0x40, 0xff);
+    outb(0x40, 0xff);
 
-    // FIXME: Check "Legacy replacement" mapping
-    enable_irq(0);
-    enable_irq(2);
+    let start = read_count();
+    while start.wrapping_sub(read_count()) < target {
+        spin_loop();
+    }
 }
@@ -0,0 +1,138 @@
+//! The CMOS real-time clock (RTC).
+use crate::spinlock::SpinLock;
+use x86::io::{inb, outb};
+
+const CMOS_ADDR: u16 = 0x70;
+const CMOS_DATA: u16 = 0x71;
+
+const REG_SECONDS: u8 = 0x00;
+const REG_MINUTES: u8 = 0x02;
+const REG_HOURS: u8 = 0x04;
+const REG_DAY: u8 = 0x07;
+const REG_MONTH: u8 = 0x08;
+const REG_YEAR: u8 = 0x09;
+/// The century register. Its location is defined in the ACPI FADT but 0x32 is
+/// the de facto standard.
+const REG_CENTURY: u8 = 0x32;
+const REG_STATUS_A: u8 = 0x0a;
+const REG_STATUS_B: u8 = 0x0b;
+
+/// An update is in progress.
+const STATUS_A_UIP: u8 = 1 << 7;
+/// Values are in the binary format, not BCD.
+const STATUS_B_BINARY: u8 = 1 << 2;
+/// Hours are in the 24-hour format.
+const STATUS_B_24HOUR: u8 = 1 << 1;
+/// The PM flag in the hours register in the 12-hour format.
+const HOURS_PM: u8 = 1 << 7;
+/// An update takes about 2 milliseconds. If UIP is not cleared after this many
+/// reads, we assume the RTC does not exist.
+const MAX_UIP_WAITS: usize = 100_000;
+
+static CMOS: SpinLock<()> = SpinLock::new(());
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct RtcTime {
+    year: u32,
+    month: u32,
+    day: u32,
+    hours: u32,
+    minutes: u32,
+    seconds: u32,
+}
+
+unsafe fn read_cmos(reg: u8) -> u8 {
+    // Keep NMIs enabled (bit 7 cleared).
+    outb(CMOS_ADDR, reg & 0x7f);
+    inb(CMOS_DATA)
+}
+
+unsafe fn read_rtc_time() -> Option<RtcTime> {
+    let mut waits = 0;
+    while read_cmos(REG_STATUS_A) & STATUS_A_UIP != 0 {
+        waits += 1;
+        if waits > MAX_UIP_WAITS {
+            return None;
+        }
+
+        core::hint::spin_loop();
+    }
+
+    let status_b = read_cmos(REG_STATUS_B);
+    let decode = |value: u8| -> u32 {
+        if status_b & STATUS_B_BINARY != 0 {
+            value as u32
+        } else {
+            ((value >> 4) * 10 + (value & 0x0f)) as u32
+        }
+    };
+
+    let raw_hours = read_cmos(REG_HOURS);
+    let mut hours = decode(raw_hours & !HOURS_PM);
+    if status_b & STATUS_B_24HOUR == 0 {
+        // 12 AM is 0 o'clock and 12 PM is 12 o'clock.
+        hours %= 12;
+        if raw_hours & HOURS_PM != 0 {
+            hours += 12;
+        }
+    }
+
+    let century = match decode(read_cmos(REG_CENTURY)) {
+        century @ 19..=21 => century,
+        _ => 20,
+    };
+
+    Some(RtcTime {
+        year: century * 100 + decode(read_cmos(REG_YEAR)),
+        month: decode(read_cmos(REG_MONTH)),
+        day: decode(read_cmos(REG_DAY)),
+        hours,
+        minutes: decode(read_cmos(REG_MINUTES)),
+        seconds: decode(read_cmos(REG_SECONDS)),
+    })
+}
+
+/// Returns the number of days from 1970-01-01 to the given date.
+fn days_from_epoch(year: u32, month: u32, day: u32) -> u64 {
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = (if month <= 2 { year - 1 } else { year }) as u64;
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month = month as u64;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day as u64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Reads the RTC and returns seconds elapsed since the epoch (00:00:00 on 1
+/// January 1970, UTC). We assume the RTC is in UTC.
+///
+/// Returns `None` if the RTC is not available.
+pub fn read_rtc() -> Option<u64> {
+    let _lock = CMOS.lock();
+
+    // Read the RTC until we get the same values twice in a row: it might be
+    // updated while we're reading the registers.
+    let mut time = unsafe { read_rtc_time()? };
+    loop {
+        let next = unsafe { read_rtc_time()? };
+        if next == time {
+            break;
+        }
+
+        time = next;
+    }
+
+    let valid = time.year >= 1970
+        && (1..=12).contains(&time.month)
+        && (1..=31).contains(&time.day)
+        && time.hours < 24
+        && time.minutes < 60
+        && time.seconds < 60;
+    if !valid {
+        return None;
+    }
+
+    let days = days_from_epoch(time.year, time.month, time.day);
+    Some(days * 86400 + time.hours as u64 * 3600 + time.minutes as u64 * 60 + time.seconds as u64)
+}
@@ -0,0 +1,119 @@
+//! The TSC clocksource.
+//!
+//! The TSC frequency is taken from KVM's pvclock if it's available, or
+//! measured against the PIT otherwise.
+use super::pit;
+use crate::page_allocator::{alloc_pages, free_pages, AllocPageFlags};
+use core::arch::x86_64::__cpuid;
+use core::ptr::read_volatile;
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86::{msr::wrmsr, time::rdtsc};
+
+/// The hypervisor CPUID leaf which contains the KVM signature.
+const KVM_CPUID_SIGNATURE: u32 = 0x4000_0000;
+const KVM_CPUID_FEATURES: u32 = 0x4000_0001;
+const KVM_FEATURE_CLOCKSOURCE2: u32 = 1 << 3;
+const MSR_KVM_SYSTEM_TIME_NEW: u32 = 0x4b56_4d01;
+/// How long we measure the TSC frequency against the PIT.
+const CALIBRATION_MS: u64 = 10;
+
+/// The TSC value at the boot.
+static TSC_AT_BOOT: AtomicU64 = AtomicU64::new(0);
+/// Nanoseconds per TSC cycle in the 32.32 fixed-point representation.
+static NANOSECS_PER_CYCLE: AtomicU64 = AtomicU64::new(0);
+
+/// `struct pvclock_vcpu_time_info`.
+#[allow(unused)]
+#[repr(C)]
+struct PvClockTimeInfo {
+    version: u32,
+    pad0: u32,
+    tsc_timestamp: u64,
+    system_time: u64,
+    tsc_to_system_mul: u32,
+    tsc_shift: i8,
+    flags: u8,
+    pad: [u8; 2],
+}
+
+unsafe fn is_kvm_clock_available() -> bool {
+    let signature = __cpuid(KVM_CPUID_SIGNATURE);
+    let is_kvm = signature.ebx == 0x4b4d_564b // "KVMK"
+        && signature.ecx == 0x564b_4d56 // "VMKV"
+        && signature.edx == 0x0000_004d; // "M\0\0\0"
+
+    is_kvm
+        && signature.eax >= KVM_CPUID_FEATURES
+        && __cpuid(KVM_CPUID_FEATURES).eax & KVM_FEATURE_CLOCKSOURCE2 != 0
+}
+
+/// Reads the TSC-to-nanoseconds conversion factor from KVM's pvclock.
+unsafe fn read_kvm_clock_scale() -> Option<u64> {
+    let paddr = alloc_pages(1, AllocPageFlags::KERNEL).ok()?;
+
+    // Ask the hypervisor to fill the time info and disable it immediately: we
+    // use only the conversion factor, which doesn't change.
+    wrmsr(MSR_KVM_SYSTEM_TIME_NEW, paddr.value() as u64 | 1);
+    let info = paddr.as_ptr::<PvClockTimeInfo>();
+    let mul = read_volatile(&(*info).tsc_to_system_mul) as u64;
+    let shift = read_volatile(&(*info).tsc_shift);
+    wrmsr(MSR_KVM_SYSTEM_TIME_NEW, 0);
+    free_pages(paddr, 1);
+
+    // nanosecs = ((cycles << shift) * mul) >> 32
+    match shift {
+        0.. => Some(mul << shift),
+        _ => Some(mul >> -shift),
+    }
+    .filter(|scale| *scale != 0)
+}
+
+/// Measures the TSC frequency against the PIT.
+unsafe fn measure_tsc_scale() -> u64 {
+    let start = rdtsc();
+    pit::busy_wait_ms(CALIBRATION_MS as u32);
+    let cycles = rdtsc() - start;
+    ((CALIBRATION_MS * 1_000_000) << 32) / cycles
+}
+
+/// Returns the TSC frequency in Hz.
+pub fn tsc_frequency() -> u64 {
+    (1_000_000_000u64 << 32) / NANOSECS_PER_CYCLE.load(Ordering::Relaxed)
+}
+
+/// Returns the TSC value at the boot and nanoseconds per TSC cycle (in the
+/// 32.32 fixed-point representation). `nanosecs_since_boot` is computed from
+/// them.
+pub fn tsc_params() -> (u64, u64) {
+    (
+        TSC_AT_BOOT.load(Ordering::Relaxed),
+        NANOSECS_PER_CYCLE.load(Ordering::Relaxed),
+    )
+}
+
+/// Returns nanoseconds elapsed since the boot.
+pub fn nanosecs_since_boot() -> usize {
+    let cycles = unsafe { rdtsc() } - TSC_AT_BOOT.load(Ordering::Relaxed);
+    let scale = NANOSECS_PER_CYCLE.load(Ordering::Relaxed);
+    ((cycles as u128 * scale as u128) >> 32) as usize
+}
+
+pub unsafe fn init() {
+    let scale = if is_kvm_clock_available() {
+        read_kvm_clock_scale()
+    } else {
+        None
+    };
+
+    let scale = match scale {
+        Some(scale) => {
+            trace!("TSC: using the scale from kvmclock");
+            scale
+        }
+        None => measure_tsc_scale(),
+    };
+
+    NANOSECS_PER_CYCLE.store(scale, Ordering::Relaxed);
+    TSC_AT_BOOT.store(rdtsc(), Ordering::Relaxed);
+    trace!("TSC: {} Hz", tsc_frequency());
+}
@@ -0,0 +1,107 @@
+//
+// Checks *at() system calls: openat(2) flags, AT_EMPTY_PATH,
+// AT_SYMLINK_NOFOLLOW, utimensat(2) timestamps, fchmodat(2), fchownat(2), and
+// readlinkat(2) on /proc/self/fd.
+//
+#include "test.h"
+
+int main(void) {
+    CHECK(mkdir("/tmp/at_syscalls", 0755) == 0);
+    int dir = open("/tmp/at_syscalls", O_RDONLY | O_DIRECTORY);
+    CHECK(dir >= 0);
+
+    // O_CREAT | O_EXCL fails if the file exists.
+    int fd = openat(dir, "file", O_WRONLY | O_CREAT | O_EXCL, 0644);
+    CHECK(fd >= 0);
+    CHECK(write(fd, "hello", 5) == 5);
+    CHECK(close(fd) == 0);
+    CHECK_ERRNO(openat(dir, "file", O_WRONLY | O_CREAT | O_EXCL, 0644), EEXIST);
+    check_file("/tmp/at_syscalls/file", "hello");
+
+    // O_CREAT without O_EXCL opens the existing file as it is.
+    fd = openat(dir, "file", O_RDONLY | O_CREAT, 0644);
+    CHECK(fd >= 0);
+    struct stat st;
+    CHECK(fstat(fd, &st) == 0 && st.st_size == 5);
+
+    // The access mode is taken from the flags.
+    CHECK_ERRNO(write(fd, "x", 1), EBADF);
+    CHECK(close(fd) == 0);
+    fd = openat(dir, "file", O_WRONLY);
+    CHECK(fd >= 0);
+    char buf[8];
+    CHECK_ERRNO(read(fd, buf, sizeof(buf)), EBADF);
+    CHECK(close(fd) == 0);
+
+    // O_TRUNC truncates the existing file.
+    fd = openat(dir, "file", O_WRONLY | O_TRUNC);
+    CHECK(fd >= 0);
+    CHECK(fstat(fd, &st) == 0 && st.st_size == 0);
+
+    // AT_EMPTY_PATH refers to the file `fd` itself.
+    struct stat st2;
+    CHECK(fstatat(fd, "", &st2, AT_EMPTY_PATH) == 0);
+    CHECK(st2.st_ino == st.st_ino);
+    CHECK_ERRNO(fstatat(fd, "", &st2, 0), ENOENT);
+    CHECK(close(fd) == 0);
+
+    // AT_SYMLINK_NOFOLLOW stats the symbolic link itself.
+    CHECK(symlinkat("file", dir, "symlink") == 0);
+    CHECK(fstatat(dir, "symlink", &st, 0) == 0 && S_ISREG(st.st_mode));
+    CHECK(fstatat(dir, "symlink", &st, AT_SYMLINK_NOFOLLOW) == 0 && S_ISLNK(st.st_mode));
+    CHECK(unlinkat(dir, "symlink", 0) == 0);
+    CHECK(fstatat(dir, "file", &st, 0) == 0);
+
+    // Timestamps before the epoch are valid.
+    struct timespec times[2] = {
+        { .tv_sec = -1, .tv_nsec = 0 },
+        { .tv_sec = -86400, .tv_nsec = 500 },
+    };
+    CHECK(utimensat(dir, "file", times, 0) == 0);
+    CHECK(fstatat(dir, "file", &st, 0) == 0);
+    CHECK(st.st_atim.tv_sec == -1 && st.st_atim.tv_nsec == 0);
+    CHECK(st.st_mtim.tv_sec == -86400 && st.st_mtim.tv_nsec == 500);
+
+    // tv_nsec is range-checked unless it's UTIME_NOW or UTIME_OMIT.
+    times[1].tv_nsec = 1000000000;
+    CHECK_ERRNO(utimensat(dir, "file", times, 0), EINVAL);
+    times[0].tv_nsec = UTIME_NOW;
+    times[1].tv_nsec = UTIME_OMIT;
+    CHECK(utimensat(dir, "file", times, 0) == 0);
+    CHECK(fstatat(dir, "file", &st, 0) == 0);
+    CHECK(st.st_atim.tv_sec > 0);
+    CHECK(st.st_mtim.tv_sec == -86400 && st.st_mtim.tv_nsec == 500);
+
+    // fchmodat(2) changes only the permission bits.
+    CHECK(fchmodat(dir, "file", 0600, 0) == 0);
+    CHECK(fstatat(dir, "file", &st, 0) == 0 && st.st_mode == (S_IFREG | 0600));
+
+    // fchownat(2) leaves the ID -1 unchanged.
+    CHECK(fchownat(dir, "file", 123, 456, 0) == 0);
+    CHECK(fstatat(dir, "file", &st, 0) == 0 && st.st_uid == 123 && st.st_gid == 456);
+    CHECK(fchownat(dir, "file", -1, 789, 0) == 0);
+    CHECK(fstatat(dir, "file", &st, 0) == 0 && st.st_uid == 123 && st.st_gid == 789);
+
+    // readlinkat(2) resolves /proc/self/fd/<fd> relative to the directory.
+    char path[32], link[64];
+    snprintf(path, sizeof(path), "/proc/self/fd/%d", dir);
+    CHECK(readlinkat(AT_FDCWD, path, link, sizeof(link)) == 16);
+    CHECK(memcmp(link, "/tmp/at_syscalls", 16) == 0);
+    int proc = open("/proc", O_RDONLY | O_DIRECTORY);
+    CHECK(proc >= 0);
+    snprintf(path, sizeof(path), "self/fd/%d", dir);
+    CHECK(readlinkat(proc, path, link, sizeof(link)) == 16);
+    CHECK(memcmp(link, "/tmp/at_syscalls", 16) == 0);
+    CHECK(close(proc) == 0);
+    CHECK_ERRNO(readlinkat(AT_FDCWD, "/proc/self/fd/x", link, sizeof(link)), ENOENT);
+
+    // unlinkat(2) with AT_REMOVEDIR removes only directories.
+    CHECK(mkdirat(dir, "subdir", 0755) == 0);
+    CHECK_ERRNO(unlinkat(dir, "subdir", 0), EISDIR);
+    CHECK_ERRNO(unlinkat(dir, "file", AT_REMOVEDIR), ENOTDIR);
+    CHECK(unlinkat(dir, "subdir", AT_REMOVEDIR) == 0);
+    CHECK(unlinkat(dir, "file", 0) == 0);
+
+    CHECK(close(dir) == 0);
+    return 0;
+}
@@ -0,0 +1,54 @@
+//
+// Checks that a process with a long non-ASCII argv[0] dumps core and its
+// name is truncated to 15 bytes at a character boundary.
+//
+#include "test.h"
+#include <signal.h>
+#include <sys/resource.h>
+#include <sys/wait.h>
+
+// The basename is 25 bytes long: "x" followed by 3-byte characters.
+#define ARGV0 "/bin/x漢字漢字漢字漢字"
+#define COMM "x漢字漢字"
+
+int main(int argc, char **argv) {
+    if (argc > 1 && strcmp(argv[1], "crash") == 0) {
+        raise(SIGSEGV);
+        return 1;
+    }
+
+    int fd = open("/proc/sys/kernel/core_pattern", O_WRONLY);
+    CHECK(fd >= 0);
+    CHECK(write(fd, "/tmp/core.%e", 12) == 12);
+    CHECK(close(fd) == 0);
+    struct rlimit limit = { .rlim_cur = RLIM_INFINITY, .rlim_max = RLIM_INFINITY };
+    CHECK(setrlimit(RLIMIT_CORE, &limit) == 0);
+
+    pid_t pid = fork();
+    CHECK(pid >= 0);
+    if (pid == 0) {
+        execl(argv[0], ARGV0, "crash", NULL);
+        _exit(1);
+    }
+
+    int status;
+    CHECK(waitpid(pid, &status, 0) == pid);
+    CHECK(WIFSIGNALED(status) && WTERMSIG(status) == SIGSEGV);
+    CHECK(WCOREDUMP(status));
+
+    // The core file is named after the truncated name, which is also in
+    // NT_PRPSINFO.
+    fd = open("/tmp/core." COMM, O_RDONLY);
+    CHECK(fd >= 0);
+    struct stat st;
+    CHECK(fstat(fd, &st) == 0);
+    char *core = malloc(st.st_size);
+    CHECK(core != NULL);
+    CHECK(read(fd, core, st.st_size) == st.st_size);
+    CHECK(memcmp(core, "\177ELF", 4) == 0);
+    CHECK(memmem(core, st.st_size, COMM, sizeof(COMM)) != NULL);
+    CHECK(close(fd) == 0);
+    CHECK(unlink("/tmp/core." COMM) == 0);
+    free(core);
+    return 0;
+}
@@ -0,0 +1,278 @@
+//
+// Checks writes to an ext2 file system mounted at argv[1]: creating, linking,
+// renaming and removing files and directories, and that a failed creation
+// doesn't leak inodes or blocks.
+//
+// Run it with "write" to make changes, remount the file system, and run it
+// with "verify" to check that they have been persisted.
+//
+#include "test.h"
+#include <dirent.h>
+#include <sys/statfs.h>
+
+#define RENAME_NOREPLACE (1 << 0)
+#define RENAME_EXCHANGE (1 << 1)
+#define RENAME_WHITEOUT (1 << 2)
+
+#define LONG_TARGET                                                          \
+    "a-symlink-target-longer-than-60-bytes-which-does-not-fit-in-the-inode"
+
+static int rename2(const char *old, const char *new, unsigned flags) {
+    return syscall(SYS_renameat2, AT_FDCWD, old, AT_FDCWD, new, flags);
+}
+
+static nlink_t nlink(const char *path) {
+    struct stat st;
+    CHECK(lstat(path, &st) == 0);
+    return st.st_nlink;
+}
+
+static ino_t ino(const char *path) {
+    struct stat st;
+    CHECK(lstat(path, &st) == 0);
+    return st.st_ino;
+}
+
+static void check_link(const char *path, const char *expected) {
+    char buf[256];
+    ssize_t len = readlink(path, buf, sizeof(buf));
+    CHECK(len == (ssize_t) strlen(expected));
+    CHECK(memcmp(buf, expected, len) == 0);
+}
+
+static void check_free(struct statfs *expected) {
+    struct statfs st;
+    CHECK(statfs(".", &st) == 0);
+    CHECK(st.f_bfree == expected->f_bfree);
+    CHECK(st.f_ffree == expected->f_ffree);
+}
+
+// Creates, links, renames and removes files and directories.
+static void check_namespace(void) {
+    // Files and hard links.
+    write_file("file", "hello");
+    check_file("file", "hello");
+    CHECK(nlink("file") == 1);
+    CHECK(link("file", "link") == 0);
+    CHECK(ino("link") == ino("file"));
+    CHECK(nlink("file") == 2);
+    CHECK_ERRNO(link("file", "link"), EEXIST);
+    CHECK(nlink("file") == 2);
+    CHECK(unlink("link") == 0);
+    CHECK(nlink("file") == 1);
+    CHECK_ERRNO(unlink("link"), ENOENT);
+
+    // Directories.
+    nlink_t root_links = nlink(".");
+    CHECK(mkdir("dir", 0755) == 0);
+    CHECK(nlink("dir") == 2);
+    CHECK(nlink(".") == root_links + 1);
+    CHECK_ERRNO(mkdir("dir", 0755), EEXIST);
+    CHECK(nlink(".") == root_links + 1);
+    CHECK(mkdir("dir/sub", 0755) == 0);
+    CHECK(nlink("dir") == 3);
+    CHECK_ERRNO(rmdir("dir"), ENOTEMPTY);
+    CHECK_ERRNO(unlink("dir"), EISDIR);
+    CHECK(rmdir("dir/sub") == 0);
+    CHECK(nlink("dir") == 2);
+
+    // Renaming files within and across directories.
+    CHECK(rename("file", "renamed") == 0);
+    CHECK(access("file", F_OK) == -1);
+    check_file("renamed", "hello");
+    CHECK(rename("renamed", "dir/file") == 0);
+    check_file("dir/file", "hello");
+    write_file("victim", "replaced");
+    CHECK(rename("dir/file", "victim") == 0);
+    check_file("victim", "hello");
+    CHECK(access("dir/file", F_OK) == -1);
+
+    // Renaming a directory updates the link counts of both parents.
+    CHECK(mkdir("dir2", 0755) == 0);
+    CHECK(rename("dir2", "dir/dir2") == 0);
+    CHECK(nlink("dir") == 3);
+    CHECK(nlink(".") == root_links + 1);
+    CHECK(ino("dir/dir2/..") == ino("dir"));
+    CHECK_ERRNO(rename("dir", "dir/dir2/dir"), EINVAL);
+    CHECK(rename("dir/dir2", "dir2") == 0);
+    CHECK(nlink("dir") == 2);
+    CHECK(ino("dir2/..") == ino("."));
+    CHECK(rmdir("dir2") == 0);
+    CHECK(nlink(".") == root_links + 1);
+
+    // renameat2(2) flags.
+    write_file("a", "a");
+    write_file("b", "b");
+    CHECK_ERRNO(rename2("a", "b", RENAME_NOREPLACE), EEXIST);
+    CHECK(rename2("a", "b", RENAME_EXCHANGE) == 0);
+    check_file("a", "b");
+    check_file("b", "a");
+    CHECK(rename2("a", "dir", RENAME_EXCHANGE) == 0);
+    check_file("dir", "b");
+    CHECK(ino("a/..") == ino("."));
+    CHECK(rename2("dir", "a", RENAME_EXCHANGE) == 0);
+    CHECK_ERRNO(rename2("a", "c", RENAME_WHITEOUT), EINVAL);
+    CHECK(rename2("a", "c", RENAME_NOREPLACE) == 0);
+    CHECK(unlink("b") == 0);
+    CHECK(unlink("c") == 0);
+
+    // Symbolic links.
+    CHECK(symlink("victim", "short") == 0);
+    check_link("short", "victim");
+    check_file("short", "hello");
+    CHECK(symlink(LONG_TARGET, "long") == 0);
+    check_link("long", LONG_TARGET);
+    CHECK_ERRNO(symlink("victim", "short"), EEXIST);
+    CHECK(unlink("short") == 0);
+    CHECK(unlink("long") == 0);
+    CHECK(unlink("victim") == 0);
+    CHECK(rmdir("dir") == 0);
+    CHECK(nlink(".") == root_links);
+}
+
+// Grows a directory beyond a block and removes all of its entries.
+static void check_large_dir(void) {
+    char path[64];
+    CHECK(mkdir("large", 0755) == 0);
+    for (int i = 0; i < 200; i++) {
+        snprintf(path, sizeof(path), "large/file-with-a-long-name-%d", i);
+        write_file(path, "x");
+    }
+    for (int i = 0; i < 200; i += 2) {
+        snprintf(path, sizeof(path), "large/file-with-a-long-name-%d", i);
+        CHECK(unlink(path) == 0);
+    }
+
+    int count = 0;
+    DIR *dir = opendir("large");
+    CHECK(dir != NULL);
+    struct dirent *entry;
+    while ((entry = readdir(dir)) != NULL) {
+        count++;
+    }
+    CHECK(closedir(dir) == 0);
+    CHECK(count == 100 + 2);
+
+    for (int i = 1; i < 200; i += 2) {
+        snprintf(path, sizeof(path), "large/file-with-a-long-name-%d", i);
+        CHECK(unlink(path) == 0);
+    }
+    CHECK(rmdir("large") == 0);
+}
+
+// Fills up the file system and checks that creating a file fails without
+// leaking the inode allocated for it.
+static void check_enospc(void) {
+    char path[300];
+    char name[250];
+    memset(name, 'n', sizeof(name) - 1);
+    name[sizeof(name) - 1] = '\0';
+
+    // Fill the block of a directory except for a few entries.
+    CHECK(mkdir("full", 0755) == 0);
+    int fd = open("filler", O_WRONLY | O_CREAT | O_TRUNC, 0644);
+    CHECK(fd >= 0);
+    static char buf[4096];
+    memset(buf, 'f', sizeof(buf));
+    for (;;) {
+        ssize_t written = write(fd, buf, sizeof(buf));
+        if (written < 0) {
+            CHECK(errno == ENOSPC);
+            break;
+        }
+        CHECK(written > 0);
+    }
+    // Free blocks may be left over in other block groups if they're smaller
+    // than the write size.
+    while (write(fd, buf, 1) == 1) {
+    }
+    CHECK(errno == ENOSPC);
+    CHECK(close(fd) == 0);
+
+    struct statfs before;
+    CHECK(statfs(".", &before) == 0);
+    CHECK(before.f_bfree == 0);
+
+    // Each entry takes more than 256 bytes, so a few of them fill the
+    // directory's first block, and then adding one more needs a new block.
+    int created = 0;
+    for (;;) {
+        snprintf(path, sizeof(path), "full/%d%s", created, name);
+        fd = open(path, O_WRONLY | O_CREAT | O_EXCL, 0644);
+        if (fd < 0) {
+            CHECK(errno == ENOSPC);
+            break;
+        }
+        CHECK(close(fd) == 0);
+        created++;
+    }
+    CHECK(created > 0);
+    CHECK_ERRNO(access(path, F_OK), ENOENT);
+    CHECK_ERRNO(mkdir(path, 0755), ENOSPC);
+    CHECK_ERRNO(symlink("target", path), ENOSPC);
+    CHECK_ERRNO(link("filler", path), ENOSPC);
+    CHECK(nlink("filler") == 1);
+    CHECK(nlink("full") == 2);
+
+    struct statfs after;
+    CHECK(statfs(".", &after) == 0);
+    CHECK(after.f_bfree == 0);
+    CHECK(after.f_ffree == before.f_ffree - created);
+
+    for (int i = 0; i < created; i++) {
+        snprintf(path, sizeof(path), "full/%d%s", i, name);
+        CHECK(unlink(path) == 0);
+    }
+    CHECK(rmdir("full") == 0);
+    CHECK(unlink("filler") == 0);
+}
+
+static void write_phase(void) {
+    struct statfs initial;
+    CHECK(statfs(".", &initial) == 0);
+
+    check_namespace();
+    check_large_dir();
+    check_enospc();
+    check_free(&initial);
+
+    // Leave some files to be checked after remounting.
+    CHECK(mkdir("persist", 0755) == 0);
+    write_file("persist/file", "persisted");
+    CHECK(link("persist/file", "persist/link") == 0);
+    CHECK(symlink(LONG_TARGET, "persist/symlink") == 0);
+    CHECK(mkdir("persist/dir", 0700) == 0);
+    write_file("persist/removed", "removed");
+    CHECK(unlink("persist/removed") == 0);
+}
+
+static void verify_phase(void) {
+    check_file("persist/file", "persisted");
+    CHECK(nlink("persist/file") == 2);
+    CHECK(ino("persist/link") == ino("persist/file"));
+    check_link("persist/symlink", LONG_TARGET);
+    CHECK(nlink("persist") == 3);
+    struct stat st;
+    CHECK(stat("persist/dir", &st) == 0);
+    CHECK(S_ISDIR(st.st_mode));
+    CHECK((st.st_mode & 0777) == 0700);
+    CHECK_ERRNO(access("persist/removed", F_OK), ENOENT);
+
+    CHECK(unlink("persist/file") == 0);
+    CHECK(unlink("persist/link") == 0);
+    CHECK(unlink("persist/symlink") == 0);
+    CHECK(rmdir("persist/dir") == 0);
+    CHECK(rmdir("persist") == 0);
+}
+
+int main(int argc, char **argv) {
+    CHECK(argc == 3);
+    CHECK(chdir(argv[1]) == 0);
+    if (!strcmp(argv[2], "write")) {
+        write_phase();
+    } else {
+        CHECK(!strcmp(argv[2], "verify"));
+        verify_phase();
+    }
+    return 0;
+}
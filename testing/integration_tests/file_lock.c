@@ -0,0 +1,205 @@
+//
+// Checks advisory file locks: conflicts between processes in F_SETLK and
+// F_GETLK, merging and splitting of record locks, OFD locks, flock(2), and
+// deadlock detection in F_SETLKW.
+//
+#include "test.h"
+#include <sys/file.h>
+#include <sys/wait.h>
+
+#define PATH "/tmp/file_lock"
+
+static int set_lock(int fd, int cmd, short type, off_t start, off_t len) {
+    struct flock fl = {
+        .l_type = type,
+        .l_whence = SEEK_SET,
+        .l_start = start,
+        .l_len = len,
+    };
+    return fcntl(fd, cmd, &fl);
+}
+
+// Returns the lock which prevents placing the given lock.
+static struct flock get_lock(int fd, int cmd, short type, off_t start, off_t len) {
+    struct flock fl = {
+        .l_type = type,
+        .l_whence = SEEK_SET,
+        .l_start = start,
+        .l_len = len,
+    };
+    CHECK(fcntl(fd, cmd, &fl) == 0);
+    return fl;
+}
+
+static void check_lock(struct flock fl, short type, off_t start, off_t len, pid_t pid) {
+    CHECK(fl.l_type == type);
+    if (type != F_UNLCK) {
+        CHECK(fl.l_start == start);
+        CHECK(fl.l_len == len);
+        CHECK(fl.l_pid == pid);
+    }
+}
+
+static void wait_child(pid_t pid) {
+    int status;
+    CHECK(waitpid(pid, &status, 0) == pid);
+    CHECK(WIFEXITED(status) && WEXITSTATUS(status) == 0);
+}
+
+// POSIX record locks held by the parent as seen by a child process.
+static void check_record_locks(int fd) {
+    pid_t parent = getpid();
+
+    // Adjacent and overlapping locks are merged into one.
+    CHECK(set_lock(fd, F_SETLK, F_WRLCK, 0, 10) == 0);
+    CHECK(set_lock(fd, F_SETLK, F_WRLCK, 10, 10) == 0);
+    CHECK(set_lock(fd, F_SETLK, F_WRLCK, 15, 15) == 0);
+    // Locks held by the process itself don't conflict.
+    check_lock(get_lock(fd, F_GETLK, F_WRLCK, 0, 0), F_UNLCK, 0, 0, 0);
+
+    pid_t pid = fork();
+    CHECK(pid >= 0);
+    if (pid == 0) {
+        check_lock(get_lock(fd, F_GETLK, F_WRLCK, 5, 1), F_WRLCK, 0, 30, parent);
+        check_lock(get_lock(fd, F_GETLK, F_RDLCK, 29, 10), F_WRLCK, 0, 30, parent);
+        check_lock(get_lock(fd, F_GETLK, F_WRLCK, 30, 0), F_UNLCK, 0, 0, 0);
+        CHECK_ERRNO(set_lock(fd, F_SETLK, F_RDLCK, 25, 1), EAGAIN);
+        CHECK(set_lock(fd, F_SETLK, F_WRLCK, 30, 10) == 0);
+        exit(0);
+    }
+    wait_child(pid);
+
+    // Placing a lock of another type or unlocking in the middle splits the
+    // lock.
+    CHECK(set_lock(fd, F_SETLK, F_RDLCK, 10, 10) == 0);
+    CHECK(set_lock(fd, F_SETLK, F_UNLCK, 25, 1) == 0);
+    pid = fork();
+    CHECK(pid >= 0);
+    if (pid == 0) {
+        check_lock(get_lock(fd, F_GETLK, F_RDLCK, 0, 10), F_WRLCK, 0, 10, parent);
+        check_lock(get_lock(fd, F_GETLK, F_WRLCK, 10, 1), F_RDLCK, 10, 10, parent);
+        check_lock(get_lock(fd, F_GETLK, F_WRLCK, 25, 1), F_UNLCK, 0, 0, 0);
+        check_lock(get_lock(fd, F_GETLK, F_RDLCK, 20, 5), F_WRLCK, 20, 5, parent);
+        // Read locks can be shared.
+        CHECK(set_lock(fd, F_SETLK, F_RDLCK, 12, 3) == 0);
+        CHECK_ERRNO(set_lock(fd, F_SETLK, F_WRLCK, 12, 3), EAGAIN);
+        exit(0);
+    }
+    wait_child(pid);
+
+    // Closing any file descriptor of the file releases the POSIX locks.
+    int fd2 = open(PATH, O_RDWR);
+    CHECK(fd2 >= 0);
+    CHECK(close(fd2) == 0);
+    pid = fork();
+    CHECK(pid >= 0);
+    if (pid == 0) {
+        check_lock(get_lock(fd, F_GETLK, F_WRLCK, 0, 0), F_UNLCK, 0, 0, 0);
+        exit(0);
+    }
+    wait_child(pid);
+}
+
+// OFD locks are owned by the open file description, not by the process.
+static void check_ofd_locks(int fd) {
+    int fd1 = open(PATH, O_RDWR);
+    int fd2 = open(PATH, O_RDWR);
+    CHECK(fd1 >= 0 && fd2 >= 0);
+
+    CHECK(set_lock(fd1, F_OFD_SETLK, F_WRLCK, 0, 10) == 0);
+    CHECK_ERRNO(set_lock(fd2, F_OFD_SETLK, F_RDLCK, 5, 1), EAGAIN);
+    check_lock(get_lock(fd2, F_OFD_GETLK, F_RDLCK, 0, 0), F_WRLCK, 0, 10, -1);
+    // They conflict with POSIX locks even in the same process.
+    CHECK_ERRNO(set_lock(fd, F_SETLK, F_WRLCK, 0, 1), EAGAIN);
+    CHECK(set_lock(fd, F_SETLK, F_WRLCK, 10, 1) == 0);
+    CHECK_ERRNO(set_lock(fd1, F_OFD_SETLK, F_WRLCK, 10, 1), EAGAIN);
+    CHECK(set_lock(fd, F_SETLK, F_UNLCK, 0, 0) == 0);
+
+    // A duplicated file descriptor shares the locks.
+    int dup_fd = dup(fd1);
+    CHECK(dup_fd >= 0);
+    CHECK(set_lock(dup_fd, F_OFD_SETLK, F_WRLCK, 10, 10) == 0);
+    CHECK(close(dup_fd) == 0);
+    check_lock(get_lock(fd2, F_OFD_GETLK, F_WRLCK, 0, 0), F_WRLCK, 0, 20, -1);
+
+    // Closing the last file descriptor releases the locks.
+    CHECK(close(fd1) == 0);
+    CHECK(set_lock(fd2, F_OFD_SETLK, F_WRLCK, 0, 0) == 0);
+    CHECK(close(fd2) == 0);
+}
+
+static void check_flock(void) {
+    int fd1 = open(PATH, O_RDWR);
+    int fd2 = open(PATH, O_RDWR);
+    CHECK(fd1 >= 0 && fd2 >= 0);
+
+    CHECK(flock(fd1, LOCK_EX) == 0);
+    CHECK_ERRNO(flock(fd2, LOCK_EX | LOCK_NB), EWOULDBLOCK);
+    CHECK_ERRNO(flock(fd2, LOCK_SH | LOCK_NB), EWOULDBLOCK);
+    // flock(2) locks don't conflict with record locks.
+    CHECK(set_lock(fd2, F_OFD_SETLK, F_WRLCK, 0, 0) == 0);
+    CHECK(set_lock(fd2, F_OFD_SETLK, F_UNLCK, 0, 0) == 0);
+
+    // Shared locks.
+    CHECK(flock(fd1, LOCK_SH) == 0);
+    CHECK(flock(fd2, LOCK_SH | LOCK_NB) == 0);
+
+    // A child shares the lock of the inherited file descriptor.
+    pid_t pid = fork();
+    CHECK(pid >= 0);
+    if (pid == 0) {
+        CHECK_ERRNO(flock(fd1, LOCK_EX | LOCK_NB), EWOULDBLOCK);
+        CHECK(flock(fd2, LOCK_UN) == 0);
+        CHECK(flock(fd1, LOCK_EX | LOCK_NB) == 0);
+        exit(0);
+    }
+    wait_child(pid);
+    CHECK_ERRNO(flock(fd2, LOCK_SH | LOCK_NB), EWOULDBLOCK);
+
+    // Closing the last file descriptor releases the lock.
+    CHECK(close(fd1) == 0);
+    CHECK(flock(fd2, LOCK_EX | LOCK_NB) == 0);
+    CHECK(close(fd2) == 0);
+}
+
+// F_SETLKW sleeps until the lock is released, and fails with EDEADLK if it
+// would never be released.
+static void check_setlkw(int fd) {
+    int pipe_fds[2];
+    CHECK(pipe(pipe_fds) == 0);
+    CHECK(set_lock(fd, F_SETLK, F_WRLCK, 0, 1) == 0);
+
+    pid_t pid = fork();
+    CHECK(pid >= 0);
+    if (pid == 0) {
+        CHECK(set_lock(fd, F_SETLK, F_WRLCK, 1, 1) == 0);
+        CHECK(write(pipe_fds[1], "x", 1) == 1);
+        // Blocks until the parent unlocks it.
+        CHECK(set_lock(fd, F_SETLKW, F_WRLCK, 0, 1) == 0);
+        exit(0);
+    }
+
+    char buf;
+    CHECK(read(pipe_fds[0], &buf, 1) == 1);
+    // Wait for the child to block in F_SETLKW.
+    usleep(200 * 1000);
+    CHECK_ERRNO(set_lock(fd, F_SETLKW, F_WRLCK, 1, 1), EDEADLK);
+    CHECK(set_lock(fd, F_SETLK, F_UNLCK, 0, 1) == 0);
+    wait_child(pid);
+    CHECK(close(pipe_fds[0]) == 0);
+    CHECK(close(pipe_fds[1]) == 0);
+}
+
+int main(void) {
+    int fd = open(PATH, O_RDWR | O_CREAT | O_TRUNC, 0644);
+    CHECK(fd >= 0);
+
+    check_record_locks(fd);
+    check_ofd_locks(fd);
+    check_flock();
+    check_setlkw(fd);
+
+    CHECK(close(fd) == 0);
+    CHECK(unlink(PATH) == 0);
+    return 0;
+}
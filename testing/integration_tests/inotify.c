@@ -0,0 +1,133 @@
+//
+// Checks inotify(7): entry events, rename cookies, close events and the
+// event queue overflow.
+//
+#include "test.h"
+#include <sys/inotify.h>
+#include <sys/wait.h>
+
+// The default value of /proc/sys/fs/inotify/max_queued_events.
+#define MAX_QUEUED_EVENTS 16384
+
+#define MASK                                                                 \
+    (IN_CREATE | IN_DELETE | IN_MOVED_FROM | IN_MOVED_TO | IN_CLOSE_WRITE |  \
+     IN_CLOSE_NOWRITE)
+
+static char buf[4096] __attribute__((aligned(__alignof__(struct inotify_event))));
+static size_t buf_len = 0;
+static size_t buf_off = 0;
+
+// Returns the next event or NULL if there are no more queued events.
+static struct inotify_event *next_event(int fd) {
+    if (buf_off >= buf_len) {
+        ssize_t len = read(fd, buf, sizeof(buf));
+        if (len < 0 && errno == EAGAIN) {
+            return NULL;
+        }
+
+        CHECK(len > 0);
+        buf_len = len;
+        buf_off = 0;
+    }
+
+    struct inotify_event *event = (struct inotify_event *) &buf[buf_off];
+    buf_off += sizeof(*event) + event->len;
+    return event;
+}
+
+static struct inotify_event *expect_event(int fd, int wd, uint32_t mask, const char *name) {
+    struct inotify_event *event = next_event(fd);
+    CHECK(event != NULL);
+    if (event->wd != wd || event->mask != mask
+        || strcmp(event->len ? event->name : "", name) != 0) {
+        fprintf(stderr, "expected (wd=%d, mask=%x, name=\"%s\") but got (wd=%d, mask=%x, name=\"%s\")\n",
+                wd, mask, name, event->wd, event->mask, event->len ? event->name : "");
+        exit(1);
+    }
+
+    return event;
+}
+
+int main(void) {
+    CHECK(mkdir("/tmp/inotify", 0755) == 0);
+    CHECK(mkdir("/tmp/inotify/sub", 0755) == 0);
+
+    int fd = inotify_init1(IN_NONBLOCK);
+    CHECK(fd >= 0);
+    int wd = inotify_add_watch(fd, "/tmp/inotify", MASK);
+    int sub_wd = inotify_add_watch(fd, "/tmp/inotify/sub", MASK);
+    CHECK(wd >= 0 && sub_wd >= 0 && wd != sub_wd);
+    CHECK(next_event(fd) == NULL);
+
+    // Creating, writing and closing a file.
+    write_file("/tmp/inotify/a", "hello");
+    expect_event(fd, wd, IN_CREATE, "a");
+    expect_event(fd, wd, IN_CLOSE_WRITE, "a");
+    check_file("/tmp/inotify/a", "hello");
+    expect_event(fd, wd, IN_CLOSE_NOWRITE, "a");
+    CHECK(mkdir("/tmp/inotify/dir", 0755) == 0);
+    expect_event(fd, wd, IN_CREATE | IN_ISDIR, "dir");
+    CHECK(next_event(fd) == NULL);
+
+    // IN_MOVED_FROM and IN_MOVED_TO have the same cookie.
+    CHECK(rename("/tmp/inotify/a", "/tmp/inotify/sub/b") == 0);
+    uint32_t cookie = expect_event(fd, wd, IN_MOVED_FROM, "a")->cookie;
+    CHECK(cookie != 0);
+    CHECK(expect_event(fd, sub_wd, IN_MOVED_TO, "b")->cookie == cookie);
+    CHECK(rename("/tmp/inotify/sub/b", "/tmp/inotify/c") == 0);
+    uint32_t cookie2 = expect_event(fd, sub_wd, IN_MOVED_FROM, "b")->cookie;
+    CHECK(cookie2 != 0 && cookie2 != cookie);
+    CHECK(expect_event(fd, wd, IN_MOVED_TO, "c")->cookie == cookie2);
+    CHECK(next_event(fd) == NULL);
+
+    // Files left opened by an exiting process are closed as well.
+    pid_t pid = fork();
+    CHECK(pid >= 0);
+    if (pid == 0) {
+        if (open("/tmp/inotify/c", O_WRONLY) < 0) {
+            _exit(1);
+        }
+
+        _exit(0);
+    }
+
+    int status;
+    CHECK(waitpid(pid, &status, 0) == pid);
+    CHECK(WIFEXITED(status) && WEXITSTATUS(status) == 0);
+    expect_event(fd, wd, IN_CLOSE_WRITE, "c");
+    CHECK(next_event(fd) == NULL);
+
+    // Closing a file after its watch is removed doesn't queue events.
+    int file = open("/tmp/inotify/c", O_RDONLY);
+    CHECK(file >= 0);
+    CHECK(inotify_rm_watch(fd, wd) == 0);
+    expect_event(fd, wd, IN_IGNORED, "");
+    CHECK(close(file) == 0);
+    CHECK(next_event(fd) == NULL);
+    CHECK_ERRNO(inotify_rm_watch(fd, wd), EINVAL);
+
+    // Once the queue is full, a single IN_Q_OVERFLOW event is queued and
+    // further events are dropped.
+    for (int i = 0; i < MAX_QUEUED_EVENTS; i++) {
+        CHECK(mkdir("/tmp/inotify/sub/d", 0755) == 0);
+        CHECK(rmdir("/tmp/inotify/sub/d") == 0);
+    }
+
+    for (int i = 0; i < MAX_QUEUED_EVENTS; i++) {
+        struct inotify_event *event = next_event(fd);
+        CHECK(event != NULL);
+        CHECK(event->wd == sub_wd);
+        CHECK(event->mask == ((i % 2 ? IN_DELETE : IN_CREATE) | IN_ISDIR));
+    }
+
+    expect_event(fd, -1, IN_Q_OVERFLOW, "");
+    CHECK(next_event(fd) == NULL);
+
+    // Closing the inotify instance with an active watch.
+    CHECK(close(fd) == 0);
+    CHECK(rmdir("/tmp/inotify/sub") == 0);
+    CHECK(rmdir("/tmp/inotify/dir") == 0);
+    CHECK(unlink("/tmp/inotify/c") == 0);
+    CHECK(rmdir("/tmp/inotify") == 0);
+    return 0;
+}
@@ -0,0 +1,95 @@
+//
+// Checks lseek(2) with SEEK_DATA/SEEK_HOLE, ESPIPE on pipes, and positional
+// and vectored I/O.
+//
+#include "test.h"
+#include <sys/uio.h>
+
+#ifndef SEEK_DATA
+#define SEEK_DATA 3
+#endif
+#ifndef SEEK_HOLE
+#define SEEK_HOLE 4
+#endif
+#ifndef RWF_APPEND
+#define RWF_APPEND 0x10
+#endif
+
+#define PAGE_SIZE 4096
+
+static ssize_t pwritev2_(int fd, const struct iovec *iov, int iovcnt, off_t offset, int flags) {
+    return syscall(SYS_pwritev2, fd, iov, iovcnt, offset, 0, flags);
+}
+
+static ssize_t preadv2_(int fd, const struct iovec *iov, int iovcnt, off_t offset, int flags) {
+    return syscall(SYS_preadv2, fd, iov, iovcnt, offset, 0, flags);
+}
+
+int main(void) {
+    // A sparse file on tmpfs: data in the first and the fourth pages.
+    int fd = open("/tmp/positional_io", O_RDWR | O_CREAT | O_TRUNC, 0644);
+    CHECK(fd >= 0);
+    CHECK(pwrite(fd, "data", 4, 0) == 4);
+    CHECK(pwrite(fd, "data", 4, 3 * PAGE_SIZE) == 4);
+    off_t size = 3 * PAGE_SIZE + 4;
+
+    CHECK(lseek(fd, 0, SEEK_DATA) == 0);
+    CHECK(lseek(fd, 0, SEEK_HOLE) == PAGE_SIZE);
+    CHECK(lseek(fd, PAGE_SIZE, SEEK_DATA) == 3 * PAGE_SIZE);
+    CHECK(lseek(fd, 2 * PAGE_SIZE, SEEK_HOLE) == 2 * PAGE_SIZE);
+    // The end of the file is an implicit hole.
+    CHECK(lseek(fd, 3 * PAGE_SIZE, SEEK_HOLE) == size);
+    CHECK_ERRNO(lseek(fd, size, SEEK_DATA), ENXIO);
+    CHECK_ERRNO(lseek(fd, size, SEEK_HOLE), ENXIO);
+    CHECK(lseek(fd, 0, SEEK_END) == size);
+
+    // The hole reads as zeroes.
+    char buf[16];
+    CHECK(pread(fd, buf, 4, 2 * PAGE_SIZE) == 4);
+    CHECK(memcmp(buf, "\0\0\0\0", 4) == 0);
+
+    // pread64/pwrite64 don't move the file position.
+    CHECK(lseek(fd, 1, SEEK_SET) == 1);
+    CHECK(pread(fd, buf, 4, 0) == 4 && memcmp(buf, "data", 4) == 0);
+    CHECK(lseek(fd, 0, SEEK_CUR) == 1);
+    CHECK(close(fd) == 0);
+
+    // RWF_APPEND appends regardless of the offset.
+    fd = open("/tmp/positional_io", O_RDWR | O_TRUNC);
+    CHECK(fd >= 0);
+    CHECK(write(fd, "hello", 5) == 5);
+    struct iovec iov[2] = {
+        { .iov_base = " ", .iov_len = 1 },
+        { .iov_base = "world", .iov_len = 5 },
+    };
+    CHECK(pwritev2_(fd, iov, 2, 0, RWF_APPEND) == 6);
+    CHECK(lseek(fd, 0, SEEK_CUR) == 5);
+    check_file("/tmp/positional_io", "hello world");
+
+    // preadv2 with the offset -1 reads from (and advances) the file position.
+    char a[3], b[3];
+    struct iovec riov[2] = {
+        { .iov_base = a, .iov_len = sizeof(a) },
+        { .iov_base = b, .iov_len = sizeof(b) },
+    };
+    CHECK(lseek(fd, 0, SEEK_SET) == 0);
+    CHECK(preadv2_(fd, riov, 2, -1, 0) == 6);
+    CHECK(memcmp(a, "hel", 3) == 0 && memcmp(b, "lo ", 3) == 0);
+    CHECK(lseek(fd, 0, SEEK_CUR) == 6);
+    CHECK(close(fd) == 0);
+    CHECK(unlink("/tmp/positional_io") == 0);
+
+    // Pipes are not seekable.
+    int fds[2];
+    CHECK(pipe(fds) == 0);
+    CHECK(write(fds[1], "abc", 3) == 3);
+    CHECK_ERRNO(lseek(fds[0], 0, SEEK_SET), ESPIPE);
+    CHECK_ERRNO(pread(fds[0], buf, 1, 0), ESPIPE);
+    CHECK_ERRNO(pwrite(fds[1], "x", 1, 0), ESPIPE);
+    CHECK_ERRNO(pwritev2_(fds[1], iov, 2, 0, 0), ESPIPE);
+    CHECK(read(fds[0], buf, sizeof(buf)) == 3);
+    CHECK(close(fds[0]) == 0);
+    CHECK(close(fds[1]) == 0);
+
+    return 0;
+}
@@ -0,0 +1,62 @@
+//
+// Checks renameat2(2) flags on tmpfs.
+//
+#include "test.h"
+
+#ifndef RENAME_NOREPLACE
+#define RENAME_NOREPLACE (1 << 0)
+#endif
+#ifndef RENAME_EXCHANGE
+#define RENAME_EXCHANGE (1 << 1)
+#endif
+
+static int renameat2_(const char *old_path, const char *new_path, unsigned flags) {
+    return syscall(SYS_renameat2, AT_FDCWD, old_path, AT_FDCWD, new_path, flags);
+}
+
+int main(void) {
+    CHECK(mkdir("/tmp/rename_flags", 0755) == 0);
+    CHECK(chdir("/tmp/rename_flags") == 0);
+    write_file("a", "A");
+    write_file("b", "B");
+    CHECK(mkdir("dir", 0755) == 0);
+
+    // RENAME_NOREPLACE fails if the destination exists.
+    CHECK_ERRNO(renameat2_("a", "b", RENAME_NOREPLACE), EEXIST);
+    check_file("a", "A");
+    check_file("b", "B");
+    CHECK(renameat2_("a", "c", RENAME_NOREPLACE) == 0);
+    CHECK(access("a", F_OK) == -1);
+    check_file("c", "A");
+
+    // RENAME_EXCHANGE swaps two files atomically.
+    CHECK(renameat2_("b", "c", RENAME_EXCHANGE) == 0);
+    check_file("b", "A");
+    check_file("c", "B");
+
+    // ... and a file and a directory.
+    CHECK(renameat2_("b", "dir", RENAME_EXCHANGE) == 0);
+    struct stat st;
+    CHECK(stat("b", &st) == 0 && S_ISDIR(st.st_mode));
+    check_file("dir", "A");
+
+    // RENAME_EXCHANGE fails if the destination doesn't exist.
+    CHECK_ERRNO(renameat2_("c", "nonexistent", RENAME_EXCHANGE), ENOENT);
+    CHECK_ERRNO(renameat2_("c", "b", RENAME_NOREPLACE | RENAME_EXCHANGE), EINVAL);
+
+    // A directory can't be moved into itself.
+    CHECK_ERRNO(renameat2_("b", "b/sub", 0), EINVAL);
+
+    // A non-empty directory can't be replaced.
+    CHECK(mkdir("d1", 0755) == 0);
+    CHECK(mkdir("d2", 0755) == 0);
+    write_file("d2/file", "");
+    CHECK_ERRNO(renameat2_("d1", "d2", 0), ENOTEMPTY);
+    CHECK_ERRNO(renameat2_("c", "d1", 0), EISDIR);
+    CHECK_ERRNO(renameat2_("d1", "c", 0), ENOTDIR);
+    CHECK(unlink("d2/file") == 0);
+    CHECK(renameat2_("d1", "d2", 0) == 0);
+    CHECK(access("d1", F_OK) == -1);
+
+    return 0;
+}
@@ -0,0 +1,105 @@
+//
+// Checks splice(2), tee(2), sendfile(2) and copy_file_range(2).
+//
+#include "test.h"
+#include <sys/sendfile.h>
+
+static ssize_t copy_file_range_(int fd_in, off_t *off_in, int fd_out, off_t *off_out,
+                                size_t len, unsigned flags) {
+    return syscall(SYS_copy_file_range, fd_in, off_in, fd_out, off_out, len, flags);
+}
+
+// Checks the contents of a file which may contain NUL bytes.
+static void check_contents(int fd, const char *expected, size_t len) {
+    char buf[64];
+    CHECK(pread(fd, buf, sizeof(buf), 0) == (ssize_t) len);
+    CHECK(memcmp(buf, expected, len) == 0);
+}
+
+int main(void) {
+    write_file("/tmp/splice_src", "0123456789");
+    int src = open("/tmp/splice_src", O_RDONLY);
+    int dst = open("/tmp/splice_dst", O_RDWR | O_CREAT | O_TRUNC, 0644);
+    CHECK(src >= 0 && dst >= 0);
+    int p[2], q[2];
+    CHECK(pipe(p) == 0 && pipe(q) == 0);
+
+    // file -> pipe -> file with explicit offsets: the offsets are written
+    // back and the file positions are left unchanged.
+    off_t off_in = 2;
+    CHECK(splice(src, &off_in, p[1], NULL, 4, 0) == 4);
+    CHECK(off_in == 6);
+    CHECK(lseek(src, 0, SEEK_CUR) == 0);
+    off_t off_out = 1;
+    CHECK(splice(p[0], NULL, dst, &off_out, 4, 0) == 4);
+    CHECK(off_out == 5);
+    CHECK(lseek(dst, 0, SEEK_CUR) == 0);
+    check_contents(dst, "\0002345", 5);
+
+    // Without offsets, the file positions are used and advanced.
+    CHECK(lseek(src, 7, SEEK_SET) == 7);
+    CHECK(splice(src, NULL, p[1], NULL, 16, 0) == 3);
+    CHECK(lseek(src, 0, SEEK_CUR) == 10);
+    CHECK(lseek(dst, 5, SEEK_SET) == 5);
+    CHECK(splice(p[0], NULL, dst, NULL, 16, 0) == 3);
+    CHECK(lseek(dst, 0, SEEK_CUR) == 8);
+    check_contents(dst, "\0002345789", 8);
+
+    // Offsets can't be specified for pipes, and one end must be a pipe.
+    off_in = 0;
+    CHECK_ERRNO(splice(p[0], &off_in, dst, NULL, 1, 0), ESPIPE);
+    CHECK_ERRNO(splice(src, NULL, dst, NULL, 1, 0), EINVAL);
+
+    // pipe -> pipe: tee(2) copies the data without consuming it.
+    char buf[16];
+    CHECK(write(p[1], "abc", 3) == 3);
+    CHECK(tee(p[0], q[1], 16, 0) == 3);
+    CHECK(read(p[0], buf, sizeof(buf)) == 3 && memcmp(buf, "abc", 3) == 0);
+    CHECK(read(q[0], buf, sizeof(buf)) == 3 && memcmp(buf, "abc", 3) == 0);
+    CHECK_ERRNO(tee(src, q[1], 1, 0), EINVAL);
+
+    // pipe -> pipe: splice(2) moves the data.
+    CHECK(write(p[1], "xyz", 3) == 3);
+    CHECK(splice(p[0], NULL, q[1], NULL, 16, 0) == 3);
+    CHECK(read(q[0], buf, sizeof(buf)) == 3 && memcmp(buf, "xyz", 3) == 0);
+    CHECK(write(p[1], "!", 1) == 1);
+    CHECK(close(p[1]) == 0);
+    CHECK(read(p[0], buf, sizeof(buf)) == 1);
+
+    // sendfile(2) with an offset leaves the input file position unchanged.
+    CHECK(lseek(src, 0, SEEK_SET) == 0);
+    off_in = 4;
+    CHECK(sendfile(q[1], src, &off_in, 3) == 3);
+    CHECK(off_in == 7);
+    CHECK(lseek(src, 0, SEEK_CUR) == 0);
+    CHECK(read(q[0], buf, sizeof(buf)) == 3 && memcmp(buf, "456", 3) == 0);
+    CHECK(sendfile(q[1], src, NULL, 2) == 2);
+    CHECK(lseek(src, 0, SEEK_CUR) == 2);
+    CHECK(read(q[0], buf, sizeof(buf)) == 2 && memcmp(buf, "01", 2) == 0);
+
+    // copy_file_range(2) between regular files.
+    CHECK(ftruncate(dst, 0) == 0);
+    off_in = 3;
+    off_out = 2;
+    CHECK(copy_file_range_(src, &off_in, dst, &off_out, 4, 0) == 4);
+    CHECK(off_in == 7 && off_out == 6);
+    CHECK(lseek(src, 0, SEEK_CUR) == 2);
+    CHECK(lseek(dst, 0, SEEK_CUR) == 8);
+    check_contents(dst, "\0\0003456", 6);
+    CHECK(lseek(dst, 0, SEEK_SET) == 0);
+    CHECK(copy_file_range_(src, NULL, dst, NULL, 2, 0) == 2);
+    CHECK(lseek(src, 0, SEEK_CUR) == 4);
+    CHECK(lseek(dst, 0, SEEK_CUR) == 2);
+    check_file("/tmp/splice_dst", "233456");
+
+    // Overlapping ranges in the same file and non-zero flags are rejected.
+    off_in = 0;
+    off_out = 2;
+    CHECK_ERRNO(copy_file_range_(dst, &off_in, dst, &off_out, 4, 0), EINVAL);
+    CHECK_ERRNO(copy_file_range_(src, NULL, dst, NULL, 1, 1), EINVAL);
+    CHECK_ERRNO(copy_file_range_(q[0], NULL, dst, NULL, 1, 0), EINVAL);
+
+    CHECK(unlink("/tmp/splice_src") == 0);
+    CHECK(unlink("/tmp/splice_dst") == 0);
+    return 0;
+}
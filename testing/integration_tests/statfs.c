@@ -0,0 +1,116 @@
+//
+// Checks statfs(2) on a tmpfs mounted at argv[1] with "size=64k,nr_inodes=8":
+// the free blocks and inodes follow writes and removals, and creating files
+// or writing data beyond the limits fails with ENOSPC.
+//
+#include "test.h"
+#include <sys/statfs.h>
+
+#define TMPFS_MAGIC 0x01021994
+#define MAX_BLOCKS 16
+#define MAX_INODES 8
+
+static void check_free(unsigned long blocks, unsigned long inodes) {
+    struct statfs st;
+    CHECK(statfs(".", &st) == 0);
+    CHECK(st.f_type == TMPFS_MAGIC);
+    CHECK(st.f_bsize == 4096);
+    CHECK(st.f_blocks == MAX_BLOCKS);
+    CHECK(st.f_files == MAX_INODES);
+    CHECK(st.f_bfree == blocks);
+    CHECK(st.f_bavail == blocks);
+    CHECK(st.f_ffree == inodes);
+}
+
+// Fills up the file system with a file and removes it.
+static void check_size_limit(void) {
+    static char buf[4096];
+    memset(buf, 'a', sizeof(buf));
+
+    int fd = open("file", O_RDWR | O_CREAT | O_TRUNC, 0644);
+    CHECK(fd >= 0);
+    check_free(MAX_BLOCKS, MAX_INODES - 2);
+
+    CHECK(write(fd, buf, sizeof(buf)) == sizeof(buf));
+    CHECK(write(fd, buf, 1) == 1);
+    check_free(MAX_BLOCKS - 2, MAX_INODES - 2);
+
+    struct statfs st;
+    CHECK(fstatfs(fd, &st) == 0);
+    CHECK(st.f_bfree == MAX_BLOCKS - 2);
+
+    // Shrinking the file frees the pages beyond the new size.
+    CHECK(ftruncate(fd, 100) == 0);
+    check_free(MAX_BLOCKS - 1, MAX_INODES - 2);
+
+    // Holes don't use pages.
+    CHECK(pwrite(fd, buf, 1, 8 * sizeof(buf)) == 1);
+    check_free(MAX_BLOCKS - 2, MAX_INODES - 2);
+    CHECK(ftruncate(fd, 0) == 0);
+    CHECK(lseek(fd, 0, SEEK_SET) == 0);
+    check_free(MAX_BLOCKS, MAX_INODES - 2);
+
+    size_t written = 0;
+    for (;;) {
+        ssize_t len = write(fd, buf, sizeof(buf));
+        if (len < 0) {
+            CHECK(errno == ENOSPC);
+            break;
+        }
+        CHECK(len == sizeof(buf));
+        written += len;
+    }
+    CHECK(written == MAX_BLOCKS * sizeof(buf));
+    check_free(0, MAX_INODES - 2);
+    CHECK_ERRNO(pwrite(fd, buf, 1, written), ENOSPC);
+    // Overwriting the existing data doesn't need more pages.
+    CHECK(pwrite(fd, buf, sizeof(buf), 0) == sizeof(buf));
+
+    // The pages are freed when the file is removed and closed.
+    CHECK(unlink("file") == 0);
+    check_free(0, MAX_INODES - 2);
+    CHECK(close(fd) == 0);
+    check_free(MAX_BLOCKS, MAX_INODES - 1);
+}
+
+// Creates files until running out of inodes and removes them.
+static void check_inode_limit(void) {
+    char path[32];
+    int created = 0;
+    for (;;) {
+        snprintf(path, sizeof(path), "file%d", created);
+        int fd = open(path, O_WRONLY | O_CREAT | O_EXCL, 0644);
+        if (fd < 0) {
+            CHECK(errno == ENOSPC);
+            break;
+        }
+        CHECK(close(fd) == 0);
+        created++;
+    }
+
+    // The root directory uses one.
+    CHECK(created == MAX_INODES - 1);
+    check_free(MAX_BLOCKS, 0);
+    CHECK_ERRNO(mkdir("dir", 0755), ENOSPC);
+    CHECK_ERRNO(symlink("file0", "symlink"), ENOSPC);
+
+    for (int i = 0; i < created; i++) {
+        snprintf(path, sizeof(path), "file%d", i);
+        CHECK(unlink(path) == 0);
+    }
+    check_free(MAX_BLOCKS, MAX_INODES - 1);
+    CHECK(mkdir("dir", 0755) == 0);
+    check_free(MAX_BLOCKS, MAX_INODES - 2);
+    CHECK(rmdir("dir") == 0);
+    check_free(MAX_BLOCKS, MAX_INODES - 1);
+}
+
+int main(int argc, char **argv) {
+    CHECK(argc == 2);
+    CHECK(chdir(argv[1]) == 0);
+    check_free(MAX_BLOCKS, MAX_INODES - 1);
+
+    check_size_limit();
+    check_inode_limit();
+    return 0;
+}
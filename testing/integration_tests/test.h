@@ -0,0 +1,60 @@
+//
+// Helpers for integration test programs. Each program checks the behavior of
+// system calls and exits with 1 on the first failure.
+//
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/stat.h>
+#include <sys/syscall.h>
+#include <unistd.h>
+
+// Fails the test if `expr` is false.
+#define CHECK(expr)                                                          \
+    do {                                                                     \
+        if (!(expr)) {                                                       \
+            fprintf(stderr, "%s:%d: CHECK(%s) failed (errno=%d: %s)\n",      \
+                    __FILE__, __LINE__, #expr, errno, strerror(errno));      \
+            exit(1);                                                         \
+        }                                                                    \
+    } while (0)
+
+// Fails the test if `expr` doesn't fail with `expected_errno`.
+#define CHECK_ERRNO(expr, expected_errno)                                    \
+    do {                                                                     \
+        errno = 0;                                                           \
+        long __ret = (long) (expr);                                          \
+        if (__ret != -1 || errno != (expected_errno)) {                      \
+            fprintf(stderr, "%s:%d: %s returned %ld (errno=%d: %s), "        \
+                    "expected %s\n", __FILE__, __LINE__, #expr, __ret,       \
+                    errno, strerror(errno), #expected_errno);                \
+            exit(1);                                                         \
+        }                                                                    \
+    } while (0)
+
+// Writes `contents` into a new file at `path`.
+static inline void write_file(const char *path, const char *contents) {
+    int fd = open(path, O_WRONLY | O_CREAT | O_TRUNC, 0644);
+    CHECK(fd >= 0);
+    CHECK(write(fd, contents, strlen(contents)) == (ssize_t) strlen(contents));
+    CHECK(close(fd) == 0);
+}
+
+// Fails the test if the contents of the file at `path` is not `expected`.
+static inline void check_file(const char *path, const char *expected) {
+    char buf[256];
+    int fd = open(path, O_RDONLY);
+    CHECK(fd >= 0);
+    ssize_t len = read(fd, buf, sizeof(buf) - 1);
+    CHECK(len >= 0);
+    buf[len] = '\0';
+    CHECK(close(fd) == 0);
+    if (strcmp(buf, expected) != 0) {
+        fprintf(stderr, "%s: expected \"%s\" but got \"%s\"\n", path,
+                expected, buf);
+        exit(1);
+    }
+}
@@ -30,6 +30,8 @@ ARCHS = {
             "-object",
             "filter-dump,id=fiter0,netdev=net0,file=virtio-net.pcap",
             "-device",
+            "virtio-rng-pci,disable-legacy=on,disable-modern=off",
+            "-device",
             "isa-debug-exit,iobase=0x501,iosize=2",
             "-d",
             "guest_errors,unimp",
@@ -46,6 +48,8 @@ def main():
     parser.add_argument("--kvm", action="store_true")
     parser.add_argument("--append-cmdline", action="append")
     parser.add_argument("--log-serial")
+    parser.add_argument("--disk", action="append")
+    parser.add_argument("--virtfs", help="A host directory mounted on /mnt.")
     parser.add_argument("--qemu")
     parser.add_argument("kernel_elf", help="The kernel ELF executable.")
     parser.add_argument("qemu_args", nargs="*")
@@ -85,6 +89,18 @@ def main():
     if args.log_serial:
         argv += ["-serial", args.log_serial]
         cmdline += ["serial1=on"]
+    if args.disk:
+        for i, disk in enumerate(args.disk):
+            argv += [
+                "-drive", f"file={disk},if=none,id=disk{i},format=raw",
+                "-device", f"virtio-blk-pci,drive=disk{i},disable-legacy=on,disable-modern=off"
+            ]
+    if args.virtfs:
+        argv += [
+            "-virtfs",
+            f"local,path={args.virtfs},mount_tag=host,security_model=none"
+        ]
+        cmdline += ["virtfs=host:/mnt"]
     if args.qemu_args:
         argv += args.qemu_args
 